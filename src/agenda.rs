@@ -0,0 +1,542 @@
+//! `Due:`/`Remind:` dates: natural-language input, `qn due`, `qn agenda`
+//! and `qn remind`.
+//!
+//! The headers may hold whatever was typed (`tomorrow 9am`, `fri`, `+3d`).
+//! Until qn saves the note, a natural value is read relative to the note's
+//! Updated time. `note::store_note` then rewrites it as a timestamp against
+//! that same Updated time, before the save bumps it, so the date does not
+//! move; `qn edit` resolves values typed in the editor against now.
+
+use crate::note::{
+    Note, TIME_FMT, now_fixed, parse_note, parse_timestamp, timestamp_string,
+    write_note,
+};
+use crate::shared::lock::lock_note;
+use crate::shared::migrate::resolve_active_note_path;
+use crate::{Area, args, parse_list_options, select_notes};
+use chrono::{
+    DateTime, Datelike, Days, Duration, FixedOffset, NaiveDate, NaiveTime,
+    TimeZone, Weekday,
+};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use yansi::Paint;
+
+/// Time used when the input names a day but no time.
+const DEFAULT_HOUR: u32 = 9;
+const DEFAULT_AGENDA_DAYS: u64 = 7;
+
+/// A note with the date it is due or should remind.
+type Dated = (DateTime<FixedOffset>, Note);
+
+const WHEN_HINT: &str =
+    "try `tomorrow 9am`, `fri`, `+3d`, `in 2 hours` or `2025-06-01 14:00`";
+
+fn weekday(word: &str) -> Option<Weekday> {
+    const DAYS: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Mon),
+        ("tuesday", Weekday::Tue),
+        ("wednesday", Weekday::Wed),
+        ("thursday", Weekday::Thu),
+        ("friday", Weekday::Fri),
+        ("saturday", Weekday::Sat),
+        ("sunday", Weekday::Sun),
+    ];
+    if word.len() < 3 {
+        return None;
+    }
+    DAYS.iter().find(|(name, _)| name.starts_with(word)).map(|(_, d)| *d)
+}
+
+/// `today`, `tomorrow`, a weekday (the next one after today) or a date.
+fn day(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match word {
+        "today" => Some(today),
+        "tomorrow" | "tmr" | "tmrw" => today.succ_opt(),
+        "yesterday" => today.pred_opt(),
+        _ => {
+            if let Some(day) = weekday(word) {
+                let ahead = (day.num_days_from_monday() + 7
+                    - today.weekday().num_days_from_monday())
+                    % 7;
+                return today.checked_add_days(Days::new(match ahead {
+                    0 => 7,
+                    n => n.into(),
+                }));
+            }
+            NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+        }
+    }
+}
+
+/// `9am`, `9:30pm`, `14:00`, `noon` or `midnight`.
+fn time_of_day(word: &str) -> Option<NaiveTime> {
+    match word {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+    let (clock, pm) = match (word.strip_suffix("am"), word.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, Some(false)),
+        (_, Some(clock)) => (clock, Some(true)),
+        _ => (word, None),
+    };
+    let (hour, minute): (u32, u32) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse().ok()?, m.parse().ok()?),
+        None if pm.is_some() => (clock.parse().ok()?, 0),
+        None => return None,
+    };
+    let hour = match pm {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(pm) => hour % 12 + if pm { 12 } else { 0 },
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// `+3d`, `2w`, `+4h` or `30min`.
+fn offset(word: &str) -> Option<Duration> {
+    let word = word.strip_prefix('+').unwrap_or(word);
+    let split = word.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = word.split_at(split);
+    let count: i64 = count.parse().ok()?;
+    match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => {
+            Duration::try_minutes(count)
+        }
+        "h" | "hr" | "hrs" | "hour" | "hours" => Duration::try_hours(count),
+        "d" | "day" | "days" => Duration::try_days(count),
+        "w" | "wk" | "wks" | "week" | "weeks" => Duration::try_weeks(count),
+        _ => None,
+    }
+}
+
+/// Resolve natural input against `now`: a day word, a time, an offset, or
+/// a day or offset followed by a time. Full timestamps pass through.
+pub(crate) fn parse_when(
+    input: &str,
+    now: DateTime<FixedOffset>,
+) -> Option<DateTime<FixedOffset>> {
+    let input = input.trim();
+    if let Some(dt) = parse_timestamp(input)
+        .or_else(|| DateTime::parse_from_rfc3339(input).ok())
+    {
+        return Some(dt);
+    }
+
+    // Glue split forms back together: `9 am`, `3 days`.
+    let words: Vec<String> =
+        input.to_lowercase().split_whitespace().map(String::from).collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let word = &words[i];
+        if matches!(word.as_str(), "in" | "at" | "on" | "next" | "by") {
+            i += 1;
+            continue;
+        }
+        if word.chars().all(|c| c.is_ascii_digit() || c == ':')
+            && let Some(next) = words.get(i + 1)
+        {
+            let joined = format!("{word}{next}");
+            if next == "am" || next == "pm" || offset(&joined).is_some() {
+                tokens.push(joined);
+                i += 2;
+                continue;
+            }
+        }
+        tokens.push(word.clone());
+        i += 1;
+    }
+
+    let today = now.date_naive();
+    let mut date: Option<NaiveDate> = None;
+    let mut time: Option<NaiveTime> = None;
+    let mut shift: Option<Duration> = None;
+    for token in &tokens {
+        let open = date.is_none() && shift.is_none();
+        if time.is_none()
+            && let Some(t) = time_of_day(token)
+        {
+            time = Some(t);
+        } else if open && let Some(d) = day(token, today) {
+            date = Some(d);
+        } else if open && let Some(d) = offset(token) {
+            shift = Some(d);
+        } else {
+            return None;
+        }
+    }
+
+    let at = |date: NaiveDate, time: NaiveTime| {
+        now.offset().from_local_datetime(&date.and_time(time)).single()
+    };
+    let default_time = NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0)?;
+    match (date, time, shift) {
+        (Some(date), time, _) => at(date, time.unwrap_or(default_time)),
+        (None, Some(time), Some(shift)) => at((now + shift).date_naive(), time),
+        (None, Some(time), None) => at(today, time),
+        (None, None, Some(shift)) => Some(now + shift),
+        (None, None, None) => None,
+    }
+}
+
+/// When a stored `Due:`/`Remind:` value falls; natural values count from
+/// the note's Updated time.
+pub(crate) fn resolve_when(
+    value: &str,
+    note: &Note,
+) -> Option<DateTime<FixedOffset>> {
+    parse_timestamp(value).or_else(|| {
+        parse_when(
+            value,
+            parse_timestamp(&note.updated).unwrap_or_else(now_fixed),
+        )
+    })
+}
+
+/// Rewrite natural `Due:`/`Remind:` values as timestamps resolved against
+/// `now`; values qn cannot read are left for the user to fix.
+pub(crate) fn normalize_dates(note: &mut Note, now: DateTime<FixedOffset>) {
+    for value in [&mut note.due, &mut note.remind].into_iter().flatten() {
+        if parse_timestamp(value).is_none()
+            && let Some(dt) = parse_when(value, now)
+        {
+            *value = dt.format(TIME_FMT).to_string();
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Due,
+    Remind,
+}
+
+impl Field {
+    fn slot(self, note: &mut Note) -> &mut Option<String> {
+        match self {
+            Field::Due => &mut note.due,
+            Field::Remind => &mut note.remind,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Due => "Due",
+            Field::Remind => "Reminder",
+        }
+    }
+}
+
+/// Show, set or clear one note's date: `<id> [when...|--clear]`.
+fn set_date(
+    args: Vec<String>,
+    dir: &Path,
+    field: Field,
+    usage: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut args = args.into_iter();
+    let id = args.next().ok_or(usage)?;
+    let rest: Vec<String> = args.collect();
+    let _lock = lock_note(dir, &id)?;
+    let path = resolve_active_note_path(dir, &id)
+        .ok_or_else(|| format!("Note {id} not found"))?;
+    let mut note = parse_note(&path, fs::metadata(&path)?.len())?;
+    let label = field.label();
+
+    if rest.is_empty() {
+        let current = field.slot(&mut note).clone();
+        match current {
+            Some(value) => match resolve_when(&value, &note) {
+                Some(dt) => println!("{}", dt.format(TIME_FMT)),
+                None => println!("{value}"),
+            },
+            None => println!("{label}: none"),
+        }
+        return Ok(());
+    }
+    let message = if matches!(rest.as_slice(), [flag] if flag == "--clear") {
+        if field.slot(&mut note).take().is_none() {
+            return Err(
+                format!("Note {id} has no {}", label.to_lowercase()).into()
+            );
+        }
+        format!("Cleared {} on {id}", label.to_lowercase())
+    } else {
+        let when = rest.join(" ");
+        let dt = parse_when(&when, now_fixed()).ok_or_else(|| {
+            format!("Could not read {when:?} as a date; {WHEN_HINT}")
+        })?;
+        let stamp = dt.format(TIME_FMT).to_string();
+        *field.slot(&mut note) = Some(stamp.clone());
+        format!("{label} {id}: {stamp}")
+    };
+    note.updated = timestamp_string();
+    write_note(&note, path.parent().unwrap_or(dir))?;
+    println!("{message}");
+    Ok(())
+}
+
+/// Handle `qn due <id> [when...|--clear]`.
+pub(crate) fn due(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
+    set_date(args, dir, Field::Due, "Usage: qn due <id> [when...|--clear]")
+}
+
+/// One agenda line: when, id, title and tags.
+fn entry_line(when: DateTime<FixedOffset>, note: &Note, color: bool) -> String {
+    let stamp = when.format("%a %d%b %H:%M").to_string();
+    let tags = note.tags.join(" ");
+    if !color {
+        return format!("  {stamp}  {}  {}  {tags}", note.id, note.title)
+            .trim_end()
+            .to_string();
+    }
+    format!(
+        "  {}  {}  {}  {}",
+        Paint::rgb(&stamp, 137, 180, 250),
+        Paint::rgb(&note.id, 108, 112, 134),
+        note.title,
+        Paint::rgb(&tags, 148, 226, 213)
+    )
+    .trim_end()
+    .to_string()
+}
+
+fn heading(title: &str, rgb: (u8, u8, u8), color: bool) -> String {
+    if color {
+        Paint::rgb(title, rgb.0, rgb.1, rgb.2).bold().to_string()
+    } else {
+        title.to_string()
+    }
+}
+
+/// Active notes with a `field` date (and the given `-t` filters), soonest
+/// first.
+fn dated_notes(
+    dir: &Path,
+    tag_filters: &[String],
+    field: Field,
+) -> Result<Vec<Dated>, Box<dyn Error>> {
+    let mut list_args = Vec::new();
+    for tag in tag_filters {
+        list_args.extend(["-t".to_string(), tag.clone()]);
+    }
+    let notes =
+        select_notes(dir, Area::Active, &parse_list_options(list_args)?)?;
+    let mut dated: Vec<Dated> = notes
+        .into_iter()
+        .filter_map(|mut note| {
+            let value = field.slot(&mut note).clone()?;
+            Some((resolve_when(&value, &note)?, note))
+        })
+        .collect();
+    dated.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.id.cmp(&b.1.id)));
+    Ok(dated)
+}
+
+/// Agenda sections for `notes` (sorted by due time): overdue, today and the
+/// next `days` days.
+fn agenda_sections(
+    notes: &[Dated],
+    today: NaiveDate,
+    days: u64,
+) -> [Vec<usize>; 3] {
+    let horizon = today.checked_add_days(Days::new(days)).unwrap_or(today);
+    let mut sections: [Vec<usize>; 3] = Default::default();
+    for (i, (due, _)) in notes.iter().enumerate() {
+        let date = due.date_naive();
+        if date < today {
+            sections[0].push(i);
+        } else if date == today {
+            sections[1].push(i);
+        } else if date <= horizon {
+            sections[2].push(i);
+        }
+    }
+    sections
+}
+
+/// Handle `qn agenda [--days N] [-t tag]...`.
+pub(crate) fn show_agenda(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut days = DEFAULT_AGENDA_DAYS;
+    let mut tag_filters = Vec::new();
+    let mut parser = args::ArgParser::new(args, "agenda");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "-d" | "--days" => {
+                let raw = parser.extract_value("-d/--days")?;
+                days = raw
+                    .parse()
+                    .map_err(|_| format!("Invalid number of days: {raw}"))?;
+            }
+            "-t" | "--tag" => {
+                if let Some(tag) = parser.extract_tag()? {
+                    tag_filters.push(tag);
+                }
+            }
+            other => {
+                return Err(format!(
+                    "Unknown argument for agenda: {other}\n\
+Usage: qn agenda [--days N] [-t tag]"
+                )
+                .into());
+            }
+        }
+    }
+
+    let notes = dated_notes(dir, &tag_filters, Field::Due)?;
+    let sections = agenda_sections(&notes, now_fixed().date_naive(), days);
+    if sections.iter().all(Vec::is_empty) {
+        println!("Nothing due in the next {days} days.");
+        return Ok(());
+    }
+    let color = env::var("NO_COLOR").is_err();
+    let upcoming = format!("Upcoming ({days} days)");
+    let titles = [
+        ("Overdue", (243, 139, 168)),
+        ("Today", (249, 226, 175)),
+        (upcoming.as_str(), (137, 180, 250)),
+    ];
+    let mut first = true;
+    for ((title, rgb), section) in titles.into_iter().zip(&sections) {
+        if section.is_empty() {
+            continue;
+        }
+        if !first {
+            println!();
+        }
+        first = false;
+        println!("{}", heading(title, rgb, color));
+        for &i in section {
+            let (due, note) = &notes[i];
+            println!("{}", entry_line(*due, note, color));
+        }
+    }
+    Ok(())
+}
+
+/// Handle `qn remind [--check]` and `qn remind <id> [when...|--clear]`.
+pub(crate) fn remind(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str =
+        "Usage: qn remind [--check] | qn remind <id> [when...|--clear]";
+    let check = match args.first().map(String::as_str) {
+        Some("--check") if args.len() == 1 => true,
+        Some(other) if other.starts_with('-') => {
+            return Err(
+                format!("Unknown flag for remind: {other}\n{USAGE}").into()
+            );
+        }
+        Some(_) => return set_date(args, dir, Field::Remind, USAGE),
+        None => false,
+    };
+
+    let now = now_fixed();
+    let color = env::var("NO_COLOR").is_err();
+    let notes = dated_notes(dir, &[], Field::Remind)?;
+    let (pending, later): (Vec<_>, Vec<_>) =
+        notes.iter().partition(|(at, _)| *at <= now);
+    if check {
+        for (at, note) in &pending {
+            println!("{}", entry_line(*at, note, color));
+        }
+        if pending.is_empty() {
+            return Ok(());
+        }
+        return Err(format!(
+            "{} pending reminder{}; clear with `qn remind <id> --clear`",
+            pending.len(),
+            if pending.len() == 1 { "" } else { "s" }
+        )
+        .into());
+    }
+    if notes.is_empty() {
+        println!("No reminders set.");
+        return Ok(());
+    }
+    for (title, rgb, section) in [
+        ("Pending", (243, 139, 168), &pending),
+        ("Upcoming", (137, 180, 250), &later),
+    ] {
+        if section.is_empty() {
+            continue;
+        }
+        println!("{}", heading(title, rgb, color));
+        for (at, note) in section {
+            println!("{}", entry_line(*at, note, color));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<FixedOffset> {
+        // A Wednesday afternoon.
+        DateTime::parse_from_str("15Oct25 14:30 +02:00", TIME_FMT).unwrap()
+    }
+
+    fn when(input: &str) -> Option<String> {
+        parse_when(input, now()).map(|dt| dt.format(TIME_FMT).to_string())
+    }
+
+    #[test]
+    fn reads_natural_dates_times_and_offsets() {
+        assert_eq!(
+            when("tomorrow 9am").as_deref(),
+            Some("16Oct25 09:00 +02:00")
+        );
+        assert_eq!(when("fri").as_deref(), Some("17Oct25 09:00 +02:00"));
+        assert_eq!(when("Wednesday").as_deref(), Some("22Oct25 09:00 +02:00"));
+        assert_eq!(
+            when("next mon 5:30 pm").as_deref(),
+            Some("20Oct25 17:30 +02:00")
+        );
+        assert_eq!(when("+3d").as_deref(), Some("18Oct25 14:30 +02:00"));
+        assert_eq!(when("in 2 hours").as_deref(), Some("15Oct25 16:30 +02:00"));
+        assert_eq!(when("+1w noon").as_deref(), Some("22Oct25 12:00 +02:00"));
+        assert_eq!(when("18:00").as_deref(), Some("15Oct25 18:00 +02:00"));
+        assert_eq!(when("2025-11-02").as_deref(), Some("02Nov25 09:00 +02:00"));
+        assert_eq!(
+            when("01Dec25 08:00 +01:00").as_deref(),
+            Some("01Dec25 08:00 +01:00")
+        );
+        for bad in ["", "someday", "13pm", "fri fri", "+3x", "tu"] {
+            assert_eq!(when(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn normalizes_headers_and_groups_the_agenda() {
+        let mut note = crate::note::parse_note_text(
+            "n1".to_string(),
+            "Title: Rent\nCreated: 01Oct25 09:00 +02:00\n\
+Updated: 14Oct25 10:00 +02:00\nDue: tomorrow\nRemind: whenever\nTags:\n---\n",
+            0,
+        );
+        // Unsaved natural values count from Updated.
+        let due = resolve_when(note.due.as_deref().unwrap(), &note).unwrap();
+        assert_eq!(due.format(TIME_FMT).to_string(), "15Oct25 09:00 +02:00");
+        normalize_dates(&mut note, now());
+        assert_eq!(note.due.as_deref(), Some("16Oct25 09:00 +02:00"));
+        assert_eq!(note.remind.as_deref(), Some("whenever"));
+
+        let dated: Vec<Dated> =
+            ["yesterday", "today 8am", "today 11pm", "+2d", "+9d"]
+                .iter()
+                .map(|w| (parse_when(w, now()).unwrap(), note.clone()))
+                .collect();
+        let sections = agenda_sections(&dated, now().date_naive(), 7);
+        assert_eq!(sections, [vec![0], vec![1, 2], vec![3]]);
+    }
+}
@@ -0,0 +1,590 @@
+//! `qn backup` / `qn restore`: the whole store as one `.tar.gz` snapshot.
+//!
+//! The archive holds `active/`, `migrated/<batch>/`, `trash/` and `archive/`
+//! note files plus a `MANIFEST` listing each file's area, id, size and
+//! SHA-256. Restores verify every checksum before touching the target.
+
+use crate::note::{ensure_dir, generate_new_id, note_path};
+use crate::shared::atomic::atomic_write;
+use crate::shared::lock::{LockMode, lock_store};
+use crate::shared::migrate::{
+    collect_ids_across_areas, list_store_note_files, migrated_dir,
+};
+use crate::{Area, area_dir, args};
+use chrono::Local;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+const MANIFEST: &str = "MANIFEST";
+const MANIFEST_HEADER: &str = "qn-backup v1";
+const SNAPSHOT_PREFIX: &str = "qn-backup-";
+const SNAPSHOT_SUFFIX: &str = ".tar.gz";
+
+/// One note file inside a backup.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    /// Path inside the archive, e.g. `migrated/batch1/abc.md`.
+    path: String,
+    id: String,
+    size: u64,
+    sha256: String,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Archive path for a note file under `base`, or `None` for stray files.
+fn archive_path(base: &Path, file: &Path) -> Option<String> {
+    let rel = file.strip_prefix(base).ok()?;
+    let parts: Vec<&str> =
+        rel.components().filter_map(|c| c.as_os_str().to_str()).collect();
+    match parts.as_slice() {
+        [name] => Some(format!("active/{name}")),
+        ["migrated", batch, name] => Some(format!("migrated/{batch}/{name}")),
+        [area @ ("trash" | "archive"), name] => Some(format!("{area}/{name}")),
+        _ => None,
+    }
+}
+
+/// Where an archive path lands in a store rooted at `base`.
+fn store_path(base: &Path, archived: &str) -> Option<PathBuf> {
+    let parts: Vec<&str> = archived.split('/').collect();
+    let safe = |s: &&str| {
+        !s.is_empty()
+            && Path::new(s)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+    };
+    if !parts.iter().all(safe) {
+        return None;
+    }
+    match parts.as_slice() {
+        ["active", name] => Some(base.join(name)),
+        ["migrated", batch, name] => {
+            Some(migrated_dir(base).join(batch).join(name))
+        }
+        ["trash", name] => Some(area_dir(base, Area::Trash).join(name)),
+        ["archive", name] => Some(area_dir(base, Area::Archive).join(name)),
+        _ => None,
+    }
+}
+
+fn render_manifest(entries: &[Entry]) -> String {
+    let mut out =
+        format!("{MANIFEST_HEADER}\ncreated\t{}\n", Local::now().to_rfc3339());
+    for e in entries {
+        out.push_str(&format!(
+            "{}\t{}\t{}\t{}\n",
+            e.sha256, e.size, e.id, e.path
+        ));
+    }
+    out
+}
+
+fn parse_manifest(text: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut lines = text.lines();
+    if lines.next() != Some(MANIFEST_HEADER) {
+        return Err("Not a qn backup (missing or unknown MANIFEST)".into());
+    }
+    let mut entries = Vec::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            ["created", _] => {}
+            [sha256, size, id, path] => entries.push(Entry {
+                sha256: sha256.to_string(),
+                size: size
+                    .parse()
+                    .map_err(|_| format!("Bad size in MANIFEST: {line}"))?,
+                id: id.to_string(),
+                path: path.to_string(),
+            }),
+            _ => return Err(format!("Bad MANIFEST line: {line}").into()),
+        }
+    }
+    Ok(entries)
+}
+
+/// Write a snapshot of `base` to `out`; returns the number of notes.
+fn write_backup(base: &Path, out: &Path) -> Result<usize, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    let mut contents = Vec::new();
+    for file in list_store_note_files(base)? {
+        let Some(path) = archive_path(base, &file) else { continue };
+        let Some(id) = file.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let bytes = fs::read(&file)?;
+        entries.push(Entry {
+            path,
+            id: id.to_string(),
+            size: bytes.len() as u64,
+            sha256: sha256_hex(&bytes),
+        });
+        contents.push(bytes);
+    }
+
+    let mut encoded = Vec::new();
+    {
+        let gz = GzEncoder::new(&mut encoded, Compression::default());
+        let mut tar = tar::Builder::new(gz);
+        let mtime = Local::now().timestamp().max(0) as u64;
+        let mut append = |path: &str, bytes: &[u8]| -> io::Result<()> {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_cksum();
+            tar.append_data(&mut header, path, bytes)
+        };
+        append(MANIFEST, render_manifest(&entries).as_bytes())?;
+        for (entry, bytes) in entries.iter().zip(&contents) {
+            append(&entry.path, bytes)?;
+        }
+        tar.into_inner()?.finish()?;
+    }
+    if let Some(parent) = out.parent() {
+        ensure_dir(parent)?;
+    }
+    atomic_write(out, &encoded)?;
+    Ok(entries.len())
+}
+
+/// Keep the newest `keep` snapshots named `qn-backup-*.tar.gz` in `dir`.
+fn rotate_snapshots(dir: &Path, keep: usize) -> io::Result<Vec<PathBuf>> {
+    let mut snapshots: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+                n.starts_with(SNAPSHOT_PREFIX) && n.ends_with(SNAPSHOT_SUFFIX)
+            })
+        })
+        .collect();
+    // Names embed a sortable timestamp, so name order is age order.
+    snapshots.sort();
+    let excess = snapshots.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = snapshots.drain(..excess).collect();
+    for path in &removed {
+        fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+/// Handle `qn backup -o <file|dir> [--rotate N]`.
+pub(crate) fn backup_notes(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "Usage: qn backup -o <file.tar.gz|dir> [--rotate N]";
+    let mut out: Option<PathBuf> = None;
+    let mut rotate: Option<usize> = None;
+    let mut parser = args::ArgParser::new(args, "backup");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "-o" | "--out" => {
+                out = Some(PathBuf::from(parser.extract_value("-o/--out")?));
+            }
+            "--rotate" => {
+                let raw = parser.extract_value("--rotate")?;
+                let keep =
+                    raw.parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(
+                        || format!("Invalid --rotate count: {raw}"),
+                    )?;
+                rotate = Some(keep);
+            }
+            other => {
+                return Err(format!(
+                    "Unknown argument for backup: {other}\n{USAGE}"
+                )
+                .into());
+            }
+        }
+    }
+    let out = out.ok_or(USAGE)?;
+
+    // A directory target (or any rotated backup) gets a timestamped name.
+    let target_is_dir = out.is_dir()
+        || out.as_os_str().to_string_lossy().ends_with('/')
+        || (rotate.is_some()
+            && !out.to_string_lossy().ends_with(SNAPSHOT_SUFFIX));
+    let file = if target_is_dir {
+        out.join(format!(
+            "{SNAPSHOT_PREFIX}{}{SNAPSHOT_SUFFIX}",
+            Local::now().format("%Y%m%d-%H%M%S%.3f")
+        ))
+    } else {
+        out.clone()
+    };
+
+    let count = write_backup(dir, &file)?;
+    println!("Backed up {count} note(s) to {}", file.display());
+    if let Some(keep) = rotate {
+        let snapshot_dir = file.parent().unwrap_or(Path::new("."));
+        for removed in rotate_snapshots(snapshot_dir, keep)? {
+            println!("Removed old backup {}", removed.display());
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RestoreMode {
+    /// Only allowed when the target has no notes.
+    Fresh,
+    Merge,
+    Replace,
+}
+
+/// A manifest entry with the file contents that matched it.
+type Verified = (Entry, Vec<u8>);
+
+/// Read and verify a backup; nothing is written if any check fails.
+fn read_backup(file: &Path) -> Result<Vec<Verified>, Box<dyn Error>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(file)?));
+    let mut manifest: Option<Vec<Entry>> = None;
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    for item in archive.entries()? {
+        let mut item = item?;
+        let path = item.path()?.to_string_lossy().into_owned();
+        let mut bytes = Vec::new();
+        item.read_to_end(&mut bytes)?;
+        if path == MANIFEST {
+            manifest = Some(parse_manifest(&String::from_utf8(bytes)?)?);
+        } else {
+            files.insert(path, bytes);
+        }
+    }
+    let manifest =
+        manifest.ok_or("Not a qn backup (missing or unknown MANIFEST)")?;
+
+    let mut verified = Vec::new();
+    for entry in manifest {
+        let bytes = files
+            .remove(&entry.path)
+            .ok_or_else(|| format!("Backup is missing {}", entry.path))?;
+        if bytes.len() as u64 != entry.size
+            || sha256_hex(&bytes) != entry.sha256
+        {
+            return Err(format!("Checksum mismatch for {}", entry.path).into());
+        }
+        verified.push((entry, bytes));
+    }
+    if let Some(extra) = files.keys().next() {
+        return Err(
+            format!("Backup has a file not in MANIFEST: {extra}").into()
+        );
+    }
+    Ok(verified)
+}
+
+/// Handle `qn restore <file> [--into dir] [--merge|--replace]`.
+pub(crate) fn restore_backup(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str =
+        "Usage: qn restore <file.tar.gz> [--into dir] [--merge|--replace]";
+    let mut file: Option<PathBuf> = None;
+    let mut target = dir.to_path_buf();
+    let mut mode = RestoreMode::Fresh;
+    let mut parser = args::ArgParser::new(args, "restore");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--into" => target = PathBuf::from(parser.extract_value("--into")?),
+            "--merge" => mode = RestoreMode::Merge,
+            "--replace" => mode = RestoreMode::Replace,
+            other if !other.starts_with('-') && file.is_none() => {
+                file = Some(PathBuf::from(other));
+            }
+            other => {
+                return Err(format!(
+                    "Unknown argument for restore: {other}\n{USAGE}"
+                )
+                .into());
+            }
+        }
+    }
+    let file = file.ok_or(USAGE)?;
+    let notes = plan_restore(&target, read_backup(&file)?)?;
+
+    ensure_dir(&target)?;
+    let _lock = lock_store(&target, LockMode::Exclusive)?;
+    let existing = list_store_note_files(&target)?;
+    if mode == RestoreMode::Fresh && !existing.is_empty() {
+        return Err(format!(
+            "{} already has {} note(s); pass --merge to add to them or \
+--replace to discard them",
+            target.display(),
+            existing.len()
+        )
+        .into());
+    }
+    // Replaced notes wait in a hidden directory until the backup has been
+    // written, so a failure can put them back.
+    let aside = if mode == RestoreMode::Replace {
+        Some(set_aside(&target, &existing)?)
+    } else {
+        None
+    };
+
+    let mut written = Vec::new();
+    let (restored, renamed, skipped) =
+        match write_restored(&target, notes, &mut written) {
+            Ok(counts) => counts,
+            Err(e) => {
+                for path in &written {
+                    let _ = fs::remove_file(path);
+                }
+                if let Some(aside) = &aside {
+                    put_back(&target, aside)?;
+                }
+                return Err(e);
+            }
+        };
+    if let Some(aside) = &aside {
+        fs::remove_dir_all(aside)?;
+    }
+    println!(
+        "Restored {restored} note(s) into {} ({renamed} renamed, {skipped} \
+already present)",
+        target.display()
+    );
+    Ok(())
+}
+
+/// Pair every verified note with the file it restores to, refusing the
+/// whole backup if any path is unsafe or an id appears twice.
+fn plan_restore(
+    target: &Path,
+    notes: Vec<Verified>,
+) -> Result<Vec<(PathBuf, Verified)>, Box<dyn Error>> {
+    let mut ids = HashSet::new();
+    let mut planned = Vec::new();
+    for (entry, bytes) in notes {
+        let dest = store_path(target, &entry.path)
+            .ok_or_else(|| format!("Unsafe path in backup: {}", entry.path))?;
+        if dest.file_stem().and_then(|s| s.to_str()) != Some(&entry.id) {
+            return Err(format!(
+                "Backup entry {} does not match id {}",
+                entry.path, entry.id
+            )
+            .into());
+        }
+        if !ids.insert(entry.id.clone()) {
+            return Err(format!("Backup holds note {} twice", entry.id).into());
+        }
+        planned.push((dest, (entry, bytes)));
+    }
+    Ok(planned)
+}
+
+fn aside_dir(target: &Path) -> PathBuf {
+    target.join(format!(".restore-{}", std::process::id()))
+}
+
+/// Move the store's note files under a hidden directory, keeping their
+/// relative paths; undone if any move fails.
+fn set_aside(target: &Path, existing: &[PathBuf]) -> io::Result<PathBuf> {
+    let aside = aside_dir(target);
+    for path in existing {
+        let Ok(rel) = path.strip_prefix(target) else { continue };
+        let moved = aside.join(rel);
+        let result = ensure_dir(moved.parent().unwrap_or(&aside))
+            .and_then(|_| fs::rename(path, &moved));
+        if let Err(e) = result {
+            let _ = put_back(target, &aside);
+            return Err(e);
+        }
+    }
+    Ok(aside)
+}
+
+/// Move set-aside notes back to where they were and drop `aside`.
+fn put_back(target: &Path, aside: &Path) -> io::Result<()> {
+    if !aside.exists() {
+        return Ok(());
+    }
+    for path in list_store_note_files(aside)? {
+        let Ok(rel) = path.strip_prefix(aside) else { continue };
+        let original = target.join(rel);
+        ensure_dir(original.parent().unwrap_or(target))?;
+        fs::rename(&path, &original)?;
+    }
+    fs::remove_dir_all(aside)
+}
+
+/// Write planned notes into `target`, recording each file in `written`;
+/// returns (restored, renamed, skipped).
+fn write_restored(
+    target: &Path,
+    notes: Vec<(PathBuf, Verified)>,
+    written: &mut Vec<PathBuf>,
+) -> Result<(usize, usize, usize), Box<dyn Error>> {
+    let mut taken: HashSet<String> = collect_ids_across_areas(target)?;
+    let (mut restored, mut renamed, mut skipped) = (0, 0, 0);
+    for (dest, (entry, bytes)) in notes {
+        let dest_dir = dest.parent().unwrap_or(target).to_path_buf();
+        let mut dest = dest;
+        if taken.contains(&entry.id) {
+            if fs::read(&dest).is_ok_and(|current| current == bytes) {
+                skipped += 1;
+                continue;
+            }
+            // Same rule as `ensure_unique_id`: keep the id unless taken,
+            // otherwise mint a fresh one.
+            let new_id = generate_new_id(&dest_dir, &mut taken)?;
+            println!("Restored {} as {new_id} (id already in use)", entry.id);
+            dest = note_path(&dest_dir, &new_id);
+            renamed += 1;
+        }
+        ensure_dir(&dest_dir)?;
+        atomic_write(&dest, &bytes)?;
+        written.push(dest.clone());
+        if let Some(id) = dest.file_stem().and_then(|s| s.to_str()) {
+            taken.insert(id.to_string());
+        }
+        restored += 1;
+    }
+    Ok((restored, renamed, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn archive_paths_round_trip_and_reject_escapes() {
+        let base = Path::new("/notes");
+        for file in [
+            "/notes/a.md",
+            "/notes/migrated/b1/b.md",
+            "/notes/trash/c.md",
+            "/notes/archive/d.md",
+        ] {
+            let archived = archive_path(base, Path::new(file)).unwrap();
+            assert_eq!(store_path(base, &archived).unwrap(), Path::new(file));
+        }
+        assert!(
+            archive_path(base, Path::new("/notes/.history/x/1.md")).is_none()
+        );
+        assert!(store_path(base, "active/../../etc/passwd").is_none());
+        assert!(store_path(base, "migrated/../x.md").is_none());
+    }
+
+    #[test]
+    fn corrupted_backup_fails_verification() {
+        let tmp = tempdir().unwrap();
+        let store = tmp.path().join("store");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(store.join("n1.md"), "Title: One\n---\nbody\n").unwrap();
+        let file = tmp.path().join("b.tar.gz");
+        assert_eq!(write_backup(&store, &file).unwrap(), 1);
+        assert_eq!(read_backup(&file).unwrap().len(), 1);
+
+        // Rebuild the archive with a tampered note but the original manifest.
+        let mut manifest = String::new();
+        let mut archive =
+            tar::Archive::new(GzDecoder::new(File::open(&file).unwrap()));
+        for item in archive.entries().unwrap() {
+            let mut item = item.unwrap();
+            if item.path().unwrap().to_str() == Some(MANIFEST) {
+                item.read_to_string(&mut manifest).unwrap();
+            }
+        }
+        let mut encoded = Vec::new();
+        {
+            let gz = GzEncoder::new(&mut encoded, Compression::default());
+            let mut tar = tar::Builder::new(gz);
+            for (path, bytes) in [
+                (MANIFEST, manifest.as_bytes()),
+                ("active/n1.md", b"Title: Evil\n---\nbody\n".as_slice()),
+            ] {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(bytes.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                tar.append_data(&mut header, path, bytes).unwrap();
+            }
+            tar.into_inner().unwrap().finish().unwrap();
+        }
+        fs::write(&file, encoded).unwrap();
+        let err = read_backup(&file).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+    }
+
+    #[test]
+    fn replace_refuses_an_unsafe_backup_before_touching_notes() {
+        let tmp = tempdir().unwrap();
+        let store = tmp.path().join("store");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(store.join("keep.md"), "Title: Keep\n---\nbody\n").unwrap();
+
+        let good = b"Title: Good\n---\nbody\n".as_slice();
+        let evil = b"Title: Evil\n---\nbody\n".as_slice();
+        let manifest = render_manifest(
+            &[("active/g1.md", "g1", good), ("active/../x.md", "x", evil)].map(
+                |(path, id, bytes)| Entry {
+                    path: path.to_string(),
+                    id: id.to_string(),
+                    size: bytes.len() as u64,
+                    sha256: sha256_hex(bytes),
+                },
+            ),
+        );
+        let mut encoded = Vec::new();
+        {
+            let gz = GzEncoder::new(&mut encoded, Compression::default());
+            let mut tar = tar::Builder::new(gz);
+            for (path, bytes) in [
+                (MANIFEST, manifest.as_bytes()),
+                ("active/g1.md", good),
+                ("active/../x.md", evil),
+            ] {
+                // `set_path` refuses `..`, so write the name field directly.
+                let mut header = tar::Header::new_gnu();
+                let name = &mut header.as_gnu_mut().unwrap().name;
+                name[..path.len()].copy_from_slice(path.as_bytes());
+                header.set_size(bytes.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                tar.append(&header, bytes).unwrap();
+            }
+            tar.into_inner().unwrap().finish().unwrap();
+        }
+        let file = tmp.path().join("evil.tar.gz");
+        fs::write(&file, encoded).unwrap();
+
+        let args = [&file, Path::new("--into"), &store, Path::new("--replace")]
+            .map(|a| a.to_string_lossy().into_owned())
+            .to_vec();
+        let err = restore_backup(args, &store).unwrap_err();
+        assert!(err.to_string().contains("Unsafe path"), "{err}");
+        assert!(store.join("keep.md").exists());
+        assert!(!store.join("g1.md").exists());
+        assert!(!tmp.path().join("x.md").exists());
+    }
+
+    #[test]
+    fn rotation_keeps_newest_snapshots() {
+        let tmp = tempdir().unwrap();
+        for stamp in ["20250101", "20250102", "20250103"] {
+            fs::write(tmp.path().join(format!("qn-backup-{stamp}.tar.gz")), "")
+                .unwrap();
+        }
+        fs::write(tmp.path().join("other.tar.gz"), "").unwrap();
+        let removed = rotate_snapshots(tmp.path(), 2).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(!tmp.path().join("qn-backup-20250101.tar.gz").exists());
+        assert!(tmp.path().join("qn-backup-20250103.tar.gz").exists());
+        assert!(tmp.path().join("other.tar.gz").exists());
+    }
+}
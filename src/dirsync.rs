@@ -0,0 +1,436 @@
+//! `qn sync-dir <dir>`: reconcile two note stores, e.g. a local store and a
+//! copy in a cloud-synced folder.
+//!
+//! Each side remembers the state both stores had after the last sync in
+//! `.qn-sync/<peer>/`. A note changed on one side only is copied over; a
+//! note changed on both is merged (line-level three-way merge of the body,
+//! union of tags, newest Updated). What cannot be merged stays as this
+//! store's version on both sides, plus a `#conflict` note showing both.
+
+use crate::history::save_revision;
+use crate::note::{
+    Note, NoteFormat, Properties, cmp_dt, configured_format, ensure_dir,
+    generate_new_id, parse_note_text, serialize_note, timestamp_string,
+};
+use crate::shared::atomic::atomic_write;
+use crate::shared::diff::merge3;
+use crate::shared::lock::{LockMode, lock_store};
+use crate::shared::migrate::list_store_note_files;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SYNC_DIR: &str = ".qn-sync";
+const CONFLICT_TAG: &str = "#conflict";
+
+/// A note file: its path relative to the store root and its contents.
+type NoteFile = (String, Vec<u8>);
+
+/// Note files of a store by id.
+type Files = BTreeMap<String, NoteFile>;
+
+fn scan(root: &Path) -> io::Result<Files> {
+    let mut files = Files::new();
+    if !root.is_dir() {
+        return Ok(files);
+    }
+    for path in list_store_note_files(root)? {
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let Ok(rel) = path.strip_prefix(root) else { continue };
+        let rel: Vec<&str> =
+            rel.components().filter_map(|c| c.as_os_str().to_str()).collect();
+        files.insert(id.to_string(), (rel.join("/"), fs::read(&path)?));
+    }
+    Ok(files)
+}
+
+/// Where `root` keeps the last synced state shared with `peer`.
+fn base_dir(root: &Path, peer: &Path) -> PathBuf {
+    let digest = Sha256::digest(peer.to_string_lossy().as_bytes());
+    let key: String =
+        digest.iter().take(8).map(|b| format!("{b:02x}")).collect();
+    root.join(SYNC_DIR).join(key)
+}
+
+/// Make `dir` hold exactly `files`, touching only what changed.
+fn write_base(dir: &Path, peer: &Path, files: &Files) -> io::Result<()> {
+    let current = scan(dir)?;
+    for (id, (rel, _)) in &current {
+        if files.get(id).is_none_or(|(new_rel, _)| new_rel != rel) {
+            fs::remove_file(dir.join(rel))?;
+        }
+    }
+    for (id, (rel, bytes)) in files {
+        if current.get(id) != Some(&(rel.clone(), bytes.clone())) {
+            let path = dir.join(rel);
+            ensure_dir(path.parent().unwrap_or(dir))?;
+            atomic_write(&path, bytes)?;
+        }
+    }
+    ensure_dir(dir)?;
+    fs::write(dir.join("PEER"), format!("{}\n", peer.display()))
+}
+
+/// Write `file` into `root`, replacing the note's copy at `old_rel`.
+fn put(
+    root: &Path,
+    id: &str,
+    old: Option<&NoteFile>,
+    file: &NoteFile,
+) -> io::Result<()> {
+    let (rel, bytes) = file;
+    let path = root.join(rel);
+    if let Some((old_rel, old_bytes)) = old {
+        if old_rel == rel && old_bytes == bytes {
+            return Ok(());
+        }
+        if old_rel == rel
+            && let Ok(previous) = std::str::from_utf8(old_bytes)
+        {
+            save_revision(&path, id, previous)?;
+        }
+    }
+    ensure_dir(path.parent().unwrap_or(root))?;
+    atomic_write(&path, bytes)?;
+    if let Some((old_rel, _)) = old
+        && old_rel != rel
+    {
+        fs::remove_file(root.join(old_rel))?;
+    }
+    Ok(())
+}
+
+fn parse(id: &str, file: &NoteFile) -> Note {
+    let text = String::from_utf8_lossy(&file.1);
+    parse_note_text(id.to_string(), &text, file.1.len() as u64)
+}
+
+/// Three-way pick of one value; `None` when both sides changed it
+/// differently.
+fn pick<T: PartialEq + Clone>(
+    base: Option<&T>,
+    ours: &T,
+    theirs: &T,
+) -> Option<T> {
+    if ours == theirs || base == Some(theirs) {
+        Some(ours.clone())
+    } else if base == Some(ours) {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+/// Merge two edited versions of a note, or say why that is not possible.
+fn merge_note(
+    id: &str,
+    base: Option<&NoteFile>,
+    ours: &NoteFile,
+    theirs: &NoteFile,
+) -> Result<NoteFile, String> {
+    let b = base.map(|f| parse(id, f));
+    let (o, t) = (parse(id, ours), parse(id, theirs));
+    let newer_is_theirs = cmp_dt(&t.updated, &o.updated) == Ordering::Greater;
+
+    // Area and the matching Deleted/Archived stamps move together; when both
+    // sides moved the note, the later edit wins.
+    let place = |n: &Note, f: &NoteFile| {
+        (f.0.clone(), n.deleted_at.clone(), n.archived_at.clone())
+    };
+    let (rel, deleted_at, archived_at) = pick(
+        b.as_ref().zip(base).map(|(n, f)| place(n, f)).as_ref(),
+        &place(&o, ours),
+        &place(&t, theirs),
+    )
+    .unwrap_or_else(|| {
+        if newer_is_theirs { place(&t, theirs) } else { place(&o, ours) }
+    });
+
+    let title = pick(b.as_ref().map(|n| &n.title), &o.title, &t.title)
+        .ok_or("both sides changed the title")?;
+    let created = pick(b.as_ref().map(|n| &n.created), &o.created, &t.created)
+        .unwrap_or_else(|| {
+            std::cmp::min_by(o.created.clone(), t.created.clone(), |a, b| {
+                cmp_dt(a, b)
+            })
+        });
+    let updated =
+        if newer_is_theirs { t.updated.clone() } else { o.updated.clone() };
+    let due = pick(b.as_ref().map(|n| &n.due), &o.due, &t.due)
+        .ok_or("both sides changed the due date")?;
+    let remind = pick(b.as_ref().map(|n| &n.remind), &o.remind, &t.remind)
+        .ok_or("both sides changed the reminder")?;
+
+    let mut tags = o.tags.clone();
+    for tag in &t.tags {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+
+    let mut properties = Properties::default();
+    let mut keys: Vec<&str> = o.properties.iter().map(|(k, _)| k).collect();
+    for (key, _) in t.properties.iter() {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    for key in keys {
+        let base_value = b.as_ref().map(|n| n.properties.get(key));
+        let value = pick(
+            base_value.as_ref(),
+            &o.properties.get(key),
+            &t.properties.get(key),
+        )
+        .ok_or_else(|| format!("both sides changed property {key}"))?;
+        if let Some(value) = value {
+            properties.set_from(key, value, &[&o.properties, &t.properties]);
+        }
+    }
+
+    let base_body = b.as_ref().map(|n| n.body.as_str()).unwrap_or_default();
+    let body = merge3(base_body, &o.body, &t.body, "ours", "theirs")
+        .map_err(|_| "both sides changed the same lines".to_string())?;
+
+    let merged = Note {
+        id: id.to_string(),
+        title,
+        created,
+        updated,
+        deleted_at,
+        archived_at,
+        due,
+        remind,
+        body,
+        tags,
+        properties,
+        format: o.format,
+        size_bytes: 0,
+    };
+    Ok((rel, serialize_note(&merged, o.format).into_bytes()))
+}
+
+/// Backtick fence longer than any run of backticks in `text`.
+fn fence_for(text: &str) -> String {
+    let longest =
+        text.split(|c| c != '`').map(str::len).max().unwrap_or_default();
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// A `#conflict` note holding both versions of note `id`.
+fn conflict_note(
+    new_id: &str,
+    id: &str,
+    reason: &str,
+    sides: [(&str, &NoteFile); 2],
+) -> NoteFile {
+    let title = parse(id, sides[0].1).title;
+    let mut body = format!(
+        "Sync could not merge note {id}: {reason}. Both stores keep the \
+version from {}; copy what you need from the other version into {id}, then \
+delete this note.\n",
+        sides[0].0
+    );
+    for (label, file) in sides {
+        let text = String::from_utf8_lossy(&file.1);
+        let fence = fence_for(&text);
+        body.push_str(&format!(
+            "\n## {label}\n\n{fence}markdown\n{}\n{fence}\n",
+            text.trim_end()
+        ));
+    }
+    let now = timestamp_string();
+    let mut properties = Properties::default();
+    properties.set("conflict-of", id);
+    let note = Note {
+        id: new_id.to_string(),
+        title: format!("Conflict: {title}"),
+        created: now.clone(),
+        updated: now,
+        deleted_at: None,
+        archived_at: None,
+        due: None,
+        remind: None,
+        body,
+        tags: vec![CONFLICT_TAG.to_string()],
+        properties,
+        format: configured_format().unwrap_or(NoteFormat::Legacy),
+        size_bytes: 0,
+    };
+    let format = note.format;
+    (format!("{new_id}.md"), serialize_note(&note, format).into_bytes())
+}
+
+#[derive(Default)]
+struct Summary {
+    pulled: usize,
+    pushed: usize,
+    merged: usize,
+    conflicts: usize,
+}
+
+/// Handle `qn sync-dir <dir>`.
+pub(crate) fn sync_dirs(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let [peer] = args.as_slice() else {
+        return Err("Usage: qn sync-dir <dir>".into());
+    };
+    let peer = PathBuf::from(peer);
+    ensure_dir(&peer)?;
+    let local = fs::canonicalize(dir)?;
+    let peer = fs::canonicalize(&peer)?;
+    if local == peer {
+        return Err("Cannot sync the notes directory with itself".into());
+    }
+    let _peer_lock = lock_store(&peer, LockMode::Exclusive)?;
+
+    let ours = scan(&local)?;
+    let theirs = scan(&peer)?;
+    let base = scan(&base_dir(&local, &peer))?;
+    let ours_label = "this store".to_string();
+    let theirs_label = peer.display().to_string();
+
+    let ids: BTreeSet<&String> =
+        ours.keys().chain(theirs.keys()).chain(base.keys()).collect();
+    let mut reserved: HashSet<String> =
+        ids.iter().map(|s| s.to_string()).collect();
+    let mut synced = Files::new();
+    let mut summary = Summary::default();
+    for id in ids {
+        let (o, t, b) = (ours.get(id), theirs.get(id), base.get(id));
+        let kept = match (o, t) {
+            (Some(o), Some(t)) if o == t => Some(o.clone()),
+            (Some(o), Some(t)) if b == Some(o) => {
+                put(&local, id, Some(o), t)?;
+                summary.pulled += 1;
+                Some(t.clone())
+            }
+            (Some(o), Some(t)) if b == Some(t) => {
+                put(&peer, id, Some(t), o)?;
+                summary.pushed += 1;
+                Some(o.clone())
+            }
+            (Some(o), Some(t)) => match merge_note(id, b, o, t) {
+                Ok(merged) => {
+                    put(&local, id, Some(o), &merged)?;
+                    put(&peer, id, Some(t), &merged)?;
+                    summary.merged += 1;
+                    Some(merged)
+                }
+                Err(reason) => {
+                    put(&peer, id, Some(t), o)?;
+                    let new_id = generate_new_id(&local, &mut reserved)?;
+                    let note = conflict_note(
+                        &new_id,
+                        id,
+                        &reason,
+                        [(&ours_label, o), (&theirs_label, t)],
+                    );
+                    put(&local, &new_id, None, &note)?;
+                    put(&peer, &new_id, None, &note)?;
+                    println!(
+                        "Conflict in {id}: {reason}; both versions are in \
+note {new_id}"
+                    );
+                    synced.insert(new_id, note);
+                    summary.conflicts += 1;
+                    Some(o.clone())
+                }
+            },
+            // Present on one side only: purged on the other if it was
+            // synced before and is unchanged since, otherwise new or edited.
+            (Some(o), None) if b == Some(o) => {
+                fs::remove_file(local.join(&o.0))?;
+                summary.pulled += 1;
+                None
+            }
+            (Some(o), None) => {
+                put(&peer, id, None, o)?;
+                summary.pushed += 1;
+                Some(o.clone())
+            }
+            (None, Some(t)) if b == Some(t) => {
+                fs::remove_file(peer.join(&t.0))?;
+                summary.pushed += 1;
+                None
+            }
+            (None, Some(t)) => {
+                put(&local, id, None, t)?;
+                summary.pulled += 1;
+                Some(t.clone())
+            }
+            (None, None) => None,
+        };
+        if let Some(file) = kept {
+            synced.insert(id.clone(), file);
+        }
+    }
+
+    write_base(&base_dir(&local, &peer), &peer, &synced)?;
+    write_base(&base_dir(&peer, &local), &local, &synced)?;
+    println!(
+        "Synced with {}: {} pulled, {} pushed, {} merged, {} conflict(s)",
+        peer.display(),
+        summary.pulled,
+        summary.pushed,
+        summary.merged,
+        summary.conflicts
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(rel: &str, title: &str, tags: &str, body: &str) -> NoteFile {
+        (
+            rel.to_string(),
+            format!(
+                "Title: {title}\nCreated: 01Jan25 10:00 +00:00\nUpdated: \
+01Jan25 10:00 +00:00\nTags: {tags}\n---\n{body}"
+            )
+            .into_bytes(),
+        )
+    }
+
+    #[test]
+    fn merges_bodies_and_unions_tags() {
+        let base = file("n.md", "Plan", "#a", "one\ntwo\nthree\n");
+        let mut ours = file("n.md", "Plan", "#a, #b", "ONE\ntwo\nthree\n");
+        ours.1 = String::from_utf8(ours.1)
+            .unwrap()
+            .replace("Updated: 01Jan25 10:00", "Updated: 03Jan25 10:00")
+            .into_bytes();
+        let theirs =
+            file("archive/n.md", "Plan", "#a, #c", "one\ntwo\nthree\nfour\n");
+        let (rel, bytes) =
+            merge_note("n", Some(&base), &ours, &theirs).unwrap();
+        let merged = parse("n", &(rel.clone(), bytes));
+        assert_eq!(rel, "archive/n.md");
+        assert_eq!(merged.body, "ONE\ntwo\nthree\nfour\n");
+        assert_eq!(merged.tags, vec!["#a", "#b", "#c"]);
+        assert!(merged.updated.starts_with("03Jan25"));
+    }
+
+    #[test]
+    fn reports_unmergeable_edits() {
+        let base = file("n.md", "Plan", "", "one\n");
+        let ours = file("n.md", "Plan A", "", "one\n");
+        let theirs = file("n.md", "Plan B", "", "one\n");
+        let err = merge_note("n", Some(&base), &ours, &theirs);
+        assert_eq!(err.unwrap_err(), "both sides changed the title");
+        let ours = file("n.md", "Plan", "", "uno\n");
+        let theirs = file("n.md", "Plan", "", "eins\n");
+        assert!(merge_note("n", Some(&base), &ours, &theirs).is_err());
+        assert_eq!(fence_for("has ```rust fences"), "````");
+    }
+}
@@ -0,0 +1,442 @@
+//! `qn export html`: standalone HTML pages for notes plus index pages.
+//!
+//! Each exported note becomes `<id>.html`; `index.html` lists every exported
+//! note and `tags/<tag>.html` lists the notes carrying one tag. Markdown links
+//! to another exported note (`<id>`, `<id>.md`, `./<id>.md`) are rewritten to
+//! its page, so the output can be browsed or hosted as-is.
+
+use crate::index::load_index;
+use crate::note::{Note, cmp_dt, parse_note};
+use crate::query::Query;
+use crate::render::markdown_options;
+use crate::shared::migrate::resolve_active_note_path;
+use crate::{Area, args, tags};
+use pulldown_cmark::{CowStr, Event, Parser, Tag, html};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str =
+    "Usage: qn export html <id>... | --all | -t <tag> | -q <query> -o <dir>";
+
+const STYLESHEET: &str = r#"
+:root { color-scheme: light dark; --accent: #1e66f5; --muted: #6c6f85; }
+body { max-width: 46rem; margin: 2rem auto; padding: 0 1rem;
+  font: 16px/1.6 -apple-system, "Segoe UI", Roboto, sans-serif; }
+header { border-bottom: 1px solid #ccd0da; margin-bottom: 1.5rem; }
+header h1 { margin-bottom: .25rem; }
+.meta { color: var(--muted); font-size: .9rem; }
+.tags a { display: inline-block; margin: .25rem .25rem 0 0; padding: 0 .5rem;
+  border-radius: 1rem; background: #e6e9ef; color: inherit;
+  text-decoration: none; font-size: .85rem; }
+a { color: var(--accent); }
+pre { background: #eff1f5; padding: .75rem; overflow-x: auto; }
+code { font-family: "JetBrains Mono", Menlo, Consolas, monospace; }
+blockquote { margin-left: 0; padding-left: 1rem; border-left: 3px solid #ccd0da;
+  color: var(--muted); }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccd0da; padding: .25rem .5rem; }
+ul.notes { list-style: none; padding: 0; }
+ul.notes li { margin: .5rem 0; }
+nav { margin-bottom: 1rem; font-size: .9rem; }
+aside.backlinks { border-top: 1px solid #ccd0da; margin-top: 2rem; }
+@media (prefers-color-scheme: dark) {
+  body { background: #1e1e2e; color: #cdd6f4; }
+  header, th, td, blockquote { border-color: #45475a; }
+  pre, .tags a { background: #313244; }
+  a { color: #89b4fa; }
+}
+"#;
+
+/// Handle `qn export <format> ...`.
+pub(crate) fn export_notes(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("html") => export_html(args.collect(), dir),
+        Some(other) => {
+            Err(format!("Unsupported export format: {other} (try html)").into())
+        }
+        None => Err(USAGE.into()),
+    }
+}
+
+fn export_html(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut ids: Vec<String> = Vec::new();
+    let mut all = false;
+    let mut tag_filters: Vec<String> = Vec::new();
+    let mut query: Option<Query> = None;
+    let mut out_dir: Option<PathBuf> = None;
+    let mut parser = args::ArgParser::new(args, "export");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--all" | "-a" => all = true,
+            "-t" | "--tag" => {
+                if let Some(tag) = parser.extract_tag()? {
+                    tag_filters.push(tag);
+                }
+            }
+            "-q" | "--query" => {
+                query =
+                    Some(Query::parse(&parser.extract_value("-q/--query")?)?);
+            }
+            "-o" | "--out" => {
+                out_dir =
+                    Some(PathBuf::from(parser.extract_value("-o/--out")?));
+            }
+            other if !other.starts_with('-') => ids.push(other.to_string()),
+            other => {
+                return Err(format!("Unknown flag for export: {other}").into());
+            }
+        }
+    }
+    let out_dir = out_dir.ok_or_else(|| {
+        format!("Provide an output directory with -o\n{USAGE}")
+    })?;
+    if ids.is_empty() && !all && tag_filters.is_empty() && query.is_none() {
+        return Err(USAGE.into());
+    }
+
+    let notes = select_notes(dir, &ids, all, &tag_filters, query.as_ref())?;
+    if notes.is_empty() {
+        println!("No notes to export.");
+        return Ok(());
+    }
+    let tag_pages = write_site(&out_dir, &notes)?;
+    println!(
+        "Exported {} note(s) to {} ({} tag page(s))",
+        notes.len(),
+        out_dir.display(),
+        tag_pages
+    );
+    Ok(())
+}
+
+/// Full notes picked by id, or by `--all` / tag / query filters.
+fn select_notes(
+    dir: &Path,
+    ids: &[String],
+    all: bool,
+    tag_filters: &[String],
+    query: Option<&Query>,
+) -> Result<Vec<Note>, Box<dyn Error>> {
+    let mut wanted: Vec<String> = ids.to_vec();
+    if all || !tag_filters.is_empty() || query.is_some() {
+        let mut listed = load_index(dir)?.notes(Area::Active);
+        listed.retain(|n| tags::note_has_tags(n, tag_filters));
+        if let Some(query) = query {
+            listed = query.filter(dir, Area::Active, listed);
+        }
+        wanted.extend(listed.into_iter().map(|n| n.id));
+    }
+
+    let mut seen = HashSet::new();
+    let mut notes = Vec::new();
+    for id in wanted {
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let path = resolve_active_note_path(dir, &id)
+            .ok_or_else(|| format!("Note {id} not found"))?;
+        notes.push(parse_note(&path, fs::metadata(&path)?.len())?);
+    }
+    notes.sort_by(|a, b| cmp_dt(&b.updated, &a.updated));
+    Ok(notes)
+}
+
+/// Write note pages, the index and tag pages; returns the tag page count.
+fn write_site(out_dir: &Path, notes: &[Note]) -> std::io::Result<usize> {
+    let tag_dir = out_dir.join("tags");
+    fs::create_dir_all(&tag_dir)?;
+    let exported: HashSet<&str> = notes.iter().map(|n| n.id.as_str()).collect();
+
+    let mut by_tag: BTreeMap<&str, Vec<&Note>> = BTreeMap::new();
+    for note in notes {
+        for tag in &note.tags {
+            by_tag.entry(tag.as_str()).or_default().push(note);
+        }
+        fs::write(
+            out_dir.join(format!("{}.html", note.id)),
+            note_page(note, &exported),
+        )?;
+    }
+
+    let index_body = format!(
+        "<header><h1>Notes</h1><p class=\"meta\">{} note(s)</p>{}</header>\n{}",
+        notes.len(),
+        tag_links(by_tag.keys().copied(), ""),
+        note_list(notes.iter(), "")
+    );
+    fs::write(out_dir.join("index.html"), page("Notes", &index_body))?;
+
+    for (tag, tagged) in &by_tag {
+        let body = format!(
+            "<nav><a href=\"../index.html\">&larr; All notes</a></nav>\n\
+<header><h1>{}</h1><p class=\"meta\">{} note(s)</p></header>\n{}",
+            escape_html(tag),
+            tagged.len(),
+            note_list(tagged.iter().copied(), "../")
+        );
+        fs::write(
+            tag_dir.join(format!("{}.html", tag_slug(tag))),
+            page(tag, &body),
+        )?;
+    }
+    Ok(by_tag.len())
+}
+
+pub(crate) fn page(title: &str, body: &str) -> String {
+    page_with_head(title, "", body)
+}
+
+/// A full HTML document; `head` is extra markup for the `<head>` element.
+pub(crate) fn page_with_head(title: &str, head: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+<title>{}</title>\n<style>{STYLESHEET}</style>\n{head}</head>\n<body>\n{body}\n\
+</body>\n</html>\n",
+        escape_html(title)
+    )
+}
+
+fn note_page(note: &Note, exported: &HashSet<&str>) -> String {
+    let body = format!(
+        "<nav><a href=\"index.html\">&larr; All notes</a></nav>\n\
+<header><h1>{}</h1>\n<p class=\"meta\">Created {} &middot; Updated {}</p>\n{}\
+</header>\n<main>\n{}</main>",
+        escape_html(&note.title),
+        escape_html(&note.created),
+        escape_html(&note.updated),
+        tag_links(note.tags.iter().map(String::as_str), ""),
+        markdown_to_html(&note.body, |id| {
+            exported.contains(id).then(|| format!("{id}.html"))
+        })
+    );
+    page(&note.title, &body)
+}
+
+fn tag_links<'a>(tags: impl Iterator<Item = &'a str>, prefix: &str) -> String {
+    let links: Vec<String> = tags
+        .map(|tag| {
+            format!(
+                "<a href=\"{prefix}tags/{}.html\">{}</a>",
+                tag_slug(tag),
+                escape_html(tag)
+            )
+        })
+        .collect();
+    if links.is_empty() {
+        return String::new();
+    }
+    format!("<p class=\"tags\">{}</p>\n", links.join(""))
+}
+
+fn note_list<'a>(
+    notes: impl Iterator<Item = &'a Note>,
+    prefix: &str,
+) -> String {
+    let mut out = String::from("<ul class=\"notes\">\n");
+    for note in notes {
+        out.push_str(&format!(
+            "<li><a href=\"{prefix}{}.html\">{}</a> \
+<span class=\"meta\">{}</span></li>\n",
+            note.id,
+            escape_html(&note.title),
+            escape_html(&note.updated)
+        ));
+    }
+    out.push_str("</ul>");
+    out
+}
+
+/// Render markdown; links naming a note are pointed at `page_for(id)` when
+/// it returns a URL.
+pub(crate) fn markdown_to_html(
+    body: &str,
+    page_for: impl Fn(&str) -> Option<String>,
+) -> String {
+    render_markdown_html(body, page_for, false)
+}
+
+/// Like [`markdown_to_html`], for pages served next to the write API: raw
+/// HTML is shown as text and only http(s) and relative URLs are kept.
+pub(crate) fn markdown_to_safe_html(
+    body: &str,
+    page_for: impl Fn(&str) -> Option<String>,
+) -> String {
+    render_markdown_html(body, page_for, true)
+}
+
+/// Whether a browser would resolve `url` relative to the page or over
+/// http(s). Browsers ignore ASCII whitespace and control characters inside a
+/// scheme, so they are dropped before looking for one.
+fn is_safe_url(url: &str) -> bool {
+    let cleaned: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect();
+    let Some(colon) = cleaned.find(':') else { return true };
+    let scheme = &cleaned[..colon];
+    let is_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    // A colon after `/`, `?` or `#` is part of a relative path or query.
+    !is_scheme
+        || ["http", "https"].contains(&scheme.to_ascii_lowercase().as_str())
+}
+
+fn render_markdown_html(
+    body: &str,
+    page_for: impl Fn(&str) -> Option<String>,
+    safe: bool,
+) -> String {
+    fn checked(url: CowStr<'_>, safe: bool) -> CowStr<'_> {
+        if !safe || is_safe_url(&url) { url } else { CowStr::from("#") }
+    }
+    let events =
+        Parser::new_ext(body, markdown_options()).map(|event| match event {
+            Event::Start(Tag::Link { link_type, dest_url, title, id }) => {
+                let rewritten =
+                    note_link_target(&dest_url).and_then(|(note, fragment)| {
+                        let url = page_for(note)?;
+                        Some(match fragment {
+                            Some(fragment) => format!("{url}#{fragment}"),
+                            None => url,
+                        })
+                    });
+                let dest_url = rewritten.map(CowStr::from).unwrap_or(dest_url);
+                let dest_url = checked(dest_url, safe);
+                Event::Start(Tag::Link { link_type, dest_url, title, id })
+            }
+            Event::Start(Tag::Image { link_type, dest_url, title, id })
+                if safe =>
+            {
+                let dest_url = checked(dest_url, safe);
+                Event::Start(Tag::Image { link_type, dest_url, title, id })
+            }
+            Event::Html(raw) | Event::InlineHtml(raw) if safe => {
+                Event::Text(raw)
+            }
+            other => other,
+        });
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+/// Ids that the note body links to, in order of first appearance.
+pub(crate) fn linked_note_ids(body: &str) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for event in Parser::new_ext(body, markdown_options()) {
+        if let Event::Start(Tag::Link { dest_url, .. }) = event
+            && let Some((id, _)) = note_link_target(&dest_url)
+            && !ids.iter().any(|seen| seen == id)
+        {
+            ids.push(id.to_string());
+        }
+    }
+    ids
+}
+
+/// Split `<id>`, `<id>.md` or `./<id>.md` (with an optional `#fragment`)
+/// into the id and fragment; URLs and other paths yield `None`.
+pub(crate) fn note_link_target(dest: &str) -> Option<(&str, Option<&str>)> {
+    let (target, fragment) = match dest.split_once('#') {
+        Some((target, fragment)) => (target, Some(fragment)),
+        None => (dest, None),
+    };
+    let target = target.strip_prefix("./").unwrap_or(target);
+    let id = target.strip_suffix(".md").unwrap_or(target);
+    if id.is_empty() || id.contains(['/', ':', '.', '?']) {
+        return None;
+    }
+    Some((id, fragment))
+}
+
+/// File name for a tag page: `#Work/Q3` becomes `work-q3`.
+pub(crate) fn tag_slug(tag: &str) -> String {
+    let slug: String = tag
+        .trim_start_matches('#')
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if slug.is_empty() { "untagged".to_string() } else { slug }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_links_to_exported_notes_only() {
+        let exported: HashSet<&str> = ["abc", "def"].into_iter().collect();
+        let html = markdown_to_html(
+            "[one](abc.md) [two](./def#part) [three](zzz.md) \
+[web](https://example.com)",
+            |id| exported.contains(id).then(|| format!("{id}.html")),
+        );
+        assert!(html.contains("href=\"abc.html\""));
+        assert!(html.contains("href=\"def.html#part\""));
+        assert!(html.contains("href=\"zzz.md\""));
+        assert!(html.contains("href=\"https://example.com\""));
+        assert_eq!(
+            linked_note_ids("[a](abc.md) [b](abc) [c](x/y.md) [d](zzz#top)"),
+            vec!["abc", "zzz"]
+        );
+    }
+
+    #[test]
+    fn safe_html_escapes_markup_and_drops_script_urls() {
+        let html = markdown_to_safe_html(
+            "<script>alert(1)</script>\n\nhi <b onclick=x>there</b> \
+[a](javascript:alert(1)) [c](https://ok.example) [d](other.md) \
+[e](/n/x?at=1:2) ![i](data:image/svg+xml,x) <vbscript:y>",
+            |id| Some(format!("/n/{id}")),
+        );
+        assert!(!html.contains("<script") && !html.contains("<b "), "{html}");
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("<a href=\"#\">a</a>"), "{html}");
+        assert!(html.contains("<img src=\"#\""), "{html}");
+        assert!(html.contains("<a href=\"#\">vbscript:y</a>"), "{html}");
+        assert!(html.contains("href=\"https://ok.example\""));
+        assert!(html.contains("href=\"/n/other\""));
+        assert!(html.contains("href=\"/n/x?at=1:2\""));
+        assert!(!is_safe_url(" JaVa\tScRiPt:x"));
+        assert!(is_safe_url("notes/a:b") && is_safe_url("#top"));
+        // Exports keep the author's own HTML.
+        assert!(markdown_to_html("<b>x</b>", |_| None).contains("<b>x</b>"));
+    }
+
+    #[test]
+    fn slugs_and_escapes() {
+        assert_eq!(tag_slug("#Work/Q3"), "work-q3");
+        assert_eq!(tag_slug("#"), "untagged");
+        assert_eq!(escape_html("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+}
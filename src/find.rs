@@ -0,0 +1,241 @@
+//! `qn find`: live full-text search that jumps to the matching line.
+//!
+//! The picker runs with matching disabled; every keystroke reloads it from
+//! `qn __search <query>`, which asks the search index for notes and prints
+//! one `id:line:text` entry per matching line. The preview shows the note
+//! scrolled to that line, and choosing an entry opens `$EDITOR +line`.
+//! The index is revalidated once when `qn find` starts, so a keystroke only
+//! reads `.qn-search`.
+
+use crate::fzf::{self, FzfSelector};
+use crate::search::{
+    Clause, contains_sequence, load_search_index, open_search_index,
+    parse_query, tokenize,
+};
+use crate::shared::lock::{LockMode, lock_store};
+use crate::shared::migrate::resolve_active_note_path;
+use crate::shared::shell::shell_quote;
+use crate::{args, edit_note, git, tags};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use yansi::Paint;
+
+const USAGE: &str = "Usage: qn find [-t tag]...";
+
+/// Matching lines listed per note, and in total.
+const LINES_PER_NOTE: usize = 5;
+const MAX_ENTRIES: usize = 200;
+
+const HEADER: &str = "type to search note text · enter opens the line";
+
+/// Clauses for a query still being typed: unless it ends in a space, the
+/// last word is unfinished and matches as a prefix.
+fn live_clauses(query: &str) -> Vec<Clause> {
+    if query.ends_with(char::is_whitespace) || query.ends_with('*') {
+        parse_query(query)
+    } else {
+        parse_query(&format!("{query}*"))
+    }
+}
+
+fn line_matches(clauses: &[Clause], line: &str) -> bool {
+    let tokens = tokenize(line);
+    clauses.iter().any(|clause| match clause {
+        Clause::Term(t) => tokens.contains(t),
+        Clause::Prefix(p) => tokens.iter().any(|t| t.starts_with(p.as_str())),
+        Clause::Phrase(words) => contains_sequence(&tokens, words),
+    })
+}
+
+/// `id:line:text` entries for the notes matching `query`, best note first.
+fn search_entries(
+    dir: &Path,
+    query: &str,
+    tag_filters: &[String],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let clauses = live_clauses(query);
+    if clauses.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for hit in open_search_index(dir)?.search(&clauses) {
+        if !tags::note_has_tags(&hit.note, tag_filters) {
+            continue;
+        }
+        let Some(path) = resolve_active_note_path(dir, &hit.note.id) else {
+            continue;
+        };
+        let text = fs::read_to_string(&path)?;
+        let mut lines: Vec<(usize, &str)> = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line_matches(&clauses, line))
+            .take(LINES_PER_NOTE)
+            .map(|(n, line)| (n + 1, line.trim()))
+            .collect();
+        // A note can match on terms split across lines; point at the top.
+        if lines.is_empty() {
+            lines.push((1, hit.note.title.as_str()));
+        }
+        for (n, line) in lines {
+            entries.push(format!("{}:{n}:{}", hit.note.id, line));
+        }
+        if entries.len() >= MAX_ENTRIES {
+            entries.truncate(MAX_ENTRIES);
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+/// `-t` filters for `find` and `__search`; `__search` also takes the query.
+fn parse_args(
+    args: Vec<String>,
+    cmd: &str,
+) -> Result<(Vec<String>, Vec<String>), Box<dyn Error>> {
+    let mut tag_filters = Vec::new();
+    let mut words = Vec::new();
+    let mut literal = false;
+    let mut parser = args::ArgParser::new(args, cmd);
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            _ if literal => words.push(arg),
+            "--" => literal = true,
+            "-t" | "--tag" => {
+                if let Some(tag) = parser.extract_tag()? {
+                    tag_filters.push(tag);
+                }
+            }
+            other if other.starts_with('-') && other.len() > 1 => {
+                return Err(format!(
+                    "Unknown flag for {cmd}: {other}\n{USAGE}"
+                )
+                .into());
+            }
+            _ => words.push(arg),
+        }
+    }
+    Ok((tag_filters, words))
+}
+
+/// Handle `qn __search [-t tag]... [--] <query>`: the entries `qn find`
+/// reloads on every keystroke.
+pub(crate) fn search_lines(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let (tag_filters, words) = parse_args(args, "__search")?;
+    for entry in search_entries(dir, &words.join(" "), &tag_filters)? {
+        println!("{entry}");
+    }
+    Ok(())
+}
+
+/// Handle `qn __find-preview <id> <line>`: the note with line numbers and
+/// the hit line highlighted.
+pub(crate) fn preview(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let [id, line] = args.as_slice() else {
+        return Err("Usage: qn __find-preview <id> <line>".into());
+    };
+    let hit: usize = line.parse().unwrap_or(0);
+    let path = resolve_active_note_path(dir, id)
+        .ok_or_else(|| format!("Note {id} not found"))?;
+    let use_color = env::var("NO_COLOR").is_err();
+    for (n, text) in fs::read_to_string(&path)?.lines().enumerate() {
+        let n = n + 1;
+        let number = format!("{n:>4}");
+        if !use_color {
+            let marker = if n == hit { '>' } else { ' ' };
+            println!("{number}{marker} {text}");
+        } else if n == hit {
+            println!(
+                "{}  {}",
+                Paint::rgb(&number, 243, 139, 168).bold(),
+                Paint::rgb(text, 243, 139, 168).bold()
+            );
+        } else {
+            println!("{}  {text}", Paint::rgb(&number, 108, 112, 134));
+        }
+    }
+    Ok(())
+}
+
+/// Handle `qn find [-t tag]...`.
+pub(crate) fn find_notes(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let (tag_filters, words) = parse_args(args, "find")?;
+    if !words.is_empty() {
+        return Err(USAGE.into());
+    }
+    if !fzf::is_picker_available() {
+        return Err("qn find needs fzf or an interactive terminal".into());
+    }
+    // Pick up hand edits now rather than on every keystroke.
+    load_search_index(dir)?;
+    let exe = shell_quote(&env::current_exe()?.to_string_lossy());
+    let mut reload = format!("{exe} __search");
+    for tag in &tag_filters {
+        reload.push_str(&format!(" -t {}", shell_quote(tag)));
+    }
+    reload.push_str(" -- {q} 2>/dev/null || true");
+    let selector = FzfSelector::with_simple_preview()
+        .multi_select(false)
+        .preview(&format!("{exe} __find-preview {{1}} {{2}} 2>/dev/null"))
+        .preview_window("down:+{2}-/2")
+        .delimiter(":")
+        .header(HEADER)
+        .live_reload(&reload);
+
+    let selection = selector.pick("")?;
+    let Some((id, line)) = selection.items.first().and_then(|entry| {
+        let mut fields = entry.splitn(3, ':');
+        Some((fields.next()?.to_string(), fields.next()?.to_string()))
+    }) else {
+        println!("No selection made; nothing opened.");
+        return Ok(());
+    };
+    {
+        let _store = lock_store(dir, LockMode::Shared)?;
+        edit_note(vec![id, "--line".to_string(), line], dir)?;
+    }
+    git::autocommit(dir, "edit");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::fixtures;
+
+    #[test]
+    fn lists_each_matching_line_with_its_number() {
+        let tmp = tempfile::tempdir().unwrap();
+        let write = |id, title, tags, body| {
+            fixtures::write_note(tmp.path(), id, title, tags, body)
+        };
+        write("n1", "Roadmap", "#work", "intro\n  ship the parser\nparsers\n");
+        write("n2", "Groceries", "#home", "milk\nparse receipts\n");
+
+        let entries = search_entries(tmp.path(), "pars", &[]).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.contains(&"n1:7:ship the parser".to_string()));
+        assert!(entries.contains(&"n1:8:parsers".to_string()));
+        assert!(entries.contains(&"n2:7:parse receipts".to_string()));
+
+        // A finished word matches whole tokens only.
+        let entries = search_entries(tmp.path(), "parser ", &[]).unwrap();
+        assert_eq!(entries, vec!["n1:7:ship the parser"]);
+
+        let work = vec!["#work".to_string()];
+        let entries = search_entries(tmp.path(), "roadmap", &work).unwrap();
+        assert_eq!(entries, vec!["n1:1:Title: Roadmap"]);
+        assert!(search_entries(tmp.path(), "", &[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,429 @@
+//! YAML front matter support for Obsidian/Jekyll-compatible notes.
+//! Only the subset front matter needs is understood: `key: value` scalars,
+//! quoted strings, and inline (`[a, b]`) or block (`- a`) lists. Anything
+//! else (block scalars, nested maps) is kept verbatim as a raw property.
+
+use crate::note::{
+    Note, NoteFormat, RESERVED_HEADERS, TIME_FMT, parse_timestamp,
+};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+/// Split `---\nyaml\n---\nbody` into its YAML block and body.
+pub(crate) fn split_front_matter(raw: &str) -> Option<(&str, &str)> {
+    let rest =
+        raw.strip_prefix("---\n").or_else(|| raw.strip_prefix("---\r\n"))?;
+    if let Some(body) = rest.strip_prefix("---\n") {
+        return Some(("", body));
+    }
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+enum Value {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+/// Fill note fields from a front matter block; unknown keys become properties.
+pub(crate) fn apply_front_matter(yaml: &str, note: &mut Note) {
+    for (key, (value, raw)) in parse_yaml(yaml) {
+        match (key.to_ascii_lowercase().as_str(), value) {
+            ("title", Value::Scalar(v)) => note.title = v,
+            ("created", Value::Scalar(v)) => {
+                note.created = iso_to_timestamp(&v)
+            }
+            ("updated", Value::Scalar(v)) => {
+                note.updated = iso_to_timestamp(&v)
+            }
+            ("deleted", Value::Scalar(v)) => {
+                note.deleted_at = Some(iso_to_timestamp(&v))
+            }
+            ("archived", Value::Scalar(v)) => {
+                note.archived_at = Some(iso_to_timestamp(&v))
+            }
+            ("due", Value::Scalar(v)) if !v.is_empty() => {
+                note.due = Some(iso_to_timestamp(&v))
+            }
+            ("remind", Value::Scalar(v)) if !v.is_empty() => {
+                note.remind = Some(iso_to_timestamp(&v))
+            }
+            ("tags", value) => {
+                let items = match value {
+                    Value::List(items) => items,
+                    Value::Scalar(v) => {
+                        v.split([',', ' ']).map(|t| t.to_string()).collect()
+                    }
+                };
+                note.tags = items
+                    .iter()
+                    .map(|t| super::normalize_tag(t))
+                    .filter(|t| !t.is_empty())
+                    .collect();
+            }
+            (_, value) => {
+                let reserved = RESERVED_HEADERS
+                    .iter()
+                    .any(|r| r.eq_ignore_ascii_case(&key));
+                if key.is_empty() || reserved {
+                    continue;
+                }
+                let text = match value {
+                    Value::List(items) => format!("[{}]", items.join(", ")),
+                    Value::Scalar(v) => v,
+                };
+                match raw {
+                    Some(raw) => note.properties.set_raw(
+                        &key,
+                        &text,
+                        NoteFormat::FrontMatter,
+                        &raw,
+                    ),
+                    None => note.properties.set(&key, &text),
+                }
+            }
+        }
+    }
+}
+
+/// Serialize the note header as a YAML front matter block.
+pub(crate) fn render_front_matter(note: &Note) -> String {
+    let mut out = String::from("---\n");
+    out.push_str(&format!("title: {}\n", yaml_scalar(&note.title)));
+    out.push_str(&format!("created: {}\n", timestamp_to_iso(&note.created)));
+    out.push_str(&format!("updated: {}\n", timestamp_to_iso(&note.updated)));
+    if let Some(deleted) = &note.deleted_at {
+        out.push_str(&format!("deleted: {}\n", timestamp_to_iso(deleted)));
+    }
+    if let Some(archived) = &note.archived_at {
+        out.push_str(&format!("archived: {}\n", timestamp_to_iso(archived)));
+    }
+    if let Some(due) = &note.due {
+        out.push_str(&format!("due: {}\n", timestamp_to_iso(due)));
+    }
+    if let Some(remind) = &note.remind {
+        out.push_str(&format!("remind: {}\n", timestamp_to_iso(remind)));
+    }
+    if note.tags.is_empty() {
+        out.push_str("tags: []\n");
+    } else {
+        out.push_str("tags:\n");
+        for tag in &note.tags {
+            out.push_str(&format!(
+                "  - {}\n",
+                yaml_scalar(tag.trim_start_matches('#'))
+            ));
+        }
+    }
+    for (key, value, raw) in note.properties.entries(NoteFormat::FrontMatter) {
+        if let Some(raw) = raw {
+            out.push_str(raw);
+            out.push('\n');
+            continue;
+        }
+        let rendered = if value.starts_with('[') && value.ends_with(']') {
+            value.to_string()
+        } else {
+            yaml_scalar(value)
+        };
+        out.push_str(&format!("{key}: {rendered}\n"));
+    }
+    out.push_str("---\n");
+    out
+}
+
+/// Top-level entries as `(key, (value, raw))`, where `raw` holds the text of
+/// an entry the value does not fully capture.
+fn parse_yaml(yaml: &str) -> Vec<(String, (Value, Option<String>))> {
+    // Group lines into top-level entries: a `key:` line plus the indented
+    // (or `- item`) lines under it.
+    let mut blocks: Vec<(&str, &str, Vec<&str>)> = Vec::new();
+    for line in yaml.lines() {
+        let trimmed = line.trim();
+        let nested = line.starts_with([' ', '\t']) || trimmed.starts_with('-');
+        if let Some((_, _, lines)) = blocks.last_mut()
+            && (nested || trimmed.is_empty())
+        {
+            lines.push(line);
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let Some((key, rest)) = line.split_once(':') else { continue };
+        blocks.push((key.trim(), rest, vec![line]));
+    }
+
+    let mut entries = Vec::new();
+    for (key, rest, mut lines) in blocks {
+        while lines.last().is_some_and(|l| l.trim().is_empty()) {
+            lines.pop();
+        }
+        let rest = strip_comment(rest.trim());
+        let nested: Vec<&str> = lines[1..]
+            .iter()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect();
+        // Only plain `- item` lines make a list; maps inside items do not.
+        let items: Option<Vec<String>> = nested
+            .iter()
+            .map(|l| l.strip_prefix('-').filter(|item| !item.contains(": ")))
+            .map(|item| item.map(|item| unquote(item.trim())))
+            .collect();
+        let modelled = !rest.starts_with(['|', '>', '{'])
+            && (nested.is_empty() || (rest.is_empty() && items.is_some()));
+        if !modelled {
+            entries.push((key.to_string(), raw_value(rest, &lines)));
+            continue;
+        }
+        let value = if !nested.is_empty() {
+            Value::List(items.unwrap_or_default())
+        } else if let Some(inner) =
+            rest.strip_prefix('[').and_then(|r| r.strip_suffix(']'))
+        {
+            Value::List(
+                inner
+                    .split(',')
+                    .map(|i| unquote(i.trim()))
+                    .filter(|i| !i.is_empty())
+                    .collect(),
+            )
+        } else if rest.is_empty() && key.eq_ignore_ascii_case("tags") {
+            Value::List(Vec::new())
+        } else {
+            // A bare `key:` with no list items is an empty scalar.
+            Value::Scalar(unquote(rest))
+        };
+        entries.push((key.to_string(), (value, None)));
+    }
+    entries
+}
+
+/// An entry this parser does not model (block scalars, nested maps,
+/// multi-line values): a best-effort plain value plus its verbatim text.
+fn raw_value(rest: &str, lines: &[&str]) -> (Value, Option<String>) {
+    let body = lines[1..].iter().map(|l| l.trim());
+    let value = match rest.chars().next() {
+        Some('|') => body.collect::<Vec<_>>().join("\n"),
+        Some('>') => {
+            body.filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" ")
+        }
+        _ => std::iter::once(rest)
+            .chain(body)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    (Value::Scalar(value), Some(lines.join("\n")))
+}
+
+fn strip_comment(value: &str) -> &str {
+    if value.starts_with(['"', '\'']) {
+        return value;
+    }
+    match value.find(" #") {
+        Some(idx) => value[..idx].trim_end(),
+        None => value,
+    }
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let inner = &value[1..value.len() - 1];
+        let mut out = String::new();
+        let mut chars = inner.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '\\' {
+                match chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(other) => out.push(other),
+                    None => out.push('\\'),
+                }
+            } else {
+                out.push(ch);
+            }
+        }
+        return out;
+    }
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].replace("''", "'");
+    }
+    value.to_string()
+}
+
+/// Quote a scalar only when plain YAML would misread it.
+fn yaml_scalar(value: &str) -> String {
+    let lower = value.to_ascii_lowercase();
+    let needs_quotes = value.is_empty()
+        || value != value.trim()
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.ends_with(':')
+        || value.contains(['\n', '\t', '"'])
+        || value.starts_with([
+            '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '%', '@',
+            '`', ',', '?', '-',
+        ])
+        || matches!(
+            lower.as_str(),
+            "true" | "false" | "null" | "yes" | "no" | "~"
+        )
+        || value.parse::<f64>().is_ok();
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+/// Convert an ISO 8601 date or datetime into the note timestamp format.
+/// Values that are not recognizable dates are kept verbatim.
+pub(crate) fn iso_to_timestamp(value: &str) -> String {
+    let value = value.trim();
+    if parse_timestamp(value).is_some() {
+        return value.to_string();
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return dt.format(TIME_FMT).to_string();
+    }
+    for fmt in ["%Y-%m-%dT%H:%M:%S%.f%:z", "%Y-%m-%d %H:%M:%S%:z"] {
+        if let Ok(dt) = DateTime::parse_from_str(value, fmt) {
+            return dt.format(TIME_FMT).to_string();
+        }
+    }
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|fmt| NaiveDateTime::parse_from_str(value, fmt).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+    });
+    match naive.and_then(|n| Local.from_local_datetime(&n).earliest()) {
+        Some(dt) => dt.format(TIME_FMT).to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Render a note timestamp as RFC 3339 for front matter.
+pub(crate) fn timestamp_to_iso(ts: &str) -> String {
+    match parse_timestamp(ts) {
+        Some(dt) => dt.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        None => yaml_scalar(ts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank_note() -> Note {
+        Note {
+            id: "fm".to_string(),
+            title: String::new(),
+            created: String::new(),
+            updated: String::new(),
+            deleted_at: None,
+            archived_at: None,
+            due: None,
+            remind: None,
+            body: String::new(),
+            tags: Vec::new(),
+            properties: Default::default(),
+            format: Default::default(),
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn splits_front_matter_block() {
+        let raw = "---\ntitle: A\n---\nbody\n";
+        assert_eq!(split_front_matter(raw), Some(("title: A\n", "body\n")));
+        assert_eq!(split_front_matter("Title: A\n---\nbody"), None);
+        assert_eq!(split_front_matter("---\nno close\n"), None);
+    }
+
+    #[test]
+    fn parses_lists_dates_and_extras() {
+        let yaml = "title: \"Project: brief\"\ncreated: 2025-06-01T10:00:00+02:00\n\
+updated: 2025-06-02\ntags:\n  - work\n  - '#plan'\naliases: [brief, pb]\n\
+status: draft # inline comment\n";
+        let mut note = blank_note();
+        apply_front_matter(yaml, &mut note);
+        assert_eq!(note.title, "Project: brief");
+        assert_eq!(note.created, "01Jun25 10:00 +02:00");
+        assert!(note.updated.starts_with("02Jun25 00:00"));
+        assert_eq!(note.tags, vec!["#work", "#plan"]);
+        assert_eq!(note.properties.get("aliases"), Some("[brief, pb]"));
+        assert_eq!(note.properties.get("status"), Some("draft"));
+    }
+
+    #[test]
+    fn unmodelled_yaml_is_written_back_verbatim() {
+        let yaml = "title: Trip\ndescription: >\n  folded\n  text\n\
+meta:\n  author: bob\n  links:\n    - a\nsteps: |\n  one\n\n  two\n\
+people:\n  - name: ann\nsee: [x, y]\n";
+        let mut note = blank_note();
+        apply_front_matter(yaml, &mut note);
+        assert_eq!(note.properties.get("description"), Some("folded text"));
+        assert_eq!(note.properties.get("steps"), Some("one\n\ntwo"));
+        assert_eq!(note.properties.get("see"), Some("[x, y]"));
+
+        let rendered = render_front_matter(&note);
+        for block in [
+            "\ndescription: >\n  folded\n  text\n",
+            "\nmeta:\n  author: bob\n  links:\n    - a\n",
+            "\nsteps: |\n  one\n\n  two\n",
+            "\npeople:\n  - name: ann\n",
+            "\nsee: [x, y]\n",
+        ] {
+            assert!(rendered.contains(block), "{block:?} in\n{rendered}");
+        }
+        let (again, _) = split_front_matter(&rendered).unwrap();
+        let mut parsed = blank_note();
+        apply_front_matter(again, &mut parsed);
+        assert_eq!(parsed.properties, note.properties);
+    }
+
+    #[test]
+    fn render_round_trips() {
+        let mut note = blank_note();
+        note.title = "yes".to_string();
+        note.created = "01Jun25 10:00 +02:00".to_string();
+        note.updated = "02Jun25 11:30 +02:00".to_string();
+        note.tags = vec!["#todo".to_string()];
+        note.due = Some("05Jun25 09:00 +02:00".to_string());
+        note.properties.set("Source", "https://example.com");
+        let rendered = render_front_matter(&note);
+        assert!(rendered.contains("due: 2025-06-05T09:00:00+02:00\n"));
+        assert!(rendered.contains("title: \"yes\"\n"));
+        assert!(rendered.contains("created: 2025-06-01T10:00:00+02:00\n"));
+        assert!(rendered.contains("tags:\n  - todo\n"));
+
+        let (yaml, _) = split_front_matter(&rendered).unwrap();
+        let mut parsed = blank_note();
+        apply_front_matter(yaml, &mut parsed);
+        assert_eq!(parsed.title, note.title);
+        assert_eq!(parsed.created, note.created);
+        assert_eq!(parsed.updated, note.updated);
+        assert_eq!(parsed.tags, note.tags);
+        assert_eq!(parsed.due, note.due);
+        assert_eq!(parsed.properties, note.properties);
+    }
+}
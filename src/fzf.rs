@@ -1,3 +1,4 @@
+use crate::picker;
 use std::env;
 use std::error::Error;
 use std::io::Write;
@@ -11,6 +12,18 @@ pub struct FzfSelector {
     height: Option<String>,
     layout: Option<String>,
     preview_window: Option<String>,
+    delimiter: Option<String>,
+    header: Option<String>,
+    binds: Vec<String>,
+    expect: Vec<String>,
+    reload: Option<String>,
+}
+
+/// What the user chose, and with which `expect` key (if any)
+#[derive(Debug, Default, PartialEq)]
+pub struct Selection {
+    pub key: Option<String>,
+    pub items: Vec<String>,
 }
 
 impl FzfSelector {
@@ -21,6 +34,11 @@ impl FzfSelector {
             height: None,
             layout: None,
             preview_window: None,
+            delimiter: None,
+            header: None,
+            binds: Vec::new(),
+            expect: Vec::new(),
+            reload: None,
         }
     }
 
@@ -37,6 +55,11 @@ impl FzfSelector {
             height: None,
             layout: None,
             preview_window: None,
+            delimiter: None,
+            header: None,
+            binds: Vec::new(),
+            expect: Vec::new(),
+            reload: None,
         }
     }
 
@@ -49,6 +72,11 @@ impl FzfSelector {
             height,
             layout: Some("reverse".to_string()),
             preview_window: Some("down:wrap".to_string()),
+            delimiter: None,
+            header: None,
+            binds: Vec::new(),
+            expect: Vec::new(),
+            reload: None,
         }
     }
 
@@ -67,6 +95,49 @@ impl FzfSelector {
         self
     }
 
+    pub fn preview(mut self, command: &str) -> Self {
+        self.preview_command = Some(command.to_string());
+        self
+    }
+
+    /// `--preview-window`, e.g. `right:60%` or `down:+{2}-/2` to scroll to
+    /// the line in field 2
+    pub fn preview_window(mut self, spec: &str) -> Self {
+        self.preview_window = Some(spec.to_string());
+        self
+    }
+
+    /// Split entries into fields for `{1}`, `{+1}` and friends
+    pub fn delimiter(mut self, delimiter: &str) -> Self {
+        self.delimiter = Some(delimiter.to_string());
+        self
+    }
+
+    pub fn header(mut self, header: &str) -> Self {
+        self.header = Some(header.to_string());
+        self
+    }
+
+    /// Bind a key to fzf actions, e.g. `ctrl-d:execute(...)+reload(...)`;
+    /// the built-in picker has no equivalent and ignores binds
+    pub fn bind(mut self, binding: &str) -> Self {
+        self.binds.push(binding.to_string());
+        self
+    }
+
+    /// End the picker on `key` and report it in [`Selection::key`]
+    pub fn expect(mut self, key: &str) -> Self {
+        self.expect.push(key.to_string());
+        self
+    }
+
+    /// Replace the entries with the output of `command` whenever the query
+    /// changes, instead of filtering them; `{q}` is the quoted query
+    pub fn live_reload(mut self, command: &str) -> Self {
+        self.reload = Some(command.to_string());
+        self
+    }
+
     /// Select from a list of file paths
     pub fn select_from_paths(
         &self,
@@ -86,10 +157,13 @@ impl FzfSelector {
         &self,
         input: &str,
     ) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self.pick(input)?.items)
+    }
+
+    /// Select from raw input, reporting which `expect` key ended the picker
+    pub fn pick(&self, input: &str) -> Result<Selection, Box<dyn Error>> {
         if !is_fzf_available() {
-            return Err(
-                "fzf is not installed or QUICK_NOTES_NO_FZF is set".into()
-            );
+            return self.select_natively(input);
         }
 
         let mut cmd = Command::new("fzf");
@@ -114,6 +188,28 @@ impl FzfSelector {
             cmd.arg("--preview-window").arg(preview_window);
         }
 
+        if let Some(ref delimiter) = self.delimiter {
+            cmd.arg("--delimiter").arg(delimiter);
+        }
+
+        if let Some(ref header) = self.header {
+            cmd.arg("--header").arg(header);
+        }
+
+        for binding in &self.binds {
+            cmd.arg("--bind").arg(binding);
+        }
+
+        if !self.expect.is_empty() {
+            cmd.arg("--expect").arg(self.expect.join(","));
+        }
+
+        if let Some(ref reload) = self.reload {
+            cmd.arg("--disabled")
+                .arg("--bind")
+                .arg(format!("change:reload({reload})"));
+        }
+
         let mut child =
             cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
 
@@ -124,11 +220,44 @@ impl FzfSelector {
         let output = child.wait_with_output()?;
 
         if !output.status.success() || output.stdout.is_empty() {
-            return Ok(Vec::new()); // User cancelled
+            return Ok(Selection::default()); // User cancelled
         }
 
         let selected = String::from_utf8_lossy(&output.stdout);
-        Ok(selected.lines().map(|s| s.to_string()).collect())
+        let mut lines = selected.lines().map(|s| s.to_string());
+        // With --expect, the first line names the key (empty for Enter).
+        let key = if self.expect.is_empty() {
+            None
+        } else {
+            lines.next().filter(|k| !k.is_empty())
+        };
+        Ok(Selection { key, items: lines.collect() })
+    }
+
+    /// Fall back to the built-in picker with the same options.
+    fn select_natively(
+        &self,
+        input: &str,
+    ) -> Result<Selection, Box<dyn Error>> {
+        if !picker::is_available() {
+            return Err("fzf is not installed or QUICK_NOTES_NO_FZF is set, \
+and there is no terminal for the built-in picker"
+                .into());
+        }
+        let items = input.lines().map(String::from).collect();
+        let options = picker::Options {
+            multi: self.multi_select,
+            height: self.height.as_deref(),
+            layout: self.layout.as_deref(),
+            preview: self.preview_command.as_deref(),
+            preview_window: self.preview_window.as_deref(),
+            delimiter: self.delimiter.as_deref(),
+            header: self.header.as_deref(),
+            expect: &self.expect,
+            reload: self.reload.as_deref(),
+        };
+        let (key, items) = picker::pick(items, &options)?;
+        Ok(Selection { key, items })
     }
 
     /// Select note IDs from file paths
@@ -174,6 +303,12 @@ pub fn is_fzf_available() -> bool {
     })
 }
 
+/// Check if an interactive picker can run: fzf, or the built-in one on a
+/// terminal
+pub fn is_picker_available() -> bool {
+    is_fzf_available() || picker::is_available()
+}
+
 /// Get the renderer binary name (cached)
 fn get_renderer_name() -> &'static str {
     static RENDERER: OnceLock<&str> = OnceLock::new();
@@ -243,6 +378,25 @@ mod tests {
         assert_eq!(selector.layout.as_deref(), Some("reverse"));
     }
 
+    #[test]
+    fn test_fzf_selector_actions() {
+        let selector = FzfSelector::new()
+            .delimiter("\t")
+            .bind("ctrl-d:execute-silent(qn delete {+1})")
+            .expect("ctrl-y")
+            .expect("enter");
+
+        assert_eq!(selector.delimiter.as_deref(), Some("\t"));
+        assert_eq!(selector.binds.len(), 1);
+        assert_eq!(selector.expect, vec!["ctrl-y", "enter"]);
+
+        let selector = FzfSelector::new()
+            .live_reload("qn __search {q} || true")
+            .preview_window("up:+{2}-/2");
+        assert_eq!(selector.reload.as_deref(), Some("qn __search {q} || true"));
+        assert_eq!(selector.preview_window.as_deref(), Some("up:+{2}-/2"));
+    }
+
     #[test]
     fn test_with_note_preview() {
         let selector = FzfSelector::with_note_preview();
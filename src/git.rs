@@ -0,0 +1,427 @@
+//! Optional git versioning of the notes directory.
+//!
+//! When the notes directory has its own `.git`, every mutating command ends
+//! with a commit of the note files it wrote (the whole tree for store-wide
+//! commands), taken under `.qn-git.lock` so concurrent commands never share a
+//! commit. `qn log` reads that history and `qn sync` commits hand edits,
+//! rebases onto and pushes to a remote. Without `.git` nothing here runs. All
+//! work shells out to the `git` binary.
+
+use crate::args;
+use crate::note::parse_note;
+use crate::shared::lock::lock_git;
+use crate::shared::table::render_table;
+use std::cell::RefCell;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Commands whose changes are committed automatically.
+const AUTOCOMMIT_COMMANDS: &[&str] = &[
+    "add",
+    "new",
+    "edit",
+    "delete",
+    "archive",
+    "undelete",
+    "unarchive",
+    "migrate",
+    "migrate-ids",
+    "convert-format",
+    "seed",
+    "delete-all",
+    "prop",
+    "props",
+    "revert",
+    "restore",
+    "sync",
+    "sync-dir",
+    "tag",
+    "due",
+    "remind",
+    "done",
+    "undo-task",
+];
+
+/// qn's caches and locks; kept out of commits via `.git/info/exclude`.
+const EXCLUDES: &[&str] = &[
+    "/.qn.lock",
+    "/.qn-git.lock",
+    "/.locks/",
+    "/.qn-index",
+    "/.qn-search",
+    "/.history/",
+    "/.qn-sync/",
+];
+
+/// Commands that run under the exclusive store lock; nothing else writes
+/// meanwhile, so they commit the whole work tree.
+const STORE_WIDE_COMMANDS: &[&str] = &[
+    "migrate",
+    "migrate-ids",
+    "convert-format",
+    "delete-all",
+    "restore",
+    "sync",
+    "sync-dir",
+];
+
+const DEFAULT_REMOTE: &str = "origin";
+
+thread_local! {
+    /// Note files written or moved since the last autocommit on this thread.
+    static TOUCHED: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Remember note files a command changed, so its commit holds only those
+/// even when other qn processes are writing at the same time.
+pub(crate) fn record_touched(paths: &[&Path]) {
+    TOUCHED
+        .with(|t| t.borrow_mut().extend(paths.iter().map(|p| p.to_path_buf())));
+}
+
+pub(crate) fn is_repo(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// Run `git` in `dir` and return its stdout.
+fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Could not run git: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            stderr.trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn ensure_excludes(dir: &Path) -> io::Result<()> {
+    let path = dir.join(".git").join("info").join("exclude");
+    let current = fs::read_to_string(&path).unwrap_or_default();
+    let missing: Vec<&str> = EXCLUDES
+        .iter()
+        .copied()
+        .filter(|line| !current.lines().any(|l| l.trim() == *line))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let mut updated = current;
+    if !updated.is_empty() && !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated.push_str("# quick_notes caches and locks\n");
+    for line in missing {
+        updated.push_str(line);
+        updated.push('\n');
+    }
+    fs::create_dir_all(path.parent().unwrap_or(dir))?;
+    fs::write(path, updated)
+}
+
+/// A changed note's id and, when it still exists, its title.
+type Touched = (String, Option<String>);
+
+/// Subject (and body for several notes) of an automatic commit.
+fn commit_message(cmd: &str, notes: &[Touched]) -> String {
+    let verb = if cmd == "props" { "prop" } else { cmd };
+    let line = |(id, title): &Touched| match title {
+        Some(title) => format!("{id}: {title}"),
+        None => id.clone(),
+    };
+    match notes {
+        [] => format!("{verb}: update notes"),
+        [one] => format!("{verb} {}", line(one)),
+        many => {
+            let mut msg = format!("{verb} {} notes\n\n", many.len());
+            for note in many {
+                msg.push_str(&format!("- {}\n", line(note)));
+            }
+            msg
+        }
+    }
+}
+
+/// Notes touched by the staged changes, in path order, with a title when
+/// the note still exists somewhere in the store.
+fn staged_notes(dir: &Path) -> Result<Vec<Touched>, Box<dyn Error>> {
+    let staged =
+        git(dir, &["diff", "--cached", "--name-only", "--no-renames", "-z"])?;
+    let mut notes: Vec<Touched> = Vec::new();
+    for rel in staged.split('\0').filter(|p| p.ends_with(".md")) {
+        let path = dir.join(rel);
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let title = fs::metadata(&path)
+            .ok()
+            .and_then(|meta| parse_note(&path, meta.len()).ok())
+            .map(|note| note.title);
+        match notes.iter_mut().find(|(seen, _)| seen == id) {
+            Some(existing) => {
+                if existing.1.is_none() {
+                    existing.1 = title;
+                }
+            }
+            None => notes.push((id.to_string(), title)),
+        }
+    }
+    Ok(notes)
+}
+
+/// Stage `paths` (everything when empty) and commit them; returns whether
+/// a commit was made.
+fn commit_all(
+    dir: &Path,
+    cmd: &str,
+    paths: &[PathBuf],
+) -> Result<bool, Box<dyn Error>> {
+    ensure_excludes(dir)?;
+    // Concurrent commands each stage and commit under this lock, so one
+    // never sweeps up another's files or trips over `.git/index.lock`.
+    let _lock = lock_git(dir)?;
+    if paths.is_empty() {
+        git(dir, &["add", "-A", "--", "."])?;
+    }
+    let (present, gone): (Vec<&PathBuf>, Vec<&PathBuf>) =
+        paths.iter().partition(|p| p.exists());
+    for (files, command) in [
+        (present, &["add", "--"][..]),
+        (gone, &["rm", "-q", "--cached", "--ignore-unmatch", "--"][..]),
+    ] {
+        let rels: Vec<&str> = files
+            .iter()
+            .filter_map(|p| p.strip_prefix(dir).ok().and_then(Path::to_str))
+            .collect();
+        // Batched to stay well under the OS argument length limit.
+        for chunk in rels.chunks(500) {
+            git(dir, &[command, chunk].concat())?;
+        }
+    }
+    if git(dir, &["diff", "--cached", "--quiet"]).is_ok() {
+        return Ok(false);
+    }
+    let message = commit_message(cmd, &staged_notes(dir)?);
+    git(dir, &["commit", "-q", "-m", &message])?;
+    Ok(true)
+}
+
+/// Commit the changes `cmd` made, if the store is a git repository.
+///
+/// Failures are reported but never fail the command: the notes themselves
+/// were already written.
+pub(crate) fn autocommit(dir: &Path, cmd: &str) {
+    let mut touched = TOUCHED.with(|t| std::mem::take(&mut *t.borrow_mut()));
+    if !is_repo(dir) || !AUTOCOMMIT_COMMANDS.contains(&cmd) {
+        return;
+    }
+    if STORE_WIDE_COMMANDS.contains(&cmd) {
+        touched.clear();
+    }
+    touched.sort();
+    touched.dedup();
+    if let Err(e) = commit_all(dir, cmd, &touched) {
+        eprintln!("Git commit skipped: {e}");
+    }
+}
+
+fn require_repo(dir: &Path) -> Result<(), Box<dyn Error>> {
+    if is_repo(dir) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not a git repository; run `git init` there to enable versioning",
+            dir.display()
+        )
+        .into())
+    }
+}
+
+/// `qn log [id] [-n N]`: commits touching the store or one note.
+pub(crate) fn show_log(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "Usage: qn log [id] [-n N]";
+    require_repo(dir)?;
+    let mut id: Option<String> = None;
+    let mut limit: Option<usize> = None;
+    let mut parser = args::ArgParser::new(args, "log");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "-n" | "--limit" => {
+                let raw = parser.extract_value("-n/--limit")?;
+                limit = Some(
+                    raw.parse().map_err(|_| format!("Invalid limit: {raw}"))?,
+                );
+            }
+            other if !other.starts_with('-') && id.is_none() => {
+                id = Some(other.to_string());
+            }
+            other => {
+                return Err(format!(
+                    "Unknown argument for log: {other}\n{USAGE}"
+                )
+                .into());
+            }
+        }
+    }
+
+    if git(dir, &["rev-parse", "--verify", "-q", "HEAD"]).is_err() {
+        println!("No commits yet.");
+        return Ok(());
+    }
+    let mut log_args: Vec<String> = vec![
+        "log".into(),
+        "--format=%h%x09%ad%x09%s".into(),
+        "--date=format:%d%b%y %H:%M".into(),
+    ];
+    if let Some(n) = limit {
+        log_args.push(format!("-n{n}"));
+    }
+    if let Some(id) = &id {
+        // Notes move between areas, so follow the id in any directory.
+        log_args.push("--".into());
+        log_args.push(format!(":(glob)**/{id}.md"));
+    }
+    let log_args: Vec<&str> = log_args.iter().map(String::as_str).collect();
+    let out = git(dir, &log_args)?;
+    let rows: Vec<Vec<String>> = out
+        .lines()
+        .map(|line| line.splitn(3, '\t').map(String::from).collect())
+        .collect();
+    if rows.is_empty() {
+        match id {
+            Some(id) => println!("No commits touch {id}."),
+            None => println!("No commits yet."),
+        }
+        return Ok(());
+    }
+    let headers: Vec<String> =
+        ["Commit", "Date", "Message"].map(String::from).to_vec();
+    println!("{}", render_table(&headers, &rows));
+    Ok(())
+}
+
+fn count(dir: &Path, range: &str) -> Result<usize, Box<dyn Error>> {
+    let out = git(dir, &["rev-list", "--count", range])?;
+    out.trim()
+        .parse()
+        .map_err(|_| format!("Unexpected git output: {out}").into())
+}
+
+/// `qn sync [--remote name]`: commit pending edits, rebase onto the remote
+/// branch, then push.
+pub(crate) fn sync(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    require_repo(dir)?;
+    let mut remote = env::var("QUICK_NOTES_GIT_REMOTE")
+        .ok()
+        .filter(|r| !r.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_REMOTE.to_string());
+    let mut parser = args::ArgParser::new(args, "sync");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--remote" => remote = parser.extract_value("--remote")?,
+            other => {
+                return Err(format!(
+                    "Unknown argument for sync: {other}\nUsage: qn sync [--remote name] \
+(use qn sync-dir <dir> to reconcile with another notes directory)"
+                )
+                .into());
+            }
+        }
+    }
+    if git(dir, &["remote", "get-url", &remote]).is_err() {
+        return Err(format!(
+            "No git remote named {remote}; add one with `git -C {} remote add {remote} <url>`",
+            dir.display()
+        )
+        .into());
+    }
+
+    // Hand edits made outside qn are committed before rebasing.
+    commit_all(dir, "sync", &[])?;
+    let branch =
+        git(dir, &["symbolic-ref", "--short", "HEAD"])?.trim().to_string();
+    git(dir, &["fetch", "-q", &remote])?;
+    let upstream = format!("{remote}/{branch}");
+    let mut pulled = 0;
+    if git(dir, &["rev-parse", "--verify", "-q", &upstream]).is_ok() {
+        pulled = count(dir, &format!("HEAD..{upstream}"))?;
+        if git(dir, &["rebase", "-q", &upstream]).is_err() {
+            let _ = git(dir, &["rebase", "--abort"]);
+            return Err(format!(
+                "Sync stopped: local and {upstream} changes conflict; \
+resolve with git in {} and run qn sync again",
+                dir.display()
+            )
+            .into());
+        }
+    }
+    let has_head = git(dir, &["rev-parse", "--verify", "-q", "HEAD"]).is_ok();
+    let pushed = if !has_head {
+        0
+    } else if git(dir, &["rev-parse", "--verify", "-q", &upstream]).is_ok() {
+        count(dir, &format!("{upstream}..HEAD"))?
+    } else {
+        count(dir, "HEAD")?
+    };
+    if has_head {
+        git(dir, &["push", "-q", "-u", &remote, &branch])?;
+    }
+    println!(
+        "Synced with {upstream}: pulled {pulled}, pushed {pushed} commit(s)"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_messages_name_the_notes() {
+        let one =
+            vec![("0Abc123".to_string(), Some("Project brief".to_string()))];
+        assert_eq!(commit_message("edit", &one), "edit 0Abc123: Project brief");
+        let gone = vec![("n1".to_string(), None)];
+        assert_eq!(commit_message("props", &gone), "prop n1");
+        let many = vec![
+            ("n1".to_string(), Some("One".to_string())),
+            ("n2".to_string(), None),
+        ];
+        assert_eq!(
+            commit_message("archive", &many),
+            "archive 2 notes\n\n- n1: One\n- n2\n"
+        );
+        assert_eq!(commit_message("migrate", &[]), "migrate: update notes");
+    }
+
+    #[test]
+    fn excludes_are_added_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir_all(tmp.path().join(".git/info")).unwrap();
+        fs::write(tmp.path().join(".git/info/exclude"), "*.swp").unwrap();
+        ensure_excludes(tmp.path()).unwrap();
+        ensure_excludes(tmp.path()).unwrap();
+        let text =
+            fs::read_to_string(tmp.path().join(".git/info/exclude")).unwrap();
+        assert!(text.starts_with("*.swp\n# quick_notes"));
+        assert_eq!(text.matches("/.qn-index").count(), 1);
+    }
+}
@@ -49,7 +49,7 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
     HelpTopic {
         name: "list",
         summary: "List notes with previews; sorted by updated desc by default.",
-        usage: "qn list [--sort created|updated|size] [--asc|--desc] [-s text] [-t tag] [--relative|-r] [--all|-a]",
+        usage: "qn list [--sort created|updated|size] [--asc|--desc] [-s text] [-t tag] [-w key=value] [-q query] [--relative|-r] [--all|-a]",
         details: &[
             "Matches search text against title and body (case-insensitive).",
             "Tag filters accept normalized tags; multiple tags require that all are present.",
@@ -71,6 +71,14 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
                 name: "-t, --tag <tag>",
                 desc: "Filter by tag (normalized to #tag).",
             },
+            HelpFlag {
+                name: "-w, --where <key=value>",
+                desc: "Filter by header property (case-insensitive, repeatable).",
+            },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Filter with a query expression (see `qn help query`).",
+            },
             HelpFlag {
                 name: "--relative, -r",
                 desc: "Show age instead of absolute timestamps.",
@@ -85,12 +93,13 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
         examples: &[
             "qn list --sort size --desc",
             "qn list -s meeting -t #todo",
+            "qn list -q 'tag:#work AND NOT tag:#done AND updated:>2025-06-01'",
         ],
     },
     HelpTopic {
         name: "list-deleted",
         summary: "List trashed notes with created/updated/deleted columns.",
-        usage: "qn list-deleted [--sort created|updated|size] [--asc|--desc] [-s text] [-t tag] [--relative|-r] [--all|-a]",
+        usage: "qn list-deleted [--sort created|updated|size] [--asc|--desc] [-s text] [-t tag] [-w key=value] [-q query] [--relative|-r] [--all|-a]",
         details: &[
             "Behaves like list but reads from the trash directory and shows Deleted timestamps.",
             "Old trash entries expire after QUICK_NOTES_TRASH_RETENTION_DAYS (default 30).",
@@ -112,6 +121,14 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
                 name: "-t, --tag <tag>",
                 desc: "Filter by tag (normalized to #tag).",
             },
+            HelpFlag {
+                name: "-w, --where <key=value>",
+                desc: "Filter by header property (case-insensitive, repeatable).",
+            },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Filter with a query expression (see `qn help query`).",
+            },
             HelpFlag {
                 name: "--relative, -r",
                 desc: "Show age instead of absolute timestamps.",
@@ -128,7 +145,7 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
     HelpTopic {
         name: "list-archived",
         summary: "List archived notes; shows when each entry was archived.",
-        usage: "qn list-archived [--sort created|updated|size] [--asc|--desc] [-s text] [-t tag] [--relative|-r] [--all|-a]",
+        usage: "qn list-archived [--sort created|updated|size] [--asc|--desc] [-s text] [-t tag] [-w key=value] [-q query] [--relative|-r] [--all|-a]",
         details: &[
             "Reads from the archive directory and includes Archived timestamps.",
             "Useful for finding older notes that were tucked away but not deleted.",
@@ -150,6 +167,14 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
                 name: "-t, --tag <tag>",
                 desc: "Filter by tag (normalized to #tag).",
             },
+            HelpFlag {
+                name: "-w, --where <key=value>",
+                desc: "Filter by header property (case-insensitive, repeatable).",
+            },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Filter with a query expression (see `qn help query`).",
+            },
             HelpFlag {
                 name: "--relative, -r",
                 desc: "Show age instead of absolute timestamps.",
@@ -166,10 +191,11 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
     HelpTopic {
         name: "view",
         summary: "Render one or more notes; works as `qn view` or `qn render`.",
-        usage: "qn view <id>... [--render|-r] [--plain|-p] [-t tag]",
+        usage: "qn view <id>... [--render|-r] [--plain|-p] [-t tag] [-q query]",
         details: &[
             "Loads each id, enforces optional tag filters, and prints the header plus rendered body.",
-            "Uses glow for rich Markdown when available; falls back to internal styling.",
+            "Uses glow for rich Markdown when available; otherwise the built-in renderer styles headings, emphasis, links, quotes, nested/task lists, tables and footnotes.",
+            "Fenced code blocks are highlighted by their info string (rust, sh, sql, json, python, js/ts, toml, yaml); other languages use a flat code color.",
         ],
         flags: &[
             HelpFlag {
@@ -184,6 +210,10 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
                 name: "-t, --tag <tag>",
                 desc: "Only show notes containing the tag.",
             },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Also show every active note matching the query.",
+            },
         ],
         aliases: &["render"],
         section: Section::Command,
@@ -195,26 +225,39 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
     HelpTopic {
         name: "edit",
         summary: "Open notes in $EDITOR; supports tag guards and fzf multi-select.",
-        usage: "qn edit <id>... [-t tag]",
+        usage: "qn edit <id>... [-t tag] [-q query] [-l line]",
         details: &[
-            "When no ids are provided, fzf launches a picker with previews (default 70% height; override with QUICK_NOTES_FZF_HEIGHT or QUICK_NOTES_FZF_FULLSCREEN, unless QUICK_NOTES_NO_FZF is set).",
+            "When no ids are provided, fzf launches a picker with previews (default 70% height; override with QUICK_NOTES_FZF_HEIGHT or QUICK_NOTES_FZF_FULLSCREEN). Without fzf, or with QUICK_NOTES_NO_FZF set, the built-in picker takes its place.",
             "After saving, the Updated header is refreshed; missing tag filters skip the note.",
         ],
-        flags: &[HelpFlag {
-            name: "-t, --tag <tag>",
-            desc: "Require that selected notes contain the tag.",
-        }],
+        flags: &[
+            HelpFlag {
+                name: "-t, --tag <tag>",
+                desc: "Require that selected notes contain the tag.",
+            },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Open every active note matching the query.",
+            },
+            HelpFlag {
+                name: "-l, --line <n>",
+                desc: "Start the editor at line n (passed as `+n`).",
+            },
+        ],
         aliases: &[],
         section: Section::Command,
-        examples: &["qn edit -t #todo"],
+        examples: &[
+            "qn edit -t #todo",
+            "qn edit -q 'title:standup updated:>=2025-06-01'",
+        ],
     },
     HelpTopic {
         name: "delete",
         summary: "Soft-delete notes to trash; interactive with fzf when requested.",
-        usage: "qn delete [ids...] [--fzf] [-t tag]",
+        usage: "qn delete [ids...] [--fzf] [-t tag] [-q query]",
         details: &[
             "Moves files into the trash directory and stamps a Deleted time; trash is cleaned after retention days.",
-            "With no ids, `--fzf` (and an installed fzf) opens a multi-select picker with previews.",
+            "With no ids, `--fzf` opens a multi-select picker with previews (fzf, or the built-in picker when fzf is missing).",
         ],
         flags: &[
             HelpFlag {
@@ -225,10 +268,18 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
                 name: "-t, --tag <tag>",
                 desc: "Only delete notes containing the tag.",
             },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Delete every active note matching the query.",
+            },
         ],
         aliases: &[],
         section: Section::Command,
-        examples: &["qn delete --fzf", "qn delete id1 id2 -t #done"],
+        examples: &[
+            "qn delete --fzf",
+            "qn delete id1 id2 -t #done",
+            "qn delete -q 'tag:#scratch AND updated:<2024-01-01'",
+        ],
     },
     HelpTopic {
         name: "delete-all",
@@ -246,18 +297,28 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
     HelpTopic {
         name: "archive",
         summary: "Move notes to the archive; interactive when fzf is available.",
-        usage: "qn archive <ids...> [--fzf]",
+        usage: "qn archive <ids...> [--fzf] [-q query]",
         details: &[
             "Archives keep content indefinitely but hide from the active list.",
-            "With no ids, requires --fzf and an installed fzf to pick entries.",
+            "With no ids, --fzf picks entries with fzf or, when fzf is missing, the built-in picker.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "--fzf",
+                desc: "Interactive picker when no ids are supplied.",
+            },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Archive every active note matching the query.",
+            },
         ],
-        flags: &[HelpFlag {
-            name: "--fzf",
-            desc: "Interactive picker when no ids are supplied.",
-        }],
         aliases: &[],
         section: Section::Command,
-        examples: &["qn archive --fzf", "qn archive id1 id2"],
+        examples: &[
+            "qn archive --fzf",
+            "qn archive id1 id2",
+            "qn archive -q 'tag:#done AND updated:<2025-01-01'",
+        ],
     },
     HelpTopic {
         name: "unarchive",
@@ -312,6 +373,33 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
         section: Section::Command,
         examples: &["qn migrate ~/Downloads/old_notes"],
     },
+    HelpTopic {
+        name: "convert-format",
+        summary: "Rewrite every note with legacy headers or YAML front matter.",
+        usage: "qn convert-format <frontmatter|legacy> [--dry-run]",
+        details: &[
+            "Converts active, migrated, trashed, and archived notes; notes already in the target format are left alone.",
+            "Front matter uses lowercase keys, ISO 8601 timestamps, and a tags list without `#`, so Obsidian and Jekyll read it as-is.",
+            "Extra properties are carried over in both directions; Created/Updated are not bumped.",
+            "Front matter qn does not model (block scalars, nested maps) is kept verbatim while a note stays in front matter; converting to legacy flattens it to one line.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "--to <format>",
+                desc: "Target format (same as the positional argument).",
+            },
+            HelpFlag {
+                name: "-n, --dry-run",
+                desc: "List the notes that would change without writing them.",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn convert-format frontmatter --dry-run",
+            "qn convert-format legacy",
+        ],
+    },
     HelpTopic {
         name: "tags",
         summary: "List tags with counts and first/last usage; supports search.",
@@ -334,6 +422,25 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
         section: Section::Command,
         examples: &["qn tags -s todo", "qn tags -r"],
     },
+    HelpTopic {
+        name: "prop",
+        summary: "Read or change extra header properties on a note.",
+        usage: "qn prop set <id> <key> <value...> | qn prop get <id> [key] | qn prop unset <id> <key>",
+        details: &[
+            "Any header line besides Title/Created/Updated/Deleted/Archived/Due/Remind/Tags (e.g. `Source:`, `Project:`, `Status:`) is kept as a property and survives add/edit rewrites in its original order; lines qn cannot read as `key: value` are kept verbatim.",
+            "`set` only accepts keys without spaces or `:`; existing keys with spaces are still read and kept.",
+            "`set` and `unset` bump the Updated header; `get` without a key prints every property as `key: value`.",
+            "Filter lists by property with `qn list --where key=value`.",
+        ],
+        flags: &[],
+        aliases: &["props"],
+        section: Section::Command,
+        examples: &[
+            "qn prop set 0Abc123 Status in review",
+            "qn prop get 0Abc123 status",
+            "qn list --where project=apollo",
+        ],
+    },
     HelpTopic {
         name: "seed",
         summary: "Generate bulk test notes with optional markdown bodies.",
@@ -373,6 +480,496 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
         section: Section::Command,
         examples: &["qn stats"],
     },
+    HelpTopic {
+        name: "history",
+        summary: "List saved revisions of a note with timestamps and size changes.",
+        usage: "qn history <id>",
+        details: &[
+            "Every add/edit/prop/revert that changes a note first saves the previous file under `.history/<id>/`.",
+            "Rows are newest first; `current` is the note on disk and Change is the size delta from the revision before it.",
+            "Revisions older than QUICK_NOTES_HISTORY_RETENTION_DAYS (default 90) are pruned.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn history 0Nc8Vt2xQ"],
+    },
+    HelpTopic {
+        name: "diff",
+        summary: "Show a colored unified diff between two versions of a note.",
+        usage: "qn diff <id> [rev] [rev]",
+        details: &[
+            "With only an id, compares the newest revision with the current note.",
+            "One revision compares it with the current note; two compare them with each other.",
+            "Revisions can be shortened to any unique prefix; `current` names the note on disk.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn diff 0Nc8Vt2xQ", "qn diff 0Nc8Vt2xQ 8CuWn current"],
+    },
+    HelpTopic {
+        name: "revert",
+        summary: "Restore an active note to a saved revision.",
+        usage: "qn revert <id> <rev>",
+        details: &[
+            "Replaces title, tags, properties, and body with the revision and bumps Updated.",
+            "The version being replaced is saved as a new revision, so a revert can itself be reverted.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn revert 0Nc8Vt2xQ 8CuWn"],
+    },
+    HelpTopic {
+        name: "search",
+        summary: "Ranked full-text search over active notes.",
+        usage: "qn search <query> [-n N] [-t tag]",
+        details: &[
+            "Words must all appear (in any order); results are ranked with BM25 and title hits count three times as much as body hits.",
+            "Quote a phrase to require the words next to each other, and end a word with `*` to match any term starting with it (at least two characters).",
+            "Postings live in `.qn-search`; each qn write appends a small record to it, and searches compact those records now and then (as does `qn reindex`). Notes whose size or mtime changed by hand are re-read on the next search.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "-n, --limit <N>",
+                desc: "Show at most N results (default 20)",
+            },
+            HelpFlag {
+                name: "-t, --tag <tag>",
+                desc: "Only notes with this tag (repeatable)",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn search kubernetes rollout",
+            "qn search '\"error handling\"' deploy*",
+            "qn search retro -t work -n 5",
+        ],
+    },
+    HelpTopic {
+        name: "export",
+        summary: "Export notes as standalone HTML pages with index pages.",
+        usage: "qn export html <id>... | --all | -t tag | -q query -o <dir>",
+        details: &[
+            "Writes <id>.html per note with its title, created/updated times and tags, plus index.html and tags/<tag>.html listing the exported notes.",
+            "Pages embed their stylesheet (light and dark), so the folder can be opened locally or hosted as-is.",
+            "Markdown links to another exported note, such as [plan](<id>.md) or [plan](<id>), are rewritten to that note's page.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "-o, --out <dir>",
+                desc: "Output directory (created if missing; required).",
+            },
+            HelpFlag { name: "--all, -a", desc: "Export every active note." },
+            HelpFlag {
+                name: "-t, --tag <tag>",
+                desc: "Export notes with the tag (repeatable; all must match).",
+            },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Export notes matching a query expression.",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn export html --all -o site",
+            "qn export html -t #design -o /tmp/design-notes",
+            "qn export html 8CuXHEsTz 8CuXHEsU0 -o share",
+        ],
+    },
+    HelpTopic {
+        name: "publish",
+        summary: "Publish tagged notes as an incremental static site.",
+        usage: "qn publish [-t tag]... [-q query] -o <dir> [--title text] [--base-url url]",
+        details: &[
+            "Publishes active notes tagged #public unless other tags are given (all must match); -q narrows further.",
+            "Each note lands at posts/<yyyy>/<mm>/<slug>.html based on its created date and a `slug` property or its title; duplicates get the id appended.",
+            "Generates index.html, tags/<tag>.html, archive/index.html with archive/<yyyy-mm>.html, \"Linked from\" backlinks on posts, and an Atom feed.xml.",
+            "Runs are incremental: unchanged files are left alone and pages of notes that are no longer published are removed.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "-o, --out <dir>",
+                desc: "Site directory (required).",
+            },
+            HelpFlag {
+                name: "-t, --tag <tag>",
+                desc: "Publish notes with the tag (default #public).",
+            },
+            HelpFlag {
+                name: "-q, --query <expr>",
+                desc: "Only publish notes matching a query expression.",
+            },
+            HelpFlag {
+                name: "--title <text>",
+                desc: "Site and feed title (default Notes).",
+            },
+            HelpFlag {
+                name: "--base-url <url>",
+                desc: "Absolute site URL used for feed links.",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn publish -t #public -o site/",
+            "qn publish -o /srv/intranet/notes --title \"Team notes\" --base-url https://intranet.example/notes/",
+        ],
+    },
+    HelpTopic {
+        name: "backup",
+        summary: "Snapshot the whole notes store into one .tar.gz archive.",
+        usage: "qn backup -o <file.tar.gz|dir> [--rotate N]",
+        details: &[
+            "Captures active notes, migrated batches, trash, and archive; revision history and caches are left out.",
+            "A MANIFEST inside the archive lists each note's id, size, and SHA-256 so restore can verify it.",
+            "When -o is a directory (or --rotate is given), the file is named qn-backup-<timestamp>.tar.gz.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "-o, --out <path>",
+                desc: "Archive file or directory to write into (required).",
+            },
+            HelpFlag {
+                name: "--rotate <N>",
+                desc: "Keep only the newest N qn-backup-*.tar.gz snapshots in that directory.",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn backup -o notes.tar.gz",
+            "qn backup -o ~/backups/ --rotate 7",
+        ],
+    },
+    HelpTopic {
+        name: "restore",
+        summary: "Restore notes from a qn backup archive.",
+        usage: "qn restore <file.tar.gz> [--into dir] [--merge|--replace]",
+        details: &[
+            "Every file and its destination path are checked against the archive's MANIFEST before anything is written; a mismatch or unsafe path aborts the restore.",
+            "Restoring into a store that already has notes requires --merge or --replace.",
+            "--merge skips notes that are already present unchanged and gives a new id to a note whose id is taken, like other id conflicts.",
+            "--replace sets the target's notes in every area aside, then drops them once the backup is written; if writing fails they are put back.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "--into <dir>",
+                desc: "Restore into this directory instead of the notes directory.",
+            },
+            HelpFlag {
+                name: "--merge",
+                desc: "Add the backup's notes to existing ones.",
+            },
+            HelpFlag {
+                name: "--replace",
+                desc: "Discard existing notes before restoring.",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn restore notes.tar.gz --into /tmp/notes-copy",
+            "qn restore ~/backups/qn-backup-20250101-120000.000.tar.gz --merge",
+        ],
+    },
+    HelpTopic {
+        name: "log",
+        summary: "Show the git history of the notes directory or one note.",
+        usage: "qn log [id] [-n N]",
+        details: &[
+            "Versioning is opt-in: run `git init` inside the notes directory and every add/new/edit/delete/archive/migrate/prop/revert/restore creates a commit such as \"edit <id>: <title>\".",
+            "Each commit holds only the notes that command wrote, even when several qn commands run at once; hand edits are committed by `qn sync`.",
+            "With an id, lists the commits that touched that note in any area (active, migrated, trash, archive).",
+            "qn's caches and locks are added to .git/info/exclude so they never get committed.",
+        ],
+        flags: &[HelpFlag {
+            name: "-n, --limit <N>",
+            desc: "Show at most N commits.",
+        }],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn log", "qn log 8CuXHEsTz -n 5"],
+    },
+    HelpTopic {
+        name: "sync",
+        summary: "Sync with a git remote.",
+        usage: "qn sync [--remote name]",
+        details: &[
+            "Commits any pending hand edits, fetches the remote, rebases local commits onto <remote>/<branch>, then pushes.",
+            "If the rebase conflicts, it is aborted and nothing is pushed; resolve with git in the notes directory and sync again.",
+            "The remote defaults to QUICK_NOTES_GIT_REMOTE or origin.",
+            "To reconcile with another notes directory instead, use qn sync-dir.",
+        ],
+        flags: &[HelpFlag {
+            name: "--remote <name>",
+            desc: "Git remote to sync with.",
+        }],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "git -C ~/.quick_notes remote add origin git@example.com:me/notes.git",
+            "qn sync",
+            "qn sync --remote backup",
+        ],
+    },
+    HelpTopic {
+        name: "sync-dir",
+        summary: "Reconcile this store with another notes directory.",
+        usage: "qn sync-dir <dir>",
+        details: &[
+            "Notes changed on one side are copied to the other, using the state recorded at the last sync (in .qn-sync/) as the base.",
+            "Notes changed on both sides are merged: line-level three-way merge of the body, union of tags, the later Updated.",
+            "When that fails (same lines, title, or property changed on both sides) both stores keep this store's version and get a #conflict note showing both versions.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn sync-dir ~/Dropbox/notes"],
+    },
+    HelpTopic {
+        name: "serve",
+        summary: "Serve notes over a local HTTP JSON API.",
+        usage: "qn serve [--port N] [--host addr] [--token T] [--allow-origin origin] [--ui]",
+        details: &[
+            "Endpoints: GET/POST /notes, GET/DELETE /notes/<id>, and POST /notes/<id>/append, /tags, /archive, /restore; see the README for bodies.",
+            "GET /notes accepts the list filters as query parameters: area, tag, q, search, where, sort, order.",
+            "Binds to 127.0.0.1 by default. With a token every request needs `Authorization: Bearer <token>`; without one, only requests whose Host is localhost, 127.0.0.1, [::1] or the bound address on the served port are answered (this stops DNS rebinding).",
+            "Browser requests carrying an Origin header are refused unless --allow-origin names that origin (or *).",
+            "With --ui, paths outside /notes serve read-only HTML pages: / (notes), /archive, /trash and /n/<id>. Pages reload when note files change; with a token, open /?token=<token> once to sign in (the cookie is not accepted by /notes). Raw HTML in notes is shown as text and only http(s) and relative links are kept.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "-p, --port <N>",
+                desc: "Port to listen on (default 7878; 0 picks a free one).",
+            },
+            HelpFlag {
+                name: "--host <addr>",
+                desc: "Address to bind (default 127.0.0.1).",
+            },
+            HelpFlag {
+                name: "--token <T>",
+                desc: "Require this bearer token (default QUICK_NOTES_SERVE_TOKEN).",
+            },
+            HelpFlag {
+                name: "--allow-origin <origin>",
+                desc: "Answer CORS requests from this origin, or * for any.",
+            },
+            HelpFlag {
+                name: "--ui",
+                desc: "Also serve a read-only web UI for browsing notes.",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn serve",
+            "qn serve --port 9000 --token \"$QN_TOKEN\"",
+            "qn serve --ui",
+            "curl -d '{\"body\":\"idea\"}' http://127.0.0.1:7878/notes",
+        ],
+    },
+    HelpTopic {
+        name: "lsp",
+        summary: "Run a language server for editing notes (stdio).",
+        usage: "qn lsp",
+        details: &[
+            "Speaks the Language Server Protocol on stdin/stdout; point your editor's LSP client at `qn lsp` for markdown files.",
+            "Completion: note ids (matched by id or title) after `[[`, and existing tags on the `Tags:` header line.",
+            "Go-to-definition and hover previews on `[[id]]`, `[[id|label]]` and `[text](id.md)` links.",
+            "Diagnostics: links to notes that do not exist, unreadable Created/Updated timestamps and Due/Remind dates, a missing title and header lines qn ignores.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn lsp"],
+    },
+    HelpTopic {
+        name: "tui",
+        summary: "Browse notes in a full-screen terminal UI.",
+        usage: "qn tui",
+        details: &[
+            "Left pane: the `qn list` table for the current area; right pane: the selected note rendered as markdown (plain with NO_COLOR).",
+            "j/k or arrows move, PgUp/PgDn page, J/K scroll the preview, `/` searches titles and bodies as you type (Enter keeps, Esc clears).",
+            "t opens the tag pane: space toggles a tag filter, c clears them. Tab (or 1/2/3) switches between notes, archive and trash.",
+            "e or Enter edits in $EDITOR, a archives, d deletes, r restores from the archive or trash, + changes tags (`+ops -draft`), q quits.",
+            "Changes made elsewhere show up within a second; actions commit to git like the matching commands.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn tui", "NO_COLOR=1 qn tui"],
+    },
+    HelpTopic {
+        name: "pick",
+        summary: "Triage notes from a picker with keys for each action.",
+        usage: "qn pick [-t tag]... [-q query]",
+        details: &[
+            "Lists active notes (id, title, tags) in fzf with a rendered preview; Tab marks several notes for an action.",
+            "enter views in $PAGER, ctrl-e edits, ctrl-d moves to the trash, ctrl-a archives, ctrl-t prompts for `+tag -tag` edits; the list reloads in place after each one.",
+            "ctrl-y exits and prints the paths of the marked (or highlighted) notes, e.g. for `vim $(qn pick)`. Esc exits.",
+            "Without fzf (or with QUICK_NOTES_NO_FZF) the built-in picker runs the same keys, reopening after each action.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "-t, --tag <tag>",
+                desc: "Only list notes with this tag (repeatable).",
+            },
+            HelpFlag {
+                name: "-q, --query <query>",
+                desc: "Only list notes matching a query (see `qn help query`).",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn pick",
+            "qn pick -t #inbox",
+            "vim $(qn pick -q 'title:plan')",
+        ],
+    },
+    HelpTopic {
+        name: "find",
+        summary: "Search note text as you type and open the matching line.",
+        usage: "qn find [-t tag]...",
+        details: &[
+            "Each keystroke re-runs the full-text search (see `qn help search`) and lists every matching line as `id:line:text`; the last word matches as a prefix until you type a space.",
+            "The preview shows the note scrolled to the hit. enter opens `$EDITOR +line` on the note and refreshes its Updated header, like `qn edit`.",
+            "Without fzf (or with QUICK_NOTES_NO_FZF) the built-in picker reloads the same way.",
+        ],
+        flags: &[HelpFlag {
+            name: "-t, --tag <tag>",
+            desc: "Only search notes with this tag (repeatable).",
+        }],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn find", "qn find -t #work"],
+    },
+    HelpTopic {
+        name: "agenda",
+        summary: "List notes by due date: overdue, today and upcoming.",
+        usage: "qn agenda [--days N] [-t tag]...",
+        details: &[
+            "Reads the `Due:` header of active notes; notes due before today are overdue, and upcoming covers the next N days (7 by default).",
+            "Set a due date with `qn due <id> <when>` or by typing a `Due:` line in `qn edit`; see `qn help due` for the accepted forms.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "-d, --days <n>",
+                desc: "How many days ahead count as upcoming.",
+            },
+            HelpFlag {
+                name: "-t, --tag <tag>",
+                desc: "Only include notes with this tag (repeatable).",
+            },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn agenda", "qn agenda --days 14 -t #todo"],
+    },
+    HelpTopic {
+        name: "due",
+        summary: "Show, set or clear a note's due date.",
+        usage: "qn due <id> [when...|--clear]",
+        details: &[
+            "`when` is natural input: `today`, `tomorrow 9am`, a weekday (`fri` means the next Friday), `+3d`/`in 2 hours` (m, h, d, w), a time (`17:30`, `5pm`) or `2025-06-01 14:00`. A day without a time means 09:00.",
+            "The result is written as a `Due:` header timestamp and Updated is bumped. Natural values typed into the header by hand are resolved the next time `qn edit` saves the note.",
+            "Without `when`, prints the current due date.",
+        ],
+        flags: &[HelpFlag { name: "--clear", desc: "Remove the due date." }],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn due 0Abc123 fri",
+            "qn due 0Abc123 tomorrow 9am",
+            "qn due 0Abc123 --clear",
+        ],
+    },
+    HelpTopic {
+        name: "remind",
+        summary: "List reminders, check for pending ones, or set one on a note.",
+        usage: "qn remind [--check] | qn remind <id> [when...|--clear]",
+        details: &[
+            "Without arguments, lists notes with a `Remind:` header: pending (the time has passed) and upcoming.",
+            "`--check` prints only pending reminders and exits non-zero when there are any, so cron jobs and shell prompts can surface them; it is silent and succeeds otherwise.",
+            "With an id, sets the reminder using the same `when` forms as `qn due` (e.g. `+1h` to snooze); `--clear` dismisses it.",
+        ],
+        flags: &[HelpFlag {
+            name: "--check",
+            desc: "Print pending reminders; exit 1 if there are any.",
+        }],
+        aliases: &[],
+        section: Section::Command,
+        examples: &[
+            "qn remind 0Abc123 fri 10am",
+            "qn remind --check || notify-send 'Notes need attention'",
+            "qn remind 0Abc123 --clear",
+        ],
+    },
+    HelpTopic {
+        name: "todos",
+        summary: "List Markdown checkbox tasks across notes.",
+        usage: "qn todos [-t tag]... [--done|--open]",
+        details: &[
+            "Scans note bodies for `- [ ]` / `- [x]` list items (any bullet or numbered list; fenced code is skipped).",
+            "Each task is shown as `<id>:<line>`, the file line, so it can be passed to `qn done`, `qn undo-task` or `qn edit --line`.",
+            "An inline `@due(...)` marker is shown in the Due column, read with the same forms as `qn due` relative to the note's Updated time.",
+        ],
+        flags: &[
+            HelpFlag {
+                name: "-t, --tag <tag>",
+                desc: "Only scan notes with this tag (repeatable).",
+            },
+            HelpFlag { name: "--done", desc: "Only list checked tasks." },
+            HelpFlag { name: "--open", desc: "Only list unchecked tasks." },
+        ],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn todos --open", "qn todos -t work --done"],
+    },
+    HelpTopic {
+        name: "done",
+        summary: "Check off a task by `<id>:<line>`.",
+        usage: "qn done <id>:<line>...",
+        details: &[
+            "Turns `[ ]` into `[x]` on that line of the note, bumps Updated and saves a history revision like any other edit.",
+            "Fails if the line is not a task or the task is already done.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn done 0Abc123:7", "qn done 0Abc123:7 0Abc123:9"],
+    },
+    HelpTopic {
+        name: "undo-task",
+        summary: "Reopen a checked task by `<id>:<line>`.",
+        usage: "qn undo-task <id>:<line>...",
+        details: &[
+            "The reverse of `qn done`: turns `[x]` back into `[ ]`, bumps Updated and saves a history revision.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn undo-task 0Abc123:7"],
+    },
+    HelpTopic {
+        name: "reindex",
+        summary: "Rebuild the `.qn-index` metadata cache from the note files.",
+        usage: "qn reindex",
+        details: &[
+            "list, list-deleted, list-archived, tags, and stats read titles, tags, and timestamps from `.qn-index` instead of parsing every note.",
+            "The index is updated on every qn write and revalidated against file size/mtime, so hand edits are picked up automatically.",
+            "Run reindex if the file was damaged or copied between machines; deleting it also works.",
+            "It first removes the second copy a crash can leave when a note moves between areas on systems without an atomic no-replace rename.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Command,
+        examples: &["qn reindex"],
+    },
     HelpTopic {
         name: "path",
         summary: "Print the notes directory path.",
@@ -447,13 +1044,51 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
             "qn list-archived -s \"2023 roadmap\" -r",
         ],
     },
+    HelpTopic {
+        name: "json",
+        summary: "Machine-readable --json / --ndjson output for scripts.",
+        usage: "qn <list|list-deleted|list-archived|view|tags|stats> --json|--ndjson",
+        details: &[
+            "--json prints one JSON document (an array for lists); --ndjson prints one object per line for streaming.",
+            "Notes carry id, title, area, path, created, updated, deleted, archived (RFC 3339 or null), tags, properties and size; view adds body.",
+            "tags emits tag, count, pinned, first_used and last_used; stats emits active, trash, archive and total.",
+            "Fields are only added over time, never renamed or removed. Other commands reject the flags before the command name (`qn --json add`) and take them as text after it; a `--` ends flag parsing.",
+        ],
+        flags: &[],
+        aliases: &["ndjson"],
+        section: Section::Guide,
+        examples: &[
+            "qn list --json -t #work | jq -r '.[].title'",
+            "qn list-archived --ndjson | jq -c 'select(.size > 4096) | .id'",
+            "qn stats --json",
+        ],
+    },
+    HelpTopic {
+        name: "query",
+        summary: "Query expressions for list, view, edit, delete and archive.",
+        usage: "qn list -q '<expr>'",
+        details: &[
+            "Terms are `field:value` or bare words (matched in title and body); fields are tag, title, body, text, id, created, updated and prop.<key>.",
+            "Values are case-insensitive substrings, \"quoted phrases\", or /regexes/; tag:#work matches the tag exactly.",
+            "created/updated take a date (YYYY-MM-DD or YYYY-MM-DDTHH:MM, local time) with an optional >, >=, < or <= in front.",
+            "Combine terms with AND, OR, NOT and parentheses; adjacent terms are AND-ed. Errors point at the offending token.",
+        ],
+        flags: &[],
+        aliases: &["queries"],
+        section: Section::Guide,
+        examples: &[
+            "qn list -q 'tag:#work AND NOT tag:#done AND updated:>2025-06-01'",
+            "qn list -q '(title:\"standup\" OR body:/retro|review/) prop.status:open'",
+            "qn archive -q 'tag:#done AND updated:<2025-01-01'",
+        ],
+    },
     HelpTopic {
         name: "bulk-ops",
         summary: "Seed, prune, and archive at scale.",
         usage: "qn help bulk-ops",
         details: &[
             "Use `qn seed <count>` to create load for testing; add --markdown for realistic bodies.",
-            "Archive or delete interactively with fzf by omitting ids and passing --fzf (fzf must be installed).",
+            "Archive or delete interactively by omitting ids and passing --fzf (uses the built-in picker when fzf is not installed).",
             "Trash auto-cleans after QUICK_NOTES_TRASH_RETENTION_DAYS, or set it to 0 to disable cleanup.",
         ],
         flags: &[],
@@ -483,7 +1118,7 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
         usage: "QUICK_NOTES_TRASH_RETENTION_DAYS",
         details: &[
             "Values are interpreted in days; zero disables automatic trash cleanup.",
-            "Applied whenever listing or deleting trash so it stays tidy over time.",
+            "Applied by qn list-deleted and when deleting; the web UI, HTTP API and TUI never purge.",
         ],
         flags: &[],
         aliases: &[],
@@ -493,6 +1128,22 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
             "Example: QUICK_NOTES_TRASH_RETENTION_DAYS=7 qn list-deleted",
         ],
     },
+    HelpTopic {
+        name: "QUICK_NOTES_HISTORY_RETENTION_DAYS",
+        summary: "Control how long note revisions are kept (default 90).",
+        usage: "QUICK_NOTES_HISTORY_RETENTION_DAYS",
+        details: &[
+            "Values are interpreted in days; zero keeps every revision forever.",
+            "Applied whenever a revision is saved or a note's history is read.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Environment,
+        examples: &[
+            "Example: Keep two weeks of revisions.",
+            "Example: QUICK_NOTES_HISTORY_RETENTION_DAYS=14 qn history <id>",
+        ],
+    },
     HelpTopic {
         name: "QUICK_NOTES_PINNED_TAGS",
         summary: "Comma-separated list of tags to pin in `qn tags` output.",
@@ -509,13 +1160,79 @@ const ALL_TOPICS: &[HelpTopic<'static>] = &[
             "Example: QUICK_NOTES_PINNED_TAGS=\"#retro\" qn tags",
         ],
     },
+    HelpTopic {
+        name: "QUICK_NOTES_FORMAT",
+        summary: "Header format used when writing notes: legacy or frontmatter.",
+        usage: "QUICK_NOTES_FORMAT",
+        details: &[
+            "Unset keeps each note in the format it was read in; new notes use the legacy header.",
+            "`frontmatter` writes YAML front matter on every save; `legacy` writes Title:/Tags: headers.",
+            "Both formats are always readable; use `qn convert-format` to rewrite existing notes at once.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Environment,
+        examples: &[
+            "Example: Keep an Obsidian vault readable by writing front matter.",
+            "Example: QUICK_NOTES_FORMAT=frontmatter qn new \"Standup\" -t meeting",
+        ],
+    },
+    HelpTopic {
+        name: "QUICK_NOTES_LOCK_TIMEOUT",
+        summary: "Seconds to wait for a busy note or store lock (default 10).",
+        usage: "QUICK_NOTES_LOCK_TIMEOUT",
+        details: &[
+            "add/new/edit/delete/archive/undelete/unarchive/prop lock the notes they touch; migrate, migrate-ids, convert-format, and delete-all lock the whole store.",
+            "When a lock stays busy past the timeout the command fails without writing and names the lock it waited on.",
+            "Fractions are allowed; zero tries once and fails immediately if the lock is held.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Environment,
+        examples: &[
+            "Example: Fail fast from a keybinding instead of waiting on an open edit.",
+            "Example: QUICK_NOTES_LOCK_TIMEOUT=0.5 qn add <id> \"text\"",
+        ],
+    },
+    HelpTopic {
+        name: "QUICK_NOTES_GIT_REMOTE",
+        summary: "Git remote used by qn sync (default origin).",
+        usage: "QUICK_NOTES_GIT_REMOTE",
+        details: &[
+            "Only matters when the notes directory is a git repository.",
+            "The --remote flag of qn sync takes precedence.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Environment,
+        examples: &[
+            "Example: Sync with a second remote named backup.",
+            "Example: QUICK_NOTES_GIT_REMOTE=backup qn sync",
+        ],
+    },
+    HelpTopic {
+        name: "QUICK_NOTES_SERVE_TOKEN",
+        summary: "Bearer token required by qn serve.",
+        usage: "QUICK_NOTES_SERVE_TOKEN",
+        details: &[
+            "When set, every HTTP request must send `Authorization: Bearer <token>`.",
+            "The --token flag takes precedence.",
+        ],
+        flags: &[],
+        aliases: &[],
+        section: Section::Environment,
+        examples: &[
+            "Example: Keep the token out of shell history and process lists.",
+            "Example: QUICK_NOTES_SERVE_TOKEN=$(cat ~/.qn-token) qn serve",
+        ],
+    },
     HelpTopic {
         name: "QUICK_NOTES_NO_FZF",
         summary: "Disable fzf integrations even if fzf is installed.",
         usage: "QUICK_NOTES_NO_FZF",
         details: &[
-            "Forces commands to skip fzf popups; useful in constrained shells or CI.",
-            "Affects edit/delete/archive paths that normally launch fzf when no ids are provided.",
+            "Forces commands to skip fzf; edit/delete/archive without ids use the built-in picker instead (same multi-select, preview and QUICK_NOTES_FZF_HEIGHT).",
+            "The built-in picker needs a terminal: space-separated terms match fuzzily, Tab/Shift-Tab mark entries, Shift-Up/Down scroll the preview, Enter accepts, Esc cancels.",
         ],
         flags: &[],
         aliases: &[],
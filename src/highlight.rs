@@ -0,0 +1,474 @@
+//! Lightweight syntax highlighting for fenced code blocks.
+//! Languages are described by small keyword tables and tokenized in one pass;
+//! unknown languages fall back to a single flat code color.
+
+use yansi::{Paint, Style};
+
+/// Token colors used by the highlighter.
+pub(crate) struct Theme {
+    pub plain: (u8, u8, u8),
+    pub keyword: (u8, u8, u8),
+    pub string: (u8, u8, u8),
+    pub number: (u8, u8, u8),
+    pub comment: (u8, u8, u8),
+    pub kind: (u8, u8, u8),
+    pub function: (u8, u8, u8),
+    pub property: (u8, u8, u8),
+    pub variable: (u8, u8, u8),
+}
+
+impl Theme {
+    /// Bundled theme matching the Catppuccin palette used across the CLI.
+    pub const MOCHA: Self = Self {
+        plain: (205, 214, 244),
+        keyword: (203, 166, 247),
+        string: (166, 227, 161),
+        number: (250, 179, 135),
+        comment: (108, 112, 134),
+        kind: (249, 226, 175),
+        function: (137, 180, 250),
+        property: (148, 226, 213),
+        variable: (243, 139, 168),
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Kind,
+    Function,
+    Property,
+    Variable,
+}
+
+/// Keyword tables are whitespace-separated word lists.
+struct Language {
+    names: &'static str,
+    keywords: &'static str,
+    literals: &'static str,
+    types: &'static str,
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    case_insensitive: bool,
+    capitalized_types: bool,
+    variable_sigil: Option<char>,
+    keys: bool,
+}
+
+const BASE: Language = Language {
+    names: "",
+    keywords: "",
+    literals: "",
+    types: "",
+    line_comments: &[],
+    block_comment: None,
+    quotes: &['"'],
+    case_insensitive: false,
+    capitalized_types: false,
+    variable_sigil: None,
+    keys: false,
+};
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        names: "rust rs",
+        keywords: "\
+            as async await break const continue crate dyn else enum extern \
+            fn for if impl in let loop match mod move mut pub ref return \
+            self Self static struct super trait type unsafe use where while",
+        literals: "true false None Some Ok Err",
+        types: "\
+            bool char str u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 \
+            isize f32 f64",
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        capitalized_types: true,
+        ..BASE
+    },
+    Language {
+        names: "sh bash zsh shell console shellsession",
+        keywords: "\
+            if then else elif fi for in do done while until case esac \
+            function return local export source alias set unset exit echo \
+            cd",
+        literals: "true false",
+        line_comments: &["#"],
+        quotes: &['"', '\''],
+        variable_sigil: Some('$'),
+        ..BASE
+    },
+    Language {
+        names: "sql psql mysql sqlite postgres",
+        keywords: "\
+            select from where and or not insert into values update set \
+            delete create table drop alter index on join left right inner \
+            outer group by order having limit offset as distinct union all \
+            primary key foreign references default case when then else end \
+            in is like exists with returning asc desc",
+        literals: "null true false",
+        types: "\
+            int integer bigint smallint text varchar char boolean bool date \
+            timestamp timestamptz numeric decimal real float serial uuid \
+            jsonb json",
+        line_comments: &["--"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['\'', '"'],
+        case_insensitive: true,
+        ..BASE
+    },
+    Language {
+        names: "json jsonc json5 geojson",
+        literals: "true false null",
+        line_comments: &["//"],
+        keys: true,
+        ..BASE
+    },
+    Language {
+        names: "python py",
+        keywords: "\
+            and as assert async await break class continue def del elif \
+            else except finally for from global if import in is lambda \
+            nonlocal not or pass raise return try while with yield self",
+        literals: "True False None",
+        types: "int str float bool list dict set",
+        line_comments: &["#"],
+        quotes: &['"', '\''],
+        capitalized_types: true,
+        ..BASE
+    },
+    Language {
+        names: "javascript js jsx typescript ts tsx",
+        keywords: "\
+            async await break case catch class const continue default \
+            delete do else export extends finally for from function if \
+            import in instanceof interface let new of return switch this \
+            throw try type typeof var while yield",
+        literals: "true false null undefined",
+        types: "string number boolean any void unknown",
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        capitalized_types: true,
+        ..BASE
+    },
+    Language {
+        names: "toml",
+        literals: "true false",
+        line_comments: &["#"],
+        quotes: &['"', '\''],
+        keys: true,
+        ..BASE
+    },
+    Language {
+        names: "yaml yml",
+        literals: "true false null yes no",
+        line_comments: &["#"],
+        quotes: &['"', '\''],
+        keys: true,
+        ..BASE
+    },
+];
+
+/// Pick the language named by a fence info string such as `rust,ignore`.
+fn language_for(info: &str) -> Option<&'static Language> {
+    let name = info
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '{')
+        .next()
+        .unwrap_or("")
+        .trim_start_matches('.')
+        .to_ascii_lowercase();
+    if name.is_empty() {
+        return None;
+    }
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.names.split_whitespace().any(|n| n == name))
+}
+
+/// Highlight a fenced code block, returning one painted string per line.
+/// Unknown or missing languages get the theme's flat code color.
+pub(crate) fn highlight_code(
+    code: &str,
+    info: &str,
+    theme: &Theme,
+) -> Vec<String> {
+    let Some(lang) = language_for(info) else {
+        let (r, g, b) = theme.string;
+        return code
+            .lines()
+            .map(|line| line.paint(Style::new().rgb(r, g, b)).to_string())
+            .collect();
+    };
+
+    let mut lines = vec![String::new()];
+    for (token, text) in tokenize(code, lang) {
+        let (r, g, b) = color_for(token, theme);
+        let style = Style::new().rgb(r, g, b);
+        let style =
+            if token == Token::Comment { style.italic() } else { style };
+        let mut parts = text.split('\n').peekable();
+        while let Some(part) = parts.next() {
+            if !part.is_empty() {
+                let line = lines.last_mut().expect("at least one line");
+                line.push_str(&part.paint(style).to_string());
+            }
+            if parts.peek().is_some() {
+                lines.push(String::new());
+            }
+        }
+    }
+    if code.ends_with('\n') {
+        lines.pop();
+    }
+    lines
+}
+
+fn color_for(token: Token, theme: &Theme) -> (u8, u8, u8) {
+    match token {
+        Token::Plain => theme.plain,
+        Token::Keyword => theme.keyword,
+        Token::String => theme.string,
+        Token::Number => theme.number,
+        Token::Comment => theme.comment,
+        Token::Kind => theme.kind,
+        Token::Function => theme.function,
+        Token::Property => theme.property,
+        Token::Variable => theme.variable,
+    }
+}
+
+fn tokenize<'a>(code: &'a str, lang: &Language) -> Vec<(Token, &'a str)> {
+    let mut tokens: Vec<(Token, &str)> = Vec::new();
+    let mut push = |token: Token, text: &'a str| {
+        if let Some(last) = tokens.last_mut()
+            && last.0 == token
+        {
+            let start = last.1.as_ptr() as usize - code.as_ptr() as usize;
+            last.1 = &code[start..start + last.1.len() + text.len()];
+            return;
+        }
+        tokens.push((token, text));
+    };
+
+    let bytes = code.as_bytes();
+    let mut i = 0;
+    let mut line_start = true;
+    while i < code.len() {
+        let rest = &code[i..];
+        let ch = rest.chars().next().expect("non-empty rest");
+
+        if let Some((open, close)) = lang.block_comment
+            && let Some(inner) = rest.strip_prefix(open)
+        {
+            let end = inner
+                .find(close)
+                .map(|p| open.len() + p + close.len())
+                .unwrap_or(rest.len());
+            push(Token::Comment, &rest[..end]);
+            i += end;
+            continue;
+        }
+        if lang.line_comments.iter().any(|c| rest.starts_with(c))
+            && (ch != '#' || line_start || preceded_by_space(bytes, i))
+        {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            push(Token::Comment, &rest[..end]);
+            i += end;
+            continue;
+        }
+        if lang.quotes.contains(&ch) {
+            let end = string_end(rest, ch);
+            let token = if lang.keys && is_key(&rest[end..]) {
+                Token::Property
+            } else {
+                Token::String
+            };
+            push(token, &rest[..end]);
+            i += end;
+            line_start = false;
+            continue;
+        }
+        if lang.variable_sigil == Some(ch) {
+            let end = variable_end(rest);
+            if end > 1 {
+                push(Token::Variable, &rest[..end]);
+                i += end;
+                line_start = false;
+                continue;
+            }
+        }
+        if ch.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| {
+                    !(c.is_ascii_alphanumeric() || c == '.' || c == '_')
+                })
+                .unwrap_or(rest.len());
+            push(Token::Number, &rest[..end]);
+            i += end;
+            line_start = false;
+            continue;
+        }
+        if ch.is_alphabetic() || ch == '_' {
+            let end = rest
+                .find(|c: char| {
+                    !(c.is_alphanumeric()
+                        || c == '_'
+                        || (lang.keys && c == '-'))
+                })
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            let after = &rest[end..];
+            let token = classify_word(word, after, lang, line_start);
+            push(token, word);
+            i += end;
+            line_start = false;
+            continue;
+        }
+
+        if ch == '\n' {
+            line_start = true;
+        } else if !(ch.is_whitespace() || lang.keys && ch == '-') {
+            line_start = false;
+        }
+        push(Token::Plain, &rest[..ch.len_utf8()]);
+        i += ch.len_utf8();
+    }
+    tokens
+}
+
+fn classify_word(
+    word: &str,
+    after: &str,
+    lang: &Language,
+    line_start: bool,
+) -> Token {
+    let matches = |list: &str| {
+        list.split_whitespace().any(|w| {
+            if lang.case_insensitive {
+                w.eq_ignore_ascii_case(word)
+            } else {
+                w == word
+            }
+        })
+    };
+    if lang.keys && line_start && is_key(after) {
+        Token::Property
+    } else if matches(lang.keywords) {
+        Token::Keyword
+    } else if matches(lang.literals) {
+        Token::Number
+    } else if matches(lang.types)
+        || (lang.capitalized_types
+            && word.chars().next().is_some_and(char::is_uppercase))
+    {
+        Token::Kind
+    } else if after.starts_with('(') || after.starts_with("!(") {
+        Token::Function
+    } else {
+        Token::Plain
+    }
+}
+
+/// Keys are followed by `:` (JSON/YAML) or `=` (TOML) on the same line.
+fn is_key(after: &str) -> bool {
+    let trimmed = after.trim_start_matches([' ', '\t']);
+    trimmed.starts_with(':') || trimmed.starts_with('=')
+}
+
+fn preceded_by_space(bytes: &[u8], i: usize) -> bool {
+    i > 0 && bytes[i - 1].is_ascii_whitespace()
+}
+
+fn string_end(rest: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (idx, ch) in rest.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' && quote != '\'' {
+            escaped = true;
+        } else if ch == quote {
+            return idx + ch.len_utf8();
+        } else if ch == '\n' && quote != '`' {
+            return idx;
+        }
+    }
+    rest.len()
+}
+
+fn variable_end(rest: &str) -> usize {
+    if let Some(inner) = rest.strip_prefix("${") {
+        return inner.find('}').map(|p| p + 3).unwrap_or(rest.len());
+    }
+    rest.char_indices()
+        .skip(1)
+        .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+        .map(|(idx, _)| idx)
+        .unwrap_or(rest.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str, info: &str) -> Vec<(Token, String)> {
+        let lang = language_for(info).expect("known language");
+        tokenize(code, lang)
+            .into_iter()
+            .filter(|(t, _)| *t != Token::Plain)
+            .map(|(t, s)| (t, s.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn info_string_selects_language() {
+        assert!(language_for("rust,ignore").is_some());
+        assert!(language_for("SQL").is_some());
+        assert!(language_for("brainfuck").is_none());
+        assert!(language_for("").is_none());
+    }
+
+    #[test]
+    fn rust_tokens() {
+        let toks = kinds("fn main() { let x: u8 = 1; // hi\n}", "rust");
+        assert_eq!(toks[0], (Token::Keyword, "fn".to_string()));
+        assert_eq!(toks[1], (Token::Function, "main".to_string()));
+        assert!(toks.contains(&(Token::Kind, "u8".to_string())));
+        assert!(toks.contains(&(Token::Number, "1".to_string())));
+        assert!(toks.contains(&(Token::Comment, "// hi".to_string())));
+    }
+
+    #[test]
+    fn json_keys_and_sql_case() {
+        let toks = kinds("{\"name\": \"qn\", \"ok\": true}", "json");
+        assert_eq!(toks[0], (Token::Property, "\"name\"".to_string()));
+        assert_eq!(toks[1], (Token::String, "\"qn\"".to_string()));
+        assert_eq!(toks[3], (Token::Number, "true".to_string()));
+
+        let toks = kinds("SELECT id FROM notes -- all", "sql");
+        assert_eq!(toks[0], (Token::Keyword, "SELECT".to_string()));
+        assert_eq!(toks[1], (Token::Keyword, "FROM".to_string()));
+        assert_eq!(toks[2], (Token::Comment, "-- all".to_string()));
+    }
+
+    #[test]
+    fn shell_variables_and_comments() {
+        let toks = kinds("echo \"$HOME\" ${USER} # done", "sh");
+        assert_eq!(toks[0], (Token::Keyword, "echo".to_string()));
+        assert!(toks.contains(&(Token::Variable, "${USER}".to_string())));
+        assert_eq!(toks.last().unwrap().0, Token::Comment);
+    }
+
+    #[test]
+    fn block_comments_keep_line_count() {
+        let code = "/* one\ntwo */\nlet a = 1;\n";
+        let lines = highlight_code(code, "rust", &Theme::MOCHA);
+        assert_eq!(lines.len(), 3);
+        let unknown = highlight_code(code, "mystery", &Theme::MOCHA);
+        assert_eq!(unknown.len(), 3);
+    }
+}
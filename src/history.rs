@@ -0,0 +1,311 @@
+//! Note revision history: every `write_note` that replaces different content
+//! first saves the old file as `.history/<id>/<rev>.md`, where `rev` is the
+//! base62 microsecond timestamp of the save.
+
+use crate::note::{
+    ID_TS_WIDTH, TIME_FMT, decode_base62, encode_base62_width, note_path,
+    parse_note, timestamp_string, write_note,
+};
+use crate::shared::atomic::atomic_write;
+use crate::shared::diff::unified_diff;
+use crate::shared::lock::lock_note;
+use crate::shared::migrate::resolve_active_note_path;
+use crate::shared::table::render_table;
+use crate::{Area, area_dir, store_root};
+use chrono::{DateTime, Local, TimeZone};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub(crate) struct Revision {
+    pub rev: String,
+    pub path: PathBuf,
+    pub saved: Option<DateTime<Local>>,
+    pub size: u64,
+}
+
+pub(crate) fn history_dir(base: &Path, id: &str) -> PathBuf {
+    base.join(".history").join(id)
+}
+
+fn history_retention_days() -> i64 {
+    env::var("QUICK_NOTES_HISTORY_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &i64| *v >= 0)
+        .unwrap_or(90)
+}
+
+fn rev_time(rev: &str) -> Option<DateTime<Local>> {
+    let micros = i64::try_from(decode_base62(rev)?).ok()?;
+    Local.timestamp_micros(micros).single()
+}
+
+/// Store `content` as the newest revision of the note at `note_file`.
+pub(crate) fn save_revision(
+    note_file: &Path,
+    id: &str,
+    content: &str,
+) -> io::Result<()> {
+    let Some(base) = store_root(note_file) else { return Ok(()) };
+    let dir = history_dir(&base, id);
+    fs::create_dir_all(&dir)?;
+    let mut micros = Local::now().timestamp_micros().max(0) as u64;
+    let path = loop {
+        let candidate = dir
+            .join(format!("{}.md", encode_base62_width(micros, ID_TS_WIDTH)));
+        if !candidate.exists() {
+            break candidate;
+        }
+        micros += 1;
+    };
+    atomic_write(&path, content.as_bytes())?;
+    prune_revisions(&dir);
+    Ok(())
+}
+
+/// Drop revisions older than `QUICK_NOTES_HISTORY_RETENTION_DAYS`.
+fn prune_revisions(dir: &Path) {
+    let retention = history_retention_days();
+    if retention == 0 {
+        return;
+    }
+    let cutoff = Local::now() - chrono::Duration::days(retention);
+    for rev in read_revisions(dir).unwrap_or_default() {
+        if rev.saved.is_some_and(|saved| saved < cutoff) {
+            let _ = fs::remove_file(&rev.path);
+        }
+    }
+}
+
+fn read_revisions(dir: &Path) -> io::Result<Vec<Revision>> {
+    let mut revs = Vec::new();
+    if !dir.is_dir() {
+        return Ok(revs);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("md") {
+            continue;
+        }
+        let Some(rev) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        revs.push(Revision {
+            rev: rev.to_string(),
+            saved: rev_time(rev),
+            size: entry.metadata()?.len(),
+            path: path.clone(),
+        });
+    }
+    revs.sort_by(|a, b| a.rev.cmp(&b.rev));
+    Ok(revs)
+}
+
+/// Revisions of a note, oldest first.
+pub(crate) fn list_revisions(
+    base: &Path,
+    id: &str,
+) -> io::Result<Vec<Revision>> {
+    let dir = history_dir(base, id);
+    prune_revisions(&dir);
+    read_revisions(&dir)
+}
+
+/// Current file of a note in any area.
+fn current_note_path(base: &Path, id: &str) -> Option<PathBuf> {
+    resolve_active_note_path(base, id).or_else(|| {
+        [Area::Trash, Area::Archive]
+            .into_iter()
+            .map(|area| note_path(&area_dir(base, area), id))
+            .find(|p| p.exists())
+    })
+}
+
+/// Find a revision by exact id or unique prefix.
+fn find_revision<'a>(
+    revs: &'a [Revision],
+    id: &str,
+    wanted: &str,
+) -> Result<&'a Revision, Box<dyn Error>> {
+    if let Some(rev) = revs.iter().find(|r| r.rev == wanted) {
+        return Ok(rev);
+    }
+    let matches: Vec<&Revision> =
+        revs.iter().filter(|r| r.rev.starts_with(wanted)).collect();
+    match matches.as_slice() {
+        [rev] => Ok(rev),
+        [] => Err(format!("Note {id} has no revision {wanted}").into()),
+        _ => Err(format!("Revision {wanted} is ambiguous for {id}").into()),
+    }
+}
+
+fn format_delta(delta: i64) -> String {
+    if delta > 0 { format!("+{delta}") } else { delta.to_string() }
+}
+
+/// `qn history <id>`: list revisions newest first with size deltas.
+pub(crate) fn show_history(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let [id] = args.as_slice() else {
+        return Err("Usage: qn history <id>".into());
+    };
+    let revs = list_revisions(dir, id)?;
+    let current = current_note_path(dir, id);
+    if revs.is_empty() && current.is_none() {
+        return Err(format!("Note {id} not found").into());
+    }
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    if let Some(path) = &current {
+        let size = fs::metadata(path)?.len();
+        let note = parse_note(path, size)?;
+        let change = revs
+            .last()
+            .map(|prev| format_delta(size as i64 - prev.size as i64))
+            .unwrap_or_else(|| "n/a".to_string());
+        rows.push(vec![
+            "current".to_string(),
+            note.updated,
+            size.to_string(),
+            change,
+        ]);
+    }
+    for (idx, rev) in revs.iter().enumerate().rev() {
+        let change = if idx == 0 {
+            "n/a".to_string()
+        } else {
+            format_delta(rev.size as i64 - revs[idx - 1].size as i64)
+        };
+        rows.push(vec![
+            rev.rev.clone(),
+            rev.saved
+                .map(|t| t.format(TIME_FMT).to_string())
+                .unwrap_or_else(|| "n/a".to_string()),
+            rev.size.to_string(),
+            change,
+        ]);
+    }
+    let headers: Vec<String> =
+        ["Rev", "Saved", "Size", "Change"].map(String::from).to_vec();
+    println!("{}", render_table(&headers, &rows));
+    if revs.is_empty() {
+        println!("No earlier revisions of {id}.");
+    }
+    Ok(())
+}
+
+/// `qn diff <id> [rev] [rev]`: unified diff between two versions; the
+/// defaults are the newest revision and the current file.
+pub(crate) fn diff_revisions(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "Usage: qn diff <id> [rev] [rev]";
+    let (id, from, to) = match args.as_slice() {
+        [id] => (id, None, None),
+        [id, from] => (id, Some(from.as_str()), None),
+        [id, from, to] => (id, Some(from.as_str()), Some(to.as_str())),
+        _ => return Err(USAGE.into()),
+    };
+    let revs = list_revisions(dir, id)?;
+    let load =
+        |which: Option<&str>| -> Result<(String, String), Box<dyn Error>> {
+            match which {
+                None | Some("current") => {
+                    let path = current_note_path(dir, id)
+                        .ok_or_else(|| format!("Note {id} not found"))?;
+                    Ok(("current".to_string(), fs::read_to_string(path)?))
+                }
+                Some(wanted) => {
+                    let rev = find_revision(&revs, id, wanted)?;
+                    Ok((rev.rev.clone(), fs::read_to_string(&rev.path)?))
+                }
+            }
+        };
+    let from = match from {
+        Some(rev) => rev,
+        None => revs
+            .last()
+            .map(|r| r.rev.as_str())
+            .ok_or_else(|| format!("Note {id} has no earlier revisions"))?,
+    };
+    let (from_label, old) = load(Some(from))?;
+    let (to_label, new) = load(to)?;
+    let use_color = env::var("NO_COLOR").is_err();
+    let diff = unified_diff(
+        &old,
+        &new,
+        &format!("{id}@{from_label}"),
+        &format!("{id}@{to_label}"),
+        use_color,
+    );
+    if diff.is_empty() {
+        println!("No differences.");
+    } else {
+        print!("{diff}");
+    }
+    Ok(())
+}
+
+/// `qn revert <id> <rev>`: restore an active note's content from a revision.
+/// The replaced version is saved as a new revision, so reverts are undoable.
+pub(crate) fn revert_note(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let [id, wanted] = args.as_slice() else {
+        return Err("Usage: qn revert <id> <rev>".into());
+    };
+    let _lock = lock_note(dir, id)?;
+    let path = resolve_active_note_path(dir, id)
+        .ok_or_else(|| format!("Note {id} not found"))?;
+    let revs = list_revisions(dir, id)?;
+    let rev = find_revision(&revs, id, wanted)?;
+
+    let current = parse_note(&path, fs::metadata(&path)?.len())?;
+    let mut note = parse_note(&rev.path, rev.size)?;
+    note.id = current.id;
+    note.deleted_at = current.deleted_at;
+    note.archived_at = current.archived_at;
+    note.updated = timestamp_string();
+    write_note(&note, path.parent().unwrap_or(dir))?;
+    println!("Reverted {id} to {}", rev.rev);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn saves_and_lists_revisions_in_order() {
+        let tmp = tempdir().unwrap();
+        let file = tmp.path().join("n1.md");
+        fs::write(tmp.path().join(".qn.lock"), "").unwrap();
+        save_revision(&file, "n1", "first").unwrap();
+        save_revision(&file, "n1", "second!").unwrap();
+        let revs = list_revisions(tmp.path(), "n1").unwrap();
+        assert_eq!(revs.len(), 2);
+        assert_eq!(fs::read_to_string(&revs[0].path).unwrap(), "first");
+        assert_eq!(revs[1].size, 7);
+        assert!(revs[0].saved.is_some());
+        assert!(find_revision(&revs, "n1", &revs[1].rev[..]).is_ok());
+        assert!(find_revision(&revs, "n1", "zzz").is_err());
+    }
+
+    #[test]
+    fn history_dir_follows_store_root_across_areas() {
+        let tmp = tempdir().unwrap();
+        let trash = tmp.path().join("trash");
+        fs::create_dir_all(&trash).unwrap();
+        save_revision(&trash.join("t1.md"), "t1", "old").unwrap();
+        assert!(history_dir(tmp.path(), "t1").is_dir());
+    }
+}
@@ -0,0 +1,475 @@
+//! On-disk metadata index (`.qn-index`) so `list`/`tags`/`stats` do not have
+//! to parse every note.
+//!
+//! The file is a log of tab-separated records keyed by the note path relative
+//! to the notes directory: `+` upserts an entry, `-` removes one. Writes
+//! append a record; loading replays the log, revalidates every entry against
+//! the file's size and mtime, re-parses only what changed, and compacts the
+//! log when it drifted.
+
+use crate::note::{Note, Properties, parse_note};
+use crate::shared::atomic::atomic_write;
+use crate::shared::migrate::list_store_note_files;
+use crate::{Area, store_root};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub(crate) const INDEX_FILE: &str = ".qn-index";
+const HEADER: &str = "qn-index v2";
+/// Keep enough of the first body line to fill the list preview column.
+const PREVIEW_CHARS: usize = 120;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct IndexEntry {
+    pub rel_path: String,
+    pub size: u64,
+    pub mtime: u128,
+    pub title: String,
+    pub created: String,
+    pub updated: String,
+    pub deleted_at: Option<String>,
+    pub archived_at: Option<String>,
+    pub due: Option<String>,
+    pub remind: Option<String>,
+    pub tags: Vec<String>,
+    pub properties: Vec<(String, String)>,
+    pub preview: String,
+}
+
+impl IndexEntry {
+    fn from_note(
+        rel_path: String,
+        note: &Note,
+        size: u64,
+        mtime: u128,
+    ) -> Self {
+        let preview: String = note
+            .body
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .unwrap_or("")
+            .chars()
+            .take(PREVIEW_CHARS)
+            .collect();
+        IndexEntry {
+            rel_path,
+            size,
+            mtime,
+            title: note.title.clone(),
+            created: note.created.clone(),
+            updated: note.updated.clone(),
+            deleted_at: note.deleted_at.clone(),
+            archived_at: note.archived_at.clone(),
+            due: note.due.clone(),
+            remind: note.remind.clone(),
+            tags: note.tags.clone(),
+            properties: note
+                .properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            preview,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        let name = self.rel_path.rsplit('/').next().unwrap_or(&self.rel_path);
+        name.strip_suffix(".md").unwrap_or(name)
+    }
+
+    pub fn area(&self) -> Area {
+        area_of(&self.rel_path)
+    }
+
+    /// A metadata-only `Note`; `body` holds just the preview line.
+    pub fn to_note(&self) -> Note {
+        let mut properties = Properties::default();
+        for (k, v) in &self.properties {
+            properties.set(k, v);
+        }
+        Note {
+            id: self.id().to_string(),
+            title: self.title.clone(),
+            created: self.created.clone(),
+            updated: self.updated.clone(),
+            deleted_at: self.deleted_at.clone(),
+            archived_at: self.archived_at.clone(),
+            due: self.due.clone(),
+            remind: self.remind.clone(),
+            body: self.preview.clone(),
+            tags: self.tags.clone(),
+            properties,
+            format: Default::default(),
+            size_bytes: self.size,
+        }
+    }
+
+    fn encode(&self) -> String {
+        let props: Vec<String> = self
+            .properties
+            .iter()
+            .map(|(k, v)| format!("{}={}", escape(k), escape(v)))
+            .collect();
+        let tags: Vec<String> = self.tags.iter().map(|t| escape(t)).collect();
+        [
+            "+".to_string(),
+            escape(&self.rel_path),
+            self.size.to_string(),
+            self.mtime.to_string(),
+            escape(&self.title),
+            escape(&self.created),
+            escape(&self.updated),
+            self.deleted_at.as_deref().map(escape).unwrap_or_default(),
+            self.archived_at.as_deref().map(escape).unwrap_or_default(),
+            self.due.as_deref().map(escape).unwrap_or_default(),
+            self.remind.as_deref().map(escape).unwrap_or_default(),
+            tags.join(";"),
+            props.join(";"),
+            escape(&self.preview),
+        ]
+        .join("\t")
+    }
+
+    fn decode(fields: &[&str]) -> Option<Self> {
+        let [
+            path,
+            size,
+            mtime,
+            title,
+            created,
+            updated,
+            deleted,
+            archived,
+            due,
+            remind,
+            tags,
+            props,
+            preview,
+        ] = fields
+        else {
+            return None;
+        };
+        let optional =
+            |v: &str| if v.is_empty() { None } else { Some(unescape(v)) };
+        let list = |v: &str| -> Vec<String> {
+            if v.is_empty() {
+                Vec::new()
+            } else {
+                v.split(';').map(unescape).collect()
+            }
+        };
+        Some(IndexEntry {
+            rel_path: unescape(path),
+            size: size.parse().ok()?,
+            mtime: mtime.parse().ok()?,
+            title: unescape(title),
+            created: unescape(created),
+            updated: unescape(updated),
+            deleted_at: optional(deleted),
+            archived_at: optional(archived),
+            due: optional(due),
+            remind: optional(remind),
+            tags: list(tags),
+            properties: if props.is_empty() {
+                Vec::new()
+            } else {
+                props
+                    .split(';')
+                    .filter_map(|p| p.split_once('='))
+                    .map(|(k, v)| (unescape(k), unescape(v)))
+                    .collect()
+            },
+            preview: unescape(preview),
+        })
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            ';' => out.push_str("\\s"),
+            '=' => out.push_str("\\e"),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('s') => out.push(';'),
+            Some('e') => out.push('='),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Index entries for every note in the store, keyed by relative path.
+pub(crate) struct NoteIndex {
+    pub entries: BTreeMap<String, IndexEntry>,
+}
+
+impl NoteIndex {
+    /// Metadata-only notes for one area, in no particular order.
+    pub fn notes(&self, area: Area) -> Vec<Note> {
+        self.entries
+            .values()
+            .filter(|e| e.area() == area)
+            .map(IndexEntry::to_note)
+            .collect()
+    }
+
+    pub fn count(&self, area: Area) -> usize {
+        self.entries.values().filter(|e| e.area() == area).count()
+    }
+}
+
+pub(crate) fn index_path(base: &Path) -> PathBuf {
+    base.join(INDEX_FILE)
+}
+
+/// Area of a note from its path relative to the notes directory.
+pub(crate) fn area_of(rel_path: &str) -> Area {
+    match rel_path.split_once('/') {
+        Some(("trash", _)) => Area::Trash,
+        Some(("archive", _)) => Area::Archive,
+        _ => Area::Active,
+    }
+}
+
+/// `path` relative to `base`, with `/` separators on every platform.
+pub(crate) fn rel_path(base: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(base).ok()?;
+    let parts: Vec<&str> =
+        rel.components().filter_map(|c| c.as_os_str().to_str()).collect();
+    Some(parts.join("/"))
+}
+
+pub(crate) fn mtime_of(meta: &fs::Metadata) -> u128 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+/// Replay the log; returns the entries and how many records were read.
+fn read_log(base: &Path) -> (BTreeMap<String, IndexEntry>, usize) {
+    let mut entries = BTreeMap::new();
+    let Ok(raw) = fs::read_to_string(index_path(base)) else {
+        return (entries, 0);
+    };
+    let mut lines = raw.lines();
+    if lines.next() != Some(HEADER) {
+        return (entries, 0);
+    }
+    let mut records = 0;
+    for line in lines {
+        records += 1;
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.first() {
+            Some(&"+") => {
+                if let Some(entry) = IndexEntry::decode(&fields[1..]) {
+                    entries.insert(entry.rel_path.clone(), entry);
+                }
+            }
+            Some(&"-") if fields.len() == 2 => {
+                entries.remove(&unescape(fields[1]));
+            }
+            _ => {}
+        }
+    }
+    (entries, records)
+}
+
+fn write_full(
+    base: &Path,
+    entries: &BTreeMap<String, IndexEntry>,
+) -> io::Result<()> {
+    let mut out = String::from(HEADER);
+    out.push('\n');
+    for entry in entries.values() {
+        out.push_str(&entry.encode());
+        out.push('\n');
+    }
+    atomic_write(&index_path(base), out.as_bytes())
+}
+
+/// Load the index, re-parsing only notes whose size or mtime changed, and
+/// persist it when anything was stale.
+pub(crate) fn load_index(base: &Path) -> io::Result<NoteIndex> {
+    let (mut old, records) = read_log(base);
+    let mut entries = BTreeMap::new();
+    let mut dirty = records != old.len();
+    for path in list_store_note_files(base)? {
+        let Some(rel) = rel_path(base, &path) else { continue };
+        let meta = fs::metadata(&path)?;
+        let (size, mtime) = (meta.len(), mtime_of(&meta));
+        match old.remove(&rel) {
+            Some(entry) if entry.size == size && entry.mtime == mtime => {
+                entries.insert(rel, entry);
+            }
+            _ => {
+                dirty = true;
+                if let Ok(note) = parse_note(&path, size) {
+                    entries.insert(
+                        rel.clone(),
+                        IndexEntry::from_note(rel, &note, size, mtime),
+                    );
+                }
+            }
+        }
+    }
+    if !old.is_empty() {
+        dirty = true;
+    }
+    if dirty {
+        // The index is only a cache; a read-only store still lists fine.
+        let _ = write_full(base, &entries);
+    }
+    Ok(NoteIndex { entries })
+}
+
+/// Rebuild the index from scratch; returns the number of indexed notes.
+pub(crate) fn rebuild_index(base: &Path) -> io::Result<usize> {
+    let mut entries = BTreeMap::new();
+    for path in list_store_note_files(base)? {
+        let Some(rel) = rel_path(base, &path) else { continue };
+        let meta = fs::metadata(&path)?;
+        if let Ok(note) = parse_note(&path, meta.len()) {
+            entries.insert(
+                rel.clone(),
+                IndexEntry::from_note(rel, &note, meta.len(), mtime_of(&meta)),
+            );
+        }
+    }
+    write_full(base, &entries)?;
+    Ok(entries.len())
+}
+
+/// Notes directory owning `note_file`, if it already has an index.
+fn base_for(note_file: &Path) -> Option<PathBuf> {
+    store_root(note_file).filter(|base| index_path(base).is_file())
+}
+
+fn append(base: &Path, lines: &[String]) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).open(index_path(base))?;
+    // One write per batch so concurrent appenders do not interleave lines.
+    file.write_all(lines.concat().as_bytes())
+}
+
+/// Record a freshly written note. Best effort: a missed update is picked up
+/// by mtime revalidation on the next load.
+pub(crate) fn record_write(path: &Path, note: &Note) {
+    let Some(base) = base_for(path) else { return };
+    let (Some(rel), Ok(meta)) = (rel_path(&base, path), fs::metadata(path))
+    else {
+        return;
+    };
+    let entry = IndexEntry::from_note(rel, note, meta.len(), mtime_of(&meta));
+    let _ = append(&base, &[format!("{}\n", entry.encode())]);
+}
+
+/// Record a note moved from `src` to `dst` (and rewritten on the way).
+pub(crate) fn record_move(src: &Path, dst: &Path, note: &Note) {
+    let Some(base) = base_for(dst) else { return };
+    let (Some(old), Some(rel), Ok(meta)) =
+        (rel_path(&base, src), rel_path(&base, dst), fs::metadata(dst))
+    else {
+        return;
+    };
+    let entry = IndexEntry::from_note(rel, note, meta.len(), mtime_of(&meta));
+    let _ = append(
+        &base,
+        &[format!("-\t{}\n", escape(&old)), format!("{}\n", entry.encode())],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::write_note;
+    use tempfile::tempdir;
+
+    fn note(id: &str, title: &str, tags: &[&str]) -> Note {
+        Note {
+            id: id.to_string(),
+            title: title.to_string(),
+            created: "01Jan20 10:00 +00:00".to_string(),
+            updated: "01Jan20 10:00 +00:00".to_string(),
+            deleted_at: None,
+            archived_at: None,
+            due: None,
+            remind: None,
+            body: "\nfirst line\nsecond".to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            properties: Default::default(),
+            format: Default::default(),
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn entry_round_trips_through_encoding() {
+        let mut n = note("a1", "Tabs\tand;semi=colons\\", &["#x", "#y"]);
+        n.properties.set("Source", "a=b;c");
+        n.deleted_at = Some("02Jan20 10:00 +00:00".to_string());
+        n.due = Some("tomorrow 9am".to_string());
+        let entry = IndexEntry::from_note("trash/a1.md".into(), &n, 42, 7);
+        let line = entry.encode();
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert_eq!(IndexEntry::decode(&fields[1..]), Some(entry.clone()));
+        assert_eq!(entry.id(), "a1");
+        assert!(entry.area() == Area::Trash);
+        assert_eq!(entry.preview, "first line");
+    }
+
+    #[test]
+    fn load_revalidates_and_appends_are_replayed() {
+        let tmp = tempdir().unwrap();
+        let base = tmp.path();
+        write_note(&note("n1", "One", &["#a"]), base).unwrap();
+        write_note(&note("n2", "Two", &[]), base).unwrap();
+        assert_eq!(load_index(base).unwrap().count(Area::Active), 2);
+        assert!(index_path(base).is_file());
+
+        // Incremental write goes through the log.
+        write_note(&note("n1", "One renamed", &["#b"]), base).unwrap();
+        let (logged, _) = read_log(base);
+        assert_eq!(logged["n1.md"].title, "One renamed");
+
+        // Out-of-band edits and removals are caught by revalidation.
+        fs::write(
+            base.join("n2.md"),
+            "Title: Two edited here\nCreated:\nUpdated:\nTags:\n---\nx\n",
+        )
+        .unwrap();
+        fs::remove_file(base.join("n1.md")).unwrap();
+        let index = load_index(base).unwrap();
+        let notes = index.notes(Area::Active);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].title, "Two edited here");
+        assert_eq!(read_log(base).1, 1, "log compacted after changes");
+    }
+}
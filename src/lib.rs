@@ -17,18 +17,40 @@
 //!   list/view/render/edit/delete/delete-all/seed/tags/path/help/completion).
 //! - `Note` parsing/serialization lives in the `parse_note`, `write_note_file`,
 //!   and `note_path` helpers.
-//! - Rendering helpers (`render_markdown`, `highlight_inline_code`) keep line
-//!   structure intact for tests while supporting colored output.
+//! - `render_markdown` walks the pulldown-cmark event stream to lay out
+//!   emphasis, links, lists, tables and footnotes with ANSI colors, and passes
+//!   plain text through untouched so line structure stays intact for tests.
 //! - CLI integration with fzf completion is provided via the `completion`
 //!   handler and the shell script in `contrib/`.
 //!
 //! See `CONTRIBUTE.md` for architecture notes and development workflows, and
 //! `AGENTS.md` for usage expectations that tests enforce.
 
+mod agenda;
+mod backup;
+mod dirsync;
+mod export;
+mod find;
+mod frontmatter;
+mod git;
 mod help;
+mod highlight;
+mod history;
+mod index;
+mod lsp;
 mod note;
+mod pick;
+mod picker;
+mod publish;
+mod query;
 mod render;
+mod search;
+mod serve;
 mod shared;
+mod term;
+mod todos;
+mod tui;
+mod ui;
 
 // New refactored modules
 pub mod args;
@@ -37,19 +59,27 @@ pub mod fzf;
 pub mod operations;
 pub mod tags;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Area {
     Active,
     Trash,
     Archive,
 }
 
+use crate::index::{load_index, rebuild_index};
 use crate::note::{
-    Note, TIME_FMT, cmp_dt, ensure_dir, generate_new_id, note_path, notes_dir,
-    now_fixed, parse_note, parse_timestamp, short_timestamp, timestamp_string,
-    unique_id, write_note,
+    Note, NoteFormat, Properties, TIME_FMT, cmp_dt, configured_format,
+    ensure_dir, generate_new_id, note_path, notes_dir, now_fixed, parse_note,
+    parse_timestamp, relocate_note, short_timestamp, timestamp_string,
+    unique_id, validate_property_key, write_note, write_note_as,
+    write_note_replacing,
 };
+use crate::query::{Query, query_note_ids};
 use crate::render::{detect_glow, render_markdown};
+use crate::shared::json::{Json, OutputMode, print_items, take_output_flag};
+use crate::shared::lock::{
+    LockMode, lock_existing_note, lock_new_note, lock_note, lock_store,
+};
 use crate::shared::migrate::{
     list_active_note_files, migrate_notes, resolve_active_note_path,
 };
@@ -67,6 +97,17 @@ use std::process::{Command, Stdio};
 use terminal_size::{Height, Width, terminal_size};
 use yansi::Paint;
 
+/// Commands that accept `--json`/`--ndjson`.
+const JSON_COMMANDS: &[&str] = &[
+    "list",
+    "list-deleted",
+    "list-archived",
+    "view",
+    "render",
+    "tags",
+    "stats",
+];
+
 /// Dispatch CLI arguments to the right subcommand.
 pub fn entry() -> Result<(), Box<dyn Error>> {
     let mut args: Vec<String> = env::args().skip(1).collect();
@@ -74,39 +115,88 @@ pub fn entry() -> Result<(), Box<dyn Error>> {
         return help::run(Vec::new());
     }
 
+    let output = take_output_flag(&mut args, JSON_COMMANDS)?;
+    if args.is_empty() {
+        return help::run(Vec::new());
+    }
     let cmd = args.remove(0);
     let dir = notes_dir()?;
     ensure_dir(&dir)?;
 
+    // Whole-store rewrites exclude every other writer; per-note commands
+    // share the store and lock individual notes as they touch them.
+    let _store_lock = match cmd.as_str() {
+        "migrate" | "migrate-ids" | "convert-format" | "delete-all"
+        | "sync" | "sync-dir" | "reindex" => {
+            Some(lock_store(&dir, LockMode::Exclusive)?)
+        }
+        "add" | "new" | "edit" | "delete" | "archive" | "undelete"
+        | "unarchive" | "prop" | "props" | "seed" | "list-deleted"
+        | "revert" | "backup" | "due" | "remind" | "done" | "undo-task" => {
+            Some(lock_store(&dir, LockMode::Shared)?)
+        }
+        _ => None,
+    };
+
     match cmd.as_str() {
         "-h" | "--help" => help::run(args)?,
         "add" => quick_add(args, &dir)?,
         "new" => new_note(args, &dir)?,
-        "list" => list_notes(args, &dir)?,
-        "view" => view_note(args, &dir, true)?,
-        "render" => view_note(args, &dir, true)?,
+        "list" => list_notes(args, &dir, output)?,
+        "view" => view_note(args, &dir, true, output)?,
+        "render" => view_note(args, &dir, true, output)?,
         "edit" => edit_note(args, &dir)?,
         "delete" => delete_notes(args, &dir)?,
-        "list-deleted" => list_deleted(args, &dir)?,
-        "list-archived" => list_archived(args, &dir)?,
+        "list-deleted" => list_deleted(args, &dir, output)?,
+        "list-archived" => list_archived(args, &dir, output)?,
         "archive" => archive_notes(args, &dir)?,
         "undelete" => undelete_notes(args, &dir)?,
         "unarchive" => unarchive_notes(args, &dir)?,
         "migrate" => migrate_notes(args, &dir)?,
         "migrate-ids" => migrate_ids(&dir)?,
+        "convert-format" => convert_format(args, &dir)?,
         "seed" => seed_notes(args, &dir)?,
         "delete-all" => delete_all_notes(&dir)?,
-        "tags" => list_tags(args, &dir)?,
-        "stats" => stats(&dir)?,
+        "tags" => list_tags(args, &dir, output)?,
+        "prop" | "props" => note_properties(args, &dir)?,
+        "stats" => stats(&dir, output)?,
+        "reindex" => reindex(&dir)?,
+        "search" => search::search_notes(args, &dir)?,
+        "export" => export::export_notes(args, &dir)?,
+        "publish" => publish::publish_site(args, &dir)?,
+        "backup" => backup::backup_notes(args, &dir)?,
+        "restore" => backup::restore_backup(args, &dir)?,
+        "serve" => serve::serve(args, &dir)?,
+        "lsp" => lsp::run_lsp(args, &dir)?,
+        "tui" => tui::run_tui(args, &dir)?,
+        "pick" => pick::pick_notes(args, &dir)?,
+        "__list-ids" => pick::list_ids(args, &dir)?,
+        "__pick-tag" => pick::tag_prompt(args, &dir)?,
+        "find" => find::find_notes(args, &dir)?,
+        "agenda" => agenda::show_agenda(args, &dir)?,
+        "due" => agenda::due(args, &dir)?,
+        "remind" => agenda::remind(args, &dir)?,
+        "todos" => todos::list_todos(args, &dir)?,
+        "done" => todos::done(args, &dir)?,
+        "undo-task" => todos::undo(args, &dir)?,
+        "__search" => find::search_lines(args, &dir)?,
+        "__find-preview" => find::preview(args, &dir)?,
+        "log" => git::show_log(args, &dir)?,
+        "sync" => git::sync(args, &dir)?,
+        "sync-dir" => dirsync::sync_dirs(args, &dir)?,
+        "history" => history::show_history(args, &dir)?,
+        "diff" => history::diff_revisions(args, &dir)?,
+        "revert" => history::revert_note(args, &dir)?,
         "path" => println!("{}", dir.display()),
         "completion" => print_completion(args)?,
         "help" => help::run(args)?,
         "guide" => help::run_guides(args)?,
         other => {
             eprintln!("Unknown command: {other}");
-            help::run(Vec::new())?;
+            return help::run(Vec::new());
         }
     }
+    git::autocommit(&dir, &cmd);
 
     Ok(())
 }
@@ -134,10 +224,22 @@ fn quick_add(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     }
     let id = args[0].clone();
     let text = args[1..].join(" ");
+    append_to_note(dir, &id, &text)?;
+    println!("Appended to {id}");
+    Ok(())
+}
+
+/// Append `text` as a new line of an active note and bump `Updated`.
+pub(crate) fn append_to_note(
+    dir: &Path,
+    id: &str,
+    text: &str,
+) -> Result<Note, Box<dyn Error>> {
     if text.trim().is_empty() {
         return Err("Provide text to append".into());
     }
-    let path = resolve_active_note_path(dir, &id)
+    let _lock = lock_note(dir, id)?;
+    let path = resolve_active_note_path(dir, id)
         .ok_or_else(|| format!("Note {id} not found"))?;
     let size = fs::metadata(&path)?.len();
     let mut note = parse_note(&path, size)?;
@@ -149,8 +251,33 @@ fn quick_add(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     note.updated = timestamp_string();
     let target_dir = path.parent().unwrap_or(dir);
     write_note(&note, target_dir)?;
-    println!("Appended to {id}");
-    Ok(())
+    Ok(note)
+}
+
+/// Add and remove tags on an active note; `Updated` only moves when the
+/// tags change.
+pub(crate) fn retag_note(
+    dir: &Path,
+    id: &str,
+    add: &[String],
+    remove: &[String],
+) -> Result<Note, Box<dyn Error>> {
+    let _lock = lock_note(dir, id)?;
+    let path = resolve_active_note_path(dir, id)
+        .ok_or_else(|| format!("Note {id} not found"))?;
+    let mut note = parse_note(&path, fs::metadata(&path)?.len())?;
+    let before = note.tags.clone();
+    note.tags.retain(|t| !remove.contains(t));
+    for tag in add {
+        if !note.tags.contains(tag) {
+            note.tags.push(tag.clone());
+        }
+    }
+    if note.tags != before {
+        note.updated = timestamp_string();
+        write_note(&note, path.parent().unwrap_or(dir))?;
+    }
+    Ok(note)
 }
 
 /// Handle `qn new`, creating a note with explicit title/body and tags.
@@ -175,91 +302,186 @@ pub(crate) fn area_dir(base: &Path, area: Area) -> PathBuf {
     }
 }
 
-fn list_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
-    list_notes_in(args, dir, Area::Active)
+/// Re-read an index entry's file to get the full body.
+pub(crate) fn full_note(base: &Path, area: Area, note: &Note) -> Option<Note> {
+    let path = match area {
+        Area::Active => resolve_active_note_path(base, &note.id)?,
+        _ => note_path(&area_dir(base, area), &note.id),
+    };
+    parse_note(&path, fs::metadata(&path).ok()?.len()).ok()
+}
+
+/// Notes directory that owns a note file in any area (active, a migrated
+/// batch, trash, or archive).
+pub(crate) fn store_root(note_file: &Path) -> Option<PathBuf> {
+    let dir = note_file.parent()?;
+    // The store root carries the lock file, whatever the folder is called.
+    if dir.join(".qn.lock").is_file() {
+        return Some(dir.to_path_buf());
+    }
+    let name =
+        |p: &Path| p.file_name().and_then(|s| s.to_str()).map(str::to_owned);
+    let parent = dir.parent();
+    match name(dir).as_deref() {
+        Some("trash") | Some("archive") if parent.is_some() => {
+            parent.map(Path::to_path_buf)
+        }
+        _ if parent.and_then(name).as_deref() == Some("migrated") => {
+            parent.and_then(Path::parent).map(Path::to_path_buf)
+        }
+        _ => Some(dir.to_path_buf()),
+    }
 }
 
-fn list_deleted(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
-    list_notes_in(args, &area_dir(dir, Area::Trash), Area::Trash)
+fn list_notes(
+    args: Vec<String>,
+    dir: &Path,
+    output: OutputMode,
+) -> Result<(), Box<dyn Error>> {
+    list_notes_in(args, dir, Area::Active, output)
 }
 
-fn list_archived(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
-    list_notes_in(args, &area_dir(dir, Area::Archive), Area::Archive)
+fn list_deleted(
+    args: Vec<String>,
+    dir: &Path,
+    output: OutputMode,
+) -> Result<(), Box<dyn Error>> {
+    list_notes_in(args, dir, Area::Trash, output)
 }
 
-/// List notes with sorting, filtering, and pagination.
-/// REFACTORED: Now uses new modules (args, tags)
-fn list_notes_in(
+fn list_archived(
     args: Vec<String>,
     dir: &Path,
-    area: Area,
+    output: OutputMode,
 ) -> Result<(), Box<dyn Error>> {
-    // Parse arguments using new ArgParser
-    let mut sort_field = "updated".to_string();
-    let mut ascending = false;
-    let mut search: Option<String> = None;
-    let mut tag_filters: Vec<String> = Vec::new();
-    let mut relative_time = false;
-    let mut paginate = true;
+    list_notes_in(args, dir, Area::Archive, output)
+}
+
+/// Filters and ordering shared by the list commands and `qn serve`.
+#[derive(Debug)]
+pub(crate) struct ListOptions {
+    sort_field: String,
+    ascending: bool,
+    search: Option<String>,
+    tag_filters: Vec<String>,
+    property_filters: Vec<(String, String)>,
+    query: Option<Query>,
+    relative_time: bool,
+    paginate: bool,
+    /// Delete trash older than the retention period while listing it. Only
+    /// `qn list-deleted`, which holds the store lock, sets this; the UI, the
+    /// HTTP API and the TUI only read.
+    purge_trash: bool,
+}
+
+/// Parse `qn list` flags.
+pub(crate) fn parse_list_options(
+    args: Vec<String>,
+) -> Result<ListOptions, Box<dyn Error>> {
+    let mut opts = ListOptions {
+        sort_field: "updated".to_string(),
+        ascending: false,
+        search: None,
+        tag_filters: Vec::new(),
+        property_filters: Vec::new(),
+        query: None,
+        relative_time: false,
+        paginate: true,
+        purge_trash: false,
+    };
     let mut parser = args::ArgParser::new(args, "list");
 
     while let Some(arg) = parser.next() {
         match arg.as_str() {
             "--sort" => {
-                sort_field = parser.extract_value("--sort")?;
+                opts.sort_field = parser.extract_value("--sort")?;
             }
-            "--asc" => ascending = true,
-            "--desc" => ascending = false,
+            "--asc" => opts.ascending = true,
+            "--desc" => opts.ascending = false,
             "-s" | "--search" => {
-                search = Some(parser.extract_value("-s/--search")?);
+                opts.search = Some(parser.extract_value("-s/--search")?);
             }
             "-r" | "--relative" => {
-                relative_time = true;
+                opts.relative_time = true;
             }
-            "-a" | "--all" => paginate = false,
+            "-a" | "--all" => opts.paginate = false,
             "-t" | "--tag" => {
                 if let Some(tag) = parser.extract_tag()? {
-                    tag_filters.push(tag);
+                    opts.tag_filters.push(tag);
                 }
             }
+            "-w" | "--where" => {
+                let raw = parser.extract_value("-w/--where")?;
+                let (key, value) = raw.split_once('=').ok_or_else(|| {
+                    format!("Expected key=value for --where, got {raw}")
+                })?;
+                opts.property_filters
+                    .push((key.trim().to_string(), value.trim().to_string()));
+            }
+            "-q" | "--query" => {
+                opts.query =
+                    Some(Query::parse(&parser.extract_value("-q/--query")?)?);
+            }
             other => {
                 return Err(format!("Unknown flag for list: {}", other).into());
             }
         }
     }
+    Ok(opts)
+}
 
-    ensure_dir(dir)?;
-    if let Area::Trash = area {
-        let _ = clean_trash(dir);
+/// Notes of `area` that pass the filters, in the requested order.
+pub(crate) fn select_notes(
+    base: &Path,
+    area: Area,
+    opts: &ListOptions,
+) -> Result<Vec<Note>, Box<dyn Error>> {
+    let dir = area_dir(base, area);
+    ensure_dir(&dir)?;
+    if area == Area::Trash && opts.purge_trash {
+        let _ = clean_trash(&dir);
     }
 
-    let mut notes: Vec<Note> = Vec::new();
-    let files = match area {
-        Area::Active => list_active_note_files(dir)?,
-        _ => list_note_files(dir)?,
-    };
-    for (path, size) in files {
-        if let Ok(note) = parse_note(&path, size) {
-            notes.push(note);
-        }
+    // Metadata comes from the index; bodies are only read for searches.
+    let index = load_index(base)?;
+    let mut notes: Vec<Note> = index.notes(area);
+
+    // Use tags module for filtering
+    if !opts.tag_filters.is_empty() {
+        notes.retain(|n| tags::note_has_tags(n, &opts.tag_filters));
     }
 
-    // Filter by search query
-    if let Some(q) = &search {
-        let ql = q.to_lowercase();
+    if !opts.property_filters.is_empty() {
         notes.retain(|n| {
-            n.title.to_lowercase().contains(&ql)
-                || n.body.to_lowercase().contains(&ql)
+            opts.property_filters.iter().all(|(key, value)| {
+                n.properties
+                    .get(key)
+                    .is_some_and(|v| v.eq_ignore_ascii_case(value))
+            })
         });
     }
 
-    // Use tags module for filtering
-    if !tag_filters.is_empty() {
-        notes.retain(|n| tags::note_has_tags(n, &tag_filters));
+    // Filter by search query
+    if let Some(q) = &opts.search {
+        let ql = q.to_lowercase();
+        notes = notes
+            .into_iter()
+            .filter_map(|n| {
+                if n.title.to_lowercase().contains(&ql) {
+                    return Some(n);
+                }
+                full_note(base, area, &n)
+                    .filter(|full| full.body.to_lowercase().contains(&ql))
+            })
+            .collect();
+    }
+
+    if let Some(query) = &opts.query {
+        notes = query.filter(base, area, notes);
     }
 
     let comparator = |a: &Note, b: &Note| -> std::cmp::Ordering {
-        match sort_field.as_str() {
+        match opts.sort_field.as_str() {
             "created" => cmp_dt(&a.created, &b.created),
             "updated" => cmp_dt(&a.updated, &b.updated),
             "size" => a.size_bytes.cmp(&b.size_bytes),
@@ -269,8 +491,31 @@ fn list_notes_in(
 
     notes.sort_by(|a, b| {
         let ord = comparator(a, b);
-        if ascending { ord } else { ord.reverse() }
+        if opts.ascending { ord } else { ord.reverse() }
     });
+    Ok(notes)
+}
+
+/// List notes with sorting, filtering, and pagination.
+fn list_notes_in(
+    args: Vec<String>,
+    base: &Path,
+    area: Area,
+    output: OutputMode,
+) -> Result<(), Box<dyn Error>> {
+    let mut opts = parse_list_options(args)?;
+    opts.purge_trash = true;
+    let notes = select_notes(base, area, &opts)?;
+    let search = opts.search;
+    let relative_time = opts.relative_time;
+    let paginate = opts.paginate;
+
+    if !output.is_text() {
+        let items =
+            notes.iter().map(|n| note_json(base, area, n, false)).collect();
+        print_items(output, items);
+        return Ok(());
+    }
 
     if notes.is_empty() {
         match area {
@@ -281,18 +526,39 @@ fn list_notes_in(
         return Ok(());
     }
 
-    let now = now_fixed();
     let use_color = env::var("NO_COLOR").is_err();
+    let width = terminal_columns().unwrap_or(120);
+    let lines = list_table_lines(
+        &notes,
+        area,
+        search.as_deref(),
+        relative_time,
+        width,
+        use_color,
+    );
+    paginate_and_print(&lines, paginate)?;
+    Ok(())
+}
+
+/// `qn list` table for `notes`: a header, a `=` rule, then one row per note.
+pub(crate) fn list_table_lines(
+    notes: &[Note],
+    area: Area,
+    search: Option<&str>,
+    relative_time: bool,
+    width: usize,
+    use_color: bool,
+) -> Vec<String> {
+    let now = now_fixed();
     let previews: Vec<String> =
-        notes.iter().map(|n| preview_for_list(n, search.as_deref())).collect();
+        notes.iter().map(|n| preview_for_list(n, search)).collect();
     let tags_plain: Vec<String> =
         notes.iter().map(|n| n.tags.join(" ")).collect();
-    let term_width = terminal_columns().unwrap_or(120);
     let widths = column_widths(
-        &notes,
+        notes,
         &previews,
         &tags_plain,
-        term_width,
+        width,
         relative_time,
         &now,
         area,
@@ -337,7 +603,7 @@ fn list_notes_in(
             truncate_with_ellipsis(&previews[idx], widths.preview);
         let preview_len = display_len(&preview_raw);
         let preview_highlighted =
-            highlight_search(&preview_raw, search.as_deref(), use_color);
+            highlight_search(&preview_raw, search, use_color);
         let created = if widths.include_created {
             Some(n.created.as_str())
         } else {
@@ -377,8 +643,7 @@ fn list_notes_in(
         });
         lines.push(line);
     }
-    paginate_and_print(&lines, paginate)?;
-    Ok(())
+    lines
 }
 
 #[derive(Debug)]
@@ -523,33 +788,31 @@ fn column_widths(
 }
 
 pub(crate) fn terminal_columns() -> Option<usize> {
-    if let Ok(cols) = env::var("COLUMNS") {
-        if let Ok(v) = cols.parse::<usize>() {
-            if v > 0 {
-                return Some(v);
-            }
-        }
+    if let Ok(cols) = env::var("COLUMNS")
+        && let Ok(v) = cols.parse::<usize>()
+        && v > 0
+    {
+        return Some(v);
     }
-    if let Some((Width(w), _)) = terminal_size() {
-        if w > 0 {
-            return Some(w as usize);
-        }
+    if let Some((Width(w), _)) = terminal_size()
+        && w > 0
+    {
+        return Some(w as usize);
     }
     None
 }
 
 fn terminal_rows() -> Option<usize> {
-    if let Ok(rows) = env::var("ROWS") {
-        if let Ok(v) = rows.parse::<usize>() {
-            if v > 0 {
-                return Some(v);
-            }
-        }
+    if let Ok(rows) = env::var("ROWS")
+        && let Ok(v) = rows.parse::<usize>()
+        && v > 0
+    {
+        return Some(v);
     }
-    if let Some((_, Height(h))) = terminal_size() {
-        if h > 0 {
-            return Some(h as usize);
-        }
+    if let Some((_, Height(h))) = terminal_size()
+        && h > 0
+    {
+        return Some(h as usize);
     }
     None
 }
@@ -885,12 +1148,14 @@ fn view_note(
     args: Vec<String>,
     dir: &Path,
     force_render: bool,
+    output: OutputMode,
 ) -> Result<(), Box<dyn Error>> {
     // Parse arguments using new ArgParser
     let mut ids: Vec<String> = Vec::new();
     let mut render = force_render;
     let mut plain = false;
     let mut tag_filters: Vec<String> = Vec::new();
+    let mut query: Option<Query> = None;
     let mut parser = args::ArgParser::new(args, "view");
 
     while let Some(arg) = parser.next() {
@@ -902,6 +1167,10 @@ fn view_note(
                     tag_filters.push(tag);
                 }
             }
+            "-q" | "--query" => {
+                query =
+                    Some(Query::parse(&parser.extract_value("-q/--query")?)?);
+            }
             other if !other.starts_with('-') => {
                 ids.push(other.to_string());
             }
@@ -911,15 +1180,25 @@ fn view_note(
         }
     }
 
+    if let Some(query) = &query {
+        ids.extend(query_note_ids(dir, Area::Active, query)?);
+        if ids.is_empty() {
+            println!("No notes match the query.");
+            return Ok(());
+        }
+    }
+
     if ids.is_empty() {
         return Err(
-            "Usage: qn view <id>... [--render|-r] [--plain|-p] [-t <tag>]"
+            "Usage: qn view <id>... [--render|-r] [--plain|-p] [-t <tag>] \
+[-q <query>]"
                 .into(),
         );
     }
 
     let use_color = !plain && env::var("NO_COLOR").is_err();
     let mut errors: Vec<String> = Vec::new();
+    let mut items: Vec<Json> = Vec::new();
 
     for (idx, id) in ids.iter().enumerate() {
         let Some(path) = resolve_active_note_path(dir, id) else {
@@ -928,19 +1207,20 @@ fn view_note(
         };
 
         // Use tags module for validation
-        if !tag_filters.is_empty() {
-            if let Ok(valid) = tags::validate_note_tags(dir, id, &tag_filters) {
-                if !valid {
-                    errors.push(format!(
-                        "Note {id} does not have required tag(s)"
-                    ));
-                    continue;
-                }
-            }
+        if !tag_filters.is_empty()
+            && let Ok(valid) = tags::validate_note_tags(dir, id, &tag_filters)
+            && !valid
+        {
+            errors.push(format!("Note {id} does not have required tag(s)"));
+            continue;
         }
 
         let size = fs::metadata(&path)?.len();
         let note = parse_note(&path, size)?;
+        if !output.is_text() {
+            items.push(note_json(dir, Area::Active, &note, true));
+            continue;
+        }
         let title_display = if use_color {
             Paint::rgb(&note.title, 249, 226, 175).bold().to_string()
         } else {
@@ -956,31 +1236,27 @@ fn view_note(
             format_timestamp(&note.updated, use_color)
         );
 
-        if render && use_color {
-            if let Some(colorizer) = detect_glow() {
-                let raw_markdown = format!(
-                    "# {} ({})\nCreated: {}\nUpdated: {}\n\n{}",
-                    note.title, note.id, note.created, note.updated, note.body
-                );
-                let mut child = Command::new(colorizer)
-                    .arg("-")
-                    .stdin(Stdio::piped())
-                    .spawn()?;
-                if let Some(stdin) = child.stdin.as_mut() {
-                    stdin.write_all(raw_markdown.as_bytes())?;
-                }
-                let status = child.wait()?;
-                if status.success() {
-                    if idx + 1 != ids.len() {
-                        println!();
-                    }
-                    continue;
+        if render
+            && use_color
+            && let Some(colorizer) = detect_glow()
+        {
+            let raw_markdown = format!(
+                "# {} ({})\nCreated: {}\nUpdated: {}\n\n{}",
+                note.title, note.id, note.created, note.updated, note.body
+            );
+            let mut child = Command::new(colorizer)
+                .arg("-")
+                .stdin(Stdio::piped())
+                .spawn()?;
+            if let Some(stdin) = child.stdin.as_mut() {
+                stdin.write_all(raw_markdown.as_bytes())?;
+            }
+            let status = child.wait()?;
+            if status.success() {
+                if idx + 1 != ids.len() {
+                    println!();
                 }
-            } else {
-                eprintln!(
-                    "Hint: install `glow` for rich markdown rendering \
-(https://github.com/charmbracelet/glow)"
-                );
+                continue;
             }
         }
 
@@ -994,6 +1270,9 @@ fn view_note(
             println!();
         }
     }
+    if !output.is_text() {
+        print_items(output, items);
+    }
     if !errors.is_empty() {
         return Err(errors.remove(0).into());
     }
@@ -1007,6 +1286,8 @@ fn edit_note(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     // Parse arguments using new ArgParser
     let mut ids: Vec<String> = Vec::new();
     let mut tag_filters: Vec<String> = Vec::new();
+    let mut query: Option<Query> = None;
+    let mut line: Option<usize> = None;
     let mut parser = args::ArgParser::new(args, "edit");
 
     while let Some(arg) = parser.next() {
@@ -1016,6 +1297,16 @@ fn edit_note(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
                     tag_filters.push(tag);
                 }
             }
+            "-l" | "--line" => {
+                let raw = parser.extract_value("-l/--line")?;
+                line = Some(
+                    raw.parse().map_err(|_| format!("Invalid line: {raw}"))?,
+                );
+            }
+            "-q" | "--query" => {
+                query =
+                    Some(Query::parse(&parser.extract_value("-q/--query")?)?);
+            }
             other if !other.starts_with('-') => {
                 ids.push(other.to_string());
             }
@@ -1025,10 +1316,18 @@ fn edit_note(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
         }
     }
 
+    if let Some(query) = &query {
+        ids.extend(query_note_ids(dir, Area::Active, query)?);
+        if ids.is_empty() {
+            println!("No notes match the query.");
+            return Ok(());
+        }
+    }
+
     // Use FZF selector if no IDs provided
     if ids.is_empty() {
-        if !fzf::is_fzf_available() {
-            return Err("Usage: qn edit <id>... [-t <tag>]".into());
+        if !fzf::is_picker_available() {
+            return Err("Usage: qn edit <id>... [-t <tag>] [-q <query>]".into());
         }
 
         let files = list_active_note_files(dir)?;
@@ -1049,23 +1348,23 @@ fn edit_note(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
         }
     }
 
-    // Validate notes and collect paths
+    // Validate notes and collect paths; locks are held until the rewrite.
     let mut paths: Vec<(String, PathBuf)> = Vec::new();
+    let mut locks = Vec::new();
     for id in ids {
+        locks.extend(lock_existing_note(dir, &id)?);
         let Some(path) = resolve_active_note_path(dir, &id) else {
             eprintln!("Note {id} not found");
             continue;
         };
 
         // Use tags module for validation
-        if !tag_filters.is_empty() {
-            if let Ok(valid) = tags::validate_note_tags(dir, &id, &tag_filters)
-            {
-                if !valid {
-                    eprintln!("Note {id} does not have required tag(s)");
-                    continue;
-                }
-            }
+        if !tag_filters.is_empty()
+            && let Ok(valid) = tags::validate_note_tags(dir, &id, &tag_filters)
+            && !valid
+        {
+            eprintln!("Note {id} does not have required tag(s)");
+            continue;
         }
         paths.push((id, path));
     }
@@ -1074,9 +1373,18 @@ fn edit_note(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
         return Err("No editable notes matched the criteria".into());
     }
 
+    // Keep the pre-edit text so it can be saved as a revision.
+    let originals: Vec<Option<String>> =
+        paths.iter().map(|(_, p)| fs::read_to_string(p).ok()).collect();
+
     // Open editor
     let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
-    let status = Command::new(&editor)
+    let mut command = Command::new(&editor);
+    // `+N` opens at a line in vi, nano, emacs, micro and most others.
+    if let Some(line) = line {
+        command.arg(format!("+{line}"));
+    }
+    let status = command
         .args(paths.iter().map(|(_, p)| p))
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
@@ -1087,7 +1395,7 @@ fn edit_note(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     }
 
     // Update timestamps for edited notes
-    for (id, path) in paths {
+    for ((id, path), original) in paths.into_iter().zip(originals) {
         let size = fs::metadata(&path)?.len();
         let mut note = parse_note(&path, size)?;
 
@@ -1099,7 +1407,16 @@ fn edit_note(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
         }
 
         note.updated = timestamp_string();
-        write_note(&note, dir)?;
+        // Dates typed in this edit mean "from now", not from the old Updated
+        // that `store_note` would resolve them against.
+        agenda::normalize_dates(&mut note, now_fixed());
+        let edited = fs::read_to_string(&path).ok();
+        let previous = original.filter(|o| Some(o) != edited.as_ref());
+        write_note_replacing(
+            &note,
+            path.parent().unwrap_or(dir),
+            previous.as_deref(),
+        )?;
         println!("Updated {}", note.id);
     }
     Ok(())
@@ -1112,6 +1429,7 @@ fn delete_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     let mut use_fzf = false;
     let mut ids: Vec<String> = Vec::new();
     let mut tag_filters: Vec<String> = Vec::new();
+    let mut query: Option<Query> = None;
     let mut parser = args::ArgParser::new(args, "delete");
 
     while let Some(arg) = parser.next() {
@@ -1122,6 +1440,10 @@ fn delete_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
                     tag_filters.push(tag);
                 }
             }
+            "-q" | "--query" => {
+                query =
+                    Some(Query::parse(&parser.extract_value("-q/--query")?)?);
+            }
             other if !other.starts_with('-') => {
                 ids.push(other.to_string());
             }
@@ -1135,9 +1457,17 @@ fn delete_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     ensure_dir(&trash_dir)?;
     clean_trash(&trash_dir)?;
 
+    if let Some(query) = &query {
+        ids.extend(query_note_ids(dir, Area::Active, query)?);
+        if ids.is_empty() {
+            println!("No notes match the query; nothing deleted.");
+            return Ok(());
+        }
+    }
+
     // Use FZF selector if no IDs provided
     if ids.is_empty() {
-        if !use_fzf && !fzf::is_fzf_available() {
+        if !use_fzf && !fzf::is_picker_available() {
             return Err(
                 "Provide ids or install fzf / use --fzf for interactive delete"
                     .into(),
@@ -1170,20 +1500,19 @@ fn delete_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     // Delete notes with validation
     let mut deleted = 0;
     for id in ids {
+        let _lock = lock_existing_note(dir, &id)?;
         if resolve_active_note_path(dir, &id).is_none() {
             println!("Note {id} not found");
             continue;
         }
 
         // Use tags module for validation
-        if !tag_filters.is_empty() {
-            if let Ok(valid) = tags::validate_note_tags(dir, &id, &tag_filters)
-            {
-                if !valid {
-                    println!("Skipped {id} (missing tag filter)");
-                    continue;
-                }
-            }
+        if !tag_filters.is_empty()
+            && let Ok(valid) = tags::validate_note_tags(dir, &id, &tag_filters)
+            && !valid
+        {
+            println!("Skipped {id} (missing tag filter)");
+            continue;
         }
 
         move_note_with_timestamp(dir, &trash_dir, &id, Area::Trash)?;
@@ -1222,11 +1551,16 @@ fn archive_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     // Parse arguments using new ArgParser
     let mut use_fzf = false;
     let mut ids: Vec<String> = Vec::new();
+    let mut query: Option<Query> = None;
     let mut parser = args::ArgParser::new(args, "archive");
 
     while let Some(arg) = parser.next() {
         match arg.as_str() {
             "--fzf" => use_fzf = true,
+            "-q" | "--query" => {
+                query =
+                    Some(Query::parse(&parser.extract_value("-q/--query")?)?);
+            }
             other if !other.starts_with('-') => {
                 ids.push(other.to_string());
             }
@@ -1239,9 +1573,17 @@ fn archive_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     let archive_dir = area_dir(dir, Area::Archive);
     ensure_dir(&archive_dir)?;
 
+    if let Some(query) = &query {
+        ids.extend(query_note_ids(dir, Area::Active, query)?);
+        if ids.is_empty() {
+            println!("No notes match the query; nothing archived.");
+            return Ok(());
+        }
+    }
+
     // Use FZF selector if no IDs provided
     if ids.is_empty() {
-        if !use_fzf && !fzf::is_fzf_available() {
+        if !use_fzf && !fzf::is_picker_available() {
             return Err(
                 "Provide ids or install fzf / use --fzf for interactive archive"
                     .into(),
@@ -1270,6 +1612,7 @@ fn archive_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     // Archive notes
     let mut moved = 0;
     for id in ids {
+        let _lock = lock_existing_note(dir, &id)?;
         if resolve_active_note_path(dir, &id).is_none() {
             println!("Note {id} not found");
             continue;
@@ -1294,6 +1637,7 @@ fn undelete_notes(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
     clean_trash(&trash_dir)?;
     let mut restored = 0;
     for id in args {
+        let _lock = lock_existing_note(dir, &id)?;
         match restore_note(&id, &trash_dir, dir) {
             Ok(new_id) => {
                 println!("Restored {new_id}");
@@ -1319,6 +1663,7 @@ fn unarchive_notes(
     ensure_dir(&archive_dir)?;
     let mut restored = 0;
     for id in args {
+        let _lock = lock_existing_note(dir, &id)?;
         match restore_note(&id, &archive_dir, dir) {
             Ok(new_id) => {
                 println!("Unarchived {new_id}");
@@ -1333,9 +1678,76 @@ fn unarchive_notes(
     Ok(())
 }
 
+/// Read, set, or remove extra header properties on an active note.
+fn note_properties(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    const USAGE: &str = "Usage: qn prop set <id> <key> <value...> | \
+qn prop get <id> [key] | qn prop unset <id> <key>";
+    let mut parser = args::ArgParser::new(args, "prop");
+    let action = parser.next().ok_or(USAGE)?;
+    let id = parser.next().ok_or(USAGE)?;
+    let _lock = lock_note(dir, &id)?;
+    let path = resolve_active_note_path(dir, &id)
+        .ok_or_else(|| format!("Note {id} not found"))?;
+    let size = fs::metadata(&path)?.len();
+    let mut note = parse_note(&path, size)?;
+    let target_dir = path.parent().unwrap_or(dir);
+
+    match action.as_str() {
+        "get" => match parser.next() {
+            Some(key) => {
+                let value = note.properties.get(&key).ok_or_else(|| {
+                    format!("Note {id} has no property {key}")
+                })?;
+                println!("{value}");
+            }
+            None => {
+                for (key, value) in note.properties.iter() {
+                    println!("{key}: {value}");
+                }
+            }
+        },
+        "set" => {
+            let key = parser.next().ok_or(USAGE)?;
+            validate_property_key(&key)?;
+            let value = parser.collect_remaining().join(" ");
+            let value = value.trim();
+            if value.is_empty() {
+                return Err(format!("Provide a value for {key}").into());
+            }
+            if value.contains('\n') {
+                return Err("Property values must fit on one line".into());
+            }
+            note.properties.set(&key, value);
+            note.updated = timestamp_string();
+            write_note(&note, target_dir)?;
+            println!("Set {key} on {id}");
+        }
+        "unset" => {
+            let key = parser.next().ok_or(USAGE)?;
+            if note.properties.remove(&key).is_none() {
+                return Err(format!("Note {id} has no property {key}").into());
+            }
+            note.updated = timestamp_string();
+            write_note(&note, target_dir)?;
+            println!("Removed {key} from {id}");
+        }
+        other => {
+            return Err(format!("Unknown prop action: {other}\n{USAGE}").into());
+        }
+    }
+    Ok(())
+}
+
 /// Show tags with counts and first/last usage; supports search and relative time.
 /// REFACTORED: Now uses new modules (args, tags)
-fn list_tags(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
+fn list_tags(
+    args: Vec<String>,
+    dir: &Path,
+    output: OutputMode,
+) -> Result<(), Box<dyn Error>> {
     // Parse arguments using new ArgParser
     let mut search: Option<String> = None;
     let mut relative_time = false;
@@ -1367,31 +1779,29 @@ fn list_tags(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
 
     let mut stats: std::collections::BTreeMap<String, TagStat> =
         std::collections::BTreeMap::new();
-    for (path, size) in list_active_note_files(dir)? {
-        if let Ok(note) = parse_note(&path, size) {
-            let created = parse_timestamp(&note.created);
-            let updated = parse_timestamp(&note.updated);
-            for tag in note.tags {
-                let entry = stats.entry(tag).or_default();
-                entry.count += 1;
-                if let Some(c) = created {
-                    entry.first = match entry.first {
-                        Some(f) => Some(f.min(c)),
-                        None => Some(c),
-                    };
-                }
-                if let Some(u) = updated {
-                    entry.last = match entry.last {
-                        Some(l) => Some(l.max(u)),
-                        None => Some(u),
-                    };
-                }
+    for note in load_index(dir)?.notes(Area::Active) {
+        let created = parse_timestamp(&note.created);
+        let updated = parse_timestamp(&note.updated);
+        for tag in note.tags {
+            let entry = stats.entry(tag).or_default();
+            entry.count += 1;
+            if let Some(c) = created {
+                entry.first = match entry.first {
+                    Some(f) => Some(f.min(c)),
+                    None => Some(c),
+                };
+            }
+            if let Some(u) = updated {
+                entry.last = match entry.last {
+                    Some(l) => Some(l.max(u)),
+                    None => Some(u),
+                };
             }
         }
     }
 
-    for tag in pinned_tags {
-        stats.entry(tag).or_default();
+    for tag in &pinned_tags {
+        stats.entry(tag.clone()).or_default();
     }
 
     if let Some(q) = &search {
@@ -1399,11 +1809,43 @@ fn list_tags(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
         stats.retain(|tag, _| tag.to_lowercase().contains(&ql));
     }
 
-    if stats.is_empty() {
+    if stats.is_empty() && output.is_text() {
         println!("No tags found.");
         return Ok(());
     }
 
+    let mut rows_raw: Vec<(String, TagStat)> = stats.into_iter().collect();
+    rows_raw.sort_by(|a, b| {
+        match (a.1.last, b.1.last) {
+            (Some(la), Some(lb)) => lb.cmp(&la),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        }
+        .then_with(|| b.1.count.cmp(&a.1.count))
+        .then_with(|| a.0.cmp(&b.0))
+    });
+
+    if !output.is_text() {
+        let rfc3339 = |d: Option<DateTime<FixedOffset>>| {
+            Json::opt_string(d.map(|d| d.to_rfc3339()))
+        };
+        let items = rows_raw
+            .into_iter()
+            .map(|(tag, stat)| {
+                Json::object(vec![
+                    ("tag", Json::from(tag.as_str())),
+                    ("count", Json::from(stat.count)),
+                    ("pinned", Json::Bool(pinned_tags.contains(&tag))),
+                    ("first_used", rfc3339(stat.first)),
+                    ("last_used", rfc3339(stat.last)),
+                ])
+            })
+            .collect();
+        print_items(output, items);
+        return Ok(());
+    }
+
     let now = now_fixed();
     let use_color = env::var("NO_COLOR").is_err();
     let header_color = |text: &str| {
@@ -1427,18 +1869,7 @@ fn list_tags(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
             .map(|t| format!("Last ({t})"))
             .unwrap_or_else(|| "Last".to_string())
     };
-    let mut rows_raw: Vec<(String, TagStat)> = stats.into_iter().collect();
     let mut rows: Vec<(String, String, String, String)> = Vec::new();
-    rows_raw.sort_by(|a, b| {
-        match (a.1.last, b.1.last) {
-            (Some(la), Some(lb)) => lb.cmp(&la),
-            (Some(_), None) => std::cmp::Ordering::Less,
-            (None, Some(_)) => std::cmp::Ordering::Greater,
-            (None, None) => std::cmp::Ordering::Equal,
-        }
-        .then_with(|| b.1.count.cmp(&a.1.count))
-        .then_with(|| a.0.cmp(&b.0))
-    });
 
     for (tag, stat) in rows_raw {
         let first = stat
@@ -1600,7 +2031,14 @@ fn create_note(
     tags.sort();
     tags.dedup();
 
-    let id = unique_id(dir)?;
+    // Another process may pick the same id; the note lock settles who wins.
+    let (id, _lock) = loop {
+        let id = unique_id(dir)?;
+        let lock = lock_new_note(dir, &id)?;
+        if !note_path(dir, &id).exists() {
+            break (id, lock);
+        }
+    };
     let now = timestamp_string();
     let mut note = Note {
         id: id.clone(),
@@ -1609,8 +2047,12 @@ fn create_note(
         updated: now,
         deleted_at: None,
         archived_at: None,
+        due: None,
+        remind: None,
         body,
         tags,
+        properties: Properties::default(),
+        format: configured_format().unwrap_or_default(),
         size_bytes: 0,
     };
     write_note(&note, dir)?;
@@ -1653,6 +2095,73 @@ fn migrate_ids(dir: &Path) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Rewrite every note (active, trash, archive) in the requested header format.
+fn convert_format(args: Vec<String>, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut target: Option<NoteFormat> = None;
+    let mut dry_run = false;
+    let mut parser = args::ArgParser::new(args, "convert-format");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--to" => {
+                let value = parser.extract_value("--to")?;
+                target = Some(NoteFormat::parse(&value).ok_or_else(|| {
+                    format!("Unknown format {value}; use frontmatter or legacy")
+                })?);
+            }
+            "-n" | "--dry-run" => dry_run = true,
+            other => match NoteFormat::parse(other) {
+                Some(format) if target.is_none() => target = Some(format),
+                _ => {
+                    return Err(format!(
+                        "Unknown flag for convert-format: {other}"
+                    )
+                    .into());
+                }
+            },
+        }
+    }
+    let target = target
+        .ok_or("Usage: qn convert-format <frontmatter|legacy> [--dry-run]")?;
+
+    let mut files = list_active_note_files(dir)?;
+    for area in [Area::Trash, Area::Archive] {
+        let area_path = area_dir(dir, area);
+        if area_path.is_dir() {
+            files.extend(list_note_files(&area_path)?);
+        }
+    }
+
+    let mut converted = 0;
+    for (path, size) in files {
+        let Ok(note) = parse_note(&path, size) else { continue };
+        if note.format == target {
+            continue;
+        }
+        let rel = path.strip_prefix(dir).unwrap_or(&path);
+        if dry_run {
+            println!("Would convert {}", rel.display());
+        } else {
+            write_note_as(&note, path.parent().unwrap_or(dir), target)?;
+            println!("Converted {}", rel.display());
+        }
+        converted += 1;
+    }
+
+    let verb = if dry_run { "would be converted" } else { "converted" };
+    println!("{converted} note(s) {verb} to {}", target.label());
+    if !dry_run
+        && let Some(configured) = configured_format()
+        && configured != target
+    {
+        eprintln!(
+            "Note: QUICK_NOTES_FORMAT={} will write edited notes back as {}",
+            configured.label(),
+            configured.label()
+        );
+    }
+    Ok(())
+}
+
 pub(crate) fn list_note_files(dir: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
     let mut files = Vec::new();
     for entry in fs::read_dir(dir)? {
@@ -1684,10 +2193,11 @@ fn clean_trash(trash_dir: &Path) -> io::Result<()> {
     for (path, size) in list_note_files(trash_dir)? {
         if let Ok(note) = parse_note(&path, size) {
             let ts_str = note.deleted_at.as_deref().unwrap_or(&note.updated);
-            if let Some(ts) = parse_timestamp(ts_str) {
-                if ts < cutoff {
-                    let _ = fs::remove_file(&path);
-                }
+            if let Some(ts) = parse_timestamp(ts_str)
+                && ts < cutoff
+                && fs::remove_file(&path).is_ok()
+            {
+                git::record_touched(&[&path]);
             }
         }
     }
@@ -1704,27 +2214,16 @@ fn restore_note(
         return Err(format!("Note {id} not found").into());
     }
     ensure_dir(to_dir)?;
-    let mut final_id = id.to_string();
-    let dst = note_path(to_dir, &final_id);
     let size = fs::metadata(&src)?.len();
-    if dst.exists() {
+    let mut note = parse_note(&src, size)?;
+    if note_path(to_dir, id).exists() {
         let mut reserved = HashSet::new();
-        let new_id = generate_new_id(to_dir, &mut reserved)?;
-        let mut note = parse_note(&src, size)?;
-        note.id = new_id.clone();
-        note.deleted_at = None;
-        note.archived_at = None;
-        write_note(&note, to_dir)?;
-        fs::remove_file(src)?;
-        final_id = new_id;
-    } else {
-        let mut note = parse_note(&src, size)?;
-        note.deleted_at = None;
-        note.archived_at = None;
-        write_note(&note, to_dir)?;
-        fs::rename(src, &dst)?;
+        note.id = generate_new_id(to_dir, &mut reserved)?;
     }
-    Ok(final_id)
+    note.deleted_at = None;
+    note.archived_at = None;
+    relocate_note(&note, &src, to_dir)?;
+    Ok(note.id)
 }
 
 fn move_note_with_timestamp(
@@ -1757,8 +2256,7 @@ fn move_note_with_timestamp(
         }
     }
     ensure_dir(to_dir)?;
-    write_note(&note, to_dir)?;
-    fs::remove_file(src)?;
+    relocate_note(&note, &src, to_dir)?;
     Ok(())
 }
 
@@ -1837,6 +2335,62 @@ fn preview_for_list(note: &Note, search: Option<&str>) -> String {
     preview_line(note)
 }
 
+/// Stable JSON shape of a note for `--json`/`--ndjson` (see README).
+fn note_json(base: &Path, area: Area, note: &Note, with_body: bool) -> Json {
+    let rfc3339 = |ts: Option<&str>| {
+        Json::opt_string(ts.and_then(parse_timestamp).map(|dt| dt.to_rfc3339()))
+    };
+    // Due/Remind may still hold natural input such as `fri`.
+    let when = |value: Option<&str>| {
+        Json::opt_string(
+            value
+                .and_then(|v| agenda::resolve_when(v, note))
+                .map(|dt| dt.to_rfc3339()),
+        )
+    };
+    let path = match area {
+        Area::Active => resolve_active_note_path(base, &note.id),
+        _ => Some(note_path(&area_dir(base, area), &note.id)),
+    };
+    let properties = note
+        .properties
+        .iter()
+        .map(|(k, v)| (k.to_string(), Json::from(v)))
+        .collect();
+    let mut fields = vec![
+        ("id", Json::from(note.id.as_str())),
+        ("title", Json::from(note.title.as_str())),
+        ("area", Json::from(area_label(area))),
+        ("path", Json::opt_string(path.map(|p| p.display().to_string()))),
+        ("created", rfc3339(Some(&note.created))),
+        ("updated", rfc3339(Some(&note.updated))),
+        ("deleted", rfc3339(note.deleted_at.as_deref())),
+        ("archived", rfc3339(note.archived_at.as_deref())),
+        ("due", when(note.due.as_deref())),
+        ("remind", when(note.remind.as_deref())),
+        (
+            "tags",
+            Json::Array(
+                note.tags.iter().map(|t| Json::from(t.as_str())).collect(),
+            ),
+        ),
+        ("properties", Json::Object(properties)),
+        ("size", Json::from(note.size_bytes)),
+    ];
+    if with_body {
+        fields.push(("body", Json::from(note.body.as_str())));
+    }
+    Json::object(fields)
+}
+
+fn area_label(area: Area) -> &'static str {
+    match area {
+        Area::Active => "active",
+        Area::Trash => "trash",
+        Area::Archive => "archive",
+    }
+}
+
 fn format_tags_clamped(
     tags: &[String],
     max_width: usize,
@@ -2066,14 +2620,39 @@ fn format_relative(
     }
 }
 
-fn stats(dir: &Path) -> Result<(), Box<dyn Error>> {
-    let active = list_active_note_files(dir)?.len();
-    let trash_dir = area_dir(dir, Area::Trash);
-    let archive_dir = area_dir(dir, Area::Archive);
-    ensure_dir(&trash_dir)?;
-    ensure_dir(&archive_dir)?;
-    let trashed = list_note_files(&trash_dir)?.len();
-    let archived = list_note_files(&archive_dir)?.len();
+/// Rebuild `.qn-index` from the note files.
+fn reindex(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let repaired = note::repair_interrupted_moves(dir)?;
+    if repaired > 0 {
+        println!(
+            "Removed {repaired} duplicate note file(s) left by interrupted moves"
+        );
+    }
+    let count = rebuild_index(dir)?;
+    search::rebuild_search_index(dir)?;
+    println!("Indexed {count} note(s)");
+    Ok(())
+}
+
+fn stats(dir: &Path, output: OutputMode) -> Result<(), Box<dyn Error>> {
+    ensure_dir(&area_dir(dir, Area::Trash))?;
+    ensure_dir(&area_dir(dir, Area::Archive))?;
+    let index = load_index(dir)?;
+    let active = index.count(Area::Active);
+    let trashed = index.count(Area::Trash);
+    let archived = index.count(Area::Archive);
+
+    if !output.is_text() {
+        // A single object either way; NDJSON is one line of it.
+        let summary = Json::object(vec![
+            ("active", Json::from(active)),
+            ("trash", Json::from(trashed)),
+            ("archive", Json::from(archived)),
+            ("total", Json::from(active + trashed + archived)),
+        ]);
+        println!("{summary}");
+        return Ok(());
+    }
 
     let headers = vec!["Area".to_string(), "Count".to_string()];
     let rows = vec![
@@ -0,0 +1,756 @@
+//! `qn lsp`: a language server over stdio for editing note files.
+//!
+//! Editors get completion of note ids inside `[[...]]` links and of existing
+//! tags on the `Tags:` header line, go-to-definition and hover previews on
+//! note links (`[[id]]`, `[[id|label]]` and markdown links to `<id>.md`),
+//! and diagnostics for links to missing notes and for header fields that
+//! `parse_note` would not read. Documents are synced in full on every change;
+//! positions use the protocol's default UTF-16 columns.
+
+use crate::Area;
+use crate::agenda::resolve_when;
+use crate::export::note_link_target;
+use crate::index::load_index;
+use crate::note::{
+    RESERVED_HEADERS, cmp_dt, parse_note, parse_note_text, parse_timestamp,
+};
+use crate::render::markdown_options;
+use crate::shared::json::Json;
+use crate::shared::migrate::locate_note;
+use pulldown_cmark::{Event, Parser, Tag};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const SEVERITY_ERROR: u64 = 1;
+const SEVERITY_WARNING: u64 = 2;
+const KIND_REFERENCE: u64 = 18;
+const KIND_KEYWORD: u64 = 14;
+const METHOD_NOT_FOUND: i64 = -32601;
+const REQUEST_FAILED: i64 = -32803;
+/// Body lines shown in a hover preview.
+const PREVIEW_LINES: usize = 12;
+
+/// A link to another note, as a byte range of the document.
+#[derive(Debug, PartialEq)]
+struct Link {
+    id: String,
+    start: usize,
+    end: usize,
+    /// Wiki links and `.md` links always name a note; a bare markdown
+    /// target might be another file, so it is not reported when missing.
+    checked: bool,
+}
+
+fn wiki_link_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"\[\[([^\[\]|#]+)(?:#[^\[\]|]*)?(?:\|[^\[\]]*)?\]\]")
+            .expect("valid wiki link pattern")
+    })
+}
+
+/// Every note link in `text`, in document order.
+fn note_links(text: &str) -> Vec<Link> {
+    let mut links: Vec<Link> = wiki_link_pattern()
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let whole = caps.get(0)?;
+            let id = caps.get(1)?.as_str().trim();
+            (!id.is_empty()).then(|| Link {
+                id: id.to_string(),
+                start: whole.start(),
+                end: whole.end(),
+                checked: true,
+            })
+        })
+        .collect();
+    for (event, range) in
+        Parser::new_ext(text, markdown_options()).into_offset_iter()
+    {
+        if let Event::Start(Tag::Link { dest_url, .. }) = event
+            && let Some((id, _)) = note_link_target(&dest_url)
+        {
+            let target = dest_url.split('#').next().unwrap_or_default();
+            links.push(Link {
+                id: id.to_string(),
+                start: range.start,
+                end: range.end,
+                checked: target.ends_with(".md"),
+            });
+        }
+    }
+    links.sort_by_key(|link| link.start);
+    links
+}
+
+/// Byte offset of an LSP position, clamped to the end of its line.
+fn offset_of(text: &str, line: usize, character: usize) -> Option<usize> {
+    let mut start = 0;
+    for _ in 0..line {
+        start += text[start..].find('\n')? + 1;
+    }
+    let line_text = text[start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (i, c) in line_text.char_indices() {
+        if units >= character {
+            return Some(start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(start + line_text.len())
+}
+
+/// LSP position (line, UTF-16 column) of a byte offset.
+fn position_of(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[line_start..].encode_utf16().count();
+    (line, character)
+}
+
+fn position_json((line, character): (usize, usize)) -> Json {
+    Json::object(vec![
+        ("line", Json::from(line)),
+        ("character", Json::from(character)),
+    ])
+}
+
+fn range_json(text: &str, start: usize, end: usize) -> Json {
+    Json::object(vec![
+        ("start", position_json(position_of(text, start))),
+        ("end", position_json(position_of(text, end))),
+    ])
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+fn diagnostic(
+    text: &str,
+    (start, end): (usize, usize),
+    severity: u64,
+    message: String,
+) -> Json {
+    Json::object(vec![
+        ("range", range_json(text, start, end)),
+        ("severity", Json::from(severity)),
+        ("source", Json::from("qn")),
+        ("message", Json::from(message)),
+    ])
+}
+
+/// Byte range of the first header line starting with `key:` (any case).
+fn header_line(text: &str, header_len: usize, key: &str) -> (usize, usize) {
+    let mut start = 0;
+    for line in text[..header_len].split_inclusive('\n') {
+        let lower = line.to_ascii_lowercase();
+        if lower.starts_with(&format!("{}:", key.to_ascii_lowercase())) {
+            return (start, start + line.trim_end().len());
+        }
+        start += line.len();
+    }
+    (0, 0)
+}
+
+/// Problems `parse_note` would silently paper over, plus broken links.
+fn diagnostics(dir: &Path, text: &str) -> Vec<Json> {
+    let mut found = Vec::new();
+    let note = parse_note_text(String::new(), text, text.len() as u64);
+    let front_matter = text.starts_with("---\n") || text.starts_with("---\r\n");
+    let header_len = text.len().saturating_sub(note.body.len());
+    if !front_matter && header_len == 0 {
+        found.push(diagnostic(
+            text,
+            (0, text.find('\n').unwrap_or(text.len())),
+            SEVERITY_ERROR,
+            "No note header: start with Title/Created/Updated/Tags lines and \
+a `---` line"
+                .to_string(),
+        ));
+    }
+
+    if header_len > 0 {
+        if note.title.is_empty() {
+            found.push(diagnostic(
+                text,
+                header_line(text, header_len, "title"),
+                SEVERITY_ERROR,
+                "Missing Title header".to_string(),
+            ));
+        }
+        let stamps = [
+            ("Created", Some(&note.created)),
+            ("Updated", Some(&note.updated)),
+            ("Deleted", note.deleted_at.as_ref()),
+            ("Archived", note.archived_at.as_ref()),
+        ];
+        for (key, value) in stamps {
+            let Some(value) = value else { continue };
+            let range = header_line(text, header_len, key);
+            if value.is_empty() && range == (0, 0) {
+                found.push(diagnostic(
+                    text,
+                    range,
+                    SEVERITY_ERROR,
+                    format!("Missing {key} header"),
+                ));
+            } else if parse_timestamp(value).is_none() {
+                found.push(diagnostic(
+                    text,
+                    range,
+                    SEVERITY_ERROR,
+                    format!(
+                        "{key} is not a timestamp qn can read: {value:?} \
+(expected e.g. 20May24 12:00 -04:00)"
+                    ),
+                ));
+            }
+        }
+        let dates =
+            [("Due", note.due.as_ref()), ("Remind", note.remind.as_ref())];
+        for (key, value) in dates {
+            let Some(value) = value else { continue };
+            if resolve_when(value, &note).is_none() {
+                found.push(diagnostic(
+                    text,
+                    header_line(text, header_len, key),
+                    SEVERITY_WARNING,
+                    format!(
+                        "{key} is not a date qn can read: {value:?} \
+(try e.g. `tomorrow 9am`, `fri` or `+3d`)"
+                    ),
+                ));
+            }
+        }
+    }
+
+    if !front_matter && header_len > 0 {
+        let mut start = 0;
+        for line in text[..header_len].split_inclusive('\n') {
+            let trimmed = line.trim_end();
+            let range = (start, start + trimmed.len());
+            start += line.len();
+            if trimmed.is_empty() || trimmed == "---" {
+                continue;
+            }
+            let builtin = ["Title:", "Created:", "Updated:", "Deleted:"]
+                .into_iter()
+                .chain(["Archived:", "Due:", "Remind:", "Tags:"])
+                .any(|prefix| trimmed.starts_with(prefix));
+            if builtin {
+                continue;
+            }
+            // Other lines are kept as they are; only flag the ones that
+            // cannot be read back as the property they look like.
+            let message = match trimmed.split_once(':') {
+                None => {
+                    "Malformed header line; expected `Key: value`".to_string()
+                }
+                Some((key, _)) => {
+                    let key = key.trim();
+                    if !RESERVED_HEADERS
+                        .iter()
+                        .any(|r| r.eq_ignore_ascii_case(key))
+                    {
+                        continue;
+                    }
+                    format!(
+                        "{key} is a built-in header, not a property; \
+the line is kept as text"
+                    )
+                }
+            };
+            found.push(diagnostic(text, range, SEVERITY_WARNING, message));
+        }
+    }
+
+    for link in note_links(text) {
+        if link.start < header_len {
+            continue;
+        }
+        if link.checked && locate_note(dir, &link.id).is_none() {
+            found.push(diagnostic(
+                text,
+                (link.start, link.end),
+                SEVERITY_WARNING,
+                format!("No note with id {}", link.id),
+            ));
+        }
+    }
+    found
+}
+
+/// Whether 0-based `line` is in a plain-text header (before `---`).
+fn in_header(text: &str, line: usize) -> bool {
+    if text.starts_with("---") {
+        return false;
+    }
+    text.split('\n')
+        .position(|l| l.trim_end() == "---")
+        .is_some_and(|separator| line < separator)
+}
+
+fn completion_item(
+    label: &str,
+    kind: u64,
+    detail: String,
+    sort: usize,
+    edit: Option<Json>,
+) -> Json {
+    let mut fields = vec![
+        ("label", Json::from(label)),
+        ("kind", Json::from(kind)),
+        ("detail", Json::from(detail)),
+        ("sortText", Json::from(format!("{sort:06}"))),
+    ];
+    if let Some(edit) = edit {
+        fields.push(("textEdit", edit));
+    }
+    Json::object(fields)
+}
+
+struct Server {
+    dir: PathBuf,
+    documents: HashMap<String, String>,
+}
+
+impl Server {
+    /// Text of the open document named by `params.textDocument`.
+    fn document(&self, params: &Json) -> Result<&str, Box<dyn Error>> {
+        let uri = params
+            .get("textDocument")
+            .and_then(|d| d.get("uri"))
+            .and_then(Json::as_str)
+            .ok_or("Missing textDocument.uri")?;
+        let text = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| format!("Document {uri} is not open"))?;
+        Ok(text)
+    }
+
+    /// The open document and the byte offset of `params.position`.
+    fn cursor(&self, params: &Json) -> Result<(&str, usize), Box<dyn Error>> {
+        let text = self.document(params)?;
+        let position = params.get("position").ok_or("Missing position")?;
+        let coord = |key: &str| {
+            position.get(key).and_then(Json::as_u64).map(|n| n as usize)
+        };
+        let (Some(line), Some(character)) = (coord("line"), coord("character"))
+        else {
+            return Err("Invalid position".into());
+        };
+        let offset = offset_of(text, line, character)
+            .ok_or("Position is past the end")?;
+        Ok((text, offset))
+    }
+
+    fn link_at(&self, params: &Json) -> Result<Option<Link>, Box<dyn Error>> {
+        let (text, offset) = self.cursor(params)?;
+        Ok(note_links(text)
+            .into_iter()
+            .find(|link| link.start <= offset && offset <= link.end))
+    }
+
+    fn completion(&self, params: &Json) -> Result<Json, Box<dyn Error>> {
+        let (text, offset) = self.cursor(params)?;
+        let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let before = &text[line_start..offset];
+        let line = text[..offset].matches('\n').count();
+        let index = load_index(&self.dir)?;
+        let mut items: Vec<Json> = Vec::new();
+
+        if let Some(open) = before.rfind("[[")
+            && !before[open..].contains("]]")
+            && !before[open..].contains(['|', '#'])
+        {
+            let mut notes = index.notes(Area::Active);
+            notes.sort_by(|a, b| cmp_dt(&b.updated, &a.updated));
+            let start = line_start + open + 2;
+            for (i, note) in notes.iter().enumerate() {
+                let edit = Json::object(vec![
+                    ("range", range_json(text, start, offset)),
+                    ("newText", Json::from(note.id.as_str())),
+                ]);
+                let mut item = completion_item(
+                    &note.id,
+                    KIND_REFERENCE,
+                    note.title.clone(),
+                    i,
+                    Some(edit),
+                );
+                if let Json::Object(fields) = &mut item {
+                    // Match on the title as well as the id.
+                    fields.push((
+                        "filterText".to_string(),
+                        Json::from(format!("{} {}", note.id, note.title)),
+                    ));
+                }
+                items.push(item);
+            }
+        } else if let Some(rest) = before.strip_prefix("Tags:")
+            && in_header(text, line)
+        {
+            let typed = rest.rsplit(',').next().unwrap_or_default();
+            let typed = typed.trim_start();
+            let start = offset - typed.len();
+            let line_end =
+                text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+            let present: Vec<&str> = text[line_start + 5..line_end]
+                .split(',')
+                .map(str::trim)
+                .collect();
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for area in [Area::Active, Area::Archive, Area::Trash] {
+                for note in index.notes(area) {
+                    for tag in note.tags {
+                        *counts.entry(tag).or_default() += 1;
+                    }
+                }
+            }
+            let mut ranked: Vec<(String, usize)> = counts
+                .into_iter()
+                .filter(|(tag, _)| !present.contains(&tag.as_str()))
+                .collect();
+            ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            for (i, (tag, count)) in ranked.iter().enumerate() {
+                let edit = Json::object(vec![
+                    ("range", range_json(text, start, offset)),
+                    ("newText", Json::from(tag.as_str())),
+                ]);
+                items.push(completion_item(
+                    tag,
+                    KIND_KEYWORD,
+                    format!(
+                        "{count} note{}",
+                        if *count == 1 { "" } else { "s" }
+                    ),
+                    i,
+                    Some(edit),
+                ));
+            }
+        }
+        Ok(Json::Array(items))
+    }
+
+    fn definition(&self, params: &Json) -> Result<Json, Box<dyn Error>> {
+        let Some(link) = self.link_at(params)? else {
+            return Ok(Json::Null);
+        };
+        let Some((_, path)) = locate_note(&self.dir, &link.id) else {
+            return Ok(Json::Null);
+        };
+        let start = position_json((0, 0));
+        Ok(Json::object(vec![
+            ("uri", Json::from(path_to_uri(&path))),
+            (
+                "range",
+                Json::object(vec![("start", start.clone()), ("end", start)]),
+            ),
+        ]))
+    }
+
+    fn hover(&self, params: &Json) -> Result<Json, Box<dyn Error>> {
+        let Some(link) = self.link_at(params)? else {
+            return Ok(Json::Null);
+        };
+        let (text, _) = self.cursor(params)?;
+        let Some((area, path)) = locate_note(&self.dir, &link.id) else {
+            return Ok(Json::Null);
+        };
+        let note = parse_note(&path, fs::metadata(&path)?.len())?;
+        let mut meta = vec![format!("`{}`", note.id)];
+        match area {
+            Area::Trash => meta.push("in trash".to_string()),
+            Area::Archive => meta.push("archived".to_string()),
+            Area::Active => {}
+        }
+        meta.push(format!("updated {}", note.updated));
+        if !note.tags.is_empty() {
+            meta.push(note.tags.join(" "));
+        }
+        let preview: Vec<&str> =
+            note.body.trim().lines().take(PREVIEW_LINES).collect();
+        let value = format!(
+            "**{}**\n\n{}\n\n---\n\n{}",
+            note.title,
+            meta.join(" · "),
+            preview.join("\n")
+        );
+        Ok(Json::object(vec![
+            (
+                "contents",
+                Json::object(vec![
+                    ("kind", Json::from("markdown")),
+                    ("value", Json::from(value)),
+                ]),
+            ),
+            ("range", range_json(text, link.start, link.end)),
+        ]))
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Json {
+        let items = match self.documents.get(uri) {
+            Some(text) => diagnostics(&self.dir, text),
+            None => Vec::new(),
+        };
+        Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("method", Json::from("textDocument/publishDiagnostics")),
+            (
+                "params",
+                Json::object(vec![
+                    ("uri", Json::from(uri)),
+                    ("diagnostics", Json::Array(items)),
+                ]),
+            ),
+        ])
+    }
+
+    /// Apply a notification; returns the notifications to send back.
+    fn notify(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let doc = params.get("textDocument");
+        let Some(uri) = doc.and_then(|d| d.get("uri")).and_then(Json::as_str)
+        else {
+            return Vec::new();
+        };
+        let uri = uri.to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = doc
+                    .and_then(|d| d.get("text"))
+                    .and_then(Json::as_str)
+                    .unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+            }
+            "textDocument/didChange" => {
+                // Full sync: the last change holds the whole text.
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(<[Json]>::last)
+                    .and_then(|c| c.get("text"))
+                    .and_then(Json::as_str);
+                match text {
+                    Some(text) => {
+                        self.documents.insert(uri.clone(), text.to_string());
+                    }
+                    None => return Vec::new(),
+                }
+            }
+            "textDocument/didSave" => {}
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return Vec::new(),
+        }
+        vec![self.publish_diagnostics(&uri)]
+    }
+
+    fn request(
+        &mut self,
+        method: &str,
+        params: &Json,
+    ) -> Result<Json, (i64, String)> {
+        let failed = |e: Box<dyn Error>| (REQUEST_FAILED, e.to_string());
+        match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => Ok(Json::Null),
+            "textDocument/completion" => {
+                self.completion(params).map_err(failed)
+            }
+            "textDocument/definition" => {
+                self.definition(params).map_err(failed)
+            }
+            "textDocument/hover" => self.hover(params).map_err(failed),
+            other => Err((METHOD_NOT_FOUND, format!("Unsupported: {other}"))),
+        }
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                ("textDocumentSync", Json::from(1u64)),
+                (
+                    "completionProvider",
+                    Json::object(vec![(
+                        "triggerCharacters",
+                        Json::Array(vec![
+                            Json::from("["),
+                            Json::from("#"),
+                            Json::from(","),
+                        ]),
+                    )]),
+                ),
+                ("definitionProvider", Json::Bool(true)),
+                ("hoverProvider", Json::Bool(true)),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![
+                ("name", Json::from("qn")),
+                ("version", Json::from(env!("CARGO_PKG_VERSION"))),
+            ]),
+        ),
+    ])
+}
+
+/// Read one `Content-Length` framed message; `None` at end of input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length: Option<usize> = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(out: &mut impl Write, message: &Json) -> io::Result<()> {
+    let payload = message.to_string();
+    write!(out, "Content-Length: {}\r\n\r\n{payload}", payload.len())?;
+    out.flush()
+}
+
+/// Handle `qn lsp`: serve the protocol on stdin/stdout until `exit`.
+pub(crate) fn run_lsp(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(arg) = args.first() {
+        return Err(
+            format!("Unknown argument for lsp: {arg}\nUsage: qn lsp").into()
+        );
+    }
+    let mut server =
+        Server { dir: dir.to_path_buf(), documents: HashMap::new() };
+    let mut input = io::stdin().lock();
+    let mut out = io::stdout().lock();
+    while let Some(raw) = read_message(&mut input)? {
+        let message = match Json::parse(&raw) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("qn lsp: ignoring unreadable message: {e}");
+                continue;
+            }
+        };
+        let method =
+            message.get("method").and_then(Json::as_str).unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        if method == "exit" {
+            break;
+        }
+        let Some(id) = message.get("id").cloned() else {
+            for note in server.notify(method, &params) {
+                write_message(&mut out, &note)?;
+            }
+            continue;
+        };
+        let outcome = match server.request(method, &params) {
+            Ok(result) => ("result", result),
+            Err((code, text)) => (
+                "error",
+                Json::object(vec![
+                    ("code", Json::Number(code)),
+                    ("message", Json::from(text)),
+                ]),
+            ),
+        };
+        let reply = Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("id", id),
+            outcome,
+        ]);
+        write_message(&mut out, &reply)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_wiki_and_markdown_links() {
+        let text = "See [[a1]], [[b2|the plan]] and [c](c3.md#x), [d](d4).";
+        let links = note_links(text);
+        let ids: Vec<(&str, bool)> =
+            links.iter().map(|l| (l.id.as_str(), l.checked)).collect();
+        assert_eq!(
+            ids,
+            vec![("a1", true), ("b2", true), ("c3", true), ("d4", false)]
+        );
+        assert_eq!(&text[links[0].start..links[0].end], "[[a1]]");
+    }
+
+    #[test]
+    fn converts_positions_with_utf16_columns() {
+        let text = "Title: x\nnaïve 😀 [[a1]]\n";
+        let offset = text.find("[[").unwrap();
+        assert_eq!(position_of(text, offset), (1, 9));
+        assert_eq!(offset_of(text, 1, 9), Some(offset));
+        assert_eq!(offset_of(text, 1, 99), Some(text.len() - 1));
+        assert_eq!(offset_of(text, 5, 0), None);
+    }
+
+    #[test]
+    fn reports_bad_headers_and_broken_links() {
+        let tmp = tempfile::tempdir().unwrap();
+        let text = "Title: Plan\nCreated: yesterday\n\
+Updated: 01Jan24 10:00 +00:00\nDue: someday\nRemind: fri 9am\n\
+title: lower\nnot a header\nTags: #a\n---\n\
+Body with [[missing]] and [other](x.md).\n";
+        let messages: Vec<String> = diagnostics(tmp.path(), text)
+            .iter()
+            .filter_map(|d| d.get("message").and_then(Json::as_str))
+            .map(String::from)
+            .collect();
+        assert_eq!(messages.len(), 6, "{messages:?}");
+        assert!(messages[0].starts_with("Created is not a timestamp"));
+        assert!(messages[1].starts_with("Due is not a date"));
+        assert!(messages[2].contains("built-in header"));
+        assert!(messages[3].starts_with("Malformed header line"));
+        assert_eq!(messages[4], "No note with id missing");
+        assert_eq!(messages[5], "No note with id x");
+
+        let uri = path_to_uri(Path::new("/tmp/my notes/a1.md"));
+        assert_eq!(uri, "file:///tmp/my%20notes/a1.md");
+    }
+}
@@ -1,6 +1,8 @@
+use crate::shared::atomic::{atomic_move, atomic_write};
 use chrono::{DateTime, FixedOffset, Local};
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -9,6 +11,11 @@ use std::sync::{Mutex, OnceLock};
 pub const TIME_FMT: &str = "%d%b%y %H:%M %:z";
 pub const LEGACY_TIME_FMT: &str = "%m/%d/%Y %I:%M %p %:z";
 pub const ID_TS_WIDTH: usize = 9;
+/// Header keys owned by `Note` itself; everything else is a property.
+pub const RESERVED_HEADERS: &[&str] = &[
+    "Title", "Created", "Updated", "Deleted", "Archived", "Due", "Remind",
+    "Tags",
+];
 
 #[derive(Debug, Clone)]
 pub struct Note {
@@ -18,11 +25,174 @@ pub struct Note {
     pub updated: String,
     pub deleted_at: Option<String>,
     pub archived_at: Option<String>,
+    /// `Due:`/`Remind:` as written; see `agenda::resolve_when` for the
+    /// natural forms (`tomorrow 9am`, `fri`, `+3d`) they may still hold.
+    pub due: Option<String>,
+    pub remind: Option<String>,
     pub body: String,
     pub tags: Vec<String>,
+    pub properties: Properties,
+    pub format: NoteFormat,
     pub size_bytes: u64,
 }
 
+/// On-disk header style of a note file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoteFormat {
+    /// `Title:`/`Created:`/... lines followed by `---`.
+    #[default]
+    Legacy,
+    /// YAML front matter between two `---` lines (Obsidian/Jekyll style).
+    FrontMatter,
+}
+
+impl NoteFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "legacy" | "header" => Some(NoteFormat::Legacy),
+            "frontmatter" | "front-matter" | "yaml" => {
+                Some(NoteFormat::FrontMatter)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NoteFormat::Legacy => "legacy",
+            NoteFormat::FrontMatter => "frontmatter",
+        }
+    }
+}
+
+/// Output format forced by `QUICK_NOTES_FORMAT`, if set to a known value.
+/// When unset, notes are written back in the format they were read in.
+pub fn configured_format() -> Option<NoteFormat> {
+    std::env::var("QUICK_NOTES_FORMAT").ok().and_then(|v| NoteFormat::parse(&v))
+}
+
+/// Extra `Key: value` header fields, kept in file order so they survive a
+/// parse/write round trip. Keys are matched case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Properties(Vec<Property>);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    key: String,
+    value: String,
+    /// The entry exactly as read, when `key: value` would not reproduce it;
+    /// written back unchanged as long as the note keeps its format.
+    raw: Option<(NoteFormat, String)>,
+}
+
+impl Properties {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.find(key).map(|p| p.value.as_str())
+    }
+
+    /// Replace an existing value in place or append a new key.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.0.iter_mut().find(|p| p.key.eq_ignore_ascii_case(key)) {
+            Some(entry) => {
+                entry.value = value.to_string();
+                entry.raw = None;
+            }
+            None => self.0.push(Property {
+                key: key.to_string(),
+                value: value.to_string(),
+                raw: None,
+            }),
+        }
+    }
+
+    /// Like `set`, but remember the text the entry was read from.
+    pub(crate) fn set_raw(
+        &mut self,
+        key: &str,
+        value: &str,
+        format: NoteFormat,
+        raw: &str,
+    ) {
+        self.set(key, value);
+        if let Some(entry) =
+            self.0.iter_mut().find(|p| p.key.eq_ignore_ascii_case(key))
+        {
+            entry.raw = Some((format, raw.to_string()));
+        }
+    }
+
+    /// Set `key` to `value`, keeping the original text of whichever entry
+    /// in `sources` already holds that value.
+    pub(crate) fn set_from(
+        &mut self,
+        key: &str,
+        value: &str,
+        sources: &[&Properties],
+    ) {
+        let source = sources
+            .iter()
+            .filter_map(|s| s.find(key))
+            .find(|p| p.value == value);
+        match source.and_then(|p| p.raw.as_ref()) {
+            Some((format, raw)) => self.set_raw(key, value, *format, raw),
+            None => self.set(key, value),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let idx =
+            self.0.iter().position(|p| p.key.eq_ignore_ascii_case(key))?;
+        Some(self.0.remove(idx).value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|p| (p.key.as_str(), p.value.as_str()))
+    }
+
+    /// Entries as `(key, value, raw)`, where `raw` is the original text if
+    /// it was read from a note in `format`.
+    pub(crate) fn entries(
+        &self,
+        format: NoteFormat,
+    ) -> impl Iterator<Item = (&str, &str, Option<&str>)> {
+        self.0.iter().map(move |p| {
+            let raw = p
+                .raw
+                .as_ref()
+                .filter(|(f, _)| *f == format)
+                .map(|(_, raw)| raw.as_str());
+            (p.key.as_str(), p.value.as_str(), raw)
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn find(&self, key: &str) -> Option<&Property> {
+        self.0.iter().find(|p| p.key.eq_ignore_ascii_case(key))
+    }
+}
+
+/// Check that a property key can be written back as a header line.
+pub fn validate_property_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("Property key cannot be empty".to_string());
+    }
+    if key.contains(':') || key.chars().any(char::is_whitespace) {
+        return Err(format!(
+            "Invalid property key {key:?}; avoid spaces and ':'"
+        ));
+    }
+    if key == "---" {
+        return Err("Property key cannot be the header separator".to_string());
+    }
+    if RESERVED_HEADERS.iter().any(|r| r.eq_ignore_ascii_case(key)) {
+        return Err(format!("{key} is a built-in header, not a property"));
+    }
+    Ok(())
+}
+
 pub fn notes_dir() -> io::Result<PathBuf> {
     if let Ok(dir) = std::env::var("QUICK_NOTES_DIR") {
         return Ok(PathBuf::from(dir));
@@ -82,8 +252,141 @@ pub fn cmp_dt(a: &str, b: &str) -> Ordering {
 }
 
 pub fn write_note(note: &Note, dir: &Path) -> io::Result<()> {
+    let format = configured_format().unwrap_or(note.format);
+    write_note_as(note, dir, format)
+}
+
+/// Write a note in an explicit format, ignoring `QUICK_NOTES_FORMAT`.
+pub fn write_note_as(
+    note: &Note,
+    dir: &Path,
+    format: NoteFormat,
+) -> io::Result<()> {
+    let path = note_path(dir, &note.id);
+    let previous = fs::read_to_string(&path).ok();
+    store_note(note, &path, format, previous.as_deref())
+}
+
+/// Rewrite a note whose file was already changed outside `write_note` (by
+/// `$EDITOR`), recording `previous` as the revision it replaced.
+pub fn write_note_replacing(
+    note: &Note,
+    dir: &Path,
+    previous: Option<&str>,
+) -> io::Result<()> {
+    let format = configured_format().unwrap_or(note.format);
+    store_note(note, &note_path(dir, &note.id), format, previous)
+}
+
+/// `note` with natural `Due:`/`Remind:` values resolved. They were read
+/// relative to the Updated time in `previous` (the file being replaced), so
+/// that is the time they keep; a new note resolves them against now.
+fn resolve_dates<'a>(note: &'a Note, previous: Option<&str>) -> Cow<'a, Note> {
+    let natural = |value: &Option<String>| {
+        value.as_deref().is_some_and(|v| parse_timestamp(v).is_none())
+    };
+    if !natural(&note.due) && !natural(&note.remind) {
+        return Cow::Borrowed(note);
+    }
+    let since = previous
+        .map(|raw| parse_note_text(note.id.clone(), raw, raw.len() as u64))
+        .and_then(|old| parse_timestamp(&old.updated))
+        .unwrap_or_else(now_fixed);
+    let mut resolved = note.clone();
+    crate::agenda::normalize_dates(&mut resolved, since);
+    Cow::Owned(resolved)
+}
+
+fn store_note(
+    note: &Note,
+    path: &Path,
+    format: NoteFormat,
+    previous: Option<&str>,
+) -> io::Result<()> {
+    let note = &*resolve_dates(note, previous);
+    let content = serialize_note(note, format);
+    if let Some(previous) = previous
+        && previous != content
+    {
+        crate::history::save_revision(path, &note.id, previous)?;
+    }
+    atomic_write(path, content.as_bytes())?;
+    crate::git::record_touched(&[path]);
+    crate::index::record_write(path, note);
+    crate::search::record_write(path, note);
+    Ok(())
+}
+
+/// Move the note file at `src` into `to_dir`, rewriting it from `note`.
+/// Interrupting this leaves exactly one copy of the note on disk.
+pub fn relocate_note(note: &Note, src: &Path, to_dir: &Path) -> io::Result<()> {
+    let format = configured_format().unwrap_or(note.format);
+    let dst = note_path(to_dir, &note.id);
+    atomic_move(src, &dst, serialize_note(note, format).as_bytes())?;
+    crate::git::record_touched(&[src, &dst]);
+    crate::index::record_move(src, &dst, note);
+    crate::search::record_move(src, &dst, note);
+    Ok(())
+}
+
+/// Remove the extra copy a crash can leave when a move falls back to
+/// link-then-unlink: one id with identical contents in two areas. The copy
+/// in the area its headers point at is kept. Returns the files removed.
+pub(crate) fn repair_interrupted_moves(dir: &Path) -> io::Result<usize> {
+    use crate::Area;
+    use crate::index::{area_of, rel_path};
+
+    let mut by_id: HashMap<String, Vec<(Area, PathBuf)>> = HashMap::new();
+    for path in crate::shared::migrate::list_store_note_files(dir)? {
+        let (Some(id), Some(rel)) =
+            (path.file_stem().and_then(|s| s.to_str()), rel_path(dir, &path))
+        else {
+            continue;
+        };
+        by_id.entry(id.to_string()).or_default().push((area_of(&rel), path));
+    }
+
+    let mut removed = 0;
+    for (id, copies) in by_id {
+        if copies.len() < 2 {
+            continue;
+        }
+        let texts = copies
+            .iter()
+            .map(|(_, path)| fs::read_to_string(path))
+            .collect::<io::Result<Vec<_>>>()?;
+        if texts.iter().any(|text| *text != texts[0]) {
+            continue;
+        }
+        let note = parse_note_text(id, &texts[0], 0);
+        let home = if note.deleted_at.is_some() {
+            Area::Trash
+        } else if note.archived_at.is_some() {
+            Area::Archive
+        } else {
+            Area::Active
+        };
+        if copies.iter().filter(|(area, _)| *area == home).count() != 1 {
+            continue;
+        }
+        for (_, path) in copies.iter().filter(|(area, _)| *area != home) {
+            fs::remove_file(path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+pub fn serialize_note(note: &Note, format: NoteFormat) -> String {
     let mut body = note.body.trim_end_matches('\n').to_string();
     body.push('\n');
+    if format == NoteFormat::FrontMatter {
+        return format!(
+            "{}{}",
+            crate::frontmatter::render_front_matter(note),
+            body
+        );
+    }
     let tags_line = if note.tags.is_empty() {
         "Tags:".to_string()
     } else {
@@ -99,25 +402,70 @@ pub fn write_note(note: &Note, dir: &Path) -> io::Result<()> {
         .as_ref()
         .map(|d| format!("Archived: {d}\n"))
         .unwrap_or_default();
-    let content = format!(
-        "Title: {}\nCreated: {}\nUpdated: {}\n{}{}{}\n---\n{}",
+    let due_lines: String = [("Due", &note.due), ("Remind", &note.remind)]
+        .iter()
+        .filter_map(|(key, value)| {
+            Some(format!("{key}: {}\n", value.as_ref()?))
+        })
+        .collect();
+    let property_lines: String = note
+        .properties
+        .entries(NoteFormat::Legacy)
+        .map(|(k, v, raw)| match raw {
+            Some(raw) => format!("{raw}\n"),
+            None => format!("{k}: {}\n", v.replace('\n', " ")),
+        })
+        .collect();
+    format!(
+        "Title: {}\nCreated: {}\nUpdated: {}\n{}{}{}{}\n{}---\n{}",
         note.title,
         note.created,
         note.updated,
         deleted_line,
         archived_line,
+        due_lines,
         tags_line,
+        property_lines,
         body
-    );
-    fs::write(note_path(dir, &note.id), content)
+    )
 }
 
 pub fn parse_note(path: &Path, size_bytes: u64) -> io::Result<Note> {
     let raw = fs::read_to_string(path)?;
+    let id = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_string();
+    Ok(parse_note_text(id, &raw, size_bytes))
+}
+
+/// Parse note file contents that did not come straight from disk.
+pub(crate) fn parse_note_text(id: String, raw: &str, size_bytes: u64) -> Note {
+    if let Some((yaml, body)) = crate::frontmatter::split_front_matter(raw) {
+        let mut note = Note {
+            id,
+            title: String::new(),
+            created: String::new(),
+            updated: String::new(),
+            deleted_at: None,
+            archived_at: None,
+            due: None,
+            remind: None,
+            body: body.to_string(),
+            tags: Vec::new(),
+            properties: Properties::default(),
+            format: NoteFormat::FrontMatter,
+            size_bytes,
+        };
+        crate::frontmatter::apply_front_matter(yaml, &mut note);
+        return note;
+    }
+
     let (header, body) = if let Some(idx) = raw.find("\n---\n") {
         raw.split_at(idx + 5)
     } else {
-        ("", raw.as_str())
+        ("", raw)
     };
 
     let mut title = String::new();
@@ -125,7 +473,10 @@ pub fn parse_note(path: &Path, size_bytes: u64) -> io::Result<Note> {
     let mut updated = String::new();
     let mut deleted_at: Option<String> = None;
     let mut archived_at: Option<String> = None;
+    let mut due: Option<String> = None;
+    let mut remind: Option<String> = None;
     let mut tags: Vec<String> = Vec::new();
+    let mut properties = Properties::default();
 
     for line in header.lines() {
         if let Some(val) = line.strip_prefix("Title:") {
@@ -138,30 +489,47 @@ pub fn parse_note(path: &Path, size_bytes: u64) -> io::Result<Note> {
             deleted_at = Some(val.trim().to_string());
         } else if let Some(val) = line.strip_prefix("Archived:") {
             archived_at = Some(val.trim().to_string());
+        } else if let Some(val) = line.strip_prefix("Due:") {
+            due = Some(val.trim().to_string()).filter(|v| !v.is_empty());
+        } else if let Some(val) = line.strip_prefix("Remind:") {
+            remind = Some(val.trim().to_string()).filter(|v| !v.is_empty());
         } else if let Some(val) = line.strip_prefix("Tags:") {
             tags = val
                 .split(',')
                 .map(|t| super::normalize_tag(t.trim()))
                 .filter(|t| !t.is_empty())
                 .collect();
+        } else if line.trim().is_empty() || line == "---" {
+            continue;
+        } else {
+            // Anything else is kept, even if `qn prop set` would refuse
+            // the key; lines that do not read back as `key: value` are
+            // stored verbatim.
+            let (key, val) = line.split_once(':').unwrap_or((line, ""));
+            let (key, val) = (key.trim(), val.trim());
+            if format!("{key}: {val}") == line {
+                properties.set(key, val);
+            } else {
+                properties.set_raw(key, val, NoteFormat::Legacy, line);
+            }
         }
     }
 
-    Ok(Note {
-        id: path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or_default()
-            .to_string(),
+    Note {
+        id,
         title,
         created,
         updated,
         deleted_at,
         archived_at,
+        due,
+        remind,
         body: body.to_string(),
         tags,
+        properties,
+        format: NoteFormat::Legacy,
         size_bytes,
-    })
+    }
 }
 
 #[derive(Default)]
@@ -205,9 +573,17 @@ pub(crate) fn generate_new_id(
     }
 }
 
+const ALPHABET: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+pub(crate) fn decode_base62(value: &str) -> Option<u64> {
+    value.bytes().try_fold(0u64, |acc, b| {
+        let digit = ALPHABET.iter().position(|&c| c == b)? as u64;
+        acc.checked_mul(ALPHABET.len() as u64)?.checked_add(digit)
+    })
+}
+
 fn encode_base62(num: u64) -> String {
-    const ALPHABET: &[u8] =
-        b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
     if num == 0 {
         return "0".to_string();
     }
@@ -222,7 +598,7 @@ fn encode_base62(num: u64) -> String {
     out.iter().rev().collect()
 }
 
-fn encode_base62_width(num: u64, width: usize) -> String {
+pub(crate) fn encode_base62_width(num: u64, width: usize) -> String {
     let base = encode_base62(num);
     if base.len() >= width {
         base
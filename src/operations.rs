@@ -1,5 +1,5 @@
 use crate::note::{
-    Note, ensure_dir, generate_new_id, note_path, parse_note, write_note,
+    Note, ensure_dir, generate_new_id, note_path, parse_note, relocate_note,
 };
 use crate::shared::migrate::resolve_active_note_path;
 use std::collections::HashSet;
@@ -39,16 +39,14 @@ pub fn move_note(
     id: &str,
     update_fn: impl FnOnce(&mut Note),
 ) -> Result<(), Box<dyn Error>> {
-    let mut note = load_note(from_dir, id)?;
+    let src = resolve_active_note_path(from_dir, id)
+        .ok_or_else(|| format!("Note {} not found", id))?;
+    let size = fs::metadata(&src)?.len();
+    let mut note = parse_note(&src, size)?;
     update_fn(&mut note);
 
     ensure_dir(to_dir)?;
-    write_note(&note, to_dir)?;
-
-    // Remove old file
-    let src = resolve_active_note_path(from_dir, id)
-        .ok_or_else(|| format!("Note {} not found", id))?;
-    fs::remove_file(src)?;
+    relocate_note(&note, &src, to_dir)?;
 
     Ok(())
 }
@@ -64,10 +62,10 @@ pub fn filter_by_tags(
 
     let mut filtered = Vec::new();
     for (path, size) in files {
-        if let Ok(note) = parse_note(&path, size) {
-            if crate::tags::note_has_tags(&note, tag_filters) {
-                filtered.push(path);
-            }
+        if let Ok(note) = parse_note(&path, size)
+            && crate::tags::note_has_tags(&note, tag_filters)
+        {
+            filtered.push(path);
         }
     }
     Ok(filtered)
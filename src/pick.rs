@@ -0,0 +1,291 @@
+//! `qn pick`: a triage picker whose keys act on the highlighted notes.
+//!
+//! Under fzf every action is a `--bind` that runs `qn` and then
+//! `reload(qn __list-ids ...)`, so the list refreshes without leaving fzf.
+//! The built-in picker cannot run commands itself; it ends on the same keys,
+//! the action runs, and the picker opens again on the fresh list.
+
+use crate::fzf::{self, FzfSelector};
+use crate::query::{Query, query_note_ids};
+use crate::shared::lock::{LockMode, lock_store};
+use crate::shared::migrate::resolve_active_note_path;
+use crate::shared::shell::shell_quote;
+use crate::{
+    Area, args, git, parse_list_options, retag_note, select_notes, tags,
+};
+use std::collections::HashSet;
+use std::env;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::Command;
+
+const USAGE: &str = "Usage: qn pick [-t tag]... [-q query]";
+
+const HEADER: &str = "enter view · ctrl-e edit · ctrl-d trash · \
+ctrl-a archive · ctrl-t tag · ctrl-y print path";
+
+/// Key, fzf action, `qn` arguments (`{1}` is the current id, `{+1}` the
+/// marked ids) and whether the list needs reloading afterwards.
+const ACTIONS: &[(&str, &str, &str, bool)] = &[
+    ("enter", "execute", "view {1} | ${PAGER:-less -R}", false),
+    ("ctrl-e", "execute", "edit {+1}", true),
+    ("ctrl-d", "execute-silent", "delete {+1}", true),
+    ("ctrl-a", "execute-silent", "archive {+1}", true),
+    ("ctrl-t", "execute", "__pick-tag {+1}", true),
+];
+
+/// Key that ends the picker and prints the chosen notes' paths.
+const PRINT_PATH: &str = "ctrl-y";
+
+/// `-t`/`-q` filters, as given (so they can be passed to `__list-ids`).
+fn parse_filters(
+    args: Vec<String>,
+    cmd: &str,
+) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut filters = Vec::new();
+    let mut parser = args::ArgParser::new(args, cmd);
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "-t" | "--tag" => {
+                if let Some(tag) = parser.extract_tag()? {
+                    filters.push(("-t".to_string(), tag));
+                }
+            }
+            "-q" | "--query" => {
+                let raw = parser.extract_value("-q/--query")?;
+                Query::parse(&raw)?;
+                filters.push(("-q".to_string(), raw));
+            }
+            other => {
+                return Err(format!(
+                    "Unknown argument for {cmd}: {other}\n{USAGE}"
+                )
+                .into());
+            }
+        }
+    }
+    Ok(filters)
+}
+
+/// One `id<TAB>title<TAB>tags` line per matching active note.
+fn candidate_lines(
+    dir: &Path,
+    filters: &[(String, String)],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut list_args: Vec<String> = Vec::new();
+    let mut keep: Option<HashSet<String>> = None;
+    for (flag, value) in filters {
+        if flag == "-q" {
+            let ids = query_note_ids(dir, Area::Active, &Query::parse(value)?)?;
+            let ids: HashSet<String> = ids.into_iter().collect();
+            keep = Some(match keep {
+                Some(seen) => seen.intersection(&ids).cloned().collect(),
+                None => ids,
+            });
+        } else {
+            list_args.extend([flag.clone(), value.clone()]);
+        }
+    }
+    let notes =
+        select_notes(dir, Area::Active, &parse_list_options(list_args)?)?;
+    Ok(notes
+        .into_iter()
+        .filter(|n| keep.as_ref().is_none_or(|ids| ids.contains(&n.id)))
+        .map(|n| {
+            format!(
+                "{}\t{}\t{}",
+                n.id,
+                n.title.replace('\t', " "),
+                n.tags.join(" ")
+            )
+        })
+        .collect())
+}
+
+/// Handle `qn __list-ids`: the lines `qn pick` reloads.
+pub(crate) fn list_ids(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let filters = parse_filters(args, "__list-ids")?;
+    for line in candidate_lines(dir, &filters)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Handle `qn __pick-tag <id>...`: ask for `+tag -tag` edits on the terminal
+/// and apply them to every id.
+pub(crate) fn tag_prompt(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        return Err("Usage: qn __pick-tag <id>...".into());
+    }
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let target = match args.as_slice() {
+        [id] => id.clone(),
+        many => format!("{} notes", many.len()),
+    };
+    write!(tty, "Tags for {target} (+add -remove): ")?;
+    tty.flush()?;
+    let mut line = String::new();
+    BufReader::new(&tty).read_line(&mut line)?;
+    let (add, remove) = tags::parse_tag_edits(&line);
+    if add.is_empty() && remove.is_empty() {
+        return Ok(());
+    }
+    {
+        let _store = lock_store(dir, LockMode::Shared)?;
+        for id in &args {
+            retag_note(dir, id, &add, &remove)?;
+        }
+    }
+    git::autocommit(dir, "tag");
+    Ok(())
+}
+
+/// Shell command for an action's `qn` arguments on `ids`.
+fn action_command(exe: &str, template: &str, ids: &[String]) -> String {
+    let all: Vec<String> = ids.iter().map(|id| shell_quote(id)).collect();
+    let first = all.first().cloned().unwrap_or_default();
+    let args = template.replace("{+1}", &all.join(" ")).replace("{1}", &first);
+    format!("{exe} {args}")
+}
+
+/// fzf `--bind` specs running each action, then reloading the list.
+fn fzf_bindings(exe: &str, reload: &str) -> Vec<String> {
+    ACTIONS
+        .iter()
+        .map(|(key, action, template, changes)| {
+            let mut binding = format!("{key}:{action}({exe} {template})");
+            if *changes {
+                binding.push_str(&format!("+reload({reload})"));
+            }
+            binding
+        })
+        .collect()
+}
+
+fn selector(exe: &str, reload: &str, native: bool) -> FzfSelector {
+    let mut selector = FzfSelector::with_simple_preview()
+        .preview(&format!(
+            "env -u NO_COLOR CLICOLOR_FORCE=1 {exe} render {{1}} 2>/dev/null"
+        ))
+        .delimiter("\t")
+        .header(HEADER)
+        .expect(PRINT_PATH);
+    if native {
+        for (key, ..) in ACTIONS {
+            selector = selector.expect(key);
+        }
+    } else {
+        for binding in fzf_bindings(exe, reload) {
+            selector = selector.bind(&binding);
+        }
+    }
+    selector
+}
+
+/// Handle `qn pick`.
+pub(crate) fn pick_notes(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let filters = parse_filters(args, "pick")?;
+    if !fzf::is_picker_available() {
+        return Err("qn pick needs fzf or an interactive terminal".into());
+    }
+    let exe = shell_quote(&env::current_exe()?.to_string_lossy());
+    let mut reload = format!("{exe} __list-ids");
+    for (flag, value) in &filters {
+        reload.push_str(&format!(" {flag} {}", shell_quote(value)));
+    }
+    let native = !fzf::is_fzf_available();
+    let selector = selector(&exe, &reload, native);
+
+    loop {
+        let lines = candidate_lines(dir, &filters)?;
+        if lines.is_empty() {
+            println!("No notes to pick.");
+            return Ok(());
+        }
+        let selection = selector.pick(&lines.join("\n"))?;
+        let ids: Vec<String> = selection
+            .items
+            .iter()
+            .filter_map(|line| line.split('\t').next())
+            .filter(|id| !id.is_empty())
+            .map(String::from)
+            .collect();
+        match selection.key.as_deref() {
+            Some(PRINT_PATH) => {
+                for id in &ids {
+                    if let Some(path) = resolve_active_note_path(dir, id) {
+                        println!("{}", path.display());
+                    }
+                }
+                return Ok(());
+            }
+            // Only the built-in picker returns for the other keys.
+            Some(key) if !ids.is_empty() => {
+                let Some((_, _, template, _)) =
+                    ACTIONS.iter().find(|(k, ..)| *k == key)
+                else {
+                    return Ok(());
+                };
+                Command::new("sh")
+                    .arg("-c")
+                    .arg(action_command(&exe, template, &ids))
+                    .status()?;
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::fixtures;
+
+    #[test]
+    fn binds_actions_to_reloads_under_fzf() {
+        let binds = fzf_bindings("'qn'", "'qn' __list-ids -t '#work'");
+        assert_eq!(binds.len(), ACTIONS.len());
+        assert!(
+            binds.contains(
+                &"ctrl-d:execute-silent('qn' delete {+1})\
++reload('qn' __list-ids -t '#work')"
+                    .to_string()
+            )
+        );
+        assert!(binds.contains(
+            &"enter:execute('qn' view {1} | ${PAGER:-less -R})".to_string()
+        ));
+        assert_eq!(
+            action_command("'qn'", "delete {+1}", &["a".into(), "it's".into()]),
+            r"'qn' delete 'a' 'it'\''s'"
+        );
+        assert_eq!(
+            action_command("qn", "view {1} | less", &["a".into(), "b".into()]),
+            "qn view 'a' | less"
+        );
+    }
+
+    #[test]
+    fn lists_candidates_with_titles_and_filters() {
+        let tmp = tempfile::tempdir().unwrap();
+        fixtures::write_note(tmp.path(), "n1", "Roadmap", "#work", "body\n");
+        fixtures::write_note(tmp.path(), "n2", "Groceries", "#home", "body\n");
+        let filters = vec![("-t".to_string(), "#work".to_string())];
+        let lines = candidate_lines(tmp.path(), &filters).unwrap();
+        assert_eq!(lines, vec!["n1\tRoadmap\t#work"]);
+        let filters = vec![("-q".to_string(), "title:groc".to_string())];
+        let lines = candidate_lines(tmp.path(), &filters).unwrap();
+        assert_eq!(lines, vec!["n2\tGroceries\t#home"]);
+    }
+}
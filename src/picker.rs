@@ -0,0 +1,800 @@
+//! Built-in fuzzy picker, used by `FzfSelector` when `fzf` is missing or
+//! `QUICK_NOTES_NO_FZF` is set.
+//!
+//! It follows fzf's conventions closely enough that muscle memory carries
+//! over: space-separated terms must all match as subsequences (smart case),
+//! the best match sits next to the prompt, Tab/Shift-Tab mark entries in
+//! multi-select mode, Enter accepts and Esc cancels. Heights such as `70%`
+//! draw inline below the cursor; no height takes over the screen. A reload
+//! command swaps matching for fzf's `--disabled` + `change:reload(...)`: the
+//! command's output becomes the entries as the query changes.
+
+use crate::shared::shell::shell_quote;
+use crate::term::{Backend, Input, Key, Terminal, fit, screen_size, wrap};
+use regex::Regex;
+use std::collections::HashMap;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// Smallest inline picker, whatever the height setting says.
+const MIN_HEIGHT: usize = 10;
+
+/// What `FzfSelector` asked for, in fzf's own terms.
+pub(crate) struct Options<'a> {
+    pub(crate) multi: bool,
+    /// `--height`: rows or a percentage of the terminal.
+    pub(crate) height: Option<&'a str>,
+    /// `--layout`: `reverse` puts the prompt on top.
+    pub(crate) layout: Option<&'a str>,
+    /// `--preview`: a shell command with `{}` for the entry.
+    pub(crate) preview: Option<&'a str>,
+    /// `--preview-window`, e.g. `down:wrap` or `right:60%`.
+    pub(crate) preview_window: Option<&'a str>,
+    /// `--delimiter` for `{1}`-style fields; whitespace when unset.
+    pub(crate) delimiter: Option<&'a str>,
+    pub(crate) header: Option<&'a str>,
+    /// `--expect`: keys such as `ctrl-e` that end the picker.
+    pub(crate) expect: &'a [String],
+    /// Command with `{q}` for the query whose output replaces the entries
+    /// whenever the query changes.
+    pub(crate) reload: Option<&'a str>,
+}
+
+/// The `expect` key that ended the picker (if any) and the chosen entries;
+/// no entries means it was cancelled.
+type Outcome = (Option<String>, Vec<String>);
+
+/// Whether there is a terminal to draw the picker on.
+pub(crate) fn is_available() -> bool {
+    io::stdin().is_terminal()
+        && OpenOptions::new().read(true).write(true).open("/dev/tty").is_ok()
+}
+
+/// Show the picker on the terminal.
+pub(crate) fn pick(
+    items: Vec<String>,
+    options: &Options,
+) -> io::Result<Outcome> {
+    let preview = options.preview.map(|command| {
+        let command = command.to_string();
+        let delimiter = options.delimiter.map(String::from);
+        move |item: &str| {
+            shell_lines(&expand(&command, item, delimiter.as_deref()))
+        }
+    });
+    let reload = options.reload.map(|command| {
+        let command = command.to_string();
+        move |query: &str| {
+            shell_lines(&command.replace("{q}", &shell_quote(query)))
+        }
+    });
+    let mut picker = Picker::new(
+        items,
+        options.multi,
+        options.layout == Some("reverse"),
+        preview.map(|p| Box::new(p) as Preview),
+        PreviewWindow::parse(options.preview_window.unwrap_or_default()),
+        env::var("NO_COLOR").is_err(),
+    );
+    picker.expect = options.expect.to_vec();
+    picker.header = options.header.map(String::from);
+    picker.delimiter = options.delimiter.map(String::from);
+    picker.reload = reload.map(|r| Box::new(r) as Reload);
+    let rows = options.height.and_then(|h| parse_height(h, screen_size().1));
+    let mut terminal = match rows {
+        Some(rows) => Terminal::inline(rows)?,
+        None => Terminal::fullscreen()?,
+    };
+    picker.run(&mut terminal)
+}
+
+/// Lines `command` prints under `sh`; failures print nothing.
+fn shell_lines(command: &str) -> Vec<String> {
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map(|out| {
+            String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The `n`th (1-based) field of `item`, split on `delimiter` or whitespace.
+fn field<'a>(item: &'a str, n: usize, delimiter: Option<&str>) -> &'a str {
+    let field = match delimiter {
+        Some(d) => item.split(d).nth(n.saturating_sub(1)),
+        None => item.split_whitespace().nth(n.saturating_sub(1)),
+    };
+    field.unwrap_or_default().trim()
+}
+
+/// Fill fzf placeholders in `command` for one entry: `{}` is the whole
+/// entry and `{N}` its Nth field; the `{+...}` forms mean the same here.
+fn expand(command: &str, item: &str, delimiter: Option<&str>) -> String {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let placeholder = PLACEHOLDER
+        .get_or_init(|| Regex::new(r"\{\+?(\d*)\}").expect("valid regex"));
+    placeholder
+        .replace_all(command, |caps: &regex::Captures| {
+            let value = match caps[1].parse::<usize>() {
+                Ok(n) if n > 0 => field(item, n, delimiter),
+                _ => item,
+            };
+            shell_quote(value)
+        })
+        .into_owned()
+}
+
+/// Rows for an fzf-style height (`20`, `70%`, `~40%`); `None` means the
+/// whole screen.
+fn parse_height(spec: &str, screen_rows: usize) -> Option<usize> {
+    let spec = spec.trim().trim_start_matches('~');
+    let rows = match spec.strip_suffix('%') {
+        Some(pct) => {
+            let pct: usize = pct.trim().parse().ok()?;
+            if pct >= 100 {
+                return None;
+            }
+            screen_rows * pct / 100
+        }
+        None => spec.parse().ok()?,
+    };
+    Some(rows.max(MIN_HEIGHT).min(screen_rows))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Right,
+    Left,
+    Up,
+    Down,
+}
+
+#[derive(Debug, PartialEq)]
+struct PreviewWindow {
+    side: Side,
+    percent: usize,
+    wrap: bool,
+    hidden: bool,
+    /// Scroll offset such as `+{2}-/2`, without the `+`.
+    scroll: Option<String>,
+}
+
+impl PreviewWindow {
+    /// Parse the parts of `--preview-window` the picker understands.
+    fn parse(spec: &str) -> Self {
+        let mut window = PreviewWindow {
+            side: Side::Right,
+            percent: 50,
+            wrap: false,
+            hidden: false,
+            scroll: None,
+        };
+        for part in spec.split([':', ',']).map(str::trim) {
+            match part {
+                "right" => window.side = Side::Right,
+                "left" => window.side = Side::Left,
+                "up" | "top" => window.side = Side::Up,
+                "down" | "bottom" => window.side = Side::Down,
+                "wrap" => window.wrap = true,
+                "nowrap" => window.wrap = false,
+                "hidden" => window.hidden = true,
+                other if other.starts_with('+') => {
+                    window.scroll = Some(other[1..].to_string());
+                }
+                other => {
+                    if let Some(pct) = other.strip_suffix('%')
+                        && let Ok(pct) = pct.parse::<usize>()
+                    {
+                        window.percent = pct.clamp(10, 90);
+                    }
+                }
+            }
+        }
+        window
+    }
+}
+
+/// Preview lines to skip for a scroll spec: `N` or `{N}` (a field holding
+/// the line number) puts that line on top, and `-M` or `-/D` moves it down by
+/// `M` rows or a `D`th of the preview's `rows`.
+fn scroll_offset(
+    spec: &str,
+    item: &str,
+    delimiter: Option<&str>,
+    rows: usize,
+) -> usize {
+    let (base, back) = spec.split_once('-').unwrap_or((spec, ""));
+    let line = match base.strip_prefix('{').and_then(|b| b.strip_suffix('}')) {
+        Some(n) => n
+            .parse()
+            .ok()
+            .and_then(|n| field(item, n, delimiter).parse::<usize>().ok()),
+        None => base.parse().ok(),
+    };
+    let back = match back.strip_prefix('/') {
+        Some(d) => d.parse::<usize>().map_or(0, |d| rows / d.max(1)),
+        None => back.parse().unwrap_or(0),
+    };
+    line.unwrap_or(1).saturating_sub(1 + back)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+}
+
+/// Score `term` as a subsequence of `text`; higher is better. Returns the
+/// char positions that matched for highlighting.
+fn match_term(term: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let case_sensitive = term.chars().any(char::is_uppercase);
+    let fold = |c: char| {
+        if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) }
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = term.chars().map(fold).collect();
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    // Leftmost match to find where it ends, then walk back from that end
+    // to the latest start so the window is as tight as possible.
+    let mut p = 0;
+    let mut end = None;
+    for (i, &c) in chars.iter().enumerate() {
+        if fold(c) == pattern[p] {
+            p += 1;
+            if p == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut p = pattern.len();
+    for i in (0..=end).rev() {
+        if p > 0 && fold(chars[i]) == pattern[p - 1] {
+            p -= 1;
+            positions.push(i);
+        }
+    }
+    positions.reverse();
+
+    let mut score: i64 = 0;
+    let mut previous: Option<usize> = None;
+    for &i in &positions {
+        score += 16;
+        let boundary = i == 0
+            || !is_word_char(chars[i - 1])
+            || (chars[i - 1].is_lowercase() && chars[i].is_uppercase());
+        if boundary {
+            score += if previous.is_none() { 16 } else { 8 };
+        }
+        match previous {
+            Some(prev) if prev + 1 == i => score += 4,
+            Some(prev) => score -= 3 + (i - prev - 2) as i64,
+            None => {}
+        }
+        previous = Some(i);
+    }
+    Some((score, positions))
+}
+
+/// Match every whitespace-separated term of `query` against `text`.
+fn match_query(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let mut total = 0;
+    let mut positions: Vec<usize> = Vec::new();
+    for term in query.split_whitespace() {
+        let (score, hits) = match_term(term, text)?;
+        total += score;
+        positions.extend(hits);
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    Some((total, positions))
+}
+
+type Preview = Box<dyn Fn(&str) -> Vec<String>>;
+type Reload = Box<dyn Fn(&str) -> Vec<String>>;
+
+struct Picker {
+    items: Vec<String>,
+    query: String,
+    /// Matching item indices with highlight positions, best first.
+    matches: Vec<(usize, Vec<usize>)>,
+    cursor: usize,
+    top: usize,
+    /// Marked item indices in the order they were marked.
+    marked: Vec<usize>,
+    multi: bool,
+    reverse: bool,
+    preview: Option<Preview>,
+    previews: HashMap<usize, Vec<String>>,
+    /// First preview line shown; `None` until the scroll offset applies.
+    preview_top: Option<usize>,
+    window: PreviewWindow,
+    color: bool,
+    expect: Vec<String>,
+    header: Option<String>,
+    delimiter: Option<String>,
+    reload: Option<Reload>,
+}
+
+impl Picker {
+    fn new(
+        items: Vec<String>,
+        multi: bool,
+        reverse: bool,
+        preview: Option<Preview>,
+        window: PreviewWindow,
+        color: bool,
+    ) -> Self {
+        let mut picker = Picker {
+            items,
+            query: String::new(),
+            matches: Vec::new(),
+            cursor: 0,
+            top: 0,
+            marked: Vec::new(),
+            multi,
+            reverse,
+            preview: if window.hidden { None } else { preview },
+            previews: HashMap::new(),
+            preview_top: None,
+            window,
+            color,
+            expect: Vec::new(),
+            header: None,
+            delimiter: None,
+            reload: None,
+        };
+        picker.filter();
+        picker
+    }
+
+    fn filter(&mut self) {
+        self.cursor = 0;
+        self.top = 0;
+        self.preview_top = None;
+        if let Some(reload) = &self.reload {
+            self.items = reload(&self.query);
+            self.matches =
+                (0..self.items.len()).map(|i| (i, Vec::new())).collect();
+            self.marked.clear();
+            self.previews.clear();
+            return;
+        }
+        let mut scored: Vec<(i64, usize, Vec<usize>)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| {
+                match_query(&self.query, item).map(|(s, hits)| (s, i, hits))
+            })
+            .collect();
+        // Best score first; input order breaks ties.
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches =
+            scored.into_iter().map(|(_, i, hits)| (i, hits)).collect();
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.matches.get(self.cursor).map(|(i, _)| *i)
+    }
+
+    /// Move the cursor `delta` matches away from the best one.
+    fn step(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1) as isize;
+        let next = (self.cursor as isize + delta).clamp(0, last) as usize;
+        if next != self.cursor {
+            self.cursor = next;
+            self.preview_top = None;
+        }
+    }
+
+    fn toggle(&mut self) {
+        let Some(item) = self.current() else { return };
+        match self.marked.iter().position(|&m| m == item) {
+            Some(at) => {
+                self.marked.remove(at);
+            }
+            None => self.marked.push(item),
+        }
+    }
+
+    /// Marked entries, or the one under the cursor.
+    fn chosen(&self) -> Vec<String> {
+        let chosen: Vec<usize> = if self.marked.is_empty() {
+            self.current().into_iter().collect()
+        } else {
+            self.marked.clone()
+        };
+        chosen.into_iter().map(|i| self.items[i].clone()).collect()
+    }
+
+    /// `Some` ends the picker.
+    fn key(&mut self, key: Key, page: usize) -> Option<Outcome> {
+        let name = match key {
+            Key::Enter => Some("enter".to_string()),
+            Key::Ctrl(c) => Some(format!("ctrl-{c}")),
+            _ => None,
+        };
+        if let Some(name) = name
+            && self.expect.contains(&name)
+        {
+            return Some((Some(name), self.chosen()));
+        }
+        // Up and down are visual: the best match is at the bottom unless
+        // the layout is reversed.
+        let up = if self.reverse { -1 } else { 1 };
+        let page = page.max(1) as isize;
+        match key {
+            Key::Enter => return Some((None, self.chosen())),
+            Key::Esc | Key::Ctrl('c' | 'g' | 'q') => {
+                return Some((None, Vec::new()));
+            }
+            Key::Up | Key::Ctrl('p' | 'k') => self.step(up),
+            Key::Down | Key::Ctrl('n') => self.step(-up),
+            Key::PageUp => self.step(up * page),
+            Key::PageDown => self.step(-up * page),
+            Key::Tab | Key::BackTab if self.multi => {
+                self.toggle();
+                self.step(if key == Key::Tab { 1 } else { -1 });
+            }
+            Key::ShiftUp => {
+                self.preview_top = self.preview_top.map(|t| t.saturating_sub(1))
+            }
+            Key::ShiftDown => {
+                self.preview_top = self.preview_top.map(|t| t + 1)
+            }
+            Key::Backspace if self.query.pop().is_some() => self.filter(),
+            Key::Ctrl('u') => {
+                self.query.clear();
+                self.filter();
+            }
+            Key::Ctrl('w') => {
+                let kept =
+                    self.query.trim_end().rfind(' ').map_or(0, |i| i + 1);
+                self.query.truncate(kept);
+                self.filter();
+            }
+            Key::Char(c) => {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn preview_lines(&mut self, width: usize) -> Vec<String> {
+        let Some(item) = self.current() else {
+            return Vec::new();
+        };
+        let Some(preview) = &self.preview else {
+            return Vec::new();
+        };
+        let lines = self
+            .previews
+            .entry(item)
+            .or_insert_with(|| preview(&self.items[item]));
+        if self.window.wrap {
+            lines.iter().flat_map(|line| wrap(line, width)).collect()
+        } else {
+            lines.clone()
+        }
+    }
+
+    /// Preview lines scrolled past, `rows` being the preview's height.
+    fn preview_skip(&mut self, rows: usize) -> usize {
+        let offset = match (&self.window.scroll, self.current()) {
+            (Some(spec), Some(item)) => scroll_offset(
+                spec,
+                &self.items[item],
+                self.delimiter.as_deref(),
+                rows,
+            ),
+            _ => 0,
+        };
+        *self.preview_top.get_or_insert(offset)
+    }
+
+    fn item_line(&self, row: usize, width: usize) -> String {
+        let (item, hits) = &self.matches[row];
+        let current = row == self.cursor;
+        let marked = self.marked.contains(item);
+        let mut line = String::from(if current { ">" } else { " " });
+        if self.multi {
+            line.push(if marked { '*' } else { ' ' });
+        }
+        line.push(' ');
+        if !self.color {
+            line.push_str(&self.items[*item]);
+            return fit(&line, width);
+        }
+        if current {
+            line = format!("\x1b[1m{line}");
+        }
+        for (i, c) in self.items[*item].chars().enumerate() {
+            if hits.contains(&i) {
+                line.push_str(&format!("\x1b[32m{c}\x1b[39m"));
+            } else {
+                line.push(c);
+            }
+        }
+        fit(&line, width)
+    }
+
+    /// The prompt, the match count, the header and the entries, top to
+    /// bottom.
+    fn list_pane(&mut self, width: usize, height: usize) -> Vec<String> {
+        let header: Vec<String> = self.header.iter().cloned().collect();
+        let rows = height.saturating_sub(2 + header.len());
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if rows > 0 && self.cursor >= self.top + rows {
+            self.top = self.cursor + 1 - rows;
+        }
+        let mut entries: Vec<String> = (self.top..self.matches.len())
+            .take(rows)
+            .map(|row| self.item_line(row, width))
+            .collect();
+
+        let prompt = if self.color {
+            format!("> {}\x1b[7m \x1b[0m", self.query)
+        } else {
+            format!("> {}", self.query)
+        };
+        let mut info = format!("  {}/{}", self.matches.len(), self.items.len());
+        if self.multi && !self.marked.is_empty() {
+            info.push_str(&format!(" ({})", self.marked.len()));
+        }
+
+        let mut lines = Vec::with_capacity(height);
+        if self.reverse {
+            lines.push(prompt);
+            lines.push(info);
+            lines.extend(header);
+            lines.append(&mut entries);
+            lines.resize(height, String::new());
+        } else {
+            lines.resize(rows - entries.len().min(rows), String::new());
+            entries.reverse();
+            lines.append(&mut entries);
+            lines.extend(header);
+            lines.push(info);
+            lines.push(prompt);
+        }
+        lines.truncate(height);
+        lines.into_iter().map(|l| fit(&l, width)).collect()
+    }
+
+    fn render(&mut self, cols: usize, rows: usize) -> Vec<String> {
+        if self.preview.is_none() {
+            return self.list_pane(cols, rows);
+        }
+        match self.window.side {
+            Side::Right | Side::Left => {
+                let side = (cols * self.window.percent / 100).max(1);
+                let list_width = cols.saturating_sub(side + 1);
+                let list = self.list_pane(list_width, rows);
+                let skip = self.preview_skip(rows);
+                let preview: Vec<String> = self
+                    .preview_lines(side.saturating_sub(1))
+                    .into_iter()
+                    .skip(skip)
+                    .collect();
+                (0..rows)
+                    .map(|row| {
+                        let text = preview.get(row).map_or("", String::as_str);
+                        let pane = fit(&format!(" {text}"), side);
+                        if self.window.side == Side::Right {
+                            format!("{}│{pane}", list[row])
+                        } else {
+                            format!("{pane}│{}", list[row])
+                        }
+                    })
+                    .collect()
+            }
+            Side::Up | Side::Down => {
+                let side = (rows * self.window.percent / 100).max(1);
+                let list_rows = rows.saturating_sub(side + 1).max(3);
+                let side = rows.saturating_sub(list_rows + 1);
+                let mut list = self.list_pane(cols, list_rows);
+                let skip = self.preview_skip(side);
+                let mut preview: Vec<String> = self
+                    .preview_lines(cols)
+                    .into_iter()
+                    .skip(skip)
+                    .take(side)
+                    .map(|line| fit(&line, cols))
+                    .collect();
+                preview.resize(side, " ".repeat(cols));
+                let rule = "─".repeat(cols);
+                let mut frame = Vec::with_capacity(rows);
+                if self.window.side == Side::Down {
+                    frame.append(&mut list);
+                    frame.push(rule);
+                    frame.append(&mut preview);
+                } else {
+                    frame.append(&mut preview);
+                    frame.push(rule);
+                    frame.append(&mut list);
+                }
+                frame.truncate(rows);
+                frame
+            }
+        }
+    }
+
+    fn run(&mut self, backend: &mut impl Backend) -> io::Result<Outcome> {
+        let mut dirty = true;
+        loop {
+            let (cols, rows) = backend.size();
+            if dirty {
+                backend.draw(&self.render(cols, rows))?;
+                dirty = false;
+            }
+            match backend.read_input()? {
+                Input::Closed => return Ok((None, Vec::new())),
+                Input::Idle => {}
+                Input::Key(key) => {
+                    dirty = true;
+                    if let Some(chosen) = self.key(key, rows.saturating_sub(3))
+                    {
+                        return Ok(chosen);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::Headless;
+
+    fn sample(items: &[&str], multi: bool, reverse: bool) -> Picker {
+        let preview: Preview =
+            Box::new(|item: &str| vec![format!("preview of {item}")]);
+        Picker::new(
+            items.iter().map(|s| s.to_string()).collect(),
+            multi,
+            reverse,
+            Some(preview),
+            PreviewWindow::parse("down:wrap"),
+            false,
+        )
+    }
+
+    #[test]
+    fn ranks_tight_and_word_start_matches_first() {
+        let (gap, hits) = match_term("rm", "roam").unwrap();
+        assert_eq!(hits, vec![0, 3]);
+        let (boundary, hits) = match_term("rm", "read me").unwrap();
+        assert_eq!(hits, vec![0, 5]);
+        assert!(boundary > gap);
+        assert!(match_term("xyz", "roadmap").is_none());
+        assert!(match_term("Road", "roadmap").is_none());
+        assert!(match_query("map road", "roadmap").is_some());
+
+        let mut picker =
+            sample(&["groceries", "roadmap", "road-trip"], false, false);
+        assert_eq!(picker.matches.len(), 3);
+        for key in Headless::typed("road") {
+            picker.key(key, 5);
+        }
+        let order: Vec<&str> = picker
+            .matches
+            .iter()
+            .map(|(i, _)| picker.items[*i].as_str())
+            .collect();
+        assert_eq!(order, vec!["roadmap", "road-trip"]);
+    }
+
+    #[test]
+    fn parses_heights_and_preview_windows() {
+        assert_eq!(parse_height("70%", 40), Some(28));
+        assert_eq!(parse_height("~5", 40), Some(MIN_HEIGHT));
+        assert_eq!(parse_height("100%", 40), None);
+        assert_eq!(parse_height("junk", 40), None);
+        let window = PreviewWindow::parse("up:30%:wrap");
+        assert_eq!(
+            (window.side, window.percent, window.wrap),
+            (Side::Up, 30, true)
+        );
+        assert_eq!(PreviewWindow::parse("").side, Side::Right);
+        assert!(PreviewWindow::parse("hidden").hidden);
+    }
+
+    #[test]
+    fn marks_several_entries_and_draws_the_preview() {
+        let mut picker = sample(&["n1", "n2", "n3"], true, true);
+        let mut backend =
+            Headless::new(40, 10, &[Key::Tab, Key::Tab, Key::Enter]);
+        let (key, chosen) = picker.run(&mut backend).unwrap();
+        assert_eq!((key, chosen), (None, vec!["n1".into(), "n2".into()]));
+        let first: Vec<&str> =
+            backend.frames[0].iter().map(|l| l.trim_end()).collect();
+        assert_eq!(first[..4], [">", "  3/3", ">  n1", "   n2"]);
+        assert_eq!(first[5], "preview of n1");
+        let last = backend.last();
+        assert!(last.contains("  3/3 (2)"), "{last}");
+        assert!(last.contains(" * n2") && last.contains(">  n3"), "{last}");
+
+        let mut picker = sample(&["alpha", "beta"], false, false);
+        let mut keys = Headless::typed("bt");
+        keys.push(Key::Enter);
+        let mut backend = Headless::new(30, 10, &keys);
+        assert_eq!(picker.run(&mut backend).unwrap().1, vec!["beta"]);
+        let frame = &backend.frames[2];
+        let prompt = frame.iter().position(|l| l.starts_with("> bt")).unwrap();
+        assert_eq!(frame[prompt - 1].trim(), "1/2");
+        assert_eq!(frame[prompt - 2].trim(), "> beta");
+
+        let mut picker = sample(&["alpha"], false, false);
+        let mut backend = Headless::new(30, 10, &[Key::Esc]);
+        assert_eq!(picker.run(&mut backend).unwrap(), (None, Vec::new()));
+    }
+
+    #[test]
+    fn expected_keys_end_the_picker_and_fields_fill_placeholders() {
+        let mut picker = sample(&["n1\tRoadmap", "n2\tGroceries"], true, true);
+        picker.expect = vec!["ctrl-d".to_string(), "enter".to_string()];
+        picker.header = Some("ctrl-d delete".to_string());
+        let mut backend = Headless::new(40, 10, &[Key::Down, Key::Ctrl('d')]);
+        let outcome = picker.run(&mut backend).unwrap();
+        assert_eq!(
+            outcome,
+            (Some("ctrl-d".into()), vec!["n2\tGroceries".into()])
+        );
+        assert_eq!(backend.frames[0][2].trim_end(), "ctrl-d delete");
+
+        assert_eq!(
+            expand("qn render {1} # {}", "n1\tIt's", Some("\t")),
+            "qn render 'n1' # 'n1\tIt'\\''s'"
+        );
+        assert_eq!(expand("view {+2}", "a b", None), "view 'b'");
+    }
+
+    #[test]
+    fn reloads_entries_per_query_and_scrolls_the_preview_to_a_line() {
+        let preview: Preview =
+            Box::new(|_: &str| (1..=30).map(|n| format!("line {n}")).collect());
+        let mut picker = Picker::new(
+            vec!["start".to_string()],
+            false,
+            true,
+            Some(preview),
+            PreviewWindow::parse("up:50%:+{2}-/2"),
+            false,
+        );
+        picker.delimiter = Some(":".to_string());
+        picker.reload = Some(Box::new(|query: &str| {
+            vec![format!("n1:20:{query}"), format!("n2:3:{query}")]
+        }));
+        let mut keys = Headless::typed("zq");
+        keys.push(Key::Enter);
+        let mut backend = Headless::new(40, 21, &keys);
+        let outcome = picker.run(&mut backend).unwrap();
+        assert_eq!(outcome, (None, vec!["n1:20:zq".to_string()]));
+        let last = backend.last();
+        assert!(last.contains("> n1:20:zq") && last.contains("2/2"), "{last}");
+        // A 10-row preview centres line 20.
+        assert_eq!(backend.frames[2][0].trim_end(), "line 15");
+
+        assert_eq!(scroll_offset("12", "x", None, 10), 11);
+        assert_eq!(scroll_offset("{2}-3", "a 2", None, 10), 0);
+        assert_eq!(scroll_offset("{2}-/2", "a:40:t", Some(":"), 10), 34);
+    }
+}
@@ -0,0 +1,473 @@
+//! `qn publish`: build a browsable static site from tagged notes.
+//!
+//! Permalinks come from note metadata: `posts/<yyyy>/<mm>/<slug>.html`, using
+//! the created date and either a `slug` property or the slugified title. The
+//! site has an index, tag pages, monthly archives, backlinks on every post and
+//! an Atom feed. Files are only rewritten when their content changes, and
+//! pages from earlier runs that are no longer generated are removed (tracked
+//! in `<out>/.qn-publish`).
+
+use crate::export::{
+    escape_html, linked_note_ids, markdown_to_html, page, tag_slug,
+};
+use crate::index::load_index;
+use crate::note::{Note, cmp_dt, parse_note, parse_timestamp};
+use crate::query::Query;
+use crate::shared::migrate::resolve_active_note_path;
+use crate::{Area, args, tags};
+use chrono::{DateTime, Datelike, FixedOffset};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MANIFEST: &str = ".qn-publish";
+const DEFAULT_TAG: &str = "#public";
+const FEED_ENTRIES: usize = 20;
+const USAGE: &str = "Usage: qn publish [-t tag]... [-q query] -o <dir> \
+[--title text] [--base-url url]";
+
+struct Post {
+    note: Note,
+    created: Option<DateTime<FixedOffset>>,
+    /// Site-relative path such as `posts/2025/06/standup.html`.
+    permalink: String,
+}
+
+impl Post {
+    /// `../` repeated once per directory level of the post.
+    fn root(&self) -> String {
+        "../".repeat(self.permalink.matches('/').count())
+    }
+
+    fn month(&self) -> Option<String> {
+        self.created.map(|dt| format!("{:04}-{:02}", dt.year(), dt.month()))
+    }
+}
+
+/// Files produced by one run and what happened to them on disk.
+#[derive(Default)]
+struct SiteWriter {
+    out_dir: PathBuf,
+    generated: BTreeSet<String>,
+    written: usize,
+    unchanged: usize,
+}
+
+impl SiteWriter {
+    fn write(&mut self, rel: &str, content: &str) -> io::Result<()> {
+        let path = self.out_dir.join(rel);
+        self.generated.insert(rel.to_string());
+        if fs::read(&path).is_ok_and(|old| old == content.as_bytes()) {
+            self.unchanged += 1;
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, content)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Delete files listed by the previous run that were not generated now,
+    /// then record this run's files.
+    fn finish(&self) -> io::Result<usize> {
+        let manifest = self.out_dir.join(MANIFEST);
+        let mut removed = 0;
+        if let Ok(previous) = fs::read_to_string(&manifest) {
+            for rel in previous.lines().filter(|l| !l.is_empty()) {
+                if self.generated.contains(rel) || rel.contains("..") {
+                    continue;
+                }
+                let path = self.out_dir.join(rel);
+                if fs::remove_file(&path).is_ok() {
+                    removed += 1;
+                    // Drop directories the removal emptied (fails otherwise).
+                    let mut parent = path.parent();
+                    while let Some(dir) = parent
+                        && dir != self.out_dir
+                        && fs::remove_dir(dir).is_ok()
+                    {
+                        parent = dir.parent();
+                    }
+                }
+            }
+        }
+        let mut listing: String =
+            self.generated.iter().map(|rel| format!("{rel}\n")).collect();
+        if listing.is_empty() {
+            listing.push('\n');
+        }
+        fs::write(manifest, listing)?;
+        Ok(removed)
+    }
+}
+
+/// Lowercase ASCII words joined by `-`; empty when nothing usable remains.
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').chars().take(60).collect()
+}
+
+/// Assign permalinks; later duplicates get the note id appended.
+fn build_posts(mut notes: Vec<Note>) -> Vec<Post> {
+    notes.sort_by(|a, b| {
+        cmp_dt(&a.created, &b.created).then_with(|| a.id.cmp(&b.id))
+    });
+    let mut taken: BTreeSet<String> = BTreeSet::new();
+    let mut posts = Vec::new();
+    for note in notes {
+        let created = parse_timestamp(&note.created);
+        let dir = created
+            .map(|dt| format!("posts/{:04}/{:02}", dt.year(), dt.month()))
+            .unwrap_or_else(|| "posts/undated".to_string());
+        let slug = note
+            .properties
+            .get("slug")
+            .map(slugify)
+            .filter(|s| !s.is_empty())
+            .or_else(|| Some(slugify(&note.title)).filter(|s| !s.is_empty()))
+            .unwrap_or_else(|| note.id.to_lowercase());
+        let mut permalink = format!("{dir}/{slug}.html");
+        if !taken.insert(permalink.clone()) {
+            permalink = format!("{dir}/{slug}-{}.html", note.id);
+            taken.insert(permalink.clone());
+        }
+        posts.push(Post { note, created, permalink });
+    }
+    // Newest first everywhere the site lists posts.
+    posts.reverse();
+    posts
+}
+
+/// Handle `qn publish`.
+pub(crate) fn publish_site(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut tag_filters: Vec<String> = Vec::new();
+    let mut query: Option<Query> = None;
+    let mut out_dir: Option<PathBuf> = None;
+    let mut title = "Notes".to_string();
+    let mut base_url = String::new();
+    let mut parser = args::ArgParser::new(args, "publish");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "-t" | "--tag" => {
+                if let Some(tag) = parser.extract_tag()? {
+                    tag_filters.push(tag);
+                }
+            }
+            "-q" | "--query" => {
+                query =
+                    Some(Query::parse(&parser.extract_value("-q/--query")?)?);
+            }
+            "-o" | "--out" => {
+                out_dir =
+                    Some(PathBuf::from(parser.extract_value("-o/--out")?));
+            }
+            "--title" => title = parser.extract_value("--title")?,
+            "--base-url" => {
+                base_url = parser.extract_value("--base-url")?;
+                if !base_url.ends_with('/') {
+                    base_url.push('/');
+                }
+            }
+            other => {
+                return Err(format!(
+                    "Unknown argument for publish: {other}\n{USAGE}"
+                )
+                .into());
+            }
+        }
+    }
+    let out_dir = out_dir.ok_or(USAGE)?;
+    if tag_filters.is_empty() {
+        tag_filters.push(DEFAULT_TAG.to_string());
+    }
+
+    let mut listed = load_index(dir)?.notes(Area::Active);
+    listed.retain(|n| tags::note_has_tags(n, &tag_filters));
+    if let Some(query) = &query {
+        listed = query.filter(dir, Area::Active, listed);
+    }
+    let mut notes = Vec::new();
+    for stub in listed {
+        let Some(path) = resolve_active_note_path(dir, &stub.id) else {
+            continue;
+        };
+        notes.push(parse_note(&path, fs::metadata(&path)?.len())?);
+    }
+    let posts = build_posts(notes);
+
+    let mut site =
+        SiteWriter { out_dir: out_dir.clone(), ..Default::default() };
+    write_site(&mut site, &posts, &title, &base_url)?;
+    let removed = site.finish()?;
+    println!(
+        "Published {} note(s) to {}: {} written, {} unchanged, {} removed",
+        posts.len(),
+        out_dir.display(),
+        site.written,
+        site.unchanged,
+        removed
+    );
+    Ok(())
+}
+
+fn nav(root: &str, title: &str) -> String {
+    format!(
+        "<nav><a href=\"{root}index.html\">{}</a> &middot; \
+<a href=\"{root}archive/index.html\">Archive</a> &middot; \
+<a href=\"{root}feed.xml\">Feed</a></nav>\n",
+        escape_html(title)
+    )
+}
+
+fn post_list<'a>(posts: impl Iterator<Item = &'a Post>, root: &str) -> String {
+    let mut out = String::from("<ul class=\"notes\">\n");
+    for post in posts {
+        let date = post
+            .created
+            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "<li><span class=\"meta\">{date}</span> \
+<a href=\"{root}{}\">{}</a></li>\n",
+            post.permalink,
+            escape_html(&post.note.title)
+        ));
+    }
+    out.push_str("</ul>");
+    out
+}
+
+fn tag_links(tags: &[String], root: &str) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+    let links: String = tags
+        .iter()
+        .map(|tag| {
+            format!(
+                "<a href=\"{root}tags/{}.html\">{}</a>",
+                tag_slug(tag),
+                escape_html(tag)
+            )
+        })
+        .collect();
+    format!("<p class=\"tags\">{links}</p>\n")
+}
+
+fn write_site(
+    site: &mut SiteWriter,
+    posts: &[Post],
+    title: &str,
+    base_url: &str,
+) -> io::Result<()> {
+    let by_id: HashMap<&str, &Post> =
+        posts.iter().map(|p| (p.note.id.as_str(), p)).collect();
+
+    let mut backlinks: HashMap<&str, Vec<&Post>> = HashMap::new();
+    for post in posts {
+        for target in linked_note_ids(&post.note.body) {
+            if let Some(target) = by_id.get(target.as_str())
+                && target.note.id != post.note.id
+            {
+                backlinks
+                    .entry(target.note.id.as_str())
+                    .or_default()
+                    .push(post);
+            }
+        }
+    }
+
+    let mut by_tag: BTreeMap<&str, Vec<&Post>> = BTreeMap::new();
+    let mut by_month: BTreeMap<String, Vec<&Post>> = BTreeMap::new();
+    for post in posts {
+        for tag in &post.note.tags {
+            by_tag.entry(tag.as_str()).or_default().push(post);
+        }
+        if let Some(month) = post.month() {
+            by_month.entry(month).or_default().push(post);
+        }
+    }
+
+    for post in posts {
+        let root = post.root();
+        let note = &post.note;
+        let body = markdown_to_html(&note.body, |id| {
+            by_id.get(id).map(|p| format!("{root}{}", p.permalink))
+        });
+        let linked_from = match backlinks.get(note.id.as_str()) {
+            Some(sources) => format!(
+                "<aside class=\"backlinks\"><h2>Linked from</h2>\n{}</aside>\n",
+                post_list(sources.iter().copied(), &root)
+            ),
+            None => String::new(),
+        };
+        let html = format!(
+            "{}<header><h1>{}</h1>\n<p class=\"meta\">Created {} &middot; \
+Updated {}</p>\n{}</header>\n<main>\n{body}</main>\n{linked_from}",
+            nav(&root, title),
+            escape_html(&note.title),
+            escape_html(&note.created),
+            escape_html(&note.updated),
+            tag_links(&note.tags, &root),
+        );
+        site.write(&post.permalink, &page(&note.title, &html))?;
+    }
+
+    let index = format!(
+        "{}<header><h1>{}</h1><p class=\"meta\">{} note(s)</p>\n{}</header>\n{}",
+        nav("", title),
+        escape_html(title),
+        posts.len(),
+        tag_links(
+            &by_tag.keys().map(|t| t.to_string()).collect::<Vec<_>>(),
+            ""
+        ),
+        post_list(posts.iter(), "")
+    );
+    site.write("index.html", &page(title, &index))?;
+
+    for (tag, tagged) in &by_tag {
+        let html = format!(
+            "{}<header><h1>{}</h1><p class=\"meta\">{} note(s)</p></header>\n{}",
+            nav("../", title),
+            escape_html(tag),
+            tagged.len(),
+            post_list(tagged.iter().copied(), "../")
+        );
+        site.write(&format!("tags/{}.html", tag_slug(tag)), &page(tag, &html))?;
+    }
+
+    let mut months = String::from("<ul class=\"notes\">\n");
+    for (month, dated) in by_month.iter().rev() {
+        months.push_str(&format!(
+            "<li><a href=\"{month}.html\">{month}</a> \
+<span class=\"meta\">{} note(s)</span></li>\n",
+            dated.len()
+        ));
+        let html = format!(
+            "{}<header><h1>{month}</h1></header>\n{}",
+            nav("../", title),
+            post_list(dated.iter().copied(), "../")
+        );
+        site.write(&format!("archive/{month}.html"), &page(month, &html))?;
+    }
+    months.push_str("</ul>");
+    let archive = format!(
+        "{}<header><h1>Archive</h1></header>\n{months}",
+        nav("../", title)
+    );
+    site.write("archive/index.html", &page("Archive", &archive))?;
+
+    site.write("feed.xml", &atom_feed(posts, title, base_url))
+}
+
+/// Atom feed of the most recently updated posts.
+fn atom_feed(posts: &[Post], title: &str, base_url: &str) -> String {
+    let mut recent: Vec<&Post> = posts.iter().collect();
+    recent.sort_by(|a, b| cmp_dt(&b.note.updated, &a.note.updated));
+    recent.truncate(FEED_ENTRIES);
+    let rfc3339 = |ts: &str| {
+        parse_timestamp(ts)
+            .map(|dt| dt.to_rfc3339())
+            .unwrap_or_else(|| "1970-01-01T00:00:00+00:00".to_string())
+    };
+    let feed_updated = recent
+        .first()
+        .map(|p| rfc3339(&p.note.updated))
+        .unwrap_or_else(|| rfc3339(""));
+
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+<feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+<title>{}</title>\n<id>{}</id>\n<updated>{feed_updated}</updated>\n\
+<link href=\"{base_url}index.html\"/>\n<link rel=\"self\" href=\"{base_url}feed.xml\"/>\n",
+        escape_html(title),
+        if base_url.is_empty() {
+            "urn:qn:publish".to_string()
+        } else {
+            escape_html(base_url)
+        },
+    );
+    for post in recent {
+        let note = &post.note;
+        let content = markdown_to_html(&note.body, |_| None);
+        let categories: String = note
+            .tags
+            .iter()
+            .map(|t| format!("<category term=\"{}\"/>", escape_html(t)))
+            .collect();
+        out.push_str(&format!(
+            "<entry>\n<title>{}</title>\n<id>urn:qn:{}</id>\n\
+<link href=\"{base_url}{}\"/>\n<published>{}</published>\n\
+<updated>{}</updated>\n{categories}\n<content type=\"html\">{}</content>\n\
+</entry>\n",
+            escape_html(&note.title),
+            note.id,
+            post.permalink,
+            rfc3339(&note.created),
+            rfc3339(&note.updated),
+            escape_html(&content)
+        ));
+    }
+    out.push_str("</feed>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: &str, title: &str, created: &str) -> Note {
+        Note {
+            id: id.to_string(),
+            title: title.to_string(),
+            created: created.to_string(),
+            updated: created.to_string(),
+            deleted_at: None,
+            archived_at: None,
+            due: None,
+            remind: None,
+            body: String::new(),
+            tags: Vec::new(),
+            properties: Default::default(),
+            format: Default::default(),
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn permalinks_follow_metadata() {
+        let mut custom = note("c", "Ignored", "03Jun25 10:00 +00:00");
+        custom.properties.set("slug", "My Custom Slug");
+        let posts = build_posts(vec![
+            note("a", "Team Standup!", "01Jun25 10:00 +00:00"),
+            note("b", "Team standup", "02Jun25 10:00 +00:00"),
+            custom,
+            note("d", "???", "bad date"),
+        ]);
+        let links: Vec<(&str, &str)> = posts
+            .iter()
+            .map(|p| (p.note.id.as_str(), p.permalink.as_str()))
+            .collect();
+        assert!(links.contains(&("a", "posts/2025/06/team-standup.html")));
+        assert!(links.contains(&("b", "posts/2025/06/team-standup-b.html")));
+        assert!(links.contains(&("c", "posts/2025/06/my-custom-slug.html")));
+        assert!(links.contains(&("d", "posts/undated/d.html")));
+        assert_eq!(posts[0].note.id, "c");
+        assert_eq!(posts[0].root(), "../../../");
+    }
+}
@@ -0,0 +1,675 @@
+//! `--query` expressions: a small boolean language over note metadata.
+//!
+//! ```text
+//! tag:#work AND NOT tag:#done AND updated:>2025-06-01
+//!     AND (title:"standup" OR body:/retro|review/)
+//! ```
+//!
+//! Terms are `field:value` or a bare word (searched in title and body).
+//! Adjacent terms are AND-ed; `AND`, `OR`, `NOT` and parentheses combine them
+//! with the usual precedence (NOT > AND > OR).
+
+use crate::index::load_index;
+use crate::note::{Note, cmp_dt, parse_timestamp};
+use crate::tags::normalize_tag;
+use crate::{Area, full_note};
+use chrono::{
+    DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone,
+};
+use regex::{Regex, RegexBuilder};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+pub(crate) const FIELDS: &[&str] =
+    &["tag", "title", "body", "text", "id", "created", "updated", "prop.<key>"];
+
+/// Parse failure with the character span of the offending token.
+#[derive(Debug)]
+pub(crate) struct QueryError {
+    message: String,
+    source: String,
+    start: usize,
+    len: usize,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Invalid query: {} (column {})",
+            self.message,
+            self.start + 1
+        )?;
+        writeln!(f, "  {}", self.source)?;
+        write!(f, "  {}{}", " ".repeat(self.start), "^".repeat(self.len.max(1)))
+    }
+}
+
+impl Error for QueryError {}
+
+#[derive(Debug)]
+enum Matcher {
+    /// Case-insensitive substring (lowercased at parse time).
+    Text(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn is_match(&self, haystack: &str) -> bool {
+        match self {
+            Matcher::Text(needle) => haystack.to_lowercase().contains(needle),
+            Matcher::Regex(re) => re.is_match(haystack),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateField {
+    Created,
+    Updated,
+}
+
+/// `[start, end)` bounds in which the comparison is true.
+#[derive(Debug)]
+struct DateRange {
+    start: Option<DateTime<FixedOffset>>,
+    end: Option<DateTime<FixedOffset>>,
+}
+
+impl DateRange {
+    fn contains(&self, ts: &DateTime<FixedOffset>) -> bool {
+        self.start.is_none_or(|s| *ts >= s) && self.end.is_none_or(|e| *ts < e)
+    }
+}
+
+#[derive(Debug)]
+enum Predicate {
+    Tag(Matcher),
+    Title(Matcher),
+    Body(Matcher),
+    Text(Matcher),
+    Id(Matcher),
+    Date(DateField, DateRange),
+    Prop(String, Matcher),
+}
+
+impl Predicate {
+    fn matches(&self, note: &Note) -> bool {
+        match self {
+            Predicate::Tag(Matcher::Text(tag)) => {
+                note.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+            }
+            Predicate::Tag(m) => note.tags.iter().any(|t| m.is_match(t)),
+            Predicate::Title(m) => m.is_match(&note.title),
+            Predicate::Body(m) => m.is_match(&note.body),
+            Predicate::Text(m) => {
+                m.is_match(&note.title) || m.is_match(&note.body)
+            }
+            Predicate::Id(m) => m.is_match(&note.id),
+            Predicate::Date(field, range) => {
+                let raw = match field {
+                    DateField::Created => &note.created,
+                    DateField::Updated => &note.updated,
+                };
+                parse_timestamp(raw).is_some_and(|ts| range.contains(&ts))
+            }
+            Predicate::Prop(key, m) => {
+                note.properties.get(key).is_some_and(|v| m.is_match(v))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Pred(Predicate),
+}
+
+impl Expr {
+    fn matches(&self, note: &Note) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(note) && b.matches(note),
+            Expr::Or(a, b) => a.matches(note) || b.matches(note),
+            Expr::Not(e) => !e.matches(note),
+            Expr::Pred(p) => p.matches(note),
+        }
+    }
+
+    fn needs_body(&self) -> bool {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.needs_body() || b.needs_body()
+            }
+            Expr::Not(e) => e.needs_body(),
+            Expr::Pred(p) => {
+                matches!(p, Predicate::Body(_) | Predicate::Text(_))
+            }
+        }
+    }
+}
+
+/// A parsed `--query` expression.
+#[derive(Debug)]
+pub(crate) struct Query {
+    expr: Expr,
+}
+
+impl Query {
+    pub(crate) fn parse(source: &str) -> Result<Query, QueryError> {
+        let tokens = lex(source)?;
+        let mut parser = Parser { source, tokens, pos: 0 };
+        if parser.tokens.is_empty() {
+            return Err(parser.error_at(0, 0, "empty query"));
+        }
+        let expr = parser.parse_or()?;
+        if let Some(tok) = parser.peek() {
+            let (start, len) = (tok.start, tok.len);
+            let msg = match tok.kind {
+                TokenKind::RParen => "unmatched `)`".to_string(),
+                _ => "unexpected token".to_string(),
+            };
+            return Err(parser.error_at(start, len, &msg));
+        }
+        Ok(Query { expr })
+    }
+
+    pub(crate) fn matches(&self, note: &Note) -> bool {
+        self.expr.matches(note)
+    }
+
+    /// Whether evaluating needs the full body rather than the index preview.
+    pub(crate) fn needs_body(&self) -> bool {
+        self.expr.needs_body()
+    }
+
+    /// Keep matching notes, reading full bodies from `base` when required.
+    pub(crate) fn filter(
+        &self,
+        base: &Path,
+        area: Area,
+        notes: Vec<Note>,
+    ) -> Vec<Note> {
+        if !self.needs_body() {
+            return notes.into_iter().filter(|n| self.matches(n)).collect();
+        }
+        notes
+            .into_iter()
+            .filter_map(|n| full_note(base, area, &n))
+            .filter(|n| self.matches(n))
+            .collect()
+    }
+}
+
+/// Ids of notes in `area` matching `query`, most recently updated first.
+pub(crate) fn query_note_ids(
+    base: &Path,
+    area: Area,
+    query: &Query,
+) -> io::Result<Vec<String>> {
+    let index = load_index(base)?;
+    let mut notes = query.filter(base, area, index.notes(area));
+    notes.sort_by(|a, b| cmp_dt(&b.updated, &a.updated));
+    Ok(notes.into_iter().map(|n| n.id).collect())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term { field: Option<String>, value: Value },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Word(String),
+    Quoted(String),
+    Regex(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// Span in characters, for error carets.
+    start: usize,
+    len: usize,
+    /// Where the value starts (after `field:`).
+    value_start: usize,
+}
+
+fn error(source: &str, start: usize, len: usize, message: &str) -> QueryError {
+    QueryError {
+        message: message.to_string(),
+        source: source.to_string(),
+        start,
+        len,
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    !c.is_whitespace() && c != '(' && c != ')'
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        if c == '(' || c == ')' {
+            let kind =
+                if c == '(' { TokenKind::LParen } else { TokenKind::RParen };
+            tokens.push(Token { kind, start, len: 1, value_start: start });
+            i += 1;
+            continue;
+        }
+
+        // Optional `field:` prefix made of identifier characters.
+        let mut field = None;
+        let mut j = i;
+        while j < chars.len()
+            && (chars[j].is_alphanumeric()
+                || matches!(chars[j], '_' | '-' | '.'))
+        {
+            j += 1;
+        }
+        if j > i && j < chars.len() && chars[j] == ':' {
+            field = Some(chars[i..j].iter().collect::<String>());
+            i = j + 1;
+        }
+        let value_start = i;
+
+        let value = match chars.get(i) {
+            Some('"') => {
+                let (text, next) =
+                    read_delimited(&chars, i, '"').ok_or_else(|| {
+                        error(source, i, chars.len() - i, "unterminated quote")
+                    })?;
+                i = next;
+                Value::Quoted(text)
+            }
+            Some('/') => {
+                let (text, next) =
+                    read_delimited(&chars, i, '/').ok_or_else(|| {
+                        error(source, i, chars.len() - i, "unterminated regex")
+                    })?;
+                i = next;
+                Value::Regex(text)
+            }
+            _ => {
+                let mut end = i;
+                while end < chars.len() && is_word_char(chars[end]) {
+                    end += 1;
+                }
+                let word: String = chars[i..end].iter().collect();
+                i = end;
+                Value::Word(word)
+            }
+        };
+        if field.is_some()
+            && let Value::Word(w) = &value
+            && w.is_empty()
+        {
+            return Err(error(
+                source,
+                start,
+                i - start,
+                "missing value after field",
+            ));
+        }
+
+        let kind = match (&field, &value) {
+            (None, Value::Word(w)) if w == "AND" => TokenKind::And,
+            (None, Value::Word(w)) if w == "OR" => TokenKind::Or,
+            (None, Value::Word(w)) if w == "NOT" => TokenKind::Not,
+            _ => TokenKind::Term { field, value },
+        };
+        tokens.push(Token { kind, start, len: i - start, value_start });
+    }
+    Ok(tokens)
+}
+
+/// Read `"..."` or `/.../` starting at `open`; `\` escapes the delimiter.
+fn read_delimited(
+    chars: &[char],
+    open: usize,
+    delim: char,
+) -> Option<(String, usize)> {
+    let mut out = String::new();
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if chars.get(i + 1) == Some(&delim) => {
+                out.push(delim);
+                i += 2;
+            }
+            c if c == delim => return Some((out, i + 1)),
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    None
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn error_at(&self, start: usize, len: usize, msg: &str) -> QueryError {
+        error(self.source, start, len, msg)
+    }
+
+    fn end_error(&self, msg: &str) -> QueryError {
+        self.error_at(self.source.chars().count(), 1, msg)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_and()?;
+        while self.peek().is_some_and(|t| t.kind == TokenKind::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            match self.peek().map(|t| &t.kind) {
+                Some(TokenKind::And) => self.pos += 1,
+                // Juxtaposed terms are an implicit AND.
+                Some(TokenKind::Term { .. } | TokenKind::Not)
+                | Some(TokenKind::LParen) => {}
+                _ => return Ok(left),
+            }
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        if self.peek().is_some_and(|t| t.kind == TokenKind::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        let Some(tok) = self.peek().cloned() else {
+            return Err(self.end_error("expected a term"));
+        };
+        self.pos += 1;
+        match tok.kind.clone() {
+            TokenKind::LParen => {
+                let inner = self.parse_or()?;
+                match self.peek() {
+                    Some(t) if t.kind == TokenKind::RParen => {
+                        self.pos += 1;
+                        Ok(inner)
+                    }
+                    Some(t) => {
+                        Err(self.error_at(t.start, t.len, "expected `)`"))
+                    }
+                    None => Err(self.error_at(tok.start, 1, "unclosed `(`")),
+                }
+            }
+            TokenKind::Term { field, value } => {
+                self.predicate(&tok, field.as_deref(), value).map(Expr::Pred)
+            }
+            TokenKind::RParen => {
+                Err(self.error_at(tok.start, tok.len, "unmatched `)`"))
+            }
+            TokenKind::And | TokenKind::Or | TokenKind::Not => Err(self
+                .error_at(
+                    tok.start,
+                    tok.len,
+                    "expected a term before operator",
+                )),
+        }
+    }
+
+    fn predicate(
+        &self,
+        tok: &Token,
+        field: Option<&str>,
+        value: Value,
+    ) -> Result<Predicate, QueryError> {
+        let value_len = tok.start + tok.len - tok.value_start;
+        let Some(field) = field else {
+            return Ok(Predicate::Text(self.matcher(tok, value)?));
+        };
+        let lowered = field.to_ascii_lowercase();
+        match lowered.as_str() {
+            "tag" | "tags" => {
+                let matcher = match value {
+                    Value::Regex(_) => self.matcher(tok, value)?,
+                    Value::Word(w) | Value::Quoted(w) => {
+                        Matcher::Text(normalize_tag(&w))
+                    }
+                };
+                Ok(Predicate::Tag(matcher))
+            }
+            "title" => Ok(Predicate::Title(self.matcher(tok, value)?)),
+            "body" => Ok(Predicate::Body(self.matcher(tok, value)?)),
+            "text" => Ok(Predicate::Text(self.matcher(tok, value)?)),
+            "id" => Ok(Predicate::Id(self.matcher(tok, value)?)),
+            "created" | "updated" => {
+                let field = if lowered == "created" {
+                    DateField::Created
+                } else {
+                    DateField::Updated
+                };
+                let raw = match value {
+                    Value::Word(w) | Value::Quoted(w) => w,
+                    Value::Regex(_) => {
+                        return Err(self.error_at(
+                            tok.value_start,
+                            value_len,
+                            "dates take a comparison like >2025-06-01",
+                        ));
+                    }
+                };
+                let range = parse_date_range(&raw).ok_or_else(|| {
+                    self.error_at(
+                        tok.value_start,
+                        value_len,
+                        "expected a date like 2025-06-01, optionally with \
+>, >=, < or <=",
+                    )
+                })?;
+                Ok(Predicate::Date(field, range))
+            }
+            _ => match field.strip_prefix("prop.") {
+                Some(key) if !key.is_empty() => Ok(Predicate::Prop(
+                    key.to_string(),
+                    self.matcher(tok, value)?,
+                )),
+                _ => Err(self.error_at(
+                    tok.start,
+                    field.chars().count(),
+                    &format!(
+                        "unknown field `{field}` (expected {})",
+                        FIELDS.join(", ")
+                    ),
+                )),
+            },
+        }
+    }
+
+    fn matcher(
+        &self,
+        tok: &Token,
+        value: Value,
+    ) -> Result<Matcher, QueryError> {
+        match value {
+            Value::Word(w) | Value::Quoted(w) => {
+                Ok(Matcher::Text(w.to_lowercase()))
+            }
+            Value::Regex(pattern) => RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .map(Matcher::Regex)
+                .map_err(|e| {
+                    let detail = e.to_string();
+                    let last = detail.lines().last().unwrap_or("").trim();
+                    self.error_at(
+                        tok.value_start,
+                        tok.start + tok.len - tok.value_start,
+                        &format!(
+                            "invalid regex: {}",
+                            last.trim_start_matches("error: ")
+                        ),
+                    )
+                }),
+        }
+    }
+}
+
+/// Parse `[op]YYYY-MM-DD[THH:MM]` into the range of matching timestamps.
+fn parse_date_range(raw: &str) -> Option<DateRange> {
+    let (op, rest) = ["<=", ">=", "<", ">", "="]
+        .iter()
+        .find_map(|op| raw.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("=", raw));
+    let (start, end) = parse_date_span(rest)?;
+    let range = match op {
+        ">" => DateRange { start: Some(end), end: None },
+        ">=" => DateRange { start: Some(start), end: None },
+        "<" => DateRange { start: None, end: Some(start) },
+        "<=" => DateRange { start: None, end: Some(end) },
+        _ => DateRange { start: Some(start), end: Some(end) },
+    };
+    Some(range)
+}
+
+/// A day (or minute, with a time) in local time as `[start, end)`.
+fn parse_date_span(
+    raw: &str,
+) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+    let local = |naive: NaiveDateTime| {
+        Local.from_local_datetime(&naive).earliest().map(|dt| dt.fixed_offset())
+    };
+    if let Ok(naive) = NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M") {
+        let start = local(naive)?;
+        return Some((start, start + chrono::Duration::minutes(1)));
+    }
+    let day = NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok()?;
+    let start = local(day.and_hms_opt(0, 0, 0)?)?;
+    let end = local(day.succ_opt()?.and_hms_opt(0, 0, 0)?)?;
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(title: &str, body: &str, tags: &[&str], updated: &str) -> Note {
+        Note {
+            id: "n1".to_string(),
+            title: title.to_string(),
+            created: "01Jan25 10:00 +00:00".to_string(),
+            updated: updated.to_string(),
+            deleted_at: None,
+            archived_at: None,
+            due: None,
+            remind: None,
+            body: body.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            properties: Default::default(),
+            format: Default::default(),
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn evaluates_boolean_expressions() {
+        let q = Query::parse(
+            "tag:#work AND NOT tag:done AND updated:>2025-06-01 \
+             AND (title:\"standup\" OR body:/retro\\w+/)",
+        )
+        .unwrap();
+        let hit = note("Daily standup", "", &["#work"], "02Jul25 09:00 +00:00");
+        let body_hit = note(
+            "Misc",
+            "the retrospective",
+            &["#work"],
+            "02Jul25 09:00 +00:00",
+        );
+        let done =
+            note("standup", "", &["#work", "#done"], "02Jul25 09:00 +00:00");
+        let old = note("standup", "", &["#work"], "01May25 09:00 +00:00");
+        assert!(q.matches(&hit));
+        assert!(q.matches(&body_hit));
+        assert!(!q.matches(&done));
+        assert!(!q.matches(&old));
+        assert!(q.needs_body());
+    }
+
+    #[test]
+    fn implicit_and_and_precedence() {
+        let q = Query::parse("alpha beta OR gamma").unwrap();
+        assert!(q.matches(&note("alpha beta", "", &[], "")));
+        assert!(q.matches(&note("gamma", "", &[], "")));
+        assert!(!q.matches(&note("alpha", "", &[], "")));
+        assert!(!Query::parse("tag:x").unwrap().needs_body());
+    }
+
+    #[test]
+    fn errors_point_at_bad_token() {
+        let err = Query::parse("tag:a AND colour:red").unwrap_err();
+        assert_eq!((err.start, err.len), (10, 6));
+        assert!(err.to_string().contains("unknown field `colour`"));
+        assert!(err.to_string().ends_with("\n            ^^^^^^"));
+
+        let err = Query::parse("(tag:a OR tag:b").unwrap_err();
+        assert!(err.message.contains("unclosed"));
+        assert_eq!(err.start, 0);
+
+        let err = Query::parse("updated:>junk").unwrap_err();
+        assert_eq!(err.start, 8);
+        assert!(Query::parse("body:/(/").is_err());
+        assert!(Query::parse("a AND").is_err());
+        assert!(Query::parse("a )").unwrap_err().message.contains("unmatched"));
+    }
+
+    #[test]
+    fn date_comparisons_use_whole_days() {
+        let on = |ts: &str, q: &str| {
+            Query::parse(q).unwrap().matches(&note("", "", &[], ts))
+        };
+        let ts = &Local
+            .with_ymd_and_hms(2025, 6, 1, 15, 30, 0)
+            .unwrap()
+            .format(crate::note::TIME_FMT)
+            .to_string();
+        assert!(on(ts, "updated:2025-06-01"));
+        assert!(!on(ts, "updated:>2025-06-01"));
+        assert!(on(ts, "updated:>=2025-06-01"));
+        assert!(on(ts, "updated:<=2025-06-01"));
+        assert!(!on(ts, "updated:<2025-06-01"));
+        assert!(on(ts, "updated:>2025-05-31"));
+    }
+}
@@ -1,109 +1,491 @@
 use std::process::{Command, Stdio};
 
-use yansi::Paint;
+use pulldown_cmark::{
+    CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd,
+};
+use yansi::{Paint, Style};
 
-/// Minimal styling categories used when coloring markdown output.
-#[derive(Clone, Copy)]
-enum Style {
-    Heading,
-    Bullet,
-    Rule,
-    Code,
+use crate::highlight::{Theme, highlight_code};
+use crate::shared::table::render_table;
+
+const HEADING: (u8, u8, u8) = (148, 226, 213);
+const TITLE: (u8, u8, u8) = (249, 226, 175);
+const MARKER: (u8, u8, u8) = (249, 226, 175);
+const LINK: (u8, u8, u8) = (137, 180, 250);
+const CODE: (u8, u8, u8) = (166, 227, 161);
+const QUOTE: (u8, u8, u8) = (108, 112, 134);
+const FOOTNOTE: (u8, u8, u8) = (198, 160, 246);
+const DONE: (u8, u8, u8) = (166, 227, 161);
+
+/// Markdown extensions understood by every renderer in the crate.
+pub(crate) fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
 }
 
-/// Render markdown with lightweight styling. When `use_color` is false the
-/// original text is returned unchanged so whitespace and line counts stay
-/// stable for tests.
+/// Render markdown for the terminal. When `use_color` is false the original
+/// text is returned unchanged so whitespace and line counts stay stable for
+/// tests; otherwise the pulldown-cmark event stream is laid out with ANSI
+/// styling and wrapped to the terminal width.
 pub fn render_markdown(input: &str, use_color: bool) -> String {
     if !use_color {
         return input.to_string();
     }
 
-    let mut rendered = String::new();
-    let mut in_code_block = false;
-
-    for segment in input.split_inclusive('\n') {
-        let (line, newline) = if let Some(stripped) = segment.strip_suffix('\n')
-        {
-            (stripped, "\n")
-        } else {
-            (segment, "")
-        };
-        let trimmed = line.trim_start();
-
-        if trimmed.starts_with("```") {
-            rendered.push_str(&push_painted(line, Style::Code, true));
-            rendered.push_str(newline);
-            in_code_block = !in_code_block;
-            continue;
-        }
-
-        if in_code_block {
-            rendered.push_str(&push_painted(line, Style::Code, true));
-            rendered.push_str(newline);
-            continue;
-        }
-
-        let styled_line = if trimmed.starts_with('#') {
-            push_painted(line, Style::Heading, true)
-        } else if trimmed.starts_with("- ")
-            || trimmed.starts_with("* ")
-            || trimmed.starts_with("+ ")
-            || trimmed
-                .split_once('.')
-                .map(|(a, _)| a.chars().all(|c| c.is_ascii_digit()))
-                .unwrap_or(false)
-        {
-            push_painted(line, Style::Bullet, true)
-        } else if trimmed == "---" || trimmed == "***" || trimmed == "___" {
-            push_painted(line, Style::Rule, true)
-        } else {
-            highlight_inline_code(line)
-        };
+    let width = crate::terminal_columns().unwrap_or(100).max(20);
+    render_markdown_width(input, width)
+}
 
-        rendered.push_str(&styled_line);
-        rendered.push_str(newline);
+/// Colored terminal rendering wrapped to `width` columns.
+pub(crate) fn render_markdown_width(input: &str, width: usize) -> String {
+    let mut renderer = AnsiRenderer::new(width);
+    for event in Parser::new_ext(input, markdown_options()) {
+        renderer.event(event);
     }
+    renderer.finish()
+}
+
+/// Inline styles that can be active while text is written.
+#[derive(Clone, Copy)]
+enum Inline {
+    Emphasis,
+    Strong,
+    Strikethrough,
+    Link,
+    Image,
+    Heading(HeadingLevel),
+}
+
+#[derive(Default)]
+struct TableState {
+    head: Vec<String>,
+    rows: Vec<Vec<String>>,
+    row: Vec<String>,
+    cell: Option<String>,
+}
+
+/// Fenced or indented code collected until the block ends.
+struct CodeBlock {
+    info: String,
+    text: String,
+}
 
-    rendered
+/// Open link or image: destination plus the visible text seen so far.
+struct OpenLink {
+    dest: String,
+    text: String,
 }
 
-pub fn highlight_inline_code(line: &str) -> String {
-    if !line.contains('`') {
-        return line.to_string();
-    }
-    let mut out = String::new();
-    let mut rest = line;
-
-    while let Some(start) = rest.find('`') {
-        let (before, after_tick) = rest.split_at(start);
-        out.push_str(before);
-        let after_tick = &after_tick[1..];
-        if let Some(end) = after_tick.find('`') {
-            let (code, after) = after_tick.split_at(end);
-            out.push('`');
-            out.push_str(&Paint::blue(code).to_string());
-            out.push('`');
-            rest = &after[1..];
+struct AnsiRenderer {
+    out: String,
+    width: usize,
+    col: usize,
+    content_col: usize,
+    pending_space: Option<Style>,
+    at_line_start: bool,
+    needs_blank: bool,
+    quote_depth: usize,
+    lists: Vec<Option<u64>>,
+    indents: Vec<usize>,
+    pending_marker: Option<(String, Style)>,
+    inline: Vec<Inline>,
+    links: Vec<OpenLink>,
+    code: Option<CodeBlock>,
+    table: Option<TableState>,
+}
+
+impl AnsiRenderer {
+    fn new(width: usize) -> Self {
+        Self {
+            out: String::new(),
+            width,
+            col: 0,
+            content_col: 0,
+            pending_space: None,
+            at_line_start: true,
+            needs_blank: false,
+            quote_depth: 0,
+            lists: Vec::new(),
+            indents: Vec::new(),
+            pending_marker: None,
+            inline: Vec::new(),
+            links: Vec::new(),
+            code: None,
+            table: None,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if !self.at_line_start {
+            self.newline();
+        }
+        self.out
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => {
+                if let Some(code) = self.code.as_mut() {
+                    code.text.push_str(&text);
+                } else {
+                    self.text(&text, self.current_style());
+                }
+            }
+            Event::Code(code) => {
+                let style = self.current_style().rgb(CODE.0, CODE.1, CODE.2);
+                self.text(&code, style);
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                let style = Style::new().dim();
+                let mut lines = html.split('\n').peekable();
+                while let Some(line) = lines.next() {
+                    self.write_raw(line, style);
+                    if lines.peek().is_some() {
+                        self.newline();
+                    }
+                }
+            }
+            Event::FootnoteReference(label) => {
+                let style =
+                    Style::new().rgb(FOOTNOTE.0, FOOTNOTE.1, FOOTNOTE.2);
+                self.text(&format!("[^{label}]"), style);
+            }
+            Event::SoftBreak => {
+                if self.table.is_some() || self.col > self.content_col {
+                    self.text(" ", self.current_style());
+                }
+            }
+            Event::HardBreak => {
+                if let Some(cell) = self.table_cell() {
+                    cell.push(' ');
+                } else {
+                    self.newline();
+                }
+            }
+            Event::Rule => {
+                self.start_block();
+                self.begin_line();
+                let len = self.width.saturating_sub(self.col).clamp(3, 80);
+                self.write_raw(&"─".repeat(len), Style::new().dim());
+                self.newline();
+                self.needs_blank = true;
+            }
+            Event::TaskListMarker(done) => {
+                let (mark, style) = if done {
+                    ("☑ ", Style::new().rgb(DONE.0, DONE.1, DONE.2))
+                } else {
+                    ("☐ ", Style::new().rgb(MARKER.0, MARKER.1, MARKER.2))
+                };
+                self.text(mark, style);
+            }
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.start_block(),
+            Tag::Heading { level, .. } => {
+                self.start_block();
+                self.inline.push(Inline::Heading(level));
+            }
+            Tag::BlockQuote => {
+                self.start_block();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.start_block();
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some(CodeBlock { info, text: String::new() });
+            }
+            Tag::HtmlBlock => self.start_block(),
+            Tag::List(start) => {
+                self.start_block();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.start_block();
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        let marker = format!("{n}. ");
+                        *n += 1;
+                        marker
+                    }
+                    _ => match depth % 3 {
+                        0 => "• ".to_string(),
+                        1 => "◦ ".to_string(),
+                        _ => "▪ ".to_string(),
+                    },
+                };
+                let style =
+                    Style::new().rgb(MARKER.0, MARKER.1, MARKER.2).bold();
+                self.push_marker(marker, style);
+            }
+            Tag::FootnoteDefinition(label) => {
+                self.start_block();
+                let style =
+                    Style::new().rgb(FOOTNOTE.0, FOOTNOTE.1, FOOTNOTE.2);
+                self.push_marker(format!("[^{label}]: "), style);
+            }
+            Tag::Table(_) => {
+                self.start_block();
+                self.table = Some(TableState::default());
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    table.row = Vec::new();
+                }
+            }
+            Tag::TableCell => {
+                if let Some(table) = self.table.as_mut() {
+                    table.cell = Some(String::new());
+                }
+            }
+            Tag::Emphasis => self.inline.push(Inline::Emphasis),
+            Tag::Strong => self.inline.push(Inline::Strong),
+            Tag::Strikethrough => self.inline.push(Inline::Strikethrough),
+            Tag::Link { dest_url, .. } => {
+                self.inline.push(Inline::Link);
+                self.links.push(OpenLink {
+                    dest: dest_url.to_string(),
+                    text: String::new(),
+                });
+            }
+            Tag::Image { dest_url, .. } => {
+                self.inline.push(Inline::Image);
+                self.links.push(OpenLink {
+                    dest: dest_url.to_string(),
+                    text: String::new(),
+                });
+            }
+            Tag::MetadataBlock(_) => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => self.end_block(),
+            TagEnd::Heading(_) => {
+                self.inline.pop();
+                self.end_block();
+            }
+            TagEnd::BlockQuote => {
+                if !self.at_line_start {
+                    self.newline();
+                }
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                self.needs_blank = true;
+            }
+            TagEnd::CodeBlock => {
+                let Some(code) = self.code.take() else { return };
+                for line in
+                    highlight_code(&code.text, &code.info, &Theme::MOCHA)
+                {
+                    self.begin_line();
+                    self.out.push_str("  ");
+                    self.out.push_str(&line);
+                    self.newline();
+                }
+                self.needs_blank = true;
+            }
+            TagEnd::HtmlBlock => self.end_block(),
+            TagEnd::List(_) => {
+                self.lists.pop();
+                self.needs_blank = self.lists.is_empty();
+            }
+            TagEnd::Item | TagEnd::FootnoteDefinition => {
+                if self.pending_marker.is_some() {
+                    self.begin_line();
+                }
+                if !self.at_line_start {
+                    self.newline();
+                }
+                self.indents.pop();
+                if matches!(tag, TagEnd::FootnoteDefinition) {
+                    self.needs_blank = true;
+                }
+            }
+            TagEnd::Table => {
+                let Some(table) = self.table.take() else { return };
+                let style = Style::new().rgb(HEADING.0, HEADING.1, HEADING.2);
+                let headers: Vec<String> = table
+                    .head
+                    .iter()
+                    .map(|h| h.as_str().paint(style.bold()).to_string())
+                    .collect();
+                let rendered = render_table(&headers, &table.rows);
+                for line in rendered.lines() {
+                    self.begin_line();
+                    self.out.push_str(line);
+                    self.newline();
+                }
+                self.needs_blank = true;
+            }
+            TagEnd::TableHead => {
+                if let Some(table) = self.table.as_mut() {
+                    table.head = std::mem::take(&mut table.row);
+                }
+            }
+            TagEnd::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    let row = std::mem::take(&mut table.row);
+                    table.rows.push(row);
+                }
+            }
+            TagEnd::TableCell => {
+                if let Some(table) = self.table.as_mut() {
+                    let cell = table.cell.take().unwrap_or_default();
+                    table.row.push(cell.trim().to_string());
+                }
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.inline.pop();
+            }
+            TagEnd::Link | TagEnd::Image => {
+                self.inline.pop();
+                if let Some(link) = self.links.pop() {
+                    let dest = link.dest.trim_start_matches("mailto:");
+                    if !dest.is_empty() && link.text != dest {
+                        let style = Style::new().rgb(QUOTE.0, QUOTE.1, QUOTE.2);
+                        self.text(&format!(" ({})", link.dest), style);
+                    }
+                }
+            }
+            TagEnd::MetadataBlock(_) => {}
+        }
+    }
+
+    fn current_style(&self) -> Style {
+        self.inline.iter().fold(Style::new(), |style, inline| match inline {
+            Inline::Emphasis => style.italic(),
+            Inline::Strong => style.bold(),
+            Inline::Strikethrough => style.strike(),
+            Inline::Link => style.rgb(LINK.0, LINK.1, LINK.2).underline(),
+            Inline::Image => style.rgb(LINK.0, LINK.1, LINK.2).italic(),
+            Inline::Heading(HeadingLevel::H1) => {
+                style.rgb(TITLE.0, TITLE.1, TITLE.2).bold().underline()
+            }
+            Inline::Heading(_) => {
+                style.rgb(HEADING.0, HEADING.1, HEADING.2).bold()
+            }
+        })
+    }
+
+    fn table_cell(&mut self) -> Option<&mut String> {
+        self.table.as_mut().and_then(|t| t.cell.as_mut())
+    }
+
+    fn push_marker(&mut self, marker: String, style: Style) {
+        self.indents.push(marker.chars().count());
+        self.pending_marker = Some((marker, style));
+    }
+
+    /// Separate a new block from the previous one with a blank line.
+    fn start_block(&mut self) {
+        if !self.at_line_start {
+            self.newline();
+        }
+        if self.needs_blank && !self.out.is_empty() {
+            self.write_quote_bars(false);
+            self.newline();
+        }
+        self.needs_blank = false;
+    }
+
+    fn end_block(&mut self) {
+        if !self.at_line_start {
+            self.newline();
+        }
+        self.needs_blank = true;
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.col = 0;
+        self.content_col = 0;
+        self.pending_space = None;
+        self.at_line_start = true;
+    }
+
+    fn write_quote_bars(&mut self, trailing_space: bool) {
+        let style = Style::new().rgb(QUOTE.0, QUOTE.1, QUOTE.2);
+        for idx in 0..self.quote_depth {
+            let bar = if trailing_space || idx + 1 < self.quote_depth {
+                "│ "
+            } else {
+                "│"
+            };
+            self.out.push_str(&bar.paint(style).to_string());
+            self.col += bar.chars().count();
+        }
+    }
+
+    /// Emit blockquote bars, list indentation and any pending item marker.
+    fn begin_line(&mut self) {
+        if !self.at_line_start {
+            return;
+        }
+        self.at_line_start = false;
+        self.write_quote_bars(true);
+        let total: usize = self.indents.iter().sum();
+        if let Some((marker, style)) = self.pending_marker.take() {
+            let outer = total.saturating_sub(marker.chars().count());
+            self.out.push_str(&" ".repeat(outer));
+            self.out.push_str(&marker.trim_end().paint(style).to_string());
+            self.out.push(' ');
         } else {
-            out.push('`');
-            out.push_str(after_tick);
-            return out;
+            self.out.push_str(&" ".repeat(total));
         }
+        self.col += total;
+        self.content_col = self.col;
     }
-    out.push_str(rest);
-    out
-}
 
-fn push_painted(text: &str, style: Style, use_color: bool) -> String {
-    if !use_color {
-        return text.to_string();
+    fn write_raw(&mut self, text: &str, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+        self.begin_line();
+        self.out.push_str(&text.paint(style).to_string());
+        self.col += text.chars().count();
     }
-    match style {
-        Style::Heading => Paint::cyan(text).bold().to_string(),
-        Style::Bullet => Paint::yellow(text).bold().to_string(),
-        Style::Rule => Paint::new(text).dim().to_string(),
-        Style::Code => Paint::blue(text).to_string(),
+
+    /// Write inline text, wrapping at word boundaries to the terminal width.
+    fn text(&mut self, text: &str, style: Style) {
+        for link in self.links.iter_mut() {
+            link.text.push_str(text);
+        }
+        if let Some(cell) = self.table_cell() {
+            cell.push_str(&text.paint(style).to_string());
+            return;
+        }
+        for (idx, word) in text.split(' ').enumerate() {
+            if idx > 0 {
+                self.pending_space = Some(style);
+            }
+            if word.is_empty() {
+                continue;
+            }
+            self.begin_line();
+            let space = self
+                .pending_space
+                .take()
+                .filter(|_| self.col > self.content_col);
+            let visible = word.chars().count() + usize::from(space.is_some());
+            if self.col > self.content_col && self.col + visible > self.width {
+                self.newline();
+                self.begin_line();
+            } else if let Some(space_style) = space {
+                self.write_raw(" ", space_style);
+            }
+            self.write_raw(word, style);
+        }
     }
 }
 
@@ -121,3 +503,74 @@ pub fn detect_glow() -> Option<&'static str> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_plain(input: &str) -> String {
+        let mut renderer = AnsiRenderer::new(40);
+        for event in Parser::new_ext(input, markdown_options()) {
+            renderer.event(event);
+        }
+        strip_ansi(&renderer.finish())
+    }
+
+    fn strip_ansi(s: &str) -> String {
+        let mut out = String::new();
+        let mut chars = s.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '\x1b' {
+                for next in chars.by_ref() {
+                    if next == 'm' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            out.push(ch);
+        }
+        out
+    }
+
+    #[test]
+    fn plain_mode_is_passthrough() {
+        let input = "# Title\n\n- a\n- b\n\n```\ncode\n```\n";
+        assert_eq!(render_markdown(input, false), input);
+    }
+
+    #[test]
+    fn inline_markup_is_consumed() {
+        let out =
+            render_plain("**bold** and _em_ with `code` [site](https://x.y)");
+        assert_eq!(out, "bold and em with code site (https://x.y)\n");
+    }
+
+    #[test]
+    fn nested_and_task_lists() {
+        let out =
+            render_plain("1. one\n2. two\n   - [ ] open\n   - [x] done\n");
+        assert_eq!(out, "1. one\n2. two\n   ◦ ☐ open\n   ◦ ☑ done\n");
+    }
+
+    #[test]
+    fn tables_use_shared_layout() {
+        let out = render_plain("| A | Bee |\n|---|-----|\n| 1 | 2 |\n");
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "A | Bee");
+        assert!(lines[1].starts_with("==="));
+        assert_eq!(lines[2], "1 | 2  ");
+    }
+
+    #[test]
+    fn blockquotes_and_wrapping() {
+        let out = render_plain(
+            "> quoted words that keep going well past the forty column limit\n",
+        );
+        for line in out.lines() {
+            assert!(line.starts_with("│ "), "{line}");
+            assert!(line.chars().count() <= 40, "{line}");
+        }
+        assert!(out.lines().count() > 1);
+    }
+}
@@ -0,0 +1,747 @@
+//! `qn search`: BM25-ranked full-text search over active notes.
+//!
+//! `.qn-search` is an inverted index: a snapshot of `d` lines per note (path
+//! relative to the notes directory, size/mtime stamp, token count) and `t`
+//! lines per term listing the notes it occurs in with title and body counts,
+//! followed by a log. `store_note`/`relocate_note` append `+` (a note's term
+//! counts) and `-` (note gone) records instead of rewriting the file, like
+//! `.qn-index`. `qn search` and the start of `qn find` revalidate the stamps,
+//! so hand edits are picked up, and compact the log once it grows large;
+//! `qn reindex` compacts it too.
+
+use crate::index::{area_of, mtime_of, rel_path};
+use crate::note::{Note, parse_note};
+use crate::shared::atomic::atomic_write;
+use crate::shared::migrate::list_active_note_files;
+use crate::shared::table::{render_table, truncate_with_ellipsis};
+use crate::{
+    Area, args, highlight_search, preview_for_list, store_root, tags,
+    terminal_columns,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const SEARCH_FILE: &str = ".qn-search";
+const HEADER: &str = "qn-search v2";
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+/// A title occurrence counts as this many body occurrences.
+const TITLE_BOOST: f64 = 3.0;
+const MIN_PREFIX: usize = 2;
+/// Log records tolerated before a load rewrites the snapshot (at least a
+/// quarter of the indexed notes).
+const COMPACT_AFTER: usize = 256;
+
+/// Lowercased alphanumeric runs; everything else separates tokens.
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Doc {
+    size: u64,
+    mtime: u128,
+    length: u32,
+    /// Terms with a posting for this note, so removing it only touches
+    /// those lists.
+    terms: Vec<String>,
+}
+
+/// Occurrences of a term in one note: (title count, body count).
+type Counts = (u32, u32);
+
+pub(crate) struct SearchIndex {
+    base: PathBuf,
+    /// Keyed by note path relative to `base`.
+    docs: BTreeMap<String, Doc>,
+    /// term -> note path -> counts
+    postings: BTreeMap<String, BTreeMap<String, Counts>>,
+    /// Log records replayed on top of the last compacted snapshot.
+    log: usize,
+}
+
+fn search_path(base: &Path) -> PathBuf {
+    base.join(SEARCH_FILE)
+}
+
+/// Term counts and token total for a note.
+fn note_terms(note: &Note) -> (BTreeMap<String, Counts>, u32) {
+    let title = tokenize(&note.title);
+    let body = tokenize(&note.body);
+    let mut counts: BTreeMap<String, Counts> = BTreeMap::new();
+    for t in &title {
+        counts.entry(t.clone()).or_default().0 += 1;
+    }
+    for t in &body {
+        counts.entry(t.clone()).or_default().1 += 1;
+    }
+    (counts, (title.len() + body.len()) as u32)
+}
+
+/// A `+` log record replacing the postings of `rel`.
+fn add_record(
+    rel: &str,
+    size: u64,
+    mtime: u128,
+    length: u32,
+    counts: &BTreeMap<String, Counts>,
+) -> String {
+    let terms: Vec<String> = counts
+        .iter()
+        .map(|(term, (title, body))| format!("{term}:{title}:{body}"))
+        .collect();
+    format!("+\t{rel}\t{size}\t{mtime}\t{length}\t{}\n", terms.join(","))
+}
+
+fn remove_record(rel: &str) -> String {
+    format!("-\t{rel}\n")
+}
+
+impl SearchIndex {
+    fn empty(base: &Path) -> Self {
+        SearchIndex {
+            base: base.to_path_buf(),
+            docs: BTreeMap::new(),
+            postings: BTreeMap::new(),
+            log: 0,
+        }
+    }
+
+    /// The saved snapshot with its log replayed, or `None` if the file is
+    /// missing or unreadable.
+    fn read(base: &Path) -> Option<Self> {
+        let raw = fs::read_to_string(search_path(base)).ok()?;
+        let mut lines = raw.lines();
+        if lines.next() != Some(HEADER) {
+            return None;
+        }
+        let mut index = SearchIndex::empty(base);
+        let mut paths: Vec<String> = Vec::new();
+        for line in lines {
+            let mut fields = line.split('\t');
+            match (fields.next()?, fields.next()?) {
+                ("d", rel) => {
+                    let doc = Doc {
+                        size: fields.next()?.parse().ok()?,
+                        mtime: fields.next()?.parse().ok()?,
+                        length: fields.next()?.parse().ok()?,
+                        terms: Vec::new(),
+                    };
+                    paths.push(rel.to_string());
+                    index.docs.insert(rel.to_string(), doc);
+                }
+                ("t", term) => {
+                    let mut list = BTreeMap::new();
+                    for entry in fields.next()?.split(',') {
+                        let mut parts = entry.split(':');
+                        let n: usize = parts.next()?.parse().ok()?;
+                        let title = parts.next()?.parse().ok()?;
+                        let body = parts.next()?.parse().ok()?;
+                        let rel = paths.get(n)?;
+                        index.docs.get_mut(rel)?.terms.push(term.to_string());
+                        list.insert(rel.clone(), (title, body));
+                    }
+                    index.postings.insert(term.to_string(), list);
+                }
+                // A torn record from an interrupted append is skipped; the
+                // note's stale stamp gets it re-tokenized.
+                ("+", rel) => {
+                    index.log += 1;
+                    let _ = index.replay_add(rel, fields);
+                }
+                ("-", rel) => {
+                    index.log += 1;
+                    index.remove(rel);
+                }
+                _ => return None,
+            }
+        }
+        Some(index)
+    }
+
+    fn replay_add<'a>(
+        &mut self,
+        rel: &str,
+        mut fields: impl Iterator<Item = &'a str>,
+    ) -> Option<()> {
+        let size = fields.next()?.parse().ok()?;
+        let mtime = fields.next()?.parse().ok()?;
+        let length = fields.next()?.parse().ok()?;
+        let mut counts = BTreeMap::new();
+        for entry in fields.next()?.split(',').filter(|e| !e.is_empty()) {
+            let mut parts = entry.split(':');
+            let term = parts.next()?.to_string();
+            let title = parts.next()?.parse().ok()?;
+            let body = parts.next()?.parse().ok()?;
+            counts.insert(term, (title, body));
+        }
+        self.put(rel.to_string(), size, mtime, length, counts);
+        Some(())
+    }
+
+    /// Write a compacted snapshot, dropping the log.
+    fn save(&mut self) -> io::Result<()> {
+        let numbers: BTreeMap<&str, usize> = self
+            .docs
+            .keys()
+            .enumerate()
+            .map(|(n, rel)| (rel.as_str(), n))
+            .collect();
+        let mut out = String::from(HEADER);
+        out.push('\n');
+        for (rel, doc) in &self.docs {
+            out.push_str(&format!(
+                "d\t{rel}\t{}\t{}\t{}\n",
+                doc.size, doc.mtime, doc.length
+            ));
+        }
+        for (term, list) in &self.postings {
+            let entries: Vec<String> = list
+                .iter()
+                .map(|(rel, (title, body))| {
+                    format!("{}:{title}:{body}", numbers[rel.as_str()])
+                })
+                .collect();
+            out.push_str(&format!("t\t{term}\t{}\n", entries.join(",")));
+        }
+        atomic_write(&search_path(&self.base), out.as_bytes())?;
+        self.log = 0;
+        Ok(())
+    }
+
+    /// Whether the log has grown enough to be worth compacting.
+    fn needs_compaction(&self) -> bool {
+        self.log > COMPACT_AFTER.max(self.docs.len() / 4)
+    }
+
+    fn remove(&mut self, rel: &str) {
+        let Some(doc) = self.docs.remove(rel) else { return };
+        for term in doc.terms {
+            if let Some(list) = self.postings.get_mut(&term) {
+                list.remove(rel);
+                if list.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    fn put(
+        &mut self,
+        rel: String,
+        size: u64,
+        mtime: u128,
+        length: u32,
+        counts: BTreeMap<String, Counts>,
+    ) {
+        self.remove(&rel);
+        let terms = counts.keys().cloned().collect();
+        for (term, counts) in counts {
+            self.postings.entry(term).or_default().insert(rel.clone(), counts);
+        }
+        self.docs.insert(rel, Doc { size, mtime, length, terms });
+    }
+
+    /// Re-tokenize notes whose size or mtime changed and drop vanished
+    /// ones; returns the log records describing the changes.
+    fn revalidate(&mut self) -> io::Result<Vec<String>> {
+        let mut stale: BTreeSet<String> = self.docs.keys().cloned().collect();
+        let mut records = Vec::new();
+        for (path, _) in list_active_note_files(&self.base)? {
+            let Some(rel) = rel_path(&self.base, &path) else { continue };
+            stale.remove(&rel);
+            let meta = fs::metadata(&path)?;
+            let (size, mtime) = (meta.len(), mtime_of(&meta));
+            if self
+                .docs
+                .get(&rel)
+                .is_some_and(|d| d.size == size && d.mtime == mtime)
+            {
+                continue;
+            }
+            match parse_note(&path, size) {
+                Ok(note) => {
+                    let (counts, length) = note_terms(&note);
+                    records
+                        .push(add_record(&rel, size, mtime, length, &counts));
+                    self.put(rel, size, mtime, length, counts);
+                }
+                Err(_) => {
+                    records.push(remove_record(&rel));
+                    self.remove(&rel);
+                }
+            }
+        }
+        for rel in &stale {
+            records.push(remove_record(rel));
+            self.remove(rel);
+        }
+        Ok(records)
+    }
+}
+
+/// Load `.qn-search` and re-tokenize notes changed since it was written.
+/// Changes are appended to its log, which is compacted once it grows large.
+pub(crate) fn load_search_index(base: &Path) -> io::Result<SearchIndex> {
+    let saved = SearchIndex::read(base);
+    let missing = saved.is_none();
+    let mut index = saved.unwrap_or_else(|| SearchIndex::empty(base));
+    let records = index.revalidate()?;
+    index.log += records.len();
+    // The index is a cache; searching still works if it cannot be saved.
+    if missing || index.needs_compaction() {
+        let _ = index.save();
+    } else if !records.is_empty() {
+        let _ = append(base, &records);
+    }
+    Ok(index)
+}
+
+/// Rewrite `.qn-search` from the notes without a log, if the store has one.
+pub(crate) fn rebuild_search_index(base: &Path) -> io::Result<()> {
+    if !search_path(base).is_file() {
+        return Ok(());
+    }
+    let mut index = SearchIndex::empty(base);
+    index.revalidate()?;
+    index.save()
+}
+
+/// The saved index without checking note files, for lookups that run on
+/// every keystroke. Falls back to `load_search_index` if there is none.
+pub(crate) fn open_search_index(base: &Path) -> io::Result<SearchIndex> {
+    match SearchIndex::read(base) {
+        Some(index) => Ok(index),
+        None => load_search_index(base),
+    }
+}
+
+fn append(base: &Path, records: &[String]) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).open(search_path(base))?;
+    // One write per batch so concurrent appenders do not interleave lines.
+    file.write_all(records.concat().as_bytes())
+}
+
+/// Append records to the log of the store `path` belongs to, if it has an
+/// index. Best effort like `index::record_write`: a missed update is caught
+/// by the next revalidation.
+fn record(path: &Path, records: impl FnOnce(&Path) -> Vec<String>) {
+    let Some(base) = store_root(path) else { return };
+    if search_path(&base).is_file() {
+        let _ = append(&base, &records(&base));
+    }
+}
+
+/// The `+` record for a note written at `path`, if it is active.
+fn add_file(base: &Path, path: &Path, note: &Note) -> Option<String> {
+    let rel = rel_path(base, path)?;
+    let meta = fs::metadata(path).ok()?;
+    if area_of(&rel) != Area::Active {
+        return None;
+    }
+    let (counts, length) = note_terms(note);
+    Some(add_record(&rel, meta.len(), mtime_of(&meta), length, &counts))
+}
+
+/// Record a freshly written note.
+pub(crate) fn record_write(path: &Path, note: &Note) {
+    record(path, |base| add_file(base, path, note).into_iter().collect());
+}
+
+/// Record a note moved from `src` to `dst`; only active notes are indexed.
+pub(crate) fn record_move(src: &Path, dst: &Path, note: &Note) {
+    record(dst, |base| {
+        rel_path(base, src)
+            .map(|old| remove_record(&old))
+            .into_iter()
+            .chain(add_file(base, dst, note))
+            .collect()
+    });
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Clause {
+    Term(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+/// Split a query into terms, `"quoted phrases"`, and `prefix*` matches.
+pub(crate) fn parse_query(query: &str) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    let mut rest = query;
+    while let Some(start) = rest.find('"') {
+        clauses.extend(plain_clauses(&rest[..start]));
+        let after = &rest[start + 1..];
+        let (phrase, next) = match after.find('"') {
+            Some(end) => (&after[..end], &after[end + 1..]),
+            None => (after, ""),
+        };
+        let tokens = tokenize(phrase);
+        match tokens.len() {
+            0 => {}
+            1 => clauses.push(Clause::Term(tokens[0].clone())),
+            _ => clauses.push(Clause::Phrase(tokens)),
+        }
+        rest = next;
+    }
+    clauses.extend(plain_clauses(rest));
+    clauses
+}
+
+fn plain_clauses(text: &str) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    for word in text.split_whitespace() {
+        let prefix = word.ends_with('*');
+        let tokens = tokenize(word);
+        for (i, token) in tokens.iter().enumerate() {
+            let last = i + 1 == tokens.len();
+            if prefix && last && token.chars().count() >= MIN_PREFIX {
+                clauses.push(Clause::Prefix(token.clone()));
+            } else {
+                clauses.push(Clause::Term(token.clone()));
+            }
+        }
+    }
+    clauses
+}
+
+pub(crate) struct Hit {
+    pub note: Note,
+    pub score: f64,
+}
+
+impl SearchIndex {
+    fn avg_len(&self) -> f64 {
+        if self.docs.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.docs.values().map(|d| d.length as u64).sum();
+        total as f64 / self.docs.len() as f64
+    }
+
+    fn idf(&self, term: &str) -> f64 {
+        let n = self.docs.len() as f64;
+        let df = self.postings.get(term).map_or(0, BTreeMap::len) as f64;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    fn bm25(&self, rel: &str, term: &str, avg_len: f64) -> f64 {
+        let Some((title, body)) =
+            self.postings.get(term).and_then(|list| list.get(rel))
+        else {
+            return 0.0;
+        };
+        let tf = *body as f64 + TITLE_BOOST * *title as f64;
+        let length = self.docs.get(rel).map_or(0, |d| d.length);
+        let norm = if avg_len > 0.0 {
+            1.0 - B + B * length as f64 / avg_len
+        } else {
+            1.0
+        };
+        self.idf(term) * tf * (K1 + 1.0) / (tf + K1 * norm)
+    }
+
+    /// Terms a clause can match, used both to find and to score documents.
+    fn expand(&self, clause: &Clause) -> Vec<String> {
+        match clause {
+            Clause::Term(t) => vec![t.clone()],
+            Clause::Phrase(tokens) => tokens.clone(),
+            Clause::Prefix(p) => self
+                .postings
+                .range::<str, _>((
+                    std::ops::Bound::Included(p.as_str()),
+                    std::ops::Bound::Unbounded,
+                ))
+                .map(|(t, _)| t)
+                .take_while(|t| t.starts_with(p.as_str()))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Notes containing any of `terms` (or all of them, for phrases).
+    fn matching(&self, clause: &Clause, terms: &[String]) -> BTreeSet<&str> {
+        let lists = terms.iter().map(|t| {
+            self.postings
+                .get(t)
+                .map(|list| list.keys().map(String::as_str).collect())
+                .unwrap_or_default()
+        });
+        match clause {
+            Clause::Phrase(_) => {
+                lists.reduce(|a: BTreeSet<&str>, b| &a & &b).unwrap_or_default()
+            }
+            _ => lists.flatten().collect(),
+        }
+    }
+
+    /// Documents that satisfy every clause, best score first.
+    pub(crate) fn search(&self, clauses: &[Clause]) -> Vec<Hit> {
+        if clauses.is_empty() {
+            return Vec::new();
+        }
+        let expanded: Vec<Vec<String>> =
+            clauses.iter().map(|c| self.expand(c)).collect();
+        let mut candidates: Option<BTreeSet<&str>> = None;
+        for (clause, terms) in clauses.iter().zip(&expanded) {
+            let found = self.matching(clause, terms);
+            candidates = Some(match candidates {
+                Some(seen) => &seen & &found,
+                None => found,
+            });
+        }
+        let phrase_check =
+            clauses.iter().any(|c| matches!(c, Clause::Phrase(_)));
+        let avg_len = self.avg_len();
+        let mut hits = Vec::new();
+        for rel in candidates.unwrap_or_default() {
+            let score: f64 = expanded
+                .iter()
+                .flatten()
+                .map(|t| self.bm25(rel, t, avg_len))
+                .sum();
+            let size = self.docs.get(rel).map_or(0, |d| d.size);
+            let Ok(note) = parse_note(&self.base.join(rel), size) else {
+                continue;
+            };
+            if phrase_check && !phrases_match(clauses, &note) {
+                continue;
+            }
+            hits.push(Hit { note, score });
+        }
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.note.id.cmp(&b.note.id))
+        });
+        hits
+    }
+}
+
+pub(crate) fn contains_sequence(
+    haystack: &[String],
+    needle: &[String],
+) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn phrases_match(clauses: &[Clause], note: &Note) -> bool {
+    let title = tokenize(&note.title);
+    let body = tokenize(&note.body);
+    clauses.iter().all(|clause| match clause {
+        Clause::Phrase(tokens) => {
+            contains_sequence(&title, tokens)
+                || contains_sequence(&body, tokens)
+        }
+        _ => true,
+    })
+}
+
+/// Text to build the snippet around: the first clause found in the note.
+fn snippet_query(clauses: &[Clause], note: &Note) -> Option<String> {
+    let haystack = format!("{}\n{}", note.title, note.body).to_lowercase();
+    clauses
+        .iter()
+        .map(|clause| match clause {
+            Clause::Term(t) | Clause::Prefix(t) => t.clone(),
+            Clause::Phrase(tokens) => tokens.join(" "),
+        })
+        .find(|needle| haystack.contains(needle.as_str()))
+}
+
+/// Handle `qn search <query> [-n N] [-t tag]`.
+pub(crate) fn search_notes(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut words: Vec<String> = Vec::new();
+    let mut limit = 20usize;
+    let mut tag_filters: Vec<String> = Vec::new();
+    let mut parser = args::ArgParser::new(args, "search");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "-n" | "--limit" => {
+                let value = parser.extract_value("-n/--limit")?;
+                limit = value
+                    .parse()
+                    .map_err(|_| format!("Invalid limit: {value}"))?;
+            }
+            "-t" | "--tag" => {
+                if let Some(tag) = parser.extract_tag()? {
+                    tag_filters.push(tag);
+                }
+            }
+            other if other.starts_with('-') && other.len() > 1 => {
+                return Err(format!("Unknown flag for search: {other}").into());
+            }
+            other => words.push(other.to_string()),
+        }
+    }
+    let query = words.join(" ");
+    let clauses = parse_query(&query);
+    if clauses.is_empty() {
+        return Err("Usage: qn search <query> [-n N] [-t tag]".into());
+    }
+
+    let index = load_search_index(dir)?;
+    let mut hits = index.search(&clauses);
+    if !tag_filters.is_empty() {
+        hits.retain(|h| tags::note_has_tags(&h.note, &tag_filters));
+    }
+    if hits.is_empty() {
+        println!("No matches for {query}.");
+        return Ok(());
+    }
+    let total = hits.len();
+    hits.truncate(limit);
+
+    let use_color = env::var("NO_COLOR").is_err();
+    let width = terminal_columns().unwrap_or(120);
+    let id_width = hits.iter().map(|h| h.note.id.len()).max().unwrap_or(2);
+    // Table borders and padding take 10 columns; "Score" takes 5.
+    let match_width = width.saturating_sub(id_width.max(2) + 5 + 10).max(20);
+    let rows: Vec<Vec<String>> = hits
+        .iter()
+        .map(|hit| {
+            let needle = snippet_query(&clauses, &hit.note);
+            let preview = preview_for_list(&hit.note, needle.as_deref());
+            let preview = truncate_with_ellipsis(&preview, match_width);
+            vec![
+                hit.note.id.clone(),
+                format!("{:.2}", hit.score),
+                highlight_search(&preview, needle.as_deref(), use_color),
+            ]
+        })
+        .collect();
+    let headers: Vec<String> =
+        ["ID", "Score", "Match"].map(String::from).to_vec();
+    println!("{}", render_table(&headers, &rows));
+    if total > hits.len() {
+        println!(
+            "Showing {} of {total} matches (use -n to see more).",
+            hits.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::{relocate_note, write_note};
+    use tempfile::tempdir;
+
+    fn note(id: &str, title: &str, body: &str) -> Note {
+        Note {
+            id: id.to_string(),
+            title: title.to_string(),
+            created: "01Jan20 10:00 +00:00".to_string(),
+            updated: "01Jan20 10:00 +00:00".to_string(),
+            deleted_at: None,
+            archived_at: None,
+            due: None,
+            remind: None,
+            body: body.to_string(),
+            tags: Vec::new(),
+            properties: Default::default(),
+            format: Default::default(),
+            size_bytes: 0,
+        }
+    }
+
+    fn ids(hits: &[Hit]) -> Vec<&str> {
+        hits.iter().map(|h| h.note.id.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_terms_phrases_and_prefixes() {
+        assert_eq!(
+            parse_query("Rust \"error handling\" deploy* x*"),
+            vec![
+                Clause::Term("rust".into()),
+                Clause::Phrase(vec!["error".into(), "handling".into()]),
+                Clause::Prefix("deploy".into()),
+                Clause::Term("x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ranks_title_hits_and_filters_phrases() {
+        let tmp = tempdir().unwrap();
+        let base = tmp.path();
+        write_note(&note("a", "Kubernetes rollout", "notes"), base).unwrap();
+        write_note(
+            &note("b", "Misc", "rollout of kubernetes happened; kubernetes"),
+            base,
+        )
+        .unwrap();
+        write_note(&note("c", "Other", "nothing relevant"), base).unwrap();
+
+        let index = load_search_index(base).unwrap();
+        let hits = index.search(&parse_query("kubernetes"));
+        assert_eq!(ids(&hits), vec!["a", "b"]);
+
+        let hits = index.search(&parse_query("\"kubernetes rollout\""));
+        assert_eq!(ids(&hits), vec!["a"]);
+
+        let hits = index.search(&parse_query("kube* rollout"));
+        assert_eq!(hits.len(), 2);
+        assert!(index.search(&parse_query("kubernetes nothing")).is_empty());
+    }
+
+    #[test]
+    fn writes_update_the_saved_postings() {
+        let tmp = tempdir().unwrap();
+        let base = tmp.path();
+        write_note(&note("a", "Alpha", "first"), base).unwrap();
+        assert_eq!(load_search_index(base).unwrap().docs.len(), 1);
+
+        // qn writes and moves append to `.qn-search` without reading it.
+        let snapshot = fs::read_to_string(base.join(SEARCH_FILE)).unwrap();
+        write_note(&note("a", "Alpha", "second version"), base).unwrap();
+        write_note(&note("b", "Beta", "second"), base).unwrap();
+        let saved = fs::read_to_string(base.join(SEARCH_FILE)).unwrap();
+        let log = saved.strip_prefix(snapshot.as_str()).unwrap();
+        assert_eq!(log.lines().count(), 2, "{saved}");
+        assert!(log.contains("\talpha:1:0,second:0:1,version:0:1\n"), "{log}");
+        let index = SearchIndex::read(base).unwrap();
+        assert_eq!(index.log, 2);
+        assert!(index.search(&parse_query("first")).is_empty());
+        assert_eq!(ids(&index.search(&parse_query("second"))), ["b", "a"]);
+
+        let trash = base.join("trash");
+        fs::create_dir_all(&trash).unwrap();
+        let b = note("b", "Beta", "second");
+        relocate_note(&b, &base.join("b.md"), &trash).unwrap();
+        let index = SearchIndex::read(base).unwrap();
+        assert_eq!(ids(&index.search(&parse_query("second"))), ["a"]);
+        assert!(!index.postings.contains_key("beta"));
+
+        // Hand edits are caught when the stamps are revalidated.
+        fs::write(
+            base.join("a.md"),
+            "Title: Alpha\nCreated:\nUpdated:\nTags:\n---\nthird edit\n",
+        )
+        .unwrap();
+        let index = load_search_index(base).unwrap();
+        assert_eq!(index.search(&parse_query("third")).len(), 1);
+        assert!(index.search(&parse_query("second")).is_empty());
+
+        // Compaction folds the log back into the snapshot.
+        rebuild_search_index(base).unwrap();
+        let saved = fs::read_to_string(base.join(SEARCH_FILE)).unwrap();
+        assert!(saved.contains("\nt\tthird\t0:0:1\n"), "{saved}");
+        assert!(!saved.contains("\n+\t") && !saved.contains("\n-\t"));
+        assert_eq!(SearchIndex::read(base).unwrap().log, 0);
+    }
+}
@@ -0,0 +1,925 @@
+//! `qn serve`: a small HTTP/1.1 JSON API over the note operations, for
+//! bookmarklets, editor plugins and scripts.
+//!
+//! One thread per connection, one request per connection. Requests take the
+//! same store and note locks as the CLI, so `qn` commands can run alongside.
+//! With `--ui`, paths outside `/notes` serve the read-only HTML pages in
+//! [`crate::ui`].
+
+use crate::note::parse_note;
+use crate::shared::json::Json;
+use crate::shared::lock::{LockMode, lock_note, lock_store};
+use crate::shared::migrate::{locate_note, resolve_active_note_path};
+use crate::{
+    Area, append_to_note, area_dir, args, create_note_with_tags,
+    derive_title_from_body, git, move_note_with_timestamp, note_json,
+    parse_list_options, restore_note, retag_note, select_notes, tags, ui,
+};
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 7878;
+const MAX_BODY_BYTES: usize = 1 << 20;
+const MAX_HEADER_LINES: usize = 100;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const TOKEN_COOKIE: &str = "qn_token";
+
+#[derive(Clone)]
+struct Config {
+    dir: PathBuf,
+    token: Option<String>,
+    allow_origin: Option<String>,
+    ui: bool,
+    /// `Host` headers answered when there is no token.
+    hosts: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn query_value(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .filter(|v| !v.is_empty())
+    }
+
+    fn cookie(&self, name: &str) -> Option<String> {
+        self.header("cookie")?.split(';').find_map(|pair| {
+            let (k, v) = pair.trim().split_once('=')?;
+            (k == name).then(|| percent_decode(v))
+        })
+    }
+
+    fn json(&self) -> Result<Json, ApiError> {
+        if self.body.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Json::Object(Vec::new()));
+        }
+        let text = std::str::from_utf8(&self.body)
+            .map_err(|_| ApiError::bad_request("Body must be UTF-8"))?;
+        Json::parse(text).map_err(|e| {
+            ApiError::bad_request(format!("Invalid JSON body: {e}"))
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into() }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        ApiError::new(400, message)
+    }
+
+    fn not_found(id: &str) -> Self {
+        ApiError::new(404, format!("Note {id} not found"))
+    }
+}
+
+/// I/O trouble is the server's fault (or a busy lock); anything else came
+/// from validating the request.
+impl From<Box<dyn Error>> for ApiError {
+    fn from(err: Box<dyn Error>) -> Self {
+        match err.downcast_ref::<io::Error>().map(io::Error::kind) {
+            Some(io::ErrorKind::TimedOut) => {
+                ApiError::new(503, err.to_string())
+            }
+            Some(io::ErrorKind::NotFound) => {
+                ApiError::new(404, err.to_string())
+            }
+            Some(io::ErrorKind::InvalidInput) => {
+                ApiError::bad_request(err.to_string())
+            }
+            Some(_) => ApiError::new(500, err.to_string()),
+            None => ApiError::bad_request(err.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(err: io::Error) -> Self {
+        ApiError::from(Box::<dyn Error>::from(err))
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        303 => "See Other",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn percent_decode(raw: &str) -> String {
+    let hex = |b: Option<&u8>| b.and_then(|b| (*b as char).to_digit(16));
+    let bytes = raw.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => match (hex(bytes.get(i + 1)), hex(bytes.get(i + 2))) {
+                (Some(high), Some(low)) => {
+                    out.push((high * 16 + low) as u8);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn read_request(stream: impl Read) -> Result<Request, ApiError> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(ApiError::bad_request("Malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        query: query
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|pair| {
+                let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(k), percent_decode(v))
+            })
+            .collect(),
+        ..Request::default()
+    };
+
+    for _ in 0..MAX_HEADER_LINES {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            let length = match request.header("content-length") {
+                Some(raw) => raw.trim().parse::<usize>().map_err(|_| {
+                    ApiError::bad_request("Invalid Content-Length")
+                })?,
+                None => 0,
+            };
+            if length > MAX_BODY_BYTES {
+                return Err(ApiError::new(413, "Request body is too large"));
+            }
+            request.body = vec![0; length];
+            reader.read_exact(&mut request.body)?;
+            return Ok(request);
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| ApiError::bad_request("Malformed header"))?;
+        request
+            .headers
+            .push((name.trim().to_string(), value.trim().to_string()));
+    }
+    Err(ApiError::bad_request("Too many headers"))
+}
+
+/// Compare without an early exit so timing does not reveal the token.
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Whether `path` belongs to the HTML pages rather than the JSON API.
+fn is_ui_path(path: &str, config: &Config) -> bool {
+    config.ui && path.trim_start_matches('/').split('/').next() != Some("notes")
+}
+
+/// `Host` values naming this server on its port: the loopback names and the
+/// bound address. A DNS-rebinding page reaches the server under its own
+/// name, so without a token anything else is refused.
+fn local_hosts(addr: SocketAddr) -> Vec<String> {
+    let mut names: Vec<String> =
+        ["localhost", "127.0.0.1", "[::1]"].map(String::from).to_vec();
+    if !addr.ip().is_unspecified() {
+        let ip = match addr {
+            SocketAddr::V4(v4) => v4.ip().to_string(),
+            SocketAddr::V6(v6) => format!("[{}]", v6.ip()),
+        };
+        if !names.contains(&ip) {
+            names.push(ip);
+        }
+    }
+    let port = addr.port();
+    let mut hosts: Vec<String> =
+        names.iter().map(|name| format!("{name}:{port}")).collect();
+    if port == 80 {
+        hosts.extend(names);
+    }
+    hosts
+}
+
+fn check_access(req: &Request, config: &Config) -> Result<(), ApiError> {
+    if config.token.is_none() {
+        let host = req.header("host").unwrap_or_default();
+        if !config.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+            return Err(ApiError::new(
+                403,
+                format!(
+                    "Host {host} is not allowed without a token; use \
+localhost or pass --token"
+                ),
+            ));
+        }
+    }
+    // Browsers send Origin on cross-site requests; without an allowed
+    // origin, a web page must not be able to drive the API. The UI's own
+    // pages are same-origin.
+    let same_origin = |origin: &str| {
+        req.header("host")
+            .is_some_and(|host| origin == format!("http://{host}"))
+    };
+    if let Some(origin) = req.header("origin")
+        && !same_origin(origin)
+        && !config
+            .allow_origin
+            .as_deref()
+            .is_some_and(|allowed| allowed == "*" || allowed == origin)
+    {
+        return Err(ApiError::new(
+            403,
+            format!("Origin {origin} is not allowed"),
+        ));
+    }
+    if let Some(token) = &config.token {
+        // Browsers cannot send a bearer header when following a link, so the
+        // UI pages also take the token from `?token=` once and then a cookie.
+        // The API never does: a browser attaches the cookie to any request,
+        // including one a note's content managed to make.
+        let ui_read = is_ui_path(&req.path, config) && req.method == "GET";
+        let given = req
+            .header("authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(|t| t.trim().to_string())
+            .or_else(|| ui_read.then(|| req.cookie(TOKEN_COOKIE)).flatten())
+            .or_else(|| {
+                ui_read
+                    .then(|| req.query_value("token").map(String::from))
+                    .flatten()
+            })
+            .unwrap_or_default();
+        if !token_matches(&given, token) {
+            return Err(ApiError::new(401, "Missing or invalid bearer token"));
+        }
+    }
+    Ok(())
+}
+
+/// Current JSON of note `id`, with its body, wherever it lives.
+fn note_response(dir: &Path, id: &str) -> Result<Json, ApiError> {
+    let (area, path) =
+        locate_note(dir, id).ok_or_else(|| ApiError::not_found(id))?;
+    let note = parse_note(&path, fs::metadata(&path)?.len())?;
+    Ok(note_json(dir, area, &note, true))
+}
+
+fn string_list(body: &Json, key: &str) -> Result<Vec<String>, ApiError> {
+    match body.get(key) {
+        None | Some(Json::Null) => Ok(Vec::new()),
+        Some(value) => value
+            .as_array()
+            .and_then(|items| {
+                items.iter().map(|v| v.as_str().map(String::from)).collect()
+            })
+            .ok_or_else(|| {
+                ApiError::bad_request(format!(
+                    "{key} must be a list of strings"
+                ))
+            }),
+    }
+}
+
+fn optional_str<'a>(
+    body: &'a Json,
+    key: &str,
+) -> Result<Option<&'a str>, ApiError> {
+    match body.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(value) => value.as_str().map(Some).ok_or_else(|| {
+            ApiError::bad_request(format!("{key} must be a string"))
+        }),
+    }
+}
+
+/// `GET /notes`: the query string maps onto `qn list` flags.
+fn list(req: &Request, dir: &Path) -> Result<(u16, Json), ApiError> {
+    let mut area = Area::Active;
+    let mut list_args: Vec<String> = Vec::new();
+    for (key, value) in &req.query {
+        let flag = match key.as_str() {
+            "area" => {
+                area = match value.as_str() {
+                    "active" => Area::Active,
+                    "trash" => Area::Trash,
+                    "archive" => Area::Archive,
+                    other => {
+                        return Err(ApiError::bad_request(format!(
+                            "Unknown area {other}; use active, trash or archive"
+                        )));
+                    }
+                };
+                continue;
+            }
+            "order" => {
+                list_args.push(
+                    if value == "asc" { "--asc" } else { "--desc" }.into(),
+                );
+                continue;
+            }
+            "tag" => "-t",
+            "q" => "-q",
+            "search" => "-s",
+            "where" => "-w",
+            "sort" => "--sort",
+            other => {
+                return Err(ApiError::bad_request(format!(
+                    "Unknown query parameter {other}"
+                )));
+            }
+        };
+        list_args.push(flag.to_string());
+        list_args.push(value.clone());
+    }
+    let opts = parse_list_options(list_args)?;
+    let notes = select_notes(dir, area, &opts)?;
+    let items = notes.iter().map(|n| note_json(dir, area, n, false)).collect();
+    Ok((200, Json::Array(items)))
+}
+
+fn create(req: &Request, dir: &Path) -> Result<(u16, Json), ApiError> {
+    let body = req.json()?;
+    let text = optional_str(&body, "body")?.unwrap_or_default().to_string();
+    let title = match optional_str(&body, "title")? {
+        Some(title) if !title.trim().is_empty() => title.trim().to_string(),
+        _ if !text.trim().is_empty() => derive_title_from_body(&text),
+        _ => return Err(ApiError::bad_request("Provide a title or a body")),
+    };
+    let tags = string_list(&body, "tags")?;
+    let note = create_note_with_tags(title, text, tags, dir)?;
+    git::autocommit(dir, "new");
+    Ok((201, note_response(dir, &note.id)?))
+}
+
+fn append(
+    req: &Request,
+    dir: &Path,
+    id: &str,
+) -> Result<(u16, Json), ApiError> {
+    let body = req.json()?;
+    let text = optional_str(&body, "text")?
+        .ok_or_else(|| ApiError::bad_request("Provide text to append"))?;
+    if resolve_active_note_path(dir, id).is_none() {
+        return Err(ApiError::not_found(id));
+    }
+    append_to_note(dir, id, text)?;
+    git::autocommit(dir, "add");
+    Ok((200, note_response(dir, id)?))
+}
+
+fn retag(req: &Request, dir: &Path, id: &str) -> Result<(u16, Json), ApiError> {
+    let body = req.json()?;
+    let add = tags::normalize_tags(string_list(&body, "add")?);
+    let remove = tags::normalize_tags(string_list(&body, "remove")?);
+    if resolve_active_note_path(dir, id).is_none() {
+        return Err(ApiError::not_found(id));
+    }
+    retag_note(dir, id, &add, &remove)?;
+    git::autocommit(dir, "tag");
+    Ok((200, note_response(dir, id)?))
+}
+
+fn move_to(dir: &Path, id: &str, area: Area) -> Result<(u16, Json), ApiError> {
+    {
+        let _lock = lock_note(dir, id)?;
+        if resolve_active_note_path(dir, id).is_none() {
+            return Err(ApiError::not_found(id));
+        }
+        let to_dir = area_dir(dir, area);
+        move_note_with_timestamp(dir, &to_dir, id, area)?;
+    }
+    git::autocommit(
+        dir,
+        if area == Area::Trash { "delete" } else { "archive" },
+    );
+    Ok((200, note_response(dir, id)?))
+}
+
+fn restore(dir: &Path, id: &str) -> Result<(u16, Json), ApiError> {
+    let restored = {
+        let _lock = lock_note(dir, id)?;
+        let from = match locate_note(dir, id) {
+            Some((area, _)) if area != Area::Active => area,
+            Some(_) => {
+                return Err(ApiError::bad_request(format!(
+                    "Note {id} is not in the trash or archive"
+                )));
+            }
+            None => return Err(ApiError::not_found(id)),
+        };
+        let verb = if from == Area::Trash { "undelete" } else { "unarchive" };
+        (restore_note(id, &area_dir(dir, from), dir)?, verb)
+    };
+    git::autocommit(dir, restored.1);
+    Ok((200, note_response(dir, &restored.0)?))
+}
+
+fn route(req: &Request, dir: &Path) -> Result<(u16, Json), ApiError> {
+    let segments: Vec<&str> = req
+        .path
+        .trim_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+    let method = req.method.as_str();
+    let reads = matches!(method, "GET" | "HEAD");
+    let _store_lock =
+        if reads { None } else { Some(lock_store(dir, LockMode::Shared)?) };
+    match (method, segments.as_slice()) {
+        ("GET", ["notes"]) => list(req, dir),
+        ("POST", ["notes"]) => create(req, dir),
+        ("GET", ["notes", id]) => Ok((200, note_response(dir, id)?)),
+        ("DELETE", ["notes", id]) => move_to(dir, id, Area::Trash),
+        ("POST", ["notes", id, "append"]) => append(req, dir, id),
+        ("POST", ["notes", id, "tags"]) => retag(req, dir, id),
+        ("POST", ["notes", id, "archive"]) => move_to(dir, id, Area::Archive),
+        ("POST", ["notes", id, "restore"]) => restore(dir, id),
+        (_, ["notes"] | ["notes", _] | ["notes", _, _]) => {
+            Err(ApiError::new(405, format!("{method} is not allowed here")))
+        }
+        _ => Err(ApiError::new(404, format!("No route for {}", req.path))),
+    }
+}
+
+/// A response: status, optional typed payload and extra header lines.
+struct Reply {
+    status: u16,
+    content_type: Option<&'static str>,
+    payload: String,
+    headers: Vec<String>,
+}
+
+impl Reply {
+    fn empty(status: u16) -> Self {
+        Reply {
+            status,
+            content_type: None,
+            payload: String::new(),
+            headers: Vec::new(),
+        }
+    }
+
+    fn json(status: u16, body: &Json) -> Self {
+        Reply {
+            content_type: Some("application/json; charset=utf-8"),
+            payload: format!("{body}\n"),
+            ..Reply::empty(status)
+        }
+    }
+
+    /// An HTML page under a Content-Security-Policy that only runs scripts
+    /// carrying `nonce`.
+    fn html(status: u16, page: String, nonce: Option<&str>) -> Self {
+        let scripts = match nonce {
+            Some(nonce) => format!("script-src 'nonce-{nonce}'; "),
+            None => String::new(),
+        };
+        Reply {
+            content_type: Some("text/html; charset=utf-8"),
+            payload: page,
+            headers: vec![format!(
+                "Content-Security-Policy: default-src 'none'; {scripts}\
+connect-src 'self'; style-src 'unsafe-inline'; img-src 'self' http: https:; \
+form-action 'self'; base-uri 'none'; frame-ancestors 'none'"
+            )],
+            ..Reply::empty(status)
+        }
+    }
+}
+
+/// A fresh value for a page's script nonce. `RandomState` keys are seeded
+/// from the OS, so no two responses share one.
+fn csp_nonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let half = || RandomState::new().hash_one(count);
+    format!("{:016x}{:016x}", half(), half())
+}
+
+/// `GET` on a UI page; see [`crate::ui`].
+fn ui_route(req: &Request, config: &Config) -> Result<Reply, ApiError> {
+    if req.method != "GET" {
+        return Err(ApiError::new(405, "The web UI is read-only"));
+    }
+    let dir = config.dir.as_path();
+    // Trade a `?token=` link for a cookie and drop it from the address bar.
+    if let (Some(token), Some(_)) = (&config.token, req.query_value("token")) {
+        let rest: Vec<String> = req
+            .query
+            .iter()
+            .filter(|(k, _)| k != "token")
+            .map(|(k, v)| {
+                format!("{}={}", ui::percent_encode(k), ui::percent_encode(v))
+            })
+            .collect();
+        let mut location = req.path.clone();
+        if !rest.is_empty() {
+            location.push('?');
+            location.push_str(&rest.join("&"));
+        }
+        let mut reply = Reply::empty(303);
+        reply.headers.push(format!("Location: {location}"));
+        reply.headers.push(format!(
+            "Set-Cookie: {TOKEN_COOKIE}={}; Path=/; HttpOnly; SameSite=Strict",
+            ui::percent_encode(token)
+        ));
+        return Ok(reply);
+    }
+    let segments: Vec<&str> = req
+        .path
+        .trim_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+    let nonce = csp_nonce();
+    let area = match segments.as_slice() {
+        [] => Area::Active,
+        ["archive"] => Area::Archive,
+        ["trash"] => Area::Trash,
+        ["_version"] => {
+            return Ok(Reply {
+                content_type: Some("text/plain; charset=utf-8"),
+                payload: ui::fingerprint(dir)?,
+                ..Reply::empty(200)
+            });
+        }
+        ["n", id] => {
+            let (area, path) =
+                locate_note(dir, id).ok_or_else(|| ApiError::not_found(id))?;
+            let note = parse_note(&path, fs::metadata(&path)?.len())?;
+            let page = ui::note_page(dir, &nonce, area, &note, |other| {
+                locate_note(dir, other).is_some()
+            })?;
+            return Ok(Reply::html(200, page, Some(&nonce)));
+        }
+        _ => {
+            return Err(ApiError::new(404, format!("No page at {}", req.path)));
+        }
+    };
+    let (search, tag) = (req.query_value("q"), req.query_value("tag"));
+    let page = ui::list_page(dir, &nonce, area, search, tag)?;
+    Ok(Reply::html(200, page, Some(&nonce)))
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    reply: &Reply,
+    config: &Config,
+) -> io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        reply.status,
+        reason(reply.status),
+        reply.payload.len()
+    );
+    if let Some(content_type) = reply.content_type {
+        head.push_str(&format!("Content-Type: {content_type}\r\n"));
+    }
+    for header in &reply.headers {
+        head.push_str(header);
+        head.push_str("\r\n");
+    }
+    if reply.status == 401 {
+        head.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    if let Some(origin) = &config.allow_origin {
+        head.push_str(&format!(
+            "Access-Control-Allow-Origin: {origin}\r\n\
+Access-Control-Allow-Headers: Authorization, Content-Type\r\n\
+Access-Control-Allow-Methods: GET, POST, DELETE\r\n"
+        ));
+    }
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(b"\r\n")?;
+    stream.write_all(reply.payload.as_bytes())?;
+    stream.flush()
+}
+
+fn handle(mut stream: TcpStream, config: &Config) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut ui_page = false;
+    let result = read_request(&stream).and_then(|req| {
+        // Preflight requests carry no credentials; answer them up front.
+        if req.method == "OPTIONS" && config.allow_origin.is_some() {
+            return Ok((req, Reply::empty(204)));
+        }
+        ui_page = is_ui_path(&req.path, config);
+        check_access(&req, config)?;
+        let reply = if ui_page {
+            ui_route(&req, config)?
+        } else {
+            let (status, body) = route(&req, &config.dir)?;
+            Reply::json(status, &body)
+        };
+        Ok((req, reply))
+    });
+    match result {
+        Ok((req, reply)) => {
+            eprintln!("{} {} -> {}", req.method, req.path, reply.status);
+            respond(&mut stream, &reply, config)
+        }
+        Err(err) => {
+            eprintln!("request failed -> {}: {}", err.status, err.message);
+            let reply = if ui_page {
+                let message = if err.status == 401 {
+                    "Open this page with ?token=<token> added to the address \
+to sign in."
+                        .to_string()
+                } else {
+                    err.message
+                };
+                let title = reason(err.status);
+                let page = ui::message_page(title, &message);
+                Reply::html(err.status, page, None)
+            } else {
+                let body =
+                    Json::object(vec![("error", Json::from(err.message))]);
+                Reply::json(err.status, &body)
+            };
+            respond(&mut stream, &reply, config)
+        }
+    }
+}
+
+/// Handle `qn serve [--port N] [--host addr] [--token T] [--allow-origin o]
+/// [--ui]`.
+pub(crate) fn serve(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut port = DEFAULT_PORT;
+    let mut host = "127.0.0.1".to_string();
+    let mut token = env::var("QUICK_NOTES_SERVE_TOKEN")
+        .ok()
+        .filter(|t| !t.trim().is_empty());
+    let mut allow_origin: Option<String> = None;
+    let mut ui = false;
+    let mut parser = args::ArgParser::new(args, "serve");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "-p" | "--port" => {
+                let raw = parser.extract_value("-p/--port")?;
+                port =
+                    raw.parse().map_err(|_| format!("Invalid port: {raw}"))?;
+            }
+            "--host" => host = parser.extract_value("--host")?,
+            "--token" => token = Some(parser.extract_value("--token")?),
+            "--allow-origin" => {
+                allow_origin = Some(parser.extract_value("--allow-origin")?);
+            }
+            "--ui" => ui = true,
+            other => {
+                return Err(format!(
+                    "Unknown argument for serve: {other}\nUsage: qn serve \
+[--port N] [--host addr] [--token T] [--allow-origin origin] [--ui]"
+                )
+                .into());
+            }
+        }
+    }
+
+    let listener = TcpListener::bind((host.as_str(), port))
+        .map_err(|e| format!("Could not listen on {host}:{port}: {e}"))?;
+    let addr = listener.local_addr()?;
+    if !addr.ip().is_loopback() && token.is_none() {
+        eprintln!(
+            "Warning: serving on {addr} without a token; anyone who can reach \
+it can read and change notes. Pass --token or set QUICK_NOTES_SERVE_TOKEN."
+        );
+    }
+    println!("Serving {} on http://{addr} (Ctrl-C to stop)", dir.display());
+    if ui {
+        let sign_in = if token.is_some() { "?token=<token>" } else { "" };
+        println!("Web UI at http://{addr}/{sign_in}");
+    }
+
+    let config = Config {
+        dir: dir.to_path_buf(),
+        token,
+        allow_origin,
+        ui,
+        hosts: local_hosts(addr),
+    };
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        let config = config.clone();
+        thread::spawn(move || {
+            let _ = handle(stream, &config);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::fixtures;
+
+    #[test]
+    fn parses_request_with_query_and_body() {
+        let raw = "POST /notes/abc%20d/append?tag=%23work&q=a+b HTTP/1.1\r\n\
+Host: localhost\r\nContent-Length: 15\r\nAuthorization: Bearer s3cret\r\n\r\n\
+{\"text\":\"hi\"}\n\nextra";
+        let req = read_request(raw.as_bytes()).unwrap();
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/notes/abc d/append");
+        assert_eq!(
+            req.query,
+            vec![
+                ("tag".to_string(), "#work".to_string()),
+                ("q".to_string(), "a b".to_string())
+            ]
+        );
+        assert_eq!(
+            req.json().unwrap().get("text").and_then(Json::as_str),
+            Some("hi")
+        );
+
+        let config = Config {
+            dir: PathBuf::new(),
+            token: Some("s3cret".to_string()),
+            allow_origin: None,
+            ui: false,
+            hosts: Vec::new(),
+        };
+        assert!(check_access(&req, &config).is_ok());
+        let mut cross_site = req;
+        cross_site
+            .headers
+            .push(("Origin".into(), "https://evil.example".into()));
+        assert_eq!(check_access(&cross_site, &config).unwrap_err().status, 403);
+    }
+
+    #[test]
+    fn ui_pages_take_the_token_from_a_cookie_or_link() {
+        let config = Config {
+            dir: PathBuf::new(),
+            token: Some("s3cret".to_string()),
+            allow_origin: None,
+            ui: true,
+            hosts: Vec::new(),
+        };
+        let raw = "GET /?token=s3cret HTTP/1.1\r\nHost: 127.0.0.1:7878\r\n\
+Origin: http://127.0.0.1:7878\r\n\r\n";
+        let req = read_request(raw.as_bytes()).unwrap();
+        assert!(check_access(&req, &config).is_ok());
+        let reply = ui_route(&req, &config).unwrap();
+        assert_eq!(reply.status, 303);
+        assert!(reply.headers.contains(&"Location: /".to_string()));
+
+        let raw = "GET /n/n1 HTTP/1.1\r\n\
+Cookie: theme=dark; qn_token=s3cret\r\n\r\n";
+        let mut req = read_request(raw.as_bytes()).unwrap();
+        assert!(check_access(&req, &config).is_ok());
+        req.headers.clear();
+        assert_eq!(check_access(&req, &config).unwrap_err().status, 401);
+
+        // The API wants the bearer header, whatever cookie comes along.
+        for (method, path) in
+            [("GET", "/notes?token=s3cret"), ("DELETE", "/notes/n1")]
+        {
+            let raw = format!(
+                "{method} {path} HTTP/1.1\r\nCookie: qn_token=s3cret\r\n\r\n"
+            );
+            let req = read_request(raw.as_bytes()).unwrap();
+            assert_eq!(
+                check_access(&req, &config).unwrap_err().status,
+                401,
+                "{method} {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn ui_pages_only_run_their_own_script() {
+        let tmp = tempfile::tempdir().unwrap();
+        let body = "<script>fetch('/notes/n1', {method: 'DELETE'})</script>\n";
+        fixtures::write_note(tmp.path(), "n1", "Trap", "", body);
+        let config = Config {
+            dir: tmp.path().to_path_buf(),
+            token: None,
+            allow_origin: None,
+            ui: true,
+            hosts: Vec::new(),
+        };
+        let req = read_request(&b"GET /n/n1 HTTP/1.1\r\n\r\n"[..]).unwrap();
+        let reply = ui_route(&req, &config).unwrap();
+        assert!(reply.payload.contains("&lt;script&gt;fetch("));
+        assert_eq!(reply.payload.matches("<script").count(), 1);
+        let csp = reply
+            .headers
+            .iter()
+            .find_map(|h| h.strip_prefix("Content-Security-Policy: "))
+            .unwrap();
+        let nonce = csp
+            .split("'nonce-")
+            .nth(1)
+            .and_then(|rest| rest.split('\'').next())
+            .unwrap();
+        assert!(reply.payload.contains(&format!("<script nonce=\"{nonce}\">")));
+        assert!(csp.starts_with("default-src 'none'"), "{csp}");
+        assert_ne!(csp_nonce(), csp_nonce());
+    }
+
+    #[test]
+    fn without_a_token_only_local_host_names_are_answered() {
+        let config = Config {
+            dir: PathBuf::new(),
+            token: None,
+            allow_origin: None,
+            ui: true,
+            hosts: local_hosts("127.0.0.1:7878".parse().unwrap()),
+        };
+        let request = |host: &str| {
+            let raw = format!(
+                "GET /notes HTTP/1.1\r\nHost: {host}\r\n\
+Origin: http://{host}\r\n\r\n"
+            );
+            read_request(raw.as_bytes()).unwrap()
+        };
+        for host in ["127.0.0.1:7878", "localhost:7878", "[::1]:7878"] {
+            assert!(check_access(&request(host), &config).is_ok(), "{host}");
+        }
+        // A rebound name is same-origin with itself but not a local host.
+        for host in ["evil.example:7878", "localhost:8080", "localhost"] {
+            let err = check_access(&request(host), &config).unwrap_err();
+            assert_eq!(err.status, 403, "{host}");
+        }
+        assert!(
+            local_hosts("0.0.0.0:80".parse().unwrap())
+                .contains(&"localhost".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_bodies() {
+        let raw = format!(
+            "POST /notes HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_BYTES + 1
+        );
+        assert_eq!(read_request(raw.as_bytes()).unwrap_err().status, 413);
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
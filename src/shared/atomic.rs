@@ -0,0 +1,380 @@
+//! Crash-safe file primitives for note mutations.
+//!
+//! Every write goes to a temp file in the destination directory, is fsynced,
+//! and is then renamed over the final path. The `Fs` trait lets tests inject
+//! failures at any step.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The filesystem operations note mutations are built from.
+pub(crate) trait Fs {
+    /// Create `path` (which must not exist), write `contents`, and fsync it.
+    fn create_synced(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Rename in one step unless `to` exists; `Unsupported` where the
+    /// platform or filesystem cannot do that.
+    fn rename_noreplace(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Link `to` to the file at `from`; fails if `to` already exists.
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// Persist directory entries (renames/unlinks) to disk.
+    fn sync_dir(&self, dir: &Path) -> io::Result<()>;
+}
+
+pub(crate) struct RealFs;
+
+impl Fs for RealFs {
+    fn create_synced(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut file =
+            OpenOptions::new().write(true).create_new(true).open(path)?;
+        file.write_all(contents)?;
+        file.sync_all()
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn rename_noreplace(&self, from: &Path, to: &Path) -> io::Result<()> {
+        use rustix::fs::{CWD, RenameFlags, renameat_with};
+        use rustix::io::Errno;
+        match renameat_with(CWD, from, CWD, to, RenameFlags::NOREPLACE) {
+            Ok(()) => Ok(()),
+            // Old kernels and some filesystems (NFS, FUSE) lack the flag.
+            Err(Errno::INVAL | Errno::NOSYS | Errno::NOTSUP) => {
+                Err(io::ErrorKind::Unsupported.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn rename_noreplace(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::hard_link(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn sync_dir(&self, dir: &Path) -> io::Result<()> {
+        if cfg!(unix) { File::open(dir)?.sync_all() } else { Ok(()) }
+    }
+}
+
+/// Temp path next to `path`; hidden and not `.md`, so listings skip it.
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("note");
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{name}.{}-{n}.tmp", std::process::id()))
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    }
+}
+
+/// Replace `path` with `contents` so readers see either the old or the new
+/// file, never a partial one.
+pub(crate) fn atomic_write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    atomic_write_with(&RealFs, path, contents)
+}
+
+pub(crate) fn atomic_write_with(
+    fs: &dyn Fs,
+    path: &Path,
+    contents: &[u8],
+) -> io::Result<()> {
+    let tmp = temp_path(path);
+    let result =
+        fs.create_synced(&tmp, contents).and_then(|_| fs.rename(&tmp, path));
+    if let Err(e) = result {
+        let _ = fs.remove_file(&tmp);
+        return Err(e);
+    }
+    fs.sync_dir(parent_dir(path))
+}
+
+/// Move `src` to `dst` after rewriting it with `contents`.
+///
+/// The note is rewritten in place first and only then moved, so an
+/// interrupted move leaves exactly one complete copy: the old file, the
+/// rewritten file in the source area, or the file in its new area. An
+/// existing `dst` is never overwritten.
+pub(crate) fn atomic_move(
+    src: &Path,
+    dst: &Path,
+    contents: &[u8],
+) -> io::Result<()> {
+    atomic_move_with(&RealFs, src, dst, contents)
+}
+
+pub(crate) fn atomic_move_with(
+    fs: &dyn Fs,
+    src: &Path,
+    dst: &Path,
+    contents: &[u8],
+) -> io::Result<()> {
+    if src != dst && dst.exists() {
+        return Err(already_exists(dst));
+    }
+    atomic_write_with(fs, src, contents)?;
+    if src == dst {
+        return Ok(());
+    }
+    rename_noclobber(fs, src, dst)?;
+    fs.sync_dir(parent_dir(src))
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} already exists", path.display()),
+    )
+}
+
+/// Rename `src` to `dst` unless `dst` exists.
+///
+/// `renameat2(RENAME_NOREPLACE)` does this in one step, so a `dst` that
+/// appears after the check above still wins. Elsewhere the note is linked
+/// into place (the link is the existence check) and then unlinked from
+/// `src`; a crash in between leaves the same file in both areas, which
+/// `note::repair_interrupted_moves` clears up. Without hard links either,
+/// this falls back to a plain rename.
+fn rename_noclobber(fs: &dyn Fs, src: &Path, dst: &Path) -> io::Result<()> {
+    match fs.rename_noreplace(src, dst) {
+        Ok(()) => return fs.sync_dir(parent_dir(dst)),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(already_exists(dst));
+        }
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {}
+        Err(e) => return Err(e),
+    }
+    match fs.hard_link(src, dst) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(already_exists(dst));
+        }
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+            fs.rename(src, dst)?;
+            return fs.sync_dir(parent_dir(dst));
+        }
+        Err(e) => return Err(e),
+    }
+    if let Err(e) = fs.sync_dir(parent_dir(dst)) {
+        let _ = fs.remove_file(dst);
+        return Err(e);
+    }
+    fs.remove_file(src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use tempfile::tempdir;
+
+    /// Real filesystem that fails the `fail_at`-th operation, simulating a
+    /// crash or a full disk at that point. With `links_only` it has no
+    /// one-step no-replace rename, like most non-Linux systems.
+    struct FailingFs {
+        ops: Cell<usize>,
+        fail_at: usize,
+        links_only: bool,
+    }
+
+    impl FailingFs {
+        fn new(fail_at: usize) -> Self {
+            FailingFs { ops: Cell::new(0), fail_at, links_only: false }
+        }
+
+        fn step(&self) -> io::Result<()> {
+            let n = self.ops.get();
+            self.ops.set(n + 1);
+            if n == self.fail_at {
+                Err(io::Error::other("injected failure"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Fs for FailingFs {
+        fn create_synced(
+            &self,
+            path: &Path,
+            contents: &[u8],
+        ) -> io::Result<()> {
+            self.step()?;
+            RealFs.create_synced(path, contents)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.step()?;
+            RealFs.rename(from, to)
+        }
+
+        fn rename_noreplace(&self, from: &Path, to: &Path) -> io::Result<()> {
+            if self.links_only {
+                return Err(io::ErrorKind::Unsupported.into());
+            }
+            self.step()?;
+            RealFs.rename_noreplace(from, to)
+        }
+
+        fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.step()?;
+            RealFs.hard_link(from, to)
+        }
+
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            self.step()?;
+            RealFs.remove_file(path)
+        }
+
+        fn sync_dir(&self, dir: &Path) -> io::Result<()> {
+            self.step()?;
+            RealFs.sync_dir(dir)
+        }
+    }
+
+    fn dir_entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_type().unwrap().is_file())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn atomic_write_replaces_contents() {
+        let tmp = tempdir().unwrap();
+        let path = tmp.path().join("a.md");
+        fs::write(&path, "old").unwrap();
+        atomic_write(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(dir_entries(tmp.path()), vec!["a.md"]);
+    }
+
+    #[test]
+    fn failed_write_keeps_old_file_and_no_temp() {
+        for fail_at in 0..2 {
+            let tmp = tempdir().unwrap();
+            let path = tmp.path().join("a.md");
+            fs::write(&path, "old").unwrap();
+            let fs_layer = FailingFs::new(fail_at);
+            assert!(atomic_write_with(&fs_layer, &path, b"new").is_err());
+            assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+            assert_eq!(dir_entries(tmp.path()), vec!["a.md"]);
+        }
+    }
+
+    #[test]
+    fn interrupted_move_never_loses_or_duplicates() {
+        // Walk the failure point through every step of the move, with and
+        // without the one-step rename.
+        let mut repaired = 0;
+        for (links_only, fail_at) in
+            [false, true].into_iter().flat_map(|l| (0..8).map(move |f| (l, f)))
+        {
+            let tmp = tempdir().unwrap();
+            let from = tmp.path().to_path_buf();
+            let to = tmp.path().join("trash");
+            fs::create_dir_all(&to).unwrap();
+            let src = from.join("n1.md");
+            let dst = to.join("n1.md");
+            fs::write(&src, "Title: n1\n---\nbody\n").unwrap();
+
+            let fs_layer = FailingFs { links_only, ..FailingFs::new(fail_at) };
+            let _ = atomic_move_with(
+                &fs_layer,
+                &src,
+                &dst,
+                b"Title: n1\nDeleted: now\n---\nbody\n",
+            );
+            // A failed unlink after the link leaves both; repair keeps the
+            // copy whose headers match its area.
+            let fixed = crate::note::repair_interrupted_moves(&from).unwrap();
+            if fixed > 0 {
+                assert!(!src.exists() && dst.exists(), "fail_at={fail_at}");
+            }
+            repaired += fixed;
+
+            let copies: Vec<String> = [&src, &dst]
+                .iter()
+                .filter(|p| p.exists())
+                .map(|p| fs::read_to_string(p).unwrap())
+                .collect();
+            assert_eq!(copies.len(), 1, "fail_at={fail_at}");
+            assert!(copies[0].ends_with("---\nbody\n"), "fail_at={fail_at}");
+            let leftovers = dir_entries(&from).len() + dir_entries(&to).len();
+            assert_eq!(leftovers, 1, "fail_at={fail_at}");
+        }
+        assert_eq!(repaired, 1);
+    }
+
+    #[test]
+    fn move_refuses_to_clobber_destination() {
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("a.md");
+        let dst = tmp.path().join("b.md");
+        fs::write(&src, "a").unwrap();
+        fs::write(&dst, "b").unwrap();
+        assert!(atomic_move(&src, &dst, b"a2").is_err());
+        assert_eq!(fs::read_to_string(&src).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "b");
+    }
+
+    #[test]
+    fn move_never_overwrites_a_destination_created_after_the_check() {
+        /// Creates `dst` right before the move links it into place.
+        struct RacingFs<'a>(&'a Path);
+
+        impl Fs for RacingFs<'_> {
+            fn create_synced(&self, p: &Path, c: &[u8]) -> io::Result<()> {
+                RealFs.create_synced(p, c)
+            }
+            fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+                RealFs.rename(from, to)
+            }
+            fn rename_noreplace(&self, f: &Path, t: &Path) -> io::Result<()> {
+                fs::write(self.0, "racer")?;
+                RealFs.rename_noreplace(f, t)
+            }
+            fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+                fs::write(self.0, "racer")?;
+                RealFs.hard_link(from, to)
+            }
+            fn remove_file(&self, path: &Path) -> io::Result<()> {
+                RealFs.remove_file(path)
+            }
+            fn sync_dir(&self, dir: &Path) -> io::Result<()> {
+                RealFs.sync_dir(dir)
+            }
+        }
+
+        let tmp = tempdir().unwrap();
+        let src = tmp.path().join("a.md");
+        let dst = tmp.path().join("b.md");
+        fs::write(&src, "a").unwrap();
+        let err =
+            atomic_move_with(&RacingFs(&dst), &src, &dst, b"a2").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&src).unwrap(), "a2");
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "racer");
+    }
+}
@@ -0,0 +1,259 @@
+//! Line-based diffs: the unified diff used by `qn diff` and the three-way
+//! merge used by directory sync.
+
+use yansi::{Paint, Style};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Keep,
+    Remove,
+    Add,
+}
+
+const CONTEXT: usize = 3;
+
+/// Pair every line of `old` and `new` with an edit op via an LCS table.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let (n, m) = (old.len(), new.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::with_capacity(n + m);
+    while i < n || j < m {
+        if i < n && j < m && old[i] == new[j] {
+            ops.push((Op::Keep, old[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push((Op::Remove, old[i]));
+            i += 1;
+        } else {
+            ops.push((Op::Add, new[j]));
+            j += 1;
+        }
+    }
+    ops
+}
+
+/// Render a unified diff with three lines of context; empty when identical.
+pub(crate) fn unified_diff(
+    old: &str,
+    new: &str,
+    old_label: &str,
+    new_label: &str,
+    use_color: bool,
+) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = diff_lines(&old_lines, &new_lines);
+    if ops.iter().all(|(op, _)| *op == Op::Keep) {
+        return String::new();
+    }
+
+    let paint = |text: String, style: Style| -> String {
+        if use_color { text.paint(style).to_string() } else { text }
+    };
+    let mut out = String::new();
+    out.push_str(&paint(format!("--- {old_label}"), Style::new().bold()));
+    out.push('\n');
+    out.push_str(&paint(format!("+++ {new_label}"), Style::new().bold()));
+    out.push('\n');
+
+    // Line numbers (1-based) in old/new before each op.
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut o, mut n) = (1, 1);
+    for (op, _) in &ops {
+        positions.push((o, n));
+        match op {
+            Op::Keep => {
+                o += 1;
+                n += 1;
+            }
+            Op::Remove => o += 1,
+            Op::Add => n += 1,
+        }
+    }
+
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (op, _))| *op != Op::Keep)
+        .map(|(i, _)| i)
+        .collect();
+    let mut idx = 0;
+    while idx < changed.len() {
+        let start = changed[idx].saturating_sub(CONTEXT);
+        let mut end = changed[idx];
+        while idx < changed.len() && changed[idx] <= end + 2 * CONTEXT + 1 {
+            end = changed[idx];
+            idx += 1;
+        }
+        let end = (end + CONTEXT).min(ops.len() - 1);
+        let hunk = &ops[start..=end];
+        let old_count = hunk.iter().filter(|(op, _)| *op != Op::Add).count();
+        let new_count = hunk.iter().filter(|(op, _)| *op != Op::Remove).count();
+        let (old_start, new_start) = positions[start];
+        let old_start = if old_count == 0 { old_start - 1 } else { old_start };
+        let new_start = if new_count == 0 { new_start - 1 } else { new_start };
+        out.push_str(&paint(
+            format!("@@ -{old_start},{old_count} +{new_start},{new_count} @@"),
+            Style::new().cyan(),
+        ));
+        out.push('\n');
+        for (op, line) in hunk {
+            let rendered = match op {
+                Op::Keep => format!(" {line}"),
+                Op::Remove => paint(format!("-{line}"), Style::new().red()),
+                Op::Add => paint(format!("+{line}"), Style::new().green()),
+            };
+            out.push_str(&rendered);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// For each line of `base`, the index of the line it is kept as in `other`.
+fn kept_lines(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut kept = vec![None; base.len()];
+    let (mut b, mut o) = (0, 0);
+    for (op, _) in diff_lines(base, other) {
+        match op {
+            Op::Keep => {
+                kept[b] = Some(o);
+                b += 1;
+                o += 1;
+            }
+            Op::Remove => b += 1,
+            Op::Add => o += 1,
+        }
+    }
+    kept
+}
+
+/// Line-level three-way merge of `ours` and `theirs` against `base`.
+///
+/// Regions changed on one side only take that side; regions changed the
+/// same way on both are kept once. Otherwise the merge fails and the error
+/// holds the text with `<<<<<<<`/`=======`/`>>>>>>>` markers around each
+/// conflicting region.
+pub(crate) fn merge3(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    ours_label: &str,
+    theirs_label: &str,
+) -> Result<String, String> {
+    let base: Vec<&str> = base.lines().collect();
+    let ours: Vec<&str> = ours.lines().collect();
+    let theirs: Vec<&str> = theirs.lines().collect();
+    let in_ours = kept_lines(&base, &ours);
+    let in_theirs = kept_lines(&base, &theirs);
+
+    let mut out: Vec<String> = Vec::new();
+    let take = |out: &mut Vec<String>, lines: &[&str]| {
+        out.extend(lines.iter().map(|l| l.to_string()));
+    };
+    let mut conflicted = false;
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    while b < base.len() || o < ours.len() || t < theirs.len() {
+        if b < base.len() && in_ours[b] == Some(o) && in_theirs[b] == Some(t) {
+            out.push(base[b].to_string());
+            (b, o, t) = (b + 1, o + 1, t + 1);
+            continue;
+        }
+        // Unstable region: up to the next base line both sides kept.
+        let next = (b..base.len())
+            .find(|&i| in_ours[i].is_some() && in_theirs[i].is_some());
+        let (b_end, o_end, t_end) = match next {
+            Some(i) => (i, in_ours[i].unwrap_or(o), in_theirs[i].unwrap_or(t)),
+            None => (base.len(), ours.len(), theirs.len()),
+        };
+        let (base_part, ours_part, theirs_part) =
+            (&base[b..b_end], &ours[o..o_end], &theirs[t..t_end]);
+        if ours_part == base_part || ours_part == theirs_part {
+            take(&mut out, theirs_part);
+        } else if theirs_part == base_part {
+            take(&mut out, ours_part);
+        } else {
+            conflicted = true;
+            out.push(format!("<<<<<<< {ours_label}"));
+            take(&mut out, ours_part);
+            out.push("=======".to_string());
+            take(&mut out, theirs_part);
+            out.push(format!(">>>>>>> {theirs_label}"));
+        }
+        (b, o, t) = (b_end, o_end, t_end);
+    }
+
+    let mut text = out.join("\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    if conflicted { Err(text) } else { Ok(text) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_inputs_produce_no_diff() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "x", "y", false), "");
+    }
+
+    #[test]
+    fn renders_hunk_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let diff = unified_diff(old, new, "a", "b", false);
+        assert_eq!(
+            diff,
+            "--- a\n+++ b\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n"
+        );
+    }
+
+    #[test]
+    fn separate_hunks_for_distant_changes() {
+        let old: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let new: String = (1..=20)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                19 => "nineteen\n".to_string(),
+                _ => format!("{i}\n"),
+            })
+            .collect();
+        let diff = unified_diff(&old, &new, "a", "b", false);
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -1,5 +1,5 @@"));
+        assert!(diff.contains("+nineteen"));
+    }
+
+    #[test]
+    fn merge3_combines_separate_edits() {
+        let base = "title\n\nalpha\nbeta\ngamma\n";
+        let ours = "title\n\nALPHA\nbeta\ngamma\n";
+        let theirs = "title\n\nalpha\nbeta\ngamma\ndelta\n";
+        assert_eq!(
+            merge3(base, ours, theirs, "a", "b").unwrap(),
+            "title\n\nALPHA\nbeta\ngamma\ndelta\n"
+        );
+        assert_eq!(merge3(base, ours, ours, "a", "b").unwrap(), ours);
+    }
+
+    #[test]
+    fn merge3_marks_overlapping_edits() {
+        let err =
+            merge3("a\nb\nc\n", "a\nB1\nc\n", "a\nB2\nc\n", "here", "there")
+                .unwrap_err();
+        assert_eq!(err, "a\n<<<<<<< here\nB1\n=======\nB2\n>>>>>>> there\nc\n");
+    }
+}
@@ -0,0 +1,33 @@
+//! Note files for unit tests, in the plain header format.
+
+use std::fs;
+use std::path::Path;
+
+/// Created (and default Updated) time of fixture notes.
+pub(crate) const CREATED: &str = "01Jan24 09:00 +00:00";
+
+/// Text of a note file. The header takes five lines, so the body starts
+/// on line 6.
+pub(crate) fn note_text(
+    title: &str,
+    updated: &str,
+    tags: &str,
+    body: &str,
+) -> String {
+    format!(
+        "Title: {title}\nCreated: {CREATED}\nUpdated: {updated}\n\
+Tags: {tags}\n---\n{body}"
+    )
+}
+
+/// Write `<id>.md` into `dir`, last updated when it was created.
+pub(crate) fn write_note(
+    dir: &Path,
+    id: &str,
+    title: &str,
+    tags: &str,
+    body: &str,
+) {
+    let text = note_text(title, CREATED, tags, body);
+    fs::write(dir.join(format!("{id}.md")), text).unwrap();
+}
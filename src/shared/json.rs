@@ -0,0 +1,462 @@
+//! Minimal JSON values, a parser for request bodies, and the `--json` /
+//! `--ndjson` output switch. Objects keep insertion order so the documented
+//! field order is stable.
+
+use std::error::Error;
+use std::fmt::{self, Write as _};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object<K: Into<String>>(fields: Vec<(K, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    pub fn opt_string(value: Option<String>) -> Json {
+        value.map(Json::String).unwrap_or(Json::Null)
+    }
+
+    /// Parse one JSON document. Numbers must be integers.
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { chars: text.char_indices().peekable() };
+        let value = parser.value()?;
+        parser.skip_ws();
+        match parser.chars.next() {
+            None => Ok(value),
+            Some((at, _)) => Err(format!("Unexpected data at byte {at}")),
+        }
+    }
+
+    /// Field of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => {
+                fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(n) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, want: char) -> Result<(), String> {
+        match self.chars.next() {
+            Some((_, c)) if c == want => Ok(()),
+            Some((at, c)) => {
+                Err(format!("Expected '{want}' at byte {at}, found '{c}'"))
+            }
+            None => Err(format!("Expected '{want}', found end of input")),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for want in word.chars() {
+            self.expect(want)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_ws();
+        let Some(&(at, c)) = self.chars.peek() else {
+            return Err("Unexpected end of input".to_string());
+        };
+        match c {
+            '{' => self.object(),
+            '[' => self.array(),
+            '"' => self.string().map(Json::String),
+            't' => self.keyword("true", Json::Bool(true)),
+            'f' => self.keyword("false", Json::Bool(false)),
+            'n' => self.keyword("null", Json::Null),
+            '-' | '0'..='9' => self.number(),
+            c => Err(format!("Unexpected '{c}' at byte {at}")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_ws();
+        if self.chars.next_if(|(_, c)| *c == '}').is_some() {
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_ws();
+            let key = self.string()?;
+            self.skip_ws();
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            self.skip_ws();
+            if self.chars.next_if(|(_, c)| *c == ',').is_none() {
+                self.expect('}')?;
+                return Ok(Json::Object(fields));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.chars.next_if(|(_, c)| *c == ']').is_some() {
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            if self.chars.next_if(|(_, c)| *c == ',').is_none() {
+                self.expect(']')?;
+                return Ok(Json::Array(items));
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut digits = String::new();
+        while let Some((_, c)) = self
+            .chars
+            .next_if(|(_, c)| c.is_ascii_digit() || "+-.eE".contains(*c))
+        {
+            digits.push(c);
+        }
+        digits
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| format!("Unsupported number {digits}; use integers"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|(_, c)| c.to_digit(16))
+                .ok_or("Invalid \\u escape")?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                None => return Err("Unterminated string".to_string()),
+                Some((_, '"')) => return Ok(out),
+                Some((_, '\\')) => {
+                    let Some((at, esc)) = self.chars.next() else {
+                        return Err("Unterminated string".to_string());
+                    };
+                    match esc {
+                        '"' | '\\' | '/' => out.push(esc),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            // Surrogate pair for characters beyond the BMP.
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect('\\')?;
+                                self.expect('u')?;
+                                let low = self.hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xD800) << 10)
+                                    + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            out.push(
+                                char::from_u32(code)
+                                    .ok_or("Invalid \\u escape")?,
+                            );
+                        }
+                        other => {
+                            return Err(format!(
+                                "Invalid escape \\{other} at byte {at}"
+                            ));
+                        }
+                    }
+                }
+                Some((_, c)) => out.push(c),
+            }
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Json::Number(i64::try_from(value).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::from(value as u64)
+    }
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Compact encoding: no whitespace between tokens.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Number(n) => write!(f, "{n}"),
+            Json::String(s) => write_escaped(f, s),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_escaped(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// How a command prints its results.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputMode {
+    #[default]
+    Text,
+    /// One JSON document on stdout.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl OutputMode {
+    pub fn is_text(self) -> bool {
+        self == OutputMode::Text
+    }
+
+    pub fn flag(self) -> &'static str {
+        match self {
+            OutputMode::Text => "",
+            OutputMode::Json => "--json",
+            OutputMode::Ndjson => "--ndjson",
+        }
+    }
+}
+
+fn output_mode(arg: &str) -> Option<OutputMode> {
+    match arg {
+        "--json" => Some(OutputMode::Json),
+        "--ndjson" => Some(OutputMode::Ndjson),
+        _ => None,
+    }
+}
+
+/// Remove `--json` / `--ndjson` from `args` (the command line, command
+/// first). Flags before the command always count; after it they are only
+/// taken for the `supported` commands and never past `--`, so other
+/// commands still see them as text.
+pub fn take_output_flag(
+    args: &mut Vec<String>,
+    supported: &[&str],
+) -> Result<OutputMode, Box<dyn Error>> {
+    let mut mode = OutputMode::Text;
+    let mut set = |wanted: OutputMode| {
+        if !mode.is_text() && mode != wanted {
+            return Err("Use either --json or --ndjson, not both");
+        }
+        mode = wanted;
+        Ok(())
+    };
+    while let Some(wanted) = args.first().and_then(|a| output_mode(a)) {
+        set(wanted)?;
+        args.remove(0);
+    }
+    let Some(cmd) = args.first() else { return Ok(mode) };
+    if supported.contains(&cmd.as_str()) {
+        let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+        let mut i = 1;
+        let mut end = end;
+        while i < end {
+            match output_mode(&args[i]) {
+                Some(wanted) => {
+                    set(wanted)?;
+                    args.remove(i);
+                    end -= 1;
+                }
+                None => i += 1,
+            }
+        }
+    } else if !mode.is_text() {
+        return Err(
+            format!("{} is not supported for {cmd}", mode.flag()).into()
+        );
+    }
+    Ok(mode)
+}
+
+/// Print a list: a JSON array, or one line per item for NDJSON.
+pub fn print_items(mode: OutputMode, items: Vec<Json>) {
+    match mode {
+        OutputMode::Ndjson => {
+            for item in items {
+                println!("{item}");
+            }
+        }
+        _ => println!("{}", Json::Array(items)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_compact_json_with_escapes() {
+        let value = Json::object(vec![
+            ("id", Json::from("a\"b")),
+            ("body", Json::from("line\n\ttab\u{1}")),
+            ("size", Json::from(12u64)),
+            ("tags", Json::Array(vec![Json::from("#x")])),
+            ("deleted", Json::Null),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r##"{"id":"a\"b","body":"line\n\ttab\u0001","size":12,"tags":["#x"],"deleted":null}"##
+        );
+    }
+
+    #[test]
+    fn output_flags_are_only_taken_for_commands_that_print_json() {
+        let take = |args: &[&str]| {
+            let mut args: Vec<String> =
+                args.iter().map(|a| a.to_string()).collect();
+            let mode = take_output_flag(&mut args, &["list"]);
+            (mode.map_err(|e| e.to_string()), args)
+        };
+        assert_eq!(
+            take(&["list", "--json", "-t", "x"]),
+            (
+                Ok(OutputMode::Json),
+                vec!["list".into(), "-t".into(), "x".into()]
+            )
+        );
+        assert_eq!(
+            take(&["--ndjson", "list"]),
+            (Ok(OutputMode::Ndjson), vec!["list".into()])
+        );
+        assert_eq!(
+            take(&["list", "--", "--json"]).1,
+            vec!["list", "--", "--json"]
+        );
+        assert_eq!(
+            take(&["add", "n1", "--json"]),
+            (
+                Ok(OutputMode::Text),
+                vec!["add".into(), "n1".into(), "--json".into()]
+            )
+        );
+        assert_eq!(
+            take(&["--json", "add", "n1"]).0,
+            Err("--json is not supported for add".to_string())
+        );
+        assert!(take(&["list", "--json", "--ndjson"]).0.is_err());
+    }
+
+    #[test]
+    fn parses_what_it_prints() {
+        let text = r##" {"title":"Caf\u00e9 \"plan\"","tags":["#a", "#b"],
+            "n":-12,"ok":true,"none":null,"emoji":"\ud83d\ude00"} "##;
+        let value = Json::parse(text).unwrap();
+        assert_eq!(
+            value.get("title").and_then(Json::as_str),
+            Some("Café \"plan\"")
+        );
+        assert_eq!(value.get("n"), Some(&Json::Number(-12)));
+        assert_eq!(
+            value.get("tags").and_then(Json::as_array).map(<[_]>::len),
+            Some(2)
+        );
+        assert_eq!(value.get("emoji").and_then(Json::as_str), Some("😀"));
+        assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+        assert!(Json::parse("{\"a\":1,}").is_err());
+        assert!(Json::parse("[1] x").is_err());
+        assert!(Json::parse("1.5").is_err());
+    }
+}
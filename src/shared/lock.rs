@@ -0,0 +1,226 @@
+//! Advisory locks that keep concurrent `qn` processes from clobbering notes.
+//!
+//! The store lock (`.qn.lock`) is taken shared by commands that touch
+//! individual notes and exclusively by commands that rewrite the whole store.
+//! Per-note locks (`.locks/<id>.lock`) serialize read-modify-write cycles on
+//! one note. `.qn-git.lock` serializes automatic git commits. Locks are
+//! released when the guard is dropped.
+
+use crate::shared::migrate::locate_note;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TIMEOUT_SECS: f64 = 10.0;
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Held lock; the OS releases it when the file handle closes.
+#[derive(Debug)]
+pub(crate) struct LockGuard {
+    _file: File,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+pub(crate) fn store_lock_path(dir: &Path) -> PathBuf {
+    dir.join(".qn.lock")
+}
+
+pub(crate) fn note_lock_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(".locks").join(format!("{id}.lock"))
+}
+
+/// How long to wait for a lock, from `QUICK_NOTES_LOCK_TIMEOUT` (seconds).
+pub(crate) fn lock_timeout() -> Duration {
+    let secs = std::env::var("QUICK_NOTES_LOCK_TIMEOUT")
+        .ok()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .unwrap_or(DEFAULT_TIMEOUT_SECS);
+    Duration::from_secs_f64(secs)
+}
+
+pub(crate) fn lock_store(dir: &Path, mode: LockMode) -> io::Result<LockGuard> {
+    acquire(&store_lock_path(dir), mode, "the notes store", lock_timeout())
+}
+
+pub(crate) fn git_lock_path(dir: &Path) -> PathBuf {
+    dir.join(".qn-git.lock")
+}
+
+/// Serialize git's stage-and-commit step between processes that share the
+/// store lock.
+pub(crate) fn lock_git(dir: &Path) -> io::Result<LockGuard> {
+    acquire(&git_lock_path(dir), LockMode::Exclusive, "git", lock_timeout())
+}
+
+/// Whether `id` can name a note file: a single path component, so it
+/// cannot point outside the store.
+fn is_valid_note_id(id: &str) -> bool {
+    !id.is_empty() && !id.contains(['/', '\\', '\0']) && !id.contains("..")
+}
+
+/// Lock one note by id; ids are unique across areas so the base dir is used.
+/// The note must exist in some area, so no lock file is left for ids that
+/// do not.
+pub(crate) fn lock_note(dir: &Path, id: &str) -> io::Result<LockGuard> {
+    if !is_valid_note_id(id) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid note id: {id}"),
+        ));
+    }
+    if locate_note(dir, id).is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Note {id} not found"),
+        ));
+    }
+    acquire_note(dir, id)
+}
+
+/// Lock `id` when it names an existing note; `None` lets batch commands
+/// report the id as missing and carry on.
+pub(crate) fn lock_existing_note(
+    dir: &Path,
+    id: &str,
+) -> io::Result<Option<LockGuard>> {
+    match lock_note(dir, id) {
+        Ok(lock) => Ok(Some(lock)),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::InvalidInput
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Lock a freshly generated id before its note file is written.
+pub(crate) fn lock_new_note(dir: &Path, id: &str) -> io::Result<LockGuard> {
+    debug_assert!(is_valid_note_id(id));
+    acquire_note(dir, id)
+}
+
+fn acquire_note(dir: &Path, id: &str) -> io::Result<LockGuard> {
+    acquire(
+        &note_lock_path(dir, id),
+        LockMode::Exclusive,
+        &format!("note {id}"),
+        lock_timeout(),
+    )
+}
+
+fn acquire(
+    path: &Path,
+    mode: LockMode,
+    what: &str,
+    timeout: Duration,
+) -> io::Result<LockGuard> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        let attempt = match mode {
+            LockMode::Shared => file.try_lock_shared(),
+            LockMode::Exclusive => file.try_lock(),
+        };
+        match attempt {
+            Ok(()) => return Ok(LockGuard { _file: file }),
+            Err(TryLockError::Error(e)) => return Err(e),
+            Err(TryLockError::WouldBlock) => {}
+        }
+        if Instant::now() >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "Timed out after {:.1}s waiting for a lock on {what} ({}); \
+another qn command may still be running. Raise QUICK_NOTES_LOCK_TIMEOUT to \
+wait longer.",
+                    timeout.as_secs_f64(),
+                    path.display()
+                ),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn exclusive_lock_times_out_while_held() {
+        let tmp = tempdir().unwrap();
+        let path = note_lock_path(tmp.path(), "n1");
+        let _held =
+            acquire(&path, LockMode::Exclusive, "note n1", Duration::ZERO)
+                .unwrap();
+        let err =
+            acquire(&path, LockMode::Exclusive, "note n1", Duration::ZERO)
+                .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err.to_string().contains("note n1"));
+    }
+
+    #[test]
+    fn shared_locks_coexist_but_block_exclusive() {
+        let tmp = tempdir().unwrap();
+        let path = store_lock_path(tmp.path());
+        let a = acquire(&path, LockMode::Shared, "store", Duration::ZERO);
+        let b = acquire(&path, LockMode::Shared, "store", Duration::ZERO);
+        assert!(a.is_ok() && b.is_ok());
+        assert!(
+            acquire(&path, LockMode::Exclusive, "store", Duration::ZERO)
+                .is_err()
+        );
+        drop((a, b));
+        assert!(
+            acquire(&path, LockMode::Exclusive, "store", Duration::ZERO)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn note_locks_need_a_valid_existing_id() {
+        let tmp = tempdir().unwrap();
+        let store = tmp.path().join("store");
+        std::fs::create_dir_all(&store).unwrap();
+        std::fs::write(store.join("n1.md"), "Title: n1\n---\n").unwrap();
+        assert!(lock_note(&store, "n1").is_ok());
+
+        for id in ["../../escape", "../x", "a/b", "a\\b", "..", ""] {
+            let err = lock_note(&store, id).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{id}");
+            assert!(lock_existing_note(&store, id).unwrap().is_none());
+        }
+        let err = lock_note(&store, "n2").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(lock_existing_note(&store, "n2").unwrap().is_none());
+
+        let locks: Vec<_> = std::fs::read_dir(store.join(".locks"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(locks, vec!["n1.lock"]);
+        assert!(!tmp.path().join("escape.lock").exists());
+    }
+}
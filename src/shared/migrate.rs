@@ -51,6 +51,32 @@ pub(crate) fn resolve_active_note_path(
     None
 }
 
+/// Area and file of note `id`, looking in every area.
+pub(crate) fn locate_note(dir: &Path, id: &str) -> Option<(Area, PathBuf)> {
+    if let Some(path) = resolve_active_note_path(dir, id) {
+        return Some((Area::Active, path));
+    }
+    [Area::Trash, Area::Archive].into_iter().find_map(|area| {
+        let path = note_path(&area_dir(dir, area), id);
+        path.exists().then_some((area, path))
+    })
+}
+
+/// Every note file in the store: active, migrated batches, trash and
+/// archive, sorted by path.
+pub(crate) fn list_store_note_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> =
+        list_active_note_files(dir)?.into_iter().map(|(p, _)| p).collect();
+    for area in [Area::Trash, Area::Archive] {
+        let area_dir = area_dir(dir, area);
+        files.extend(
+            list_note_files_if_exists(&area_dir)?.into_iter().map(|(p, _)| p),
+        );
+    }
+    files.sort();
+    Ok(files)
+}
+
 fn list_note_files_if_exists(dir: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
     if dir.exists() { list_note_files(dir) } else { Ok(Vec::new()) }
 }
@@ -58,21 +84,11 @@ fn list_note_files_if_exists(dir: &Path) -> io::Result<Vec<(PathBuf, u64)>> {
 pub(crate) fn collect_ids_across_areas(
     dir: &Path,
 ) -> io::Result<HashSet<String>> {
-    let mut ids: HashSet<String> = HashSet::new();
-    for (path, _) in list_active_note_files(dir)? {
-        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-            ids.insert(stem.to_string());
-        }
-    }
-    for area in [Area::Trash, Area::Archive] {
-        let area_dir = area_dir(dir, area);
-        for (path, _) in list_note_files_if_exists(&area_dir)? {
-            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                ids.insert(stem.to_string());
-            }
-        }
-    }
-    Ok(ids)
+    Ok(list_store_note_files(dir)?
+        .iter()
+        .filter_map(|path| path.file_stem().and_then(|s| s.to_str()))
+        .map(str::to_string)
+        .collect())
 }
 
 /// Import notes from another directory into a new migrated batch, keeping timestamps.
@@ -1,2 +1,9 @@
+pub mod atomic;
+pub mod diff;
+#[cfg(test)]
+pub mod fixtures;
+pub mod json;
+pub mod lock;
 pub mod migrate;
+pub mod shell;
 pub mod table;
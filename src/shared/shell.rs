@@ -0,0 +1,6 @@
+//! Quoting for the `sh -c` command lines the pickers build.
+
+/// Quote `raw` as one POSIX shell word.
+pub(crate) fn shell_quote(raw: &str) -> String {
+    format!("'{}'", raw.replace('\'', r"'\''"))
+}
@@ -35,6 +35,19 @@ pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
     normalized
 }
 
+/// Split `+tag -tag` edits (bare words add) into tags to add and remove
+pub fn parse_tag_edits(input: &str) -> (Vec<String>, Vec<String>) {
+    let mut add: Vec<String> = Vec::new();
+    let mut remove: Vec<String> = Vec::new();
+    for word in input.split([' ', ',']).filter(|w| !w.is_empty()) {
+        match word.strip_prefix('-') {
+            Some(tag) => remove.push(tag.to_string()),
+            None => add.push(word.trim_start_matches('+').to_string()),
+        }
+    }
+    (normalize_tags(add), normalize_tags(remove))
+}
+
 /// Validate that a note at the given path has all required tags
 pub fn validate_note_tags(
     dir: &Path,
@@ -137,6 +150,13 @@ mod tests {
         assert_eq!(result, vec!["#todo", "#work"]);
     }
 
+    #[test]
+    fn test_parse_tag_edits() {
+        let (add, remove) = parse_tag_edits("+ops work, -draft");
+        assert_eq!(add, vec!["#ops", "#work"]);
+        assert_eq!(remove, vec!["#draft"]);
+    }
+
     #[test]
     fn test_note_has_tags() {
         let note = Note {
@@ -146,8 +166,12 @@ mod tests {
             updated: "now".to_string(),
             deleted_at: None,
             archived_at: None,
+            due: None,
+            remind: None,
             body: "body".to_string(),
             tags: vec!["#todo".to_string(), "#work".to_string()],
+            properties: Default::default(),
+            format: Default::default(),
             size_bytes: 0,
         };
 
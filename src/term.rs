@@ -0,0 +1,406 @@
+//! Raw terminal input and output shared by `qn tui` and the native picker.
+//!
+//! [`Terminal`] talks to `/dev/tty` directly (so stdout stays free for
+//! results), switches raw mode with `stty`, and either takes over the
+//! alternate screen or draws in a fixed number of rows below the cursor the
+//! way `fzf --height` does. Screens are plain `Vec<String>` frames, which the
+//! headless test backend records as text.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use terminal_size::{Height, Width, terminal_size};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Key {
+    Char(char),
+    Ctrl(char),
+    Enter,
+    Esc,
+    Backspace,
+    Tab,
+    BackTab,
+    Up,
+    Down,
+    ShiftUp,
+    ShiftDown,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+}
+
+pub(crate) enum Input {
+    Key(Key),
+    /// Nothing typed within the poll interval.
+    Idle,
+    /// No more input will come.
+    Closed,
+}
+
+/// Where frames go and keys come from.
+pub(crate) trait Backend {
+    /// Columns and rows available.
+    fn size(&self) -> (usize, usize);
+    /// Replace the screen with `lines` (exactly one per row).
+    fn draw(&mut self, lines: &[String]) -> io::Result<()>;
+    fn read_input(&mut self) -> io::Result<Input>;
+    /// Hand the terminal to another program (the editor) and take it back.
+    fn suspend(&mut self) -> io::Result<()>;
+    fn resume(&mut self) -> io::Result<()>;
+}
+
+/// Decode the first key in `bytes`; `None` when more bytes are needed.
+pub(crate) fn decode_key(bytes: &[u8]) -> Option<(Key, usize)> {
+    let first = *bytes.first()?;
+    let key = match first {
+        0x1b => {
+            if !matches!(bytes.get(1), Some(b'[' | b'O')) {
+                return Some((Key::Esc, 1));
+            }
+            let key = match bytes.get(2) {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                Some(b'Z') => Key::BackTab,
+                Some(b'1') if bytes.get(3..5) == Some(b";2") => {
+                    let key = match bytes.get(5) {
+                        Some(b'A') => Key::ShiftUp,
+                        Some(b'B') => Key::ShiftDown,
+                        _ => return Some((Key::Esc, 1)),
+                    };
+                    return Some((key, 6));
+                }
+                Some(code @ (b'5' | b'6')) if bytes.get(3) == Some(&b'~') => {
+                    let key =
+                        if *code == b'5' { Key::PageUp } else { Key::PageDown };
+                    return Some((key, 4));
+                }
+                _ => return Some((Key::Esc, 1)),
+            };
+            return Some((key, 3));
+        }
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        byte @ 0x01..=0x1a => Key::Ctrl((byte + b'a' - 1) as char),
+        byte if byte < 0x80 => Key::Char(byte as char),
+        byte => {
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            let text = std::str::from_utf8(bytes.get(..len)?).ok()?;
+            return Some((Key::Char(text.chars().next()?), len));
+        }
+    };
+    Some((key, 1))
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty")?)
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "stty {} failed",
+            args.join(" ")
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Size of the terminal, or 80x24 when it cannot be read.
+pub(crate) fn screen_size() -> (usize, usize) {
+    match terminal_size() {
+        Some((Width(w), Height(h))) if w > 0 && h > 0 => {
+            (w as usize, h as usize)
+        }
+        _ => (80, 24),
+    }
+}
+
+/// The controlling terminal in raw mode.
+pub(crate) struct Terminal {
+    tty: File,
+    saved: String,
+    pending: Vec<u8>,
+    /// Rows drawn below the cursor; `None` for the alternate screen.
+    inline: Option<usize>,
+}
+
+impl Terminal {
+    /// Take over the whole screen until dropped.
+    pub(crate) fn fullscreen() -> io::Result<Terminal> {
+        Terminal::open(None)
+    }
+
+    /// Draw in `rows` lines below the cursor, leaving the scrollback alone.
+    pub(crate) fn inline(rows: usize) -> io::Result<Terminal> {
+        Terminal::open(Some(rows.clamp(1, screen_size().1)))
+    }
+
+    fn open(inline: Option<usize>) -> io::Result<Terminal> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let saved = stty(&["-g"])?;
+        let mut terminal = Terminal { tty, saved, pending: Vec::new(), inline };
+        terminal.resume()?;
+        Ok(terminal)
+    }
+
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.tty.write_all(text.as_bytes())?;
+        self.tty.flush()
+    }
+}
+
+impl Backend for Terminal {
+    fn size(&self) -> (usize, usize) {
+        let (cols, rows) = screen_size();
+        (cols, self.inline.map_or(rows, |inline| inline.min(rows)))
+    }
+
+    fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        let mut frame =
+            String::from(if self.inline.is_some() { "\r" } else { "\x1b[H" });
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                frame.push_str("\r\n");
+            }
+            frame.push_str(line);
+            frame.push_str("\x1b[0m\x1b[K");
+        }
+        if self.inline.is_some() {
+            // Return to the top of the region for the next frame.
+            if lines.len() > 1 {
+                frame.push_str(&format!("\x1b[{}A", lines.len() - 1));
+            }
+            frame.push('\r');
+        } else {
+            frame.push_str("\x1b[J");
+        }
+        self.write(&frame)
+    }
+
+    fn read_input(&mut self) -> io::Result<Input> {
+        loop {
+            if let Some((key, used)) = decode_key(&self.pending) {
+                self.pending.drain(..used);
+                return Ok(Input::Key(key));
+            }
+            let mut buf = [0u8; 64];
+            // `stty min 0 time 1` makes this return after 100ms of silence.
+            let read = match self.tty.read(&mut buf) {
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => 0,
+                // The terminal went away (e.g. the window was closed).
+                Err(_) => return Ok(Input::Closed),
+            };
+            if read == 0 {
+                self.pending.clear();
+                return Ok(Input::Idle);
+            }
+            self.pending.extend_from_slice(&buf[..read]);
+        }
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        match self.inline {
+            Some(_) => self.write("\r\x1b[J\x1b[?25h")?,
+            None => self.write("\x1b[?25h\x1b[?1049l")?,
+        }
+        stty(&[&self.saved]).map(|_| ())
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        stty(&["raw", "-echo", "min", "0", "time", "1"])?;
+        match self.inline {
+            // Scroll enough blank lines into view, then go back up.
+            Some(rows) if rows > 1 => self.write(&format!(
+                "\x1b[?25l{}\x1b[{}A\r",
+                "\n".repeat(rows - 1),
+                rows - 1
+            )),
+            Some(_) => self.write("\x1b[?25l\r"),
+            None => self.write("\x1b[?1049h\x1b[?25l"),
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.suspend();
+    }
+}
+
+/// Visible text of a line, without ANSI escapes.
+pub(crate) fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for next in chars.by_ref() {
+                if next.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Clip `line` to `width` visible columns (keeping escapes) and pad it.
+pub(crate) fn fit(line: &str, width: usize) -> String {
+    let mut out = String::with_capacity(line.len() + width);
+    let mut visible = 0;
+    let mut styled = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            styled = true;
+            out.push(c);
+            for next in chars.by_ref() {
+                out.push(next);
+                if next.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if visible == width {
+            break;
+        }
+        out.push(if c == '\t' { ' ' } else { c });
+        visible += 1;
+    }
+    if styled {
+        out.push_str("\x1b[0m");
+    }
+    out.push_str(&" ".repeat(width - visible));
+    out
+}
+
+/// Split `line` into pieces of at most `width` visible columns.
+pub(crate) fn wrap(line: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut pieces = vec![String::new()];
+    let mut visible = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        let piece = pieces.last_mut().expect("at least one piece");
+        if c == '\x1b' {
+            piece.push(c);
+            for next in chars.by_ref() {
+                piece.push(next);
+                if next.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if visible == width {
+            pieces.push(c.to_string());
+            visible = 1;
+            continue;
+        }
+        piece.push(c);
+        visible += 1;
+    }
+    pieces
+}
+
+/// Selected rows: the plain text in reverse video.
+pub(crate) fn reverse(line: &str, width: usize) -> String {
+    format!("\x1b[7m{}\x1b[0m", fit(&strip_ansi(line), width))
+}
+
+/// Scripted keys in, plain-text frames out.
+#[cfg(test)]
+pub(crate) struct Headless {
+    cols: usize,
+    rows: usize,
+    keys: std::collections::VecDeque<Key>,
+    pub(crate) frames: Vec<Vec<String>>,
+}
+
+#[cfg(test)]
+impl Headless {
+    pub(crate) fn new(cols: usize, rows: usize, keys: &[Key]) -> Self {
+        Headless {
+            cols,
+            rows,
+            keys: keys.iter().copied().collect(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Characters of `text` as key presses.
+    pub(crate) fn typed(text: &str) -> Vec<Key> {
+        text.chars().map(Key::Char).collect()
+    }
+
+    pub(crate) fn last(&self) -> String {
+        self.frames.last().map(|f| f.join("\n")).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+impl Backend for Headless {
+    fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        assert_eq!(lines.len(), self.rows);
+        self.frames.push(lines.iter().map(|l| strip_ansi(l)).collect());
+        Ok(())
+    }
+
+    fn read_input(&mut self) -> io::Result<Input> {
+        Ok(self.keys.pop_front().map_or(Input::Closed, Input::Key))
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_keys_and_escape_sequences() {
+        assert_eq!(decode_key(b"\x1b[A"), Some((Key::Up, 3)));
+        assert_eq!(decode_key(b"\x1b[6~x"), Some((Key::PageDown, 4)));
+        assert_eq!(decode_key(b"\x1b[Z"), Some((Key::BackTab, 3)));
+        assert_eq!(decode_key(b"\x1b[1;2B"), Some((Key::ShiftDown, 6)));
+        assert_eq!(decode_key(b"\x1b"), Some((Key::Esc, 1)));
+        assert_eq!(decode_key(b"\x03"), Some((Key::Ctrl('c'), 1)));
+        assert_eq!(decode_key("é".as_bytes()), Some((Key::Char('é'), 2)));
+        assert_eq!(decode_key(&"é".as_bytes()[..1]), None);
+    }
+
+    #[test]
+    fn fits_and_wraps_styled_lines() {
+        assert_eq!(fit("\x1b[1mabcdef\x1b[0m", 3), "\x1b[1mabc\x1b[0m");
+        assert_eq!(fit("ab", 4), "ab  ");
+        assert_eq!(wrap("abcde", 2), vec!["ab", "cd", "e"]);
+        assert_eq!(wrap("\x1b[1mabc", 2), vec!["\x1b[1mab", "c"]);
+        assert_eq!(wrap("", 3), vec![""]);
+    }
+}
@@ -0,0 +1,325 @@
+//! Markdown checkbox tasks across notes: `qn todos`, `qn done` and
+//! `qn undo-task`.
+//!
+//! Tasks are addressed as `<id>:<line>`, the line being the file line so it
+//! also works with `$EDITOR +line`. Toggling rewrites the checkbox in the
+//! note body and saves through `write_note` like any other edit.
+
+use crate::agenda::resolve_when;
+use crate::note::{Note, parse_note_text, timestamp_string, write_note};
+use crate::shared::lock::lock_note;
+use crate::shared::migrate::resolve_active_note_path;
+use crate::shared::table::{display_len, render_table, truncate_with_ellipsis};
+use crate::{Area, args, parse_list_options, select_notes, terminal_columns};
+use regex::Regex;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use yansi::Paint;
+
+#[derive(Debug, PartialEq)]
+struct Task {
+    /// 1-based line in the note file.
+    line: usize,
+    done: bool,
+    /// Byte offset of the checkbox mark (` `/`x`) in the note body.
+    mark: usize,
+    /// Task text without the `@due(...)` marker.
+    text: String,
+    due: Option<String>,
+}
+
+fn task_regex() -> &'static Regex {
+    static TASK: OnceLock<Regex> = OnceLock::new();
+    TASK.get_or_init(|| {
+        Regex::new(r"^\s*(?:[-*+]|\d+[.)])\s+\[([ xX])\](?:\s+(.*))?$")
+            .expect("valid regex")
+    })
+}
+
+fn due_regex() -> &'static Regex {
+    static DUE: OnceLock<Regex> = OnceLock::new();
+    DUE.get_or_init(|| Regex::new(r"@due\(([^)]*)\)").expect("valid regex"))
+}
+
+/// Lines of `raw` before `body` (the header), so body lines can be
+/// numbered as file lines.
+fn header_lines(raw: &str, body: &str) -> usize {
+    raw[..raw.len().saturating_sub(body.len())].matches('\n').count()
+}
+
+/// Checkbox list items in a note body, skipping fenced code blocks.
+fn extract_tasks(body: &str, first_line: usize) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut start = 0;
+    for (i, line) in body.split_inclusive('\n').enumerate() {
+        let offset = start;
+        start += line.len();
+        let line = line.trim_end_matches(['\n', '\r']);
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(marker) =
+            ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m))
+        {
+            fence = Some(marker);
+            continue;
+        }
+        let Some(caps) = task_regex().captures(line) else { continue };
+        let mark = caps.get(1).expect("checkbox group");
+        let raw_text = caps.get(2).map_or("", |m| m.as_str());
+        let due = due_regex()
+            .captures(raw_text)
+            .map(|c| c[1].trim().to_string())
+            .filter(|d| !d.is_empty());
+        let text = due_regex().replace_all(raw_text, "");
+        tasks.push(Task {
+            line: first_line + i,
+            done: mark.as_str() != " ",
+            mark: offset + mark.start(),
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            due,
+        });
+    }
+    tasks
+}
+
+/// A note read straight from its file, with its tasks.
+fn note_tasks(
+    dir: &Path,
+    id: &str,
+) -> Result<(Note, Vec<Task>), Box<dyn Error>> {
+    let path = resolve_active_note_path(dir, id)
+        .ok_or_else(|| format!("Note {id} not found"))?;
+    let raw = fs::read_to_string(&path)?;
+    let note = parse_note_text(id.to_string(), &raw, raw.len() as u64);
+    let tasks = extract_tasks(&note.body, header_lines(&raw, &note.body) + 1);
+    Ok((note, tasks))
+}
+
+fn due_label(due: &str, note: &Note) -> String {
+    match resolve_when(due, note) {
+        Some(dt) => dt.format("%a %d%b %H:%M").to_string(),
+        None => due.to_string(),
+    }
+}
+
+/// Handle `qn todos [-t tag]... [--done|--open]`.
+pub(crate) fn list_todos(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut list_args = Vec::new();
+    let mut want: Option<bool> = None;
+    let mut parser = args::ArgParser::new(args, "todos");
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "-t" | "--tag" => {
+                if let Some(tag) = parser.extract_tag()? {
+                    list_args.extend(["-t".to_string(), tag]);
+                }
+            }
+            "--done" => want = Some(true),
+            "--open" => want = Some(false),
+            other => {
+                return Err(format!(
+                    "Unknown argument for todos: {other}\n\
+Usage: qn todos [-t tag] [--done|--open]"
+                )
+                .into());
+            }
+        }
+    }
+
+    let use_color = env::var("NO_COLOR").is_err();
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let notes =
+        select_notes(dir, Area::Active, &parse_list_options(list_args)?)?;
+    for listed in notes {
+        let Ok((note, tasks)) = note_tasks(dir, &listed.id) else { continue };
+        for task in tasks {
+            if want.is_some_and(|done| done != task.done) {
+                continue;
+            }
+            let target = format!("{}:{}", note.id, task.line);
+            let due = task
+                .due
+                .as_deref()
+                .map(|d| due_label(d, &note))
+                .unwrap_or_default();
+            rows.push(if use_color {
+                vec![
+                    Paint::rgb(&target, 108, 112, 134).to_string(),
+                    if task.done {
+                        Paint::rgb("[x]", 166, 227, 161).to_string()
+                    } else {
+                        "[ ]".to_string()
+                    },
+                    task.text,
+                    note.title.clone(),
+                    Paint::rgb(&due, 137, 180, 250).to_string(),
+                ]
+            } else {
+                vec![
+                    target,
+                    if task.done { "[x]" } else { "[ ]" }.to_string(),
+                    task.text,
+                    note.title.clone(),
+                    due,
+                ]
+            });
+        }
+    }
+    if rows.is_empty() {
+        println!("No tasks found.");
+        return Ok(());
+    }
+
+    // Give the task text whatever the other columns leave over.
+    let width = terminal_columns().unwrap_or(120);
+    let others: usize = [0, 1, 3, 4]
+        .iter()
+        .map(|&col| {
+            rows.iter().map(|r| display_len(&r[col])).max().unwrap_or(0).max(5)
+        })
+        .sum();
+    let text_width = width.saturating_sub(others + 4 * 3).max(20);
+    for row in &mut rows {
+        row[2] = truncate_with_ellipsis(&row[2], text_width);
+    }
+    let headers: Vec<String> =
+        ["Task", "Done", "Text", "Note", "Due"].map(String::from).to_vec();
+    println!("{}", render_table(&headers, &rows));
+    Ok(())
+}
+
+/// Check or uncheck the task at `<id>:<line>`.
+fn set_task(
+    dir: &Path,
+    target: &str,
+    done: bool,
+) -> Result<String, Box<dyn Error>> {
+    let (id, line) = target
+        .rsplit_once(':')
+        .and_then(|(id, line)| Some((id, line.parse::<usize>().ok()?)))
+        .ok_or_else(|| {
+            format!(
+                "Expected a task as <id>:<line> (see `qn todos`), got {target}"
+            )
+        })?;
+    let _lock = lock_note(dir, id)?;
+    let path = resolve_active_note_path(dir, id)
+        .ok_or_else(|| format!("Note {id} not found"))?;
+    let (mut note, tasks) = note_tasks(dir, id)?;
+    let task = tasks
+        .iter()
+        .find(|t| t.line == line)
+        .ok_or_else(|| format!("Line {line} of {id} is not a task"))?;
+    if task.done == done {
+        let state = if done { "done" } else { "open" };
+        return Err(format!("Task {target} is already {state}").into());
+    }
+    let mark = if done { "x" } else { " " };
+    note.body.replace_range(task.mark..task.mark + 1, mark);
+    note.updated = timestamp_string();
+    write_note(&note, path.parent().unwrap_or(dir))?;
+    let verb = if done { "Done" } else { "Reopened" };
+    Ok(format!("{verb} {target}: {}", task.text))
+}
+
+fn set_tasks(
+    args: Vec<String>,
+    dir: &Path,
+    done: bool,
+    usage: &str,
+) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() {
+        return Err(usage.into());
+    }
+    for target in &args {
+        println!("{}", set_task(dir, target, done)?);
+    }
+    Ok(())
+}
+
+/// Handle `qn done <id>:<line>...`.
+pub(crate) fn done(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    set_tasks(args, dir, true, "Usage: qn done <id>:<line>...")
+}
+
+/// Handle `qn undo-task <id>:<line>...`.
+pub(crate) fn undo(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    set_tasks(args, dir, false, "Usage: qn undo-task <id>:<line>...")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::fixtures;
+
+    #[test]
+    fn extracts_tasks_with_lines_and_due_markers() {
+        let raw = fixtures::note_text(
+            "Plan",
+            fixtures::CREATED,
+            "#todo",
+            "- [ ] pay rent @due(fri)\n  * [x] call bank\n\
+```\n- [ ] not a task\n```\n1. [X] numbered\n- [] nope\n+ [ ]\n",
+        );
+        let note = parse_note_text("n1".into(), &raw, 0);
+        let first = header_lines(&raw, &note.body) + 1;
+        assert_eq!(first, 6);
+        let tasks = extract_tasks(&note.body, first);
+        let summary: Vec<(usize, bool, &str, Option<&str>)> = tasks
+            .iter()
+            .map(|t| (t.line, t.done, t.text.as_str(), t.due.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (6, false, "pay rent", Some("fri")),
+                (7, true, "call bank", None),
+                (11, true, "numbered", None),
+                (13, false, "", None),
+            ]
+        );
+        assert_eq!(&note.body[tasks[1].mark..tasks[1].mark + 1], "x");
+    }
+
+    #[test]
+    fn toggles_a_checkbox_in_place() {
+        let tmp = tempfile::tempdir().unwrap();
+        fixtures::write_note(
+            tmp.path(),
+            "n1",
+            "Plan",
+            "",
+            "intro\n- [ ] ship it\n",
+        );
+        assert_eq!(
+            set_task(tmp.path(), "n1:7", true).unwrap(),
+            "Done n1:7: ship it"
+        );
+        let text = fs::read_to_string(tmp.path().join("n1.md")).unwrap();
+        assert!(text.contains("\n- [x] ship it\n"), "{text}");
+        assert!(!text.contains("Updated: 01Jan24"), "{text}");
+        assert!(set_task(tmp.path(), "n1:7", true).is_err());
+        assert!(set_task(tmp.path(), "n1:6", true).is_err());
+        set_task(tmp.path(), "n1:7", false).unwrap();
+        let text = fs::read_to_string(tmp.path().join("n1.md")).unwrap();
+        assert!(text.contains("\n- [ ] ship it\n"), "{text}");
+    }
+}
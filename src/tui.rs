@@ -0,0 +1,692 @@
+//! `qn tui`: a full-screen browser for the notes store.
+//!
+//! The left pane is the `qn list` table for the current area, the right pane
+//! a rendered preview of the selected note. Search filters as you type, tag
+//! filters toggle from a tag pane, and Tab cycles active/archive/trash. Edit,
+//! archive, delete, restore and retag act on the selected note with the same
+//! locks and git autocommits as the CLI.
+//!
+//! Drawing and input go through `term::Backend`: the real terminal takes
+//! over the alternate screen; tests drive [`App`] with the headless backend
+//! and check the recorded frames.
+
+use crate::note::{Note, note_path, parse_note};
+use crate::render::render_markdown_width;
+use crate::shared::lock::{LockMode, lock_note, lock_store};
+use crate::shared::migrate::resolve_active_note_path;
+use crate::shared::table::truncate_with_ellipsis;
+use crate::term::{Backend, Input, Key, Terminal, fit, reverse};
+use crate::{
+    Area, area_dir, edit_note, git, list_table_lines, move_note_with_timestamp,
+    parse_list_options, restore_note, retag_note, select_notes, tags, ui,
+};
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+/// Idle polls (about 100ms each) between checks for changes on disk.
+const REFRESH_TICKS: u32 = 10;
+const HELP: &str = "j/k move  / search  t tags  Tab area  e edit  a archive  \
+d delete  r restore  + tag  J/K scroll  q quit";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Browse,
+    Search,
+    Tags { cursor: usize },
+    Retag(String),
+}
+
+/// What the run loop should do after a key.
+#[derive(Debug, PartialEq)]
+enum Step {
+    Continue,
+    Edit(String),
+    Quit,
+}
+
+struct App {
+    dir: PathBuf,
+    area: Area,
+    use_color: bool,
+    notes: Vec<Note>,
+    /// Tags of every note in the area, with counts.
+    tags: Vec<(String, usize)>,
+    selected: usize,
+    list_top: usize,
+    preview_top: usize,
+    search: String,
+    tag_filters: Vec<String>,
+    mode: Mode,
+    status: String,
+}
+
+impl App {
+    fn new(dir: &Path, use_color: bool) -> Result<App, Box<dyn Error>> {
+        let mut app = App {
+            dir: dir.to_path_buf(),
+            area: Area::Active,
+            use_color,
+            notes: Vec::new(),
+            tags: Vec::new(),
+            selected: 0,
+            list_top: 0,
+            preview_top: 0,
+            search: String::new(),
+            tag_filters: Vec::new(),
+            mode: Mode::Browse,
+            status: String::new(),
+        };
+        app.reload()?;
+        Ok(app)
+    }
+
+    /// Re-read the area, keeping the selected note when it is still listed.
+    fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        let keep = self.current().map(|n| n.id.clone());
+        let all =
+            select_notes(&self.dir, self.area, &parse_list_options(vec![])?)?;
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for note in &all {
+            for tag in &note.tags {
+                *counts.entry(tag.clone()).or_default() += 1;
+            }
+        }
+        self.tags = counts.into_iter().collect();
+        self.tag_filters.retain(|t| self.tags.iter().any(|(tag, _)| tag == t));
+
+        let mut filters: Vec<String> = Vec::new();
+        if !self.search.trim().is_empty() {
+            filters.extend(["-s".to_string(), self.search.trim().to_string()]);
+        }
+        for tag in &self.tag_filters {
+            filters.extend(["-t".to_string(), tag.clone()]);
+        }
+        self.notes = if filters.is_empty() {
+            all
+        } else {
+            select_notes(&self.dir, self.area, &parse_list_options(filters)?)?
+        };
+        self.selected = keep
+            .and_then(|id| self.notes.iter().position(|n| n.id == id))
+            .unwrap_or(self.selected)
+            .min(self.notes.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn current(&self) -> Option<&Note> {
+        self.notes.get(self.selected)
+    }
+
+    fn select(&mut self, index: usize) {
+        let index = index.min(self.notes.len().saturating_sub(1));
+        if index != self.selected {
+            self.selected = index;
+            self.preview_top = 0;
+        }
+    }
+
+    fn area_name(&self) -> &'static str {
+        match self.area {
+            Area::Active => "active",
+            Area::Archive => "archive",
+            Area::Trash => "trash",
+        }
+    }
+
+    /// Handle one key; errors from actions end up in the status line.
+    fn handle_key(&mut self, key: Key, page: usize) -> Step {
+        match self.key(key, page) {
+            Ok(step) => step,
+            Err(e) => {
+                self.status = e.to_string();
+                Step::Continue
+            }
+        }
+    }
+
+    fn key(&mut self, key: Key, page: usize) -> Result<Step, Box<dyn Error>> {
+        self.status.clear();
+        match self.mode.clone() {
+            Mode::Search => {
+                match key {
+                    Key::Enter => self.mode = Mode::Browse,
+                    Key::Esc => {
+                        self.search.clear();
+                        self.mode = Mode::Browse;
+                    }
+                    Key::Backspace => {
+                        self.search.pop();
+                    }
+                    Key::Char(c) => self.search.push(c),
+                    _ => return Ok(Step::Continue),
+                }
+                self.reload()?;
+                return Ok(Step::Continue);
+            }
+            Mode::Tags { cursor } => {
+                let last = self.tags.len().saturating_sub(1);
+                match key {
+                    Key::Char('j') | Key::Down => {
+                        self.mode =
+                            Mode::Tags { cursor: (cursor + 1).min(last) }
+                    }
+                    Key::Char('k') | Key::Up => {
+                        self.mode =
+                            Mode::Tags { cursor: cursor.saturating_sub(1) }
+                    }
+                    Key::Char(' ') | Key::Enter => {
+                        if let Some((tag, _)) = self.tags.get(cursor) {
+                            let tag = tag.clone();
+                            match self
+                                .tag_filters
+                                .iter()
+                                .position(|t| *t == tag)
+                            {
+                                Some(i) => {
+                                    self.tag_filters.remove(i);
+                                }
+                                None => self.tag_filters.push(tag),
+                            }
+                            self.reload()?;
+                        }
+                    }
+                    Key::Char('c') => {
+                        self.tag_filters.clear();
+                        self.reload()?;
+                    }
+                    Key::Esc | Key::Char('t') | Key::Char('q') => {
+                        self.mode = Mode::Browse
+                    }
+                    _ => {}
+                }
+                return Ok(Step::Continue);
+            }
+            Mode::Retag(mut input) => {
+                match key {
+                    Key::Enter => {
+                        self.mode = Mode::Browse;
+                        self.retag(&input)?;
+                    }
+                    Key::Esc => self.mode = Mode::Browse,
+                    Key::Backspace => {
+                        input.pop();
+                        self.mode = Mode::Retag(input);
+                    }
+                    Key::Char(c) => {
+                        input.push(c);
+                        self.mode = Mode::Retag(input);
+                    }
+                    _ => {}
+                }
+                return Ok(Step::Continue);
+            }
+            Mode::Browse => {}
+        }
+
+        match key {
+            Key::Char('q') | Key::Ctrl('c') => return Ok(Step::Quit),
+            Key::Char('j') | Key::Down => self.select(self.selected + 1),
+            Key::Char('k') | Key::Up => {
+                self.select(self.selected.saturating_sub(1))
+            }
+            Key::Char('g') | Key::Home => self.select(0),
+            Key::Char('G') | Key::End => self.select(usize::MAX),
+            Key::PageDown => self.select(self.selected + page),
+            Key::PageUp => self.select(self.selected.saturating_sub(page)),
+            Key::Char('J') => self.preview_top += 1,
+            Key::Char('K') => {
+                self.preview_top = self.preview_top.saturating_sub(1)
+            }
+            Key::Char('/') => self.mode = Mode::Search,
+            Key::Esc => {
+                self.search.clear();
+                self.tag_filters.clear();
+                self.reload()?;
+            }
+            Key::Char('t') => {
+                if self.tags.is_empty() {
+                    self.status = format!("No tags in {}", self.area_name());
+                } else {
+                    self.mode = Mode::Tags { cursor: 0 };
+                }
+            }
+            Key::Tab | Key::Char('1') | Key::Char('2') | Key::Char('3') => {
+                self.area = match key {
+                    Key::Char('1') => Area::Active,
+                    Key::Char('2') => Area::Archive,
+                    Key::Char('3') => Area::Trash,
+                    _ => match self.area {
+                        Area::Active => Area::Archive,
+                        Area::Archive => Area::Trash,
+                        Area::Trash => Area::Active,
+                    },
+                };
+                self.selected = 0;
+                self.list_top = 0;
+                self.preview_top = 0;
+                self.tag_filters.clear();
+                self.reload()?;
+            }
+            Key::Char('e') | Key::Enter => {
+                let id = self.active_selection("edit")?;
+                return Ok(Step::Edit(id));
+            }
+            Key::Char('a') => {
+                let id = self.active_selection("archive")?;
+                self.move_selected(&id, Area::Archive)?;
+                self.status = format!("Archived {id}");
+            }
+            Key::Char('d') => {
+                let id = self.active_selection("delete")?;
+                self.move_selected(&id, Area::Trash)?;
+                self.status = format!("Moved {id} to the trash");
+            }
+            Key::Char('r') => self.restore()?,
+            Key::Char('+') | Key::Char('#') => {
+                self.active_selection("tag")?;
+                self.mode = Mode::Retag(String::new());
+            }
+            Key::Char('?') => self.status = HELP.to_string(),
+            _ => {}
+        }
+        Ok(Step::Continue)
+    }
+
+    /// Id of the selected note when the action works on active notes.
+    fn active_selection(&self, action: &str) -> Result<String, Box<dyn Error>> {
+        let note = self.current().ok_or("No note selected")?;
+        if self.area != Area::Active {
+            return Err(format!(
+                "Only active notes can be {}; press r to restore it first",
+                match action {
+                    "edit" => "edited",
+                    "archive" => "archived",
+                    "delete" => "deleted",
+                    _ => "tagged",
+                }
+            )
+            .into());
+        }
+        Ok(note.id.clone())
+    }
+
+    fn move_selected(
+        &mut self,
+        id: &str,
+        area: Area,
+    ) -> Result<(), Box<dyn Error>> {
+        {
+            let _store = lock_store(&self.dir, LockMode::Shared)?;
+            let _note = lock_note(&self.dir, id)?;
+            let to_dir = area_dir(&self.dir, area);
+            move_note_with_timestamp(&self.dir, &to_dir, id, area)?;
+        }
+        let verb = if area == Area::Trash { "delete" } else { "archive" };
+        git::autocommit(&self.dir, verb);
+        self.reload()
+    }
+
+    fn restore(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.area == Area::Active {
+            return Err("Restore works in the archive and trash".into());
+        }
+        let id = self.current().ok_or("No note selected")?.id.clone();
+        let restored = {
+            let _store = lock_store(&self.dir, LockMode::Shared)?;
+            let _note = lock_note(&self.dir, &id)?;
+            restore_note(&id, &area_dir(&self.dir, self.area), &self.dir)?
+        };
+        let verb =
+            if self.area == Area::Trash { "undelete" } else { "unarchive" };
+        git::autocommit(&self.dir, verb);
+        self.status = if restored == id {
+            format!("Restored {id}")
+        } else {
+            format!("Restored {id} as {restored}")
+        };
+        self.reload()
+    }
+
+    /// Apply `+tag`/`-tag` words (bare words add) to the selected note.
+    fn retag(&mut self, input: &str) -> Result<(), Box<dyn Error>> {
+        let id = self.active_selection("tag")?;
+        let (add, remove) = tags::parse_tag_edits(input);
+        if add.is_empty() && remove.is_empty() {
+            return Ok(());
+        }
+        let note = {
+            let _store = lock_store(&self.dir, LockMode::Shared)?;
+            retag_note(&self.dir, &id, &add, &remove)?
+        };
+        git::autocommit(&self.dir, "tag");
+        self.status = format!("Tags of {id}: {}", note.tags.join(" "));
+        self.reload()
+    }
+
+    fn edit(
+        &mut self,
+        id: &str,
+        backend: &mut impl Backend,
+    ) -> Result<(), Box<dyn Error>> {
+        backend.suspend()?;
+        let edited = {
+            let _store = lock_store(&self.dir, LockMode::Shared)?;
+            edit_note(vec![id.to_string()], &self.dir)
+        };
+        backend.resume()?;
+        edited?;
+        git::autocommit(&self.dir, "edit");
+        self.status = format!("Edited {id}");
+        self.reload()
+    }
+
+    fn note_file(&self, id: &str) -> Option<PathBuf> {
+        match self.area {
+            Area::Active => resolve_active_note_path(&self.dir, id),
+            area => Some(note_path(&area_dir(&self.dir, area), id)),
+        }
+    }
+
+    fn preview_lines(&self, width: usize) -> Vec<String> {
+        let Some(listed) = self.current() else {
+            return Vec::new();
+        };
+        let Some(note) = self.note_file(&listed.id).and_then(|path| {
+            let size = fs::metadata(&path).ok()?.len();
+            parse_note(&path, size).ok()
+        }) else {
+            return vec![format!("Could not read note {}", listed.id)];
+        };
+        let mut lines = vec![if self.use_color {
+            format!("\x1b[1m{}\x1b[0m", note.title)
+        } else {
+            note.title.clone()
+        }];
+        let mut meta = format!("{} · updated {}", note.id, note.updated);
+        if !note.tags.is_empty() {
+            meta.push_str(&format!(" · {}", note.tags.join(" ")));
+        }
+        lines.push(if self.use_color {
+            format!("\x1b[2m{meta}\x1b[0m")
+        } else {
+            meta
+        });
+        lines.push(String::new());
+        let body = if self.use_color {
+            render_markdown_width(&note.body, width.max(20))
+        } else {
+            note.body.clone()
+        };
+        lines.extend(body.lines().map(String::from));
+        lines
+    }
+
+    fn list_pane(&mut self, width: usize, height: usize) -> Vec<String> {
+        if let Mode::Tags { cursor } = self.mode {
+            let mut lines = vec![
+                "Tag filters (space toggles, c clears, Esc closes)".into(),
+            ];
+            lines.push("=".repeat(width));
+            let visible = height.saturating_sub(2).max(1);
+            let top = cursor.saturating_sub(visible - 1);
+            for (i, (tag, count)) in
+                self.tags.iter().enumerate().skip(top).take(visible)
+            {
+                let mark =
+                    if self.tag_filters.contains(tag) { "[x]" } else { "[ ]" };
+                let line = format!("{mark} {tag} ({count})");
+                lines.push(if i == cursor {
+                    reverse(&line, width)
+                } else {
+                    line
+                });
+            }
+            return lines;
+        }
+        if self.notes.is_empty() {
+            return vec![match (self.area, self.search.is_empty()) {
+                (Area::Active, true) if self.tag_filters.is_empty() => {
+                    "No notes yet. Try `qn add \"text\"`.".to_string()
+                }
+                _ => format!("No {} notes match.", self.area_name()),
+            }];
+        }
+        let search = Some(self.search.trim()).filter(|s| !s.is_empty());
+        let table = list_table_lines(
+            &self.notes,
+            self.area,
+            search,
+            false,
+            width,
+            self.use_color,
+        );
+        let rows = height.saturating_sub(2).max(1);
+        if self.selected < self.list_top {
+            self.list_top = self.selected;
+        } else if self.selected >= self.list_top + rows {
+            self.list_top = self.selected + 1 - rows;
+        }
+        let mut lines: Vec<String> = table.iter().take(2).cloned().collect();
+        for (i, line) in
+            table.iter().skip(2).enumerate().skip(self.list_top).take(rows)
+        {
+            lines.push(if i == self.selected {
+                reverse(line, width)
+            } else {
+                line.clone()
+            });
+        }
+        lines
+    }
+
+    /// One frame of `cols` x `rows`.
+    fn render(&mut self, cols: usize, rows: usize) -> Vec<String> {
+        let areas =
+            [
+                (Area::Active, "Notes"),
+                (Area::Archive, "Archive"),
+                (Area::Trash, "Trash"),
+            ]
+            .map(|(area, name)| {
+                if area == self.area {
+                    format!("[{name}]")
+                } else {
+                    name.into()
+                }
+            })
+            .join(" ");
+        let mut title = format!(" qn  {areas}  {} shown", self.notes.len());
+        if !self.search.is_empty() {
+            title.push_str(&format!("  /{}", self.search));
+        }
+        if !self.tag_filters.is_empty() {
+            title.push_str(&format!("  {}", self.tag_filters.join(" ")));
+        }
+
+        let body_rows = rows.saturating_sub(2);
+        let left = (cols * 45 / 100).max(20).min(cols);
+        let right = cols.saturating_sub(left + 1);
+        let list = self.list_pane(left, body_rows);
+        let preview: Vec<String> = self
+            .preview_lines(right.saturating_sub(1))
+            .into_iter()
+            .skip(self.preview_top)
+            .collect();
+
+        let mut frame = vec![reverse(&title, cols)];
+        for row in 0..body_rows {
+            let mut line = fit(list.get(row).map_or("", String::as_str), left);
+            if right > 0 {
+                line.push('│');
+                let text = preview.get(row).map_or("", String::as_str);
+                line.push_str(&fit(&format!(" {text}"), right));
+            }
+            frame.push(line);
+        }
+        let status = match &self.mode {
+            Mode::Search => format!("/{}█", self.search),
+            Mode::Retag(input) => {
+                format!("Tags (+add -remove): {input}█")
+            }
+            _ if !self.status.is_empty() => self.status.clone(),
+            _ => HELP.to_string(),
+        };
+        frame.push(fit(&truncate_with_ellipsis(&status, cols), cols));
+        frame.truncate(rows);
+        frame
+    }
+
+    /// Draw and handle keys until the user quits or input ends.
+    fn run(
+        &mut self,
+        backend: &mut impl Backend,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut version = ui::fingerprint(&self.dir)?;
+        let mut idle = 0;
+        let mut dirty = true;
+        loop {
+            let (cols, rows) = backend.size();
+            if dirty {
+                backend.draw(&self.render(cols, rows))?;
+                dirty = false;
+            }
+            match backend.read_input()? {
+                Input::Closed => return Ok(()),
+                Input::Idle => {
+                    idle += 1;
+                    if idle >= REFRESH_TICKS {
+                        idle = 0;
+                        // Pick up edits made outside the TUI.
+                        let now = ui::fingerprint(&self.dir)?;
+                        if now != version {
+                            version = now;
+                            self.reload()?;
+                            dirty = true;
+                        }
+                    }
+                }
+                Input::Key(key) => {
+                    dirty = true;
+                    match self.handle_key(key, rows.saturating_sub(4).max(1)) {
+                        Step::Continue => {}
+                        Step::Quit => return Ok(()),
+                        Step::Edit(id) => {
+                            if let Err(e) = self.edit(&id, backend) {
+                                self.status = e.to_string();
+                            }
+                        }
+                    }
+                    version = ui::fingerprint(&self.dir)?;
+                }
+            }
+        }
+    }
+}
+
+/// Handle `qn tui`.
+pub(crate) fn run_tui(
+    args: Vec<String>,
+    dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(arg) = args.first() {
+        return Err(
+            format!("Unknown argument for tui: {arg}\nUsage: qn tui").into()
+        );
+    }
+    let use_color = env::var("NO_COLOR").is_err();
+    let mut app = App::new(dir, use_color)?;
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err("qn tui needs an interactive terminal".into());
+    }
+    let mut terminal = Terminal::fullscreen()?;
+    app.run(&mut terminal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::fixtures;
+    use crate::term::Headless;
+
+    fn headless(keys: &[Key]) -> Headless {
+        Headless::new(100, 12, keys)
+    }
+
+    fn write(dir: &Path, id: &str, title: &str, updated: &str, tags: &str) {
+        let body = format!("# {title}\n\nBody of {id}.\n");
+        let text = fixtures::note_text(title, updated, tags, &body);
+        fs::write(dir.join(format!("{id}.md")), text).unwrap();
+    }
+
+    fn store() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        write(tmp.path(), "n1", "Roadmap", "03Jan24 10:00 +00:00", "#work");
+        write(tmp.path(), "n2", "Groceries", "02Jan24 10:00 +00:00", "#home");
+        write(tmp.path(), "n3", "Planning", "01Jan24 10:00 +00:00", "#work");
+        tmp
+    }
+
+    #[test]
+    fn browses_searches_and_filters() {
+        let tmp = store();
+        let mut app = App::new(tmp.path(), false).unwrap();
+        let mut script = vec![Key::Char('j')];
+        script.push(Key::Char('/'));
+        script.extend(Headless::typed("plan"));
+        script.push(Key::Enter);
+        let mut backend = headless(&script);
+        app.run(&mut backend).unwrap();
+
+        let first = backend.frames[0].join("\n");
+        assert!(first.contains("[Notes] Archive Trash  3 shown"), "{first}");
+        assert!(first.contains("│ Roadmap"), "{first}");
+        let moved = backend.frames[1].join("\n");
+        assert!(moved.contains("│ Groceries"), "{moved}");
+        let searched = backend.last();
+        assert!(searched.contains("1 shown  /plan"), "{searched}");
+        assert!(searched.contains("n3") && !searched.contains("n1 "));
+
+        let mut script = vec![Key::Esc, Key::Char('t'), Key::Char(' ')];
+        script.push(Key::Esc);
+        let mut backend = headless(&script);
+        app.run(&mut backend).unwrap();
+        let filtered = backend.last();
+        assert!(filtered.contains("1 shown  #home"), "{filtered}");
+        assert!(filtered.contains("│ Groceries"), "{filtered}");
+    }
+
+    #[test]
+    fn archives_restores_deletes_and_retags() {
+        let tmp = store();
+        let mut app = App::new(tmp.path(), false).unwrap();
+        let mut backend = headless(&[Key::Char('a')]);
+        app.run(&mut backend).unwrap();
+        assert!(tmp.path().join("archive/n1.md").exists());
+        assert!(backend.last().contains("Archived n1"), "{}", backend.last());
+
+        let mut script = vec![Key::Char('+')];
+        script.extend(Headless::typed("ops -home"));
+        script.push(Key::Enter);
+        let mut backend = headless(&script);
+        app.run(&mut backend).unwrap();
+        let retagged = fs::read_to_string(tmp.path().join("n2.md")).unwrap();
+        assert!(retagged.contains("Tags: #ops\n"), "{retagged}");
+
+        let mut backend = headless(&[Key::Tab, Key::Char('e')]);
+        app.run(&mut backend).unwrap();
+        assert!(backend.last().contains("Only active notes can be edited"));
+        let mut backend = headless(&[Key::Char('r')]);
+        app.run(&mut backend).unwrap();
+        assert!(tmp.path().join("n1.md").exists());
+        assert!(backend.last().contains("Restored n1"), "{}", backend.last());
+
+        let mut backend = headless(&[Key::Char('1'), Key::Char('d')]);
+        app.run(&mut backend).unwrap();
+        assert_eq!(fs::read_dir(tmp.path().join("trash")).unwrap().count(), 1);
+        assert!(backend.last().contains("to the trash"), "{}", backend.last());
+    }
+}
@@ -0,0 +1,313 @@
+//! `qn serve --ui`: read-only HTML pages for browsing the store.
+//!
+//! `/`, `/archive` and `/trash` list an area with a search box and a tag
+//! sidebar (`?q=` searches titles and bodies, `?tag=` filters); `/n/<id>`
+//! renders one note. Every page polls `/_version`, a fingerprint of the note
+//! files, and reloads when it changes, so edits made anywhere show up.
+//!
+//! Pages share an origin with the write API, so note bodies are rendered
+//! without raw HTML or script URLs, and the reload script carries the nonce
+//! that `serve` allows in its Content-Security-Policy.
+
+use crate::export::{escape_html, markdown_to_safe_html, page_with_head};
+use crate::note::Note;
+use crate::shared::migrate::list_store_note_files;
+use crate::{Area, parse_list_options, select_notes, tags};
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+
+/// How often pages check `/_version`.
+const RELOAD_INTERVAL_MS: u32 = 2000;
+
+const UI_STYLE: &str = r#"<style>
+body { max-width: 68rem; }
+.layout { display: flex; gap: 2rem; align-items: flex-start; }
+.sidebar { flex: 0 0 14rem; position: sticky; top: 1rem; font-size: .9rem; }
+.sidebar nav a { display: block; padding: .1rem 0; }
+.sidebar nav a.current { font-weight: bold; color: inherit; }
+.sidebar input[type=search] { width: 100%; box-sizing: border-box; }
+.sidebar ul { list-style: none; padding: 0; }
+.sidebar li.current a { font-weight: bold; }
+.layout > main { flex: 1; min-width: 0; }
+@media (max-width: 40rem) { .layout { display: block; }
+  .sidebar { position: static; } }
+</style>
+"#;
+
+fn area_url(area: Area) -> &'static str {
+    match area {
+        Area::Active => "/",
+        Area::Archive => "/archive",
+        Area::Trash => "/trash",
+    }
+}
+
+fn area_name(area: Area) -> &'static str {
+    match area {
+        Area::Active => "Notes",
+        Area::Archive => "Archive",
+        Area::Trash => "Trash",
+    }
+}
+
+/// Encode a query-string or path value.
+pub(crate) fn percent_encode(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for byte in raw.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+/// Changes whenever a note file is added, removed or rewritten.
+pub(crate) fn fingerprint(dir: &Path) -> io::Result<String> {
+    let mut hasher = DefaultHasher::new();
+    for path in list_store_note_files(dir)? {
+        let Ok(meta) = fs::metadata(&path) else { continue };
+        path.hash(&mut hasher);
+        meta.len().hash(&mut hasher);
+        meta.modified().ok().hash(&mut hasher);
+    }
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn reload_script(version: &str, nonce: &str) -> String {
+    format!(
+        "<script nonce=\"{nonce}\">\n(function () {{\n  var version = \"{version}\";\n  \
+setInterval(function () {{\n    fetch(\"/_version\", {{ cache: \"no-store\" }})\n      \
+.then(function (r) {{ return r.ok ? r.text() : version; }})\n      \
+.then(function (v) {{ if (v.trim() !== version) location.reload(); }})\n      \
+.catch(function () {{}});\n  }}, {RELOAD_INTERVAL_MS});\n}})();\n</script>\n"
+    )
+}
+
+fn tag_href(area: Area, tag: &str) -> String {
+    format!("{}?tag={}", area_url(area), percent_encode(tag))
+}
+
+fn tag_links(area: Area, tags: &[String]) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+    let links: Vec<String> = tags
+        .iter()
+        .map(|tag| {
+            format!(
+                "<a href=\"{}\">{}</a>",
+                escape_html(&tag_href(area, tag)),
+                escape_html(tag)
+            )
+        })
+        .collect();
+    format!("<p class=\"tags\">{}</p>\n", links.join(""))
+}
+
+/// Area links, the search form and the area's tags with their counts.
+fn sidebar(
+    area: Area,
+    notes: &[Note],
+    search: Option<&str>,
+    tag: Option<&str>,
+) -> String {
+    let mut out = String::from("<aside class=\"sidebar\">\n<nav>\n");
+    for other in [Area::Active, Area::Archive, Area::Trash] {
+        let class = if other == area { " class=\"current\"" } else { "" };
+        out.push_str(&format!(
+            "<a href=\"{}\"{class}>{}</a>\n",
+            area_url(other),
+            area_name(other)
+        ));
+    }
+    out.push_str("</nav>\n");
+    out.push_str(&format!(
+        "<form action=\"{}\" method=\"get\">\n\
+<input type=\"search\" name=\"q\" value=\"{}\" placeholder=\"Search\">\n",
+        area_url(area),
+        escape_html(search.unwrap_or_default())
+    ));
+    if let Some(tag) = tag {
+        out.push_str(&format!(
+            "<input type=\"hidden\" name=\"tag\" value=\"{}\">\n",
+            escape_html(tag)
+        ));
+    }
+    out.push_str("</form>\n");
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for note in notes {
+        for t in &note.tags {
+            *counts.entry(t.as_str()).or_default() += 1;
+        }
+    }
+    if !counts.is_empty() {
+        out.push_str("<h2>Tags</h2>\n<ul>\n");
+        for (name, count) in counts {
+            let class =
+                if Some(name) == tag { " class=\"current\"" } else { "" };
+            out.push_str(&format!(
+                "<li{class}><a href=\"{}\">{}</a> \
+<span class=\"meta\">{count}</span></li>\n",
+                escape_html(&tag_href(area, name)),
+                escape_html(name)
+            ));
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</aside>\n");
+    out
+}
+
+fn layout(
+    dir: &Path,
+    nonce: &str,
+    title: &str,
+    sidebar: &str,
+    main: &str,
+) -> io::Result<String> {
+    let head =
+        format!("{UI_STYLE}{}", reload_script(&fingerprint(dir)?, nonce));
+    let body = format!(
+        "<div class=\"layout\">\n{sidebar}<main>\n{main}\n</main>\n</div>"
+    );
+    Ok(page_with_head(title, &head, &body))
+}
+
+/// Notes of `area`, optionally narrowed by a search and a tag.
+pub(crate) fn list_page(
+    dir: &Path,
+    nonce: &str,
+    area: Area,
+    search: Option<&str>,
+    tag: Option<&str>,
+) -> Result<String, Box<dyn Error>> {
+    let tag = tag.map(tags::normalize_tag);
+    let tag = tag.as_deref();
+    let all = select_notes(dir, area, &parse_list_options(Vec::new())?)?;
+    let mut filters: Vec<String> = Vec::new();
+    if let Some(q) = search {
+        filters.extend(["-s".to_string(), q.to_string()]);
+    }
+    if let Some(tag) = tag {
+        filters.extend(["-t".to_string(), tag.to_string()]);
+    }
+    let notes = if filters.is_empty() {
+        all.clone()
+    } else {
+        select_notes(dir, area, &parse_list_options(filters)?)?
+    };
+
+    let mut main = format!("<header><h1>{}</h1>\n", area_name(area));
+    let mut summary = format!(
+        "{} note{}",
+        notes.len(),
+        if notes.len() == 1 { "" } else { "s" }
+    );
+    if let Some(tag) = tag {
+        summary.push_str(&format!(" tagged {}", escape_html(tag)));
+    }
+    if let Some(q) = search {
+        summary
+            .push_str(&format!(" matching &ldquo;{}&rdquo;", escape_html(q)));
+    }
+    if search.is_some() || tag.is_some() {
+        summary.push_str(&format!(
+            " &middot; <a href=\"{}\">clear</a>",
+            area_url(area)
+        ));
+    }
+    main.push_str(&format!("<p class=\"meta\">{summary}</p>\n</header>\n"));
+    main.push_str("<ul class=\"notes\">\n");
+    for note in &notes {
+        main.push_str(&format!(
+            "<li><a href=\"/n/{}\">{}</a> <span class=\"meta\">{}</span>\n{}</li>\n",
+            percent_encode(&note.id),
+            escape_html(&note.title),
+            escape_html(&note.updated),
+            tag_links(area, &note.tags)
+        ));
+    }
+    main.push_str("</ul>");
+
+    let side = sidebar(area, &all, search, tag);
+    Ok(layout(dir, nonce, area_name(area), &side, &main)?)
+}
+
+/// One note, with links to other notes pointed at their pages.
+pub(crate) fn note_page(
+    dir: &Path,
+    nonce: &str,
+    area: Area,
+    note: &Note,
+    exists: impl Fn(&str) -> bool,
+) -> Result<String, Box<dyn Error>> {
+    let all = select_notes(dir, area, &parse_list_options(Vec::new())?)?;
+    let mut meta = format!(
+        "Created {} &middot; Updated {}",
+        escape_html(&note.created),
+        escape_html(&note.updated)
+    );
+    if let Some(at) = &note.deleted_at {
+        meta.push_str(&format!(" &middot; Deleted {}", escape_html(at)));
+    }
+    if let Some(at) = &note.archived_at {
+        meta.push_str(&format!(" &middot; Archived {}", escape_html(at)));
+    }
+    if let Some(at) = &note.due {
+        meta.push_str(&format!(" &middot; Due {}", escape_html(at)));
+    }
+    let main = format!(
+        "<header><h1>{}</h1>\n<p class=\"meta\">{meta}</p>\n{}</header>\n\
+<article>\n{}</article>",
+        escape_html(&note.title),
+        tag_links(area, &note.tags),
+        markdown_to_safe_html(&note.body, |id| {
+            exists(id).then(|| format!("/n/{}", percent_encode(id)))
+        })
+    );
+    let side = sidebar(area, &all, None, None);
+    Ok(layout(dir, nonce, &note.title, &side, &main)?)
+}
+
+/// A bare page for errors, without the sidebar.
+pub(crate) fn message_page(title: &str, message: &str) -> String {
+    page_with_head(
+        title,
+        UI_STYLE,
+        &format!(
+            "<header><h1>{}</h1></header>\n<p>{}</p>\n\
+<p><a href=\"/\">&larr; All notes</a></p>",
+            escape_html(title),
+            escape_html(message)
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_tags_for_urls() {
+        assert_eq!(percent_encode("#work/q3"), "%23work%2Fq3");
+        assert_eq!(tag_href(Area::Archive, "#a b"), "/archive?tag=%23a%20b");
+    }
+
+    #[test]
+    fn fingerprint_follows_note_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let before = fingerprint(tmp.path()).unwrap();
+        fs::write(tmp.path().join("n1.md"), "Title: One\n\nbody").unwrap();
+        let after = fingerprint(tmp.path()).unwrap();
+        assert_ne!(before, after);
+        assert_eq!(after, fingerprint(tmp.path()).unwrap());
+    }
+}
@@ -960,3 +960,1575 @@ fn tags_written_in_header() {
     let note = read_note(temp.path(), &id);
     assert!(note.contains("Tags: #x, #y"));
 }
+
+#[test]
+fn unknown_headers_survive_add_and_edit() {
+    let temp = TempDir::new().unwrap();
+    let content = "Title: Sourced\nCreated: 01Jan20 10:00 -00:00\n\
+Updated: 01Jan20 10:00 -00:00\nSource: https://example.com\nTags: #ref\n\
+Project: apollo\nSource URL: https://example.com/a\nReviewed:\n---\nbody\n";
+    fs::write(temp.path().join("src1.md"), content).unwrap();
+
+    cmd(&temp).args(["add", "src1", "more"]).assert().success();
+    cmd(&temp).env("EDITOR", "true").args(["edit", "src1"]).assert().success();
+
+    let note = read_note(temp.path(), "src1");
+    assert!(note.contains("Source: https://example.com\n"));
+    assert!(note.contains("Project: apollo\n"));
+    assert!(note.contains("\nSource URL: https://example.com/a\n"), "{note}");
+    assert!(note.contains("\nReviewed:\n"), "{note}");
+    let source = note.find("Source:").unwrap();
+    let project = note.find("Project:").unwrap();
+    assert!(source < project, "property order changed:\n{note}");
+    assert!(note.contains("more"));
+}
+
+#[test]
+fn prop_set_get_unset() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "p1",
+        "Props",
+        "01Jan20 10:00 -00:00",
+        "01Jan20 10:00 -00:00",
+        &[],
+        "body",
+    );
+
+    cmd(&temp)
+        .args(["prop", "set", "p1", "Status", "in", "review"])
+        .assert()
+        .success();
+    cmd(&temp)
+        .args(["prop", "get", "p1", "status"])
+        .assert()
+        .success()
+        .stdout("in review\n");
+    let note = read_note(temp.path(), "p1");
+    assert!(note.contains("Status: in review\n"));
+    assert!(!note.contains("Updated: 01Jan20 10:00 -00:00"));
+
+    cmd(&temp).args(["prop", "set", "p1", "Title", "x"]).assert().failure();
+    cmd(&temp).args(["prop", "unset", "p1", "Status"]).assert().success();
+    cmd(&temp).args(["prop", "get", "p1", "Status"]).assert().failure();
+    assert!(!read_note(temp.path(), "p1").contains("Status:"));
+}
+
+#[test]
+fn list_where_filters_on_properties() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "w1",
+        "Alpha",
+        "01Jan20 10:00 -00:00",
+        "01Jan20 10:00 -00:00",
+        &[],
+        "body",
+    );
+    write_note_file(
+        temp.path(),
+        "w2",
+        "Beta",
+        "02Jan20 10:00 -00:00",
+        "02Jan20 10:00 -00:00",
+        &[],
+        "body",
+    );
+    cmd(&temp)
+        .args(["prop", "set", "w1", "Project", "apollo"])
+        .assert()
+        .success();
+    cmd(&temp)
+        .args(["prop", "set", "w2", "Project", "gemini"])
+        .assert()
+        .success();
+
+    let out = cmd(&temp)
+        .args(["list", "--where", "project=Apollo"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(list_ids(&out), vec!["w1".to_string()]);
+
+    cmd(&temp).args(["list", "--where", "project"]).assert().failure();
+}
+
+#[test]
+fn front_matter_notes_are_listed_and_preserved() {
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("fm1.md"),
+        "---\ntitle: \"Plan: Q3\"\ncreated: 2020-01-01T10:00:00+00:00\n\
+updated: 2020-01-02T10:00:00+00:00\ntags: [work, planning]\n\
+aliases: [q3]\ndescription: >\n  long\n  summary\nmeta:\n  author: bob\n\
+---\nOriginal body\n",
+    )
+    .unwrap();
+
+    cmd(&temp)
+        .args(["list", "-t", "planning"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Plan: Q3"));
+    cmd(&temp)
+        .args(["prop", "get", "fm1", "aliases"])
+        .assert()
+        .success()
+        .stdout("[q3]\n");
+
+    cmd(&temp).args(["add", "fm1", "appended"]).assert().success();
+    let note = read_note(temp.path(), "fm1");
+    assert!(note.starts_with("---\ntitle: \"Plan: Q3\"\n"));
+    assert!(note.contains("created: 2020-01-01T10:00:00+00:00\n"));
+    assert!(note.contains("tags:\n  - work\n  - planning\n"));
+    assert!(note.contains("aliases: [q3]\n"));
+    assert!(note.contains("\ndescription: >\n  long\n  summary\n"), "{note}");
+    assert!(note.contains("\nmeta:\n  author: bob\n---\n"), "{note}");
+    assert!(note.ends_with("Original body\nappended\n"));
+}
+
+#[test]
+fn new_notes_follow_configured_format() {
+    let temp = TempDir::new().unwrap();
+    cmd(&temp)
+        .env("QUICK_NOTES_FORMAT", "frontmatter")
+        .args(["new", "Configured", "body", "-t", "x"])
+        .assert()
+        .success();
+    let id = list_ids(
+        &cmd(&temp).args(["list"]).assert().success().get_output().stdout,
+    )
+    .remove(0);
+    let note = read_note(temp.path(), &id);
+    assert!(note.starts_with("---\ntitle: Configured\n"));
+    assert!(note.contains("tags:\n  - x\n"));
+}
+
+#[test]
+fn convert_format_round_trips_all_areas() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "c1",
+        "Active",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 11:00 +00:00",
+        &["a"],
+        "active body",
+    );
+    write_note_file(
+        temp.path(),
+        "c2",
+        "Archived",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 11:00 +00:00",
+        &[],
+        "archived body",
+    );
+    cmd(&temp).args(["archive", "c2"]).assert().success();
+    let legacy_active = read_note(temp.path(), "c1");
+
+    cmd(&temp)
+        .args(["convert-format", "frontmatter", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 note(s) would be converted"));
+    assert_eq!(read_note(temp.path(), "c1"), legacy_active);
+
+    cmd(&temp)
+        .args(["convert-format", "--to", "frontmatter"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 note(s) converted"));
+    let converted = read_note(temp.path(), "c1");
+    assert!(converted.starts_with("---\ntitle: Active\n"));
+    assert!(converted.contains("updated: 2020-01-01T11:00:00+00:00\n"));
+    let archived = read_note(&temp.path().join("archive"), "c2");
+    assert!(archived.contains("archived: "));
+
+    cmd(&temp).args(["convert-format", "legacy"]).assert().success();
+    assert_eq!(read_note(temp.path(), "c1"), legacy_active);
+    assert!(
+        read_note(&temp.path().join("archive"), "c2").starts_with("Title: ")
+    );
+
+    cmd(&temp).args(["convert-format", "xml"]).assert().failure();
+}
+
+#[test]
+fn moves_leave_one_copy_and_no_temp_files() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "m1",
+        "Mover",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 10:00 +00:00",
+        &[],
+        "body",
+    );
+    let files_in = |dir: &Path| -> Vec<String> {
+        fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|e| e.file_type().unwrap().is_file())
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .filter(|name| name != ".qn.lock")
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    cmd(&temp).args(["delete", "m1"]).assert().success();
+    assert!(files_in(temp.path()).is_empty());
+    assert_eq!(files_in(&temp.path().join("trash")), vec!["m1.md"]);
+    assert!(read_note(&temp.path().join("trash"), "m1").contains("Deleted: "));
+
+    cmd(&temp).args(["undelete", "m1"]).assert().success();
+    assert!(files_in(&temp.path().join("trash")).is_empty());
+    assert_eq!(files_in(temp.path()), vec!["m1.md"]);
+    let restored = read_note(temp.path(), "m1");
+    assert!(!restored.contains("Deleted: "));
+    assert!(restored.ends_with("---\nbody\n"));
+}
+
+#[test]
+fn concurrent_appends_are_not_lost() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "c1",
+        "Concurrent",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 10:00 +00:00",
+        &[],
+        "start",
+    );
+    let children: Vec<_> = (0..8)
+        .map(|i| {
+            std::process::Command::cargo_bin("quick_notes")
+                .unwrap()
+                .env("QUICK_NOTES_DIR", temp.path())
+                .args(["add", "c1", &format!("line-{i}")])
+                .stdout(std::process::Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+    let note = read_note(temp.path(), "c1");
+    for i in 0..8 {
+        assert!(note.contains(&format!("line-{i}\n")), "missing line-{i}");
+    }
+}
+
+#[test]
+fn held_note_lock_times_out_with_clear_error() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "l1",
+        "Locked",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 10:00 +00:00",
+        &[],
+        "body",
+    );
+    let lock_dir = temp.path().join(".locks");
+    fs::create_dir_all(&lock_dir).unwrap();
+    let held = fs::File::create(lock_dir.join("l1.lock")).unwrap();
+    held.lock().unwrap();
+
+    cmd(&temp)
+        .env("QUICK_NOTES_LOCK_TIMEOUT", "0.1")
+        .args(["add", "l1", "blocked"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("waiting for a lock on note l1"));
+    assert!(!read_note(temp.path(), "l1").contains("blocked"));
+
+    held.unlock().unwrap();
+    cmd(&temp).args(["add", "l1", "after"]).assert().success();
+}
+
+#[test]
+fn index_tracks_writes_and_out_of_band_edits() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "i1",
+        "Indexed",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 10:00 +00:00",
+        &["alpha"],
+        "body",
+    );
+    cmd(&temp)
+        .args(["list", "-t", "alpha"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("i1"));
+    assert!(temp.path().join(".qn-index").is_file());
+
+    // Hand edit outside qn: picked up through mtime/size revalidation.
+    write_note_file(
+        temp.path(),
+        "i1",
+        "Indexed",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 10:00 +00:00",
+        &["beta", "gamma"],
+        "body",
+    );
+    cmd(&temp)
+        .args(["tags"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("#gamma"))
+        .stdout(predicate::str::contains("#alpha").not());
+
+    cmd(&temp).args(["archive", "i1"]).assert().success();
+    cmd(&temp)
+        .args(["list-archived"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("i1"));
+    cmd(&temp)
+        .args(["stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"Archive\s*\|\s*1").unwrap());
+
+    fs::write(temp.path().join(".qn-index"), "garbage").unwrap();
+    cmd(&temp)
+        .args(["reindex"])
+        .assert()
+        .success()
+        .stdout("Indexed 1 note(s)\n");
+    assert!(
+        fs::read_to_string(temp.path().join(".qn-index"))
+            .unwrap()
+            .contains("archive/i1.md")
+    );
+}
+
+#[test]
+fn history_diff_and_revert() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "h1",
+        "Versioned",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 10:00 +00:00",
+        &[],
+        "line one",
+    );
+    cmd(&temp).args(["history", "h1"]).assert().success().stdout(
+        predicate::str::contains("current")
+            .and(predicate::str::contains("No earlier revisions")),
+    );
+    cmd(&temp).args(["diff", "h1"]).assert().failure();
+
+    cmd(&temp).args(["add", "h1", "line two"]).assert().success();
+    let original = fs::read_dir(temp.path().join(".history").join("h1"))
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .next()
+        .expect("revision saved");
+    let rev = original.file_stem().unwrap().to_string_lossy().to_string();
+    assert!(fs::read_to_string(&original).unwrap().ends_with("line one\n"));
+
+    cmd(&temp)
+        .args(["history", "h1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(&rev))
+        .stdout(predicate::str::contains("+"));
+    cmd(&temp)
+        .args(["diff", "h1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("--- h1@{rev}")))
+        .stdout(predicate::str::contains("+++ h1@current"))
+        .stdout(predicate::str::contains("\n+line two\n"));
+
+    cmd(&temp).args(["revert", "h1", "zzz"]).assert().failure();
+    // Unique prefixes are enough to name a revision.
+    cmd(&temp).args(["revert", "h1", &rev[..6]]).assert().success();
+    let reverted = read_note(temp.path(), "h1");
+    assert!(reverted.ends_with("---\nline one\n"));
+    assert!(!reverted.contains("Updated: 01Jan20 10:00"));
+
+    // The revert itself is undoable.
+    let revs =
+        fs::read_dir(temp.path().join(".history").join("h1")).unwrap().count();
+    assert_eq!(revs, 2);
+}
+
+#[test]
+fn search_ranks_title_matches_and_follows_edits() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "s1",
+        "Grocery list",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 10:00 +00:00",
+        &["home"],
+        "buy milk and a kubernetes sticker",
+    );
+    write_note_file(
+        temp.path(),
+        "s2",
+        "Kubernetes rollout plan",
+        "02Jan20 10:00 +00:00",
+        "02Jan20 10:00 +00:00",
+        &["work"],
+        "canary first, then the rest",
+    );
+
+    let output = cmd(&temp)
+        .args(["search", "kubernetes"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let text = String::from_utf8(output).unwrap();
+    let (s1, s2) = (text.find("s1").unwrap(), text.find("s2").unwrap());
+    assert!(s2 < s1, "title match should rank first:\n{text}");
+    assert!(temp.path().join(".qn-search").is_file());
+
+    cmd(&temp)
+        .args(["search", "\"kubernetes sticker\""])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("s1"))
+        .stdout(predicate::str::contains("s2").not());
+    cmd(&temp)
+        .args(["search", "kube*", "-t", "work"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("s2"))
+        .stdout(predicate::str::contains("s1").not());
+
+    write_note_file(
+        temp.path(),
+        "s1",
+        "Grocery list",
+        "01Jan20 10:00 +00:00",
+        "01Jan20 10:00 +00:00",
+        &["home"],
+        "buy oat milk",
+    );
+    cmd(&temp)
+        .args(["search", "sticker"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No matches"));
+}
+
+#[test]
+fn query_filters_list_and_targets_bulk_archive() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "q1",
+        "Daily standup",
+        "01Jun25 10:00 +00:00",
+        "02Jul25 10:00 +00:00",
+        &["work"],
+        "blockers",
+    );
+    write_note_file(
+        temp.path(),
+        "q2",
+        "Sprint notes",
+        "01Jun25 10:00 +00:00",
+        "03Jul25 10:00 +00:00",
+        &["work", "done"],
+        "retrospective items",
+    );
+    write_note_file(
+        temp.path(),
+        "q3",
+        "Old plan",
+        "01Jan24 10:00 +00:00",
+        "01Jan24 10:00 +00:00",
+        &["work"],
+        "retro from last year",
+    );
+
+    let output = cmd(&temp)
+        .args([
+            "list",
+            "-q",
+            "tag:#work AND NOT tag:#done AND updated:>2025-06-01 \
+             AND (title:\"standup\" OR body:/retro/)",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(list_ids(&output), vec!["q1"]);
+
+    let output = cmd(&temp)
+        .args(["list", "-q", "body:/retro\\w*/"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let mut ids = list_ids(&output);
+    ids.sort();
+    assert_eq!(ids, vec!["q2", "q3"]);
+
+    cmd(&temp)
+        .args(["list", "-q", "tag:work AND colour:red"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown field `colour`"))
+        .stderr(predicate::str::contains("              ^^^^^^"));
+
+    cmd(&temp)
+        .args(["archive", "-q", "updated:<2025-01-01"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Archived q3"));
+    assert!(temp.path().join("archive").join("q3.md").exists());
+    assert!(temp.path().join("q1.md").exists());
+}
+
+#[test]
+fn json_and_ndjson_output_follow_the_documented_schema() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "j1",
+        "Quote \"me\"",
+        "01Jun25 10:00 +00:00",
+        "02Jun25 11:30 +02:00",
+        &["work"],
+        "line one\nline two",
+    );
+    write_note_file(
+        temp.path(),
+        "j2",
+        "Second",
+        "01Jun25 10:00 +00:00",
+        "01Jun25 10:00 +00:00",
+        &[],
+        "body",
+    );
+    let path = temp.path().join("j1.md").display().to_string();
+    let size = fs::metadata(temp.path().join("j1.md")).unwrap().len();
+
+    let stdout = |args: &[&str]| {
+        let out = cmd(&temp).args(args).assert().success().get_output().clone();
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let list = stdout(&["list", "--json", "-t", "work"]);
+    assert_eq!(
+        list.trim_end(),
+        format!(
+            "[{{\"id\":\"j1\",\"title\":\"Quote \\\"me\\\"\",\"area\":\"active\",\
+\"path\":\"{path}\",\"created\":\"2025-06-01T10:00:00+00:00\",\
+\"updated\":\"2025-06-02T11:30:00+02:00\",\"deleted\":null,\"archived\":null,\
+\"due\":null,\"remind\":null,\"tags\":[\"#work\"],\"properties\":{{}},\"size\":{size}}}]"
+        )
+    );
+
+    let ndjson = stdout(&["--ndjson", "list"]);
+    assert_eq!(ndjson.lines().count(), 2);
+    assert!(ndjson.lines().all(|l| l.starts_with("{\"id\":")));
+
+    let view = stdout(&["view", "j1", "--json"]);
+    assert!(view.contains("\"body\":\"line one\\nline two"));
+
+    cmd(&temp).args(["delete", "j2"]).assert().success();
+    let trash = stdout(&["list-deleted", "--json"]);
+    assert!(trash.contains("\"area\":\"trash\""));
+    assert!(!trash.contains("\"deleted\":null"));
+
+    let tags = stdout(&["tags", "--ndjson"]);
+    assert!(tags.lines().any(|l| l.starts_with(
+        "{\"tag\":\"#work\",\"count\":1,\"pinned\":false,\
+\"first_used\":\"2025-06-01T10:00:00+00:00\""
+    )));
+
+    assert_eq!(
+        stdout(&["stats", "--json"]).trim_end(),
+        "{\"active\":1,\"trash\":1,\"archive\":0,\"total\":2}"
+    );
+    assert_eq!(stdout(&["list-archived", "--json"]).trim_end(), "[]");
+
+    cmd(&temp)
+        .args(["--json", "delete", "j1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--json is not supported for delete"));
+
+    // Other commands get the flag as an ordinary argument.
+    cmd(&temp).args(["add", "j1", "--json"]).assert().success();
+    let text = fs::read_to_string(temp.path().join("j1.md")).unwrap();
+    assert!(text.ends_with("\n--json\n"), "{text}");
+}
+
+#[test]
+fn export_html_writes_pages_indexes_and_rewrites_links() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "e1",
+        "Design <draft>",
+        "01Jun25 10:00 +00:00",
+        "02Jun25 10:00 +00:00",
+        &["design"],
+        "See [the plan](e2.md) and [elsewhere](e9.md).\n\n**bold**",
+    );
+    write_note_file(
+        temp.path(),
+        "e2",
+        "Plan",
+        "01Jun25 10:00 +00:00",
+        "01Jun25 10:00 +00:00",
+        &["design", "work"],
+        "Plan body",
+    );
+    write_note_file(
+        temp.path(),
+        "e3",
+        "Unrelated",
+        "01Jun25 10:00 +00:00",
+        "01Jun25 10:00 +00:00",
+        &["home"],
+        "nothing",
+    );
+    let out = temp.path().join("site");
+
+    cmd(&temp)
+        .args(["export", "html", "-t", "design", "-o"])
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exported 2 note(s)"));
+
+    let page = fs::read_to_string(out.join("e1.html")).unwrap();
+    assert!(page.starts_with("<!DOCTYPE html>"));
+    assert!(page.contains("<style>"));
+    assert!(page.contains("<h1>Design &lt;draft&gt;</h1>"));
+    assert!(page.contains("Created 01Jun25 10:00 +00:00"));
+    assert!(page.contains("href=\"tags/design.html\""));
+    assert!(page.contains("<a href=\"e2.html\">the plan</a>"));
+    assert!(page.contains("href=\"e9.md\""));
+    assert!(page.contains("<strong>bold</strong>"));
+
+    let index = fs::read_to_string(out.join("index.html")).unwrap();
+    assert!(index.contains("href=\"e1.html\""));
+    assert!(index.contains("href=\"e2.html\""));
+    assert!(!index.contains("e3.html"));
+    let work = fs::read_to_string(out.join("tags").join("work.html")).unwrap();
+    assert!(work.contains("href=\"../e2.html\""));
+    assert!(!work.contains("e1.html"));
+    assert!(!out.join("e3.html").exists());
+
+    cmd(&temp)
+        .args(["export", "pdf", "--all", "-o", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported export format: pdf"));
+}
+
+#[test]
+fn publish_builds_site_incrementally() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "p1",
+        "Launch Plan",
+        "01Jun25 10:00 +00:00",
+        "02Jun25 10:00 +00:00",
+        &["public", "plans"],
+        "Kickoff notes.",
+    );
+    write_note_file(
+        temp.path(),
+        "p2",
+        "Retro",
+        "05Jul25 10:00 +00:00",
+        "05Jul25 10:00 +00:00",
+        &["public"],
+        "Follow-up on [the plan](p1.md).",
+    );
+    write_note_file(
+        temp.path(),
+        "p3",
+        "Private",
+        "05Jul25 10:00 +00:00",
+        "05Jul25 10:00 +00:00",
+        &["plans"],
+        "secret",
+    );
+    let site = temp.path().join("site");
+    let publish = || {
+        let out = cmd(&temp)
+            .args(["publish", "-t", "#public", "-o"])
+            .arg(&site)
+            .args(["--base-url", "https://intranet.example/notes"])
+            .assert()
+            .success()
+            .get_output()
+            .clone();
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let first = publish();
+    assert!(first.contains("Published 2 note(s)"), "{first}");
+    let plan = site.join("posts/2025/06/launch-plan.html");
+    let retro =
+        fs::read_to_string(site.join("posts/2025/07/retro.html")).unwrap();
+    assert!(retro.contains("href=\"../../../posts/2025/06/launch-plan.html\""));
+    let plan_html = fs::read_to_string(&plan).unwrap();
+    assert!(plan_html.contains("Linked from"));
+    assert!(plan_html.contains("href=\"../../../posts/2025/07/retro.html\""));
+    assert!(site.join("tags/plans.html").exists());
+    assert!(site.join("archive/2025-06.html").exists());
+    let index = fs::read_to_string(site.join("index.html")).unwrap();
+    assert!(!index.contains("Private"));
+    let feed = fs::read_to_string(site.join("feed.xml")).unwrap();
+    assert!(feed.contains("<feed xmlns=\"http://www.w3.org/2005/Atom\">"));
+    assert!(feed.contains(
+        "<link href=\"https://intranet.example/notes/posts/2025/07/retro.html\"/>"
+    ));
+
+    let second = publish();
+    assert!(second.contains("0 written"), "{second}");
+
+    // Dropping the tag unpublishes the note and removes its page.
+    write_note_file(
+        temp.path(),
+        "p2",
+        "Retro",
+        "05Jul25 10:00 +00:00",
+        "06Jul25 10:00 +00:00",
+        &[],
+        "Follow-up on [the plan](p1.md).",
+    );
+    let third = publish();
+    assert!(third.contains("Published 1 note(s)"), "{third}");
+    assert!(!site.join("posts/2025/07/retro.html").exists());
+    assert!(!site.join("archive/2025-07.html").exists());
+    assert!(!site.join("posts/2025/07").exists());
+    assert!(!fs::read_to_string(&plan).unwrap().contains("Linked from"));
+}
+
+#[test]
+fn backup_and_restore_round_trip_with_merge_and_rotation() {
+    let temp = TempDir::new().unwrap();
+    let out = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "b1",
+        "Keep",
+        "01Jun25 10:00 +00:00",
+        "01Jun25 10:00 +00:00",
+        &["work"],
+        "original",
+    );
+    fs::create_dir_all(temp.path().join("trash")).unwrap();
+    write_note_file(
+        &temp.path().join("trash"),
+        "b2",
+        "Binned",
+        "01Jun25 10:00 +00:00",
+        "01Jun25 10:00 +00:00",
+        &[],
+        "gone",
+    );
+    let archive = out.path().join("notes.tar.gz");
+    cmd(&temp)
+        .args(["backup", "-o"])
+        .arg(&archive)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Backed up 2 note(s)"));
+
+    // A fresh directory gets an exact copy, areas included.
+    let copy = out.path().join("copy");
+    cmd(&temp)
+        .arg("restore")
+        .arg(&archive)
+        .arg("--into")
+        .arg(&copy)
+        .assert()
+        .success();
+    assert_eq!(read_note(&copy, "b1"), read_note(temp.path(), "b1"));
+    assert!(copy.join("trash/b2.md").exists());
+
+    // A populated store needs an explicit mode.
+    cmd(&temp)
+        .arg("restore")
+        .arg(&archive)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--merge"));
+
+    write_note_file(
+        temp.path(),
+        "b1",
+        "Keep",
+        "01Jun25 10:00 +00:00",
+        "02Jun25 10:00 +00:00",
+        &["work"],
+        "edited since the backup",
+    );
+    cmd(&temp)
+        .arg("restore")
+        .arg(&archive)
+        .arg("--merge")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored b1 as "))
+        .stdout(predicate::str::contains("(1 renamed, 1 already present)"));
+    assert!(read_note(temp.path(), "b1").contains("edited since the backup"));
+    let list = cmd(&temp).arg("list").assert().success();
+    assert_eq!(list_ids(&list.get_output().stdout).len(), 2);
+
+    let snapshots = out.path().join("snapshots");
+    for _ in 0..3 {
+        cmd(&temp)
+            .args(["backup", "--rotate", "2", "-o"])
+            .arg(&snapshots)
+            .assert()
+            .success();
+    }
+    assert_eq!(fs::read_dir(&snapshots).unwrap().count(), 2);
+}
+
+const GIT_IDENTITY: [(&str, &str); 4] = [
+    ("GIT_AUTHOR_NAME", "qn test"),
+    ("GIT_AUTHOR_EMAIL", "qn@example.com"),
+    ("GIT_COMMITTER_NAME", "qn test"),
+    ("GIT_COMMITTER_EMAIL", "qn@example.com"),
+];
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let out = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .envs(GIT_IDENTITY)
+        .output()
+        .expect("git");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[test]
+fn concurrent_git_autocommits_each_get_their_own_commit() {
+    let notes = TempDir::new().unwrap();
+    git(notes.path(), &["init", "-q", "-b", "main"]);
+    let ts = "01Jan20 10:00 +00:00";
+    for i in 0..6 {
+        let id = format!("g{i}");
+        write_note_file(notes.path(), &id, &id, ts, ts, &[], "start");
+    }
+    git(notes.path(), &["add", "-A"]);
+    git(notes.path(), &["commit", "-q", "-m", "seed"]);
+    fs::write(notes.path().join("stray.md"), "hand edit\n").unwrap();
+
+    let children: Vec<_> = (0..6)
+        .map(|i| {
+            std::process::Command::cargo_bin("quick_notes")
+                .unwrap()
+                .env("QUICK_NOTES_DIR", notes.path())
+                .envs(GIT_IDENTITY)
+                .args(["add", &format!("g{i}"), "more"])
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for child in children {
+        let out = child.wait_with_output().unwrap();
+        assert!(out.status.success());
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(!stderr.contains("Git commit skipped"), "{stderr}");
+    }
+
+    let log = git(notes.path(), &["log", "--format=%s"]);
+    let mut subjects: Vec<&str> =
+        log.lines().filter(|l| *l != "seed").collect();
+    subjects.sort();
+    let expected: Vec<String> =
+        (0..6).map(|i| format!("add g{i}: g{i}")).collect();
+    assert_eq!(subjects, expected);
+    for i in 0..6 {
+        let files = git(
+            notes.path(),
+            &[
+                "log",
+                "-1",
+                "--format=",
+                "--name-only",
+                "--grep",
+                &format!("g{i}:"),
+            ],
+        );
+        assert_eq!(files, format!("g{i}.md\n"));
+    }
+    // Files qn did not write stay out of these commits.
+    assert_eq!(git(notes.path(), &["status", "--porcelain"]), "?? stray.md\n");
+}
+
+#[test]
+fn git_store_commits_each_change_and_syncs_with_a_remote() {
+    let notes = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    let remote = TempDir::new().unwrap();
+    let qn = |store: &TempDir| {
+        let mut c = cmd(store);
+        c.envs(GIT_IDENTITY);
+        c
+    };
+
+    // Without a repository nothing git-related happens.
+    let plain = TempDir::new().unwrap();
+    qn(&plain).args(["new", "Draft", "x"]).assert().success();
+    assert!(!plain.path().join(".git").exists());
+    qn(&plain)
+        .arg("log")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a git repository"));
+
+    git(notes.path(), &["init", "-q", "-b", "main"]);
+    let created = qn(&notes)
+        .args(["new", "Project brief", "hello", "-t", "work"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&created.get_output().stdout);
+    let id = stdout.split_whitespace().nth(2).unwrap().to_string();
+    qn(&notes).args(["prop", "set", &id, "status", "done"]).assert().success();
+    qn(&notes).args(["archive", &id]).assert().success();
+    assert_eq!(
+        git(notes.path(), &["log", "--format=%s"]),
+        format!(
+            "archive {id}: Project brief\nprop {id}: Project brief\n\
+new {id}: Project brief\n"
+        )
+    );
+    assert_eq!(git(notes.path(), &["status", "--porcelain"]), "");
+    qn(&notes)
+        .args(["log", &id, "-n", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("archive {id}")))
+        .stdout(predicate::str::contains(format!("new {id}")).not());
+
+    git(remote.path(), &["init", "-q", "--bare", "-b", "main"]);
+    let url = remote.path().to_str().unwrap();
+    git(notes.path(), &["remote", "add", "origin", url]);
+    qn(&notes)
+        .arg("sync")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pushed 3 commit(s)"));
+
+    fs::remove_dir(other.path()).unwrap();
+    git(remote.path(), &["clone", "-q", url, other.path().to_str().unwrap()]);
+    qn(&other).args(["new", "From laptop", "y"]).assert().success();
+    qn(&other).arg("sync").assert().success();
+    qn(&notes)
+        .arg("sync")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pulled 1, pushed 0"));
+    cmd(&notes)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("From laptop"));
+}
+
+#[test]
+fn sync_dir_merges_edits_and_records_conflicts() {
+    let temp = TempDir::new().unwrap();
+    let peer = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "s1",
+        "Plan",
+        "01Jun25 10:00 +00:00",
+        "01Jun25 10:00 +00:00",
+        &["work"],
+        "one\ntwo\nthree\n",
+    );
+    let sync = || {
+        let out = cmd(&temp)
+            .arg("sync-dir")
+            .arg(peer.path())
+            .assert()
+            .success()
+            .get_output()
+            .clone();
+        String::from_utf8(out.stdout).unwrap()
+    };
+    assert!(sync().contains("1 pushed"));
+    assert_eq!(read_note(peer.path(), "s1"), read_note(temp.path(), "s1"));
+    // Plain `sync` is always the git sync, even given a path.
+    cmd(&temp)
+        .arg("sync")
+        .arg(peer.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a git repository"));
+
+    // Separate edits on both sides merge line by line.
+    write_note_file(
+        temp.path(),
+        "s1",
+        "Plan",
+        "01Jun25 10:00 +00:00",
+        "02Jun25 10:00 +00:00",
+        &["work"],
+        "ONE\ntwo\nthree\n",
+    );
+    write_note_file(
+        peer.path(),
+        "s1",
+        "Plan",
+        "01Jun25 10:00 +00:00",
+        "03Jun25 10:00 +00:00",
+        &["work", "home"],
+        "one\ntwo\nthree\nfour\n",
+    );
+    assert!(sync().contains("1 merged"));
+    let merged = read_note(temp.path(), "s1");
+    assert!(merged.contains("Updated: 03Jun25 10:00 +00:00"));
+    assert!(merged.contains("Tags: #work, #home"));
+    assert!(merged.ends_with("---\nONE\ntwo\nthree\nfour\n"));
+    assert_eq!(read_note(peer.path(), "s1"), merged);
+
+    // Overlapping edits keep this store's version and add a conflict note.
+    write_note_file(
+        temp.path(),
+        "s1",
+        "Plan",
+        "01Jun25 10:00 +00:00",
+        "04Jun25 10:00 +00:00",
+        &["work"],
+        "uno\n",
+    );
+    write_note_file(
+        peer.path(),
+        "s1",
+        "Plan",
+        "01Jun25 10:00 +00:00",
+        "04Jun25 11:00 +00:00",
+        &["work"],
+        "eins\n",
+    );
+    let out = sync();
+    assert!(out.contains("Conflict in s1"), "{out}");
+    assert_eq!(read_note(peer.path(), "s1"), read_note(temp.path(), "s1"));
+    let conflicts =
+        cmd(&temp).args(["list", "-t", "conflict"]).assert().success();
+    let conflict_id = first_list_id(&conflicts.get_output().stdout);
+    let conflict = read_note(peer.path(), &conflict_id);
+    assert!(conflict.contains("Title: Conflict: Plan"));
+    assert!(conflict.contains("uno") && conflict.contains("eins"));
+    assert!(sync().contains("0 pulled, 0 pushed, 0 merged, 0 conflict(s)"));
+}
+
+/// Minimal HTTP client: one request, returns the status and body.
+fn http(
+    port: u16,
+    method: &str,
+    path: &str,
+    body: &str,
+    token: Option<&str>,
+) -> (u16, String) {
+    use std::io::Write;
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let auth = token
+        .map(|t| format!("Authorization: Bearer {t}\r\n"))
+        .unwrap_or_default();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: localhost:{port}\r\n{auth}\
+Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+    (status, body)
+}
+
+/// Kills the spawned server even when an assertion fails. Holds its stdout
+/// open so later prints do not hit a closed pipe.
+struct ServerGuard {
+    child: std::process::Child,
+    _stdout: std::io::BufReader<std::process::ChildStdout>,
+}
+
+impl Drop for ServerGuard {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Start `qn serve --port 0 <args>` and return it with the bound port.
+fn start_server(temp: &TempDir, args: &[&str]) -> (ServerGuard, u16) {
+    use std::io::BufRead;
+    let mut child = std::process::Command::cargo_bin("quick_notes")
+        .unwrap()
+        .args(["serve", "--port", "0"])
+        .args(args)
+        .env("QUICK_NOTES_DIR", temp.path())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut banner = String::new();
+    let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    stdout.read_line(&mut banner).unwrap();
+    let port: u16 = banner
+        .split("http://127.0.0.1:")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|p| p.parse().ok())
+        .unwrap_or_else(|| panic!("no port in {banner:?}"));
+    (ServerGuard { child, _stdout: stdout }, port)
+}
+
+fn json_field(body: &str, key: &str) -> String {
+    let start = body.find(&format!("\"{key}\":\"")).expect(key) + key.len() + 4;
+    body[start..].split('"').next().unwrap().to_string()
+}
+
+#[test]
+fn serve_exposes_note_crud_over_http() {
+    let temp = TempDir::new().unwrap();
+    let (_server, port) = start_server(&temp, &["--token", "s3cret"]);
+    let token = Some("s3cret");
+
+    let (status, _) = http(port, "GET", "/notes", "", None);
+    assert_eq!(status, 401);
+
+    let (status, body) = http(
+        port,
+        "POST",
+        "/notes",
+        r#"{"title":"From the browser","body":"first line","tags":["inbox"]}"#,
+        token,
+    );
+    assert_eq!(status, 201, "{body}");
+    let id = json_field(&body, "id");
+    assert!(body.contains(r##""tags":["#inbox"]"##));
+
+    let (status, body) = http(
+        port,
+        "POST",
+        &format!("/notes/{id}/append"),
+        r#"{"text":"second line"}"#,
+        token,
+    );
+    assert_eq!(status, 200, "{body}");
+    assert!(body.contains(r#""body":"first line\nsecond line\n""#), "{body}");
+
+    let (_, body) = http(
+        port,
+        "POST",
+        &format!("/notes/{id}/tags"),
+        r##"{"add":["#work"],"remove":["inbox"]}"##,
+        token,
+    );
+    assert!(body.contains(r##""tags":["#work"]"##), "{body}");
+
+    let (_, listed) = http(port, "GET", "/notes?tag=%23work", "", token);
+    assert!(listed.starts_with('[') && listed.contains(&id), "{listed}");
+    let (_, listed) = http(port, "GET", "/notes?q=tag:inbox", "", token);
+    assert_eq!(listed.trim(), "[]");
+
+    let (_, body) =
+        http(port, "POST", &format!("/notes/{id}/archive"), "", token);
+    assert!(body.contains(r#""area":"archive""#), "{body}");
+    let (_, body) =
+        http(port, "POST", &format!("/notes/{id}/restore"), "", token);
+    assert!(body.contains(r#""area":"active""#), "{body}");
+    let (status, body) =
+        http(port, "DELETE", &format!("/notes/{id}"), "", token);
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""area":"trash""#), "{body}");
+    let (status, _) = http(port, "GET", "/notes/missing", "", token);
+    assert_eq!(status, 404);
+
+    assert!(temp.path().join(format!("trash/{id}.md")).exists());
+}
+
+#[test]
+fn serve_ui_renders_read_only_pages() {
+    let temp = TempDir::new().unwrap();
+    let ts = "01Jan24 10:00 +00:00";
+    write_note_file(
+        temp.path(),
+        "n1",
+        "Roadmap",
+        ts,
+        ts,
+        &["work"],
+        "# Plan\n\nSee [the notes](n2.md).",
+    );
+    write_note_file(temp.path(), "n2", "Meeting", ts, ts, &["work", "ops"], "");
+    let (_server, port) = start_server(&temp, &["--ui"]);
+
+    let (status, page) = http(port, "GET", "/", "", None);
+    assert_eq!(status, 200);
+    assert!(page.contains("<a href=\"/n/n1\">Roadmap</a>"), "{page}");
+    assert!(page.contains("/?tag=%23work\">#work</a> <span class=\"meta\">2"));
+
+    let (_, page) = http(port, "GET", "/?tag=ops", "", None);
+    assert!(page.contains("1 note tagged #ops") && !page.contains("Roadmap"));
+    let (_, page) = http(port, "GET", "/?q=plan", "", None);
+    assert!(page.contains("/n/n1") && !page.contains("/n/n2"), "{page}");
+
+    let (status, page) = http(port, "GET", "/n/n1", "", None);
+    assert_eq!(status, 200);
+    assert!(page.contains("<h1>Plan</h1>"), "{page}");
+    assert!(page.contains("<a href=\"/n/n2\">the notes</a>"));
+
+    let (_, before) = http(port, "GET", "/_version", "", None);
+    cmd(&temp).args(["delete", "n2"]).assert().success();
+    let (_, after) = http(port, "GET", "/_version", "", None);
+    assert_ne!(before, after);
+    let (_, page) = http(port, "GET", "/trash", "", None);
+    assert!(page.contains("<a href=\"/n/n2\">Meeting</a>"), "{page}");
+
+    // Trash past its retention period is purged by `qn list-deleted`, which
+    // holds the store lock, never by a page view or an API read.
+    let trash = temp.path().join("trash");
+    write_note_file(&trash, "old", "Stale", ts, ts, &[], "");
+    let (_, page) = http(port, "GET", "/trash", "", None);
+    assert!(page.contains("/n/old"), "{page}");
+    let (_, listed) = http(port, "GET", "/notes?area=trash", "", None);
+    assert!(listed.contains("\"old\""), "{listed}");
+    assert!(trash.join("old.md").exists());
+    cmd(&temp).arg("list-deleted").assert().success();
+    assert!(!trash.join("old.md").exists());
+
+    let (status, _) = http(port, "POST", "/", "", None);
+    assert_eq!(status, 405);
+    let (status, _) = http(port, "GET", "/n/missing", "", None);
+    assert_eq!(status, 404);
+    let (_, listed) = http(port, "GET", "/notes", "", None);
+    assert!(listed.starts_with('['), "{listed}");
+}
+
+/// Frame JSON-RPC messages the way an editor sends them to `qn lsp`.
+fn lsp_frames(messages: &[String]) -> String {
+    messages
+        .iter()
+        .map(|m| format!("Content-Length: {}\r\n\r\n{m}", m.len()))
+        .collect()
+}
+
+#[test]
+fn lsp_completes_links_and_tags_and_reports_broken_links() {
+    let temp = TempDir::new().unwrap();
+    let ts = "01Jan24 10:00 +00:00";
+    write_note_file(
+        temp.path(),
+        "n1",
+        "Roadmap",
+        ts,
+        ts,
+        &["work"],
+        "Q3 goals",
+    );
+    let uri = format!("file://{}/n2.md", temp.path().display());
+    let text = "Title: Draft\\nCreated: 01Jan24 10:00 +00:00\\n\
+Updated: 01Jan24 10:00 +00:00\\nTags: #wo\\n---\\nSee [[n1]] and [[gone]]. [[";
+    let position = |line: u32, character: u32| {
+        format!(
+            r#"{{"textDocument":{{"uri":"{uri}"}},"position":{{"line":{line},"character":{character}}}}}"#
+        )
+    };
+    let messages = vec![
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#
+            .to_string(),
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{uri}","languageId":"markdown","version":1,"text":"{text}"}}}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/completion","params":{}}}"#,
+            position(5, 29)
+        ),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"textDocument/completion","params":{}}}"#,
+            position(3, 9)
+        ),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":4,"method":"textDocument/definition","params":{}}}"#,
+            position(5, 6)
+        ),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":5,"method":"textDocument/hover","params":{}}}"#,
+            position(5, 6)
+        ),
+        r#"{"jsonrpc":"2.0","id":6,"method":"shutdown"}"#.to_string(),
+        r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+    ];
+
+    let output = cmd(&temp)
+        .arg("lsp")
+        .write_stdin(lsp_frames(&messages))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let out = String::from_utf8(output.stdout).unwrap();
+    let replies: Vec<&str> =
+        out.split("Content-Length: ").filter(|s| !s.is_empty()).collect();
+    assert_eq!(replies.len(), 7, "{out}");
+    assert!(replies[0].contains(r#""hoverProvider":true"#));
+    assert!(replies[1].contains("publishDiagnostics"));
+    assert!(replies[1].contains("No note with id gone"));
+    assert!(!replies[1].contains("No note with id n1"));
+    assert!(
+        replies[2].contains(r#""label":"n1","kind":18,"detail":"Roadmap""#),
+        "{}",
+        replies[2]
+    );
+    assert!(replies[3].contains(r##""newText":"#work""##), "{}", replies[3]);
+    assert!(replies[4].contains("/n1.md"), "{}", replies[4]);
+    assert!(replies[5].contains("**Roadmap**"), "{}", replies[5]);
+    assert!(replies[5].contains("Q3 goals"));
+    assert!(replies[6].contains(r#""id":6,"result":null"#));
+}
+
+#[test]
+fn tui_requires_a_terminal() {
+    let temp = TempDir::new().unwrap();
+    cmd(&temp).arg("tui").assert().failure().stderr(predicate::str::contains(
+        "qn tui needs an interactive terminal",
+    ));
+    cmd(&temp)
+        .args(["tui", "--bogus"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown argument for tui: --bogus"));
+}
+
+#[test]
+fn pick_lists_candidates_and_requires_a_terminal() {
+    let temp = TempDir::new().unwrap();
+    let ts = "01Jan24 10:00 +00:00";
+    write_note_file(temp.path(), "n1", "Roadmap", ts, ts, &["work"], "Q3");
+    write_note_file(temp.path(), "n2", "Groceries", ts, ts, &["home"], "eggs");
+
+    cmd(&temp)
+        .args(["__list-ids", "-t", "work"])
+        .assert()
+        .success()
+        .stdout("n1\tRoadmap\t#work\n");
+    cmd(&temp).arg("pick").assert().failure().stderr(predicate::str::contains(
+        "qn pick needs fzf or an interactive terminal",
+    ));
+}
+
+#[test]
+fn find_searches_lines_and_edit_opens_at_a_line() {
+    use std::os::unix::fs::PermissionsExt;
+    let temp = TempDir::new().unwrap();
+    let ts = "01Jan24 10:00 +00:00";
+    let body = "intro\nship the parser\nwrap up";
+    write_note_file(temp.path(), "n1", "Roadmap", ts, ts, &["work"], body);
+    write_note_file(temp.path(), "n2", "Groceries", ts, ts, &["home"], "eggs");
+
+    cmd(&temp)
+        .args(["__search", "--", "pars"])
+        .assert()
+        .success()
+        .stdout("n1:7:ship the parser\n");
+    cmd(&temp)
+        .args(["__search", "-t", "home", "--", "pars"])
+        .assert()
+        .success()
+        .stdout("");
+    cmd(&temp)
+        .env("NO_COLOR", "1")
+        .args(["__find-preview", "n1", "7"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("   7> ship the parser"))
+        .stdout(predicate::str::contains("   6  intro"));
+    cmd(&temp).arg("find").assert().failure().stderr(predicate::str::contains(
+        "qn find needs fzf or an interactive terminal",
+    ));
+
+    let editor = temp.path().join("editor.sh");
+    let log = temp.path().join("editor.log");
+    fs::write(
+        &editor,
+        format!("#!/bin/sh\necho \"$@\" > '{}'\n", log.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    cmd(&temp)
+        .env("EDITOR", &editor)
+        .args(["edit", "n1", "--line", "7"])
+        .assert()
+        .success();
+    let logged = fs::read_to_string(&log).unwrap();
+    assert!(logged.starts_with("+7 ") && logged.contains("n1.md"), "{logged}");
+}
+
+#[test]
+fn due_dates_feed_the_agenda_and_reminders_fail_the_check() {
+    let temp = TempDir::new().unwrap();
+    let ts = "01Jan24 10:00 +00:00";
+    write_note_file(temp.path(), "n1", "Rent", ts, ts, &["todo"], "pay");
+    write_note_file(temp.path(), "n2", "Dentist", ts, ts, &[], "call");
+    write_note_file(temp.path(), "n3", "Taxes", ts, ts, &["todo"], "file");
+    write_note_file(temp.path(), "n4", "Someday", ts, ts, &[], "later");
+
+    cmd(&temp).args(["due", "n1", "yesterday"]).assert().success();
+    cmd(&temp).args(["due", "n2", "today"]).assert().success();
+    cmd(&temp).args(["due", "n3", "+2d"]).assert().success();
+    cmd(&temp).args(["due", "n4", "+30d"]).assert().success();
+    cmd(&temp)
+        .args(["due", "n4", "whenever"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Could not read \"whenever\""));
+    let text = fs::read_to_string(temp.path().join("n1.md")).unwrap();
+    assert!(text.contains("\nDue: "), "{text}");
+
+    let out = cmd(&temp)
+        .env("NO_COLOR", "1")
+        .arg("agenda")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let out = String::from_utf8(out).unwrap();
+    let overdue = out.find("Overdue").unwrap();
+    let today = out.find("Today").unwrap();
+    let upcoming = out.find("Upcoming (7 days)").unwrap();
+    assert!(overdue < out.find("n1  Rent").unwrap());
+    assert!(today < out.find("n2  Dentist").unwrap());
+    assert!(upcoming < out.find("n3  Taxes  #todo").unwrap());
+    assert!(!out.contains("Someday"), "{out}");
+
+    // A hand-typed natural value is resolved when `qn edit` saves.
+    let text = fs::read_to_string(temp.path().join("n4.md")).unwrap();
+    let start = text.find("Due: ").unwrap();
+    let end = start + text[start..].find('\n').unwrap();
+    let typed = format!("{}Remind: yesterday{}", &text[..start], &text[end..]);
+    fs::write(temp.path().join("n4.md"), typed).unwrap();
+    cmd(&temp).env("EDITOR", "true").args(["edit", "n4"]).assert().success();
+    let text = fs::read_to_string(temp.path().join("n4.md")).unwrap();
+    assert!(!text.contains("yesterday") && text.contains("Remind: "), "{text}");
+
+    cmd(&temp)
+        .env("NO_COLOR", "1")
+        .args(["remind", "--check"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("n4  Someday"))
+        .stderr(predicate::str::contains("1 pending reminder"));
+    cmd(&temp).args(["remind", "n4", "--clear"]).assert().success();
+    cmd(&temp).args(["remind", "--check"]).assert().success().stdout("");
+}
+
+#[test]
+fn natural_due_dates_keep_their_day_when_other_commands_save() {
+    let temp = TempDir::new().unwrap();
+    let ts = "10Oct26 09:00 +00:00";
+    fs::write(
+        temp.path().join("n1.md"),
+        format!(
+            "Title: Plan\nCreated: {ts}\nUpdated: {ts}\nTags:\n\
+             Due: tomorrow\n---\n- [ ] book\n"
+        ),
+    )
+    .unwrap();
+
+    cmd(&temp).args(["add", "n1", "more"]).assert().success();
+    let text = fs::read_to_string(temp.path().join("n1.md")).unwrap();
+    assert!(text.contains("\nDue: 11Oct26 09:00 +00:00\n"), "{text}");
+    assert!(!text.contains(&format!("Updated: {ts}")), "{text}");
+    assert!(text.contains("more"), "{text}");
+}
+
+#[test]
+fn todos_list_tasks_and_done_toggles_them() {
+    let temp = TempDir::new().unwrap();
+    write_note_file(
+        temp.path(),
+        "n1",
+        "Chores",
+        "01Jan24 09:00 +00:00",
+        "01Jan24 09:00 +00:00",
+        &["#home"],
+        "- [ ] pay rent @due(2024-01-05)\n- [x] call bank\nnotes\n",
+    );
+
+    cmd(&temp)
+        .env("NO_COLOR", "1")
+        .arg("todos")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("n1:6"))
+        .stdout(predicate::str::contains("pay rent"))
+        .stdout(predicate::str::contains("Fri 05Jan 09:00"))
+        .stdout(predicate::str::contains("n1:7"));
+    cmd(&temp)
+        .env("NO_COLOR", "1")
+        .args(["todos", "--open"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pay rent"))
+        .stdout(predicate::str::contains("call bank").not());
+
+    cmd(&temp)
+        .args(["done", "n1:6"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Done n1:6: pay rent"));
+    let text = fs::read_to_string(temp.path().join("n1.md")).unwrap();
+    assert!(text.contains("\n- [x] pay rent @due(2024-01-05)\n"), "{text}");
+    assert!(!text.contains("Updated: 01Jan24"), "{text}");
+    cmd(&temp)
+        .env("NO_COLOR", "1")
+        .args(["todos", "--open"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No tasks found."));
+
+    cmd(&temp).args(["undo-task", "n1:7"]).assert().success();
+    let text = fs::read_to_string(temp.path().join("n1.md")).unwrap();
+    assert!(text.contains("\n- [ ] call bank\n"), "{text}");
+    cmd(&temp)
+        .args(["done", "n1:8"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Line 8 of n1 is not a task"));
+}